@@ -1,3 +1,45 @@
+use std::fs;
+
+const INFO_PLIST: &str = "Info.plist";
+const MICROPHONE_USAGE_KEY: &str = "NSMicrophoneUsageDescription";
+const MICROPHONE_USAGE_DESCRIPTION: &str = "whispr records from your microphone only while the dictation key is held and transcribes the audio locally on this Mac.";
+const PLACEHOLDER_DESCRIPTIONS: &[&str] = &["", "Your reasons here."];
+
+// macOS attributes the microphone indicator to the app using this description,
+// so make sure we never ship the template placeholder or a missing key. Tauri
+// merges Info.plist into the bundle's own, the file itself is left to whoever
+// edits it rather than rewritten by the build.
+fn verify_info_plist() {
+    println!("cargo:rerun-if-changed={}", INFO_PLIST);
+
+    let contents = match fs::read_to_string(INFO_PLIST) {
+        Ok(contents) => contents,
+        Err(e) => panic!("Failed to read {}: {}", INFO_PLIST, e),
+    };
+
+    let key_tag = format!("<key>{}</key>", MICROPHONE_USAGE_KEY);
+    let Some(key_pos) = contents.find(&key_tag) else {
+        panic!(
+            "{} has no {}, add it to the top-level dict:\n  {}\n  <string>{}</string>",
+            INFO_PLIST, MICROPHONE_USAGE_KEY, key_tag, MICROPHONE_USAGE_DESCRIPTION
+        );
+    };
+    let after_key = key_pos + key_tag.len();
+    let start = contents[after_key..].find("<string>").map(|i| after_key + i + "<string>".len());
+    let end = start.and_then(|s| contents[s..].find("</string>").map(|i| s + i));
+    let (Some(start), Some(end)) = (start, end) else {
+        panic!("{} in {} has no string value", MICROPHONE_USAGE_KEY, INFO_PLIST);
+    };
+    if PLACEHOLDER_DESCRIPTIONS.contains(&contents[start..end].trim()) {
+        panic!(
+            "{} in {} is a placeholder, describe the microphone use, e.g. \"{}\"",
+            MICROPHONE_USAGE_KEY, INFO_PLIST, MICROPHONE_USAGE_DESCRIPTION
+        );
+    }
+}
+
 fn main() {
+    verify_info_plist();
+    #[cfg(feature = "app")]
     tauri_build::build()
 }
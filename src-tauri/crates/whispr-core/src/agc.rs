@@ -0,0 +1,126 @@
+use crate::input_device::InputFormat;
+
+// Windows the level is measured over, like silence removal's
+const WINDOW_MS: usize = 20;
+// Windows quieter than this are background noise and leave the gain alone
+const GATE_DB: f32 = -60.0;
+// Share of the way to the wanted gain covered per window. Getting quieter
+// is fast so loud speech doesn't clip, getting louder slow so pauses and
+// word endings don't pump.
+const ATTACK: f32 = 0.5;
+const RELEASE: f32 = 0.1;
+
+fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Automatic gain control that brings speech to a target RMS level, so a
+/// loud headset and a quiet built-in microphone reach silence removal and
+/// whisper at about the same level. The gain follows the loudest channel's
+/// level window by window and is ramped within each window, which avoids
+/// the zipper noise of gain steps.
+pub struct Agc {
+    /// Interleaved samples per window
+    window_len: usize,
+    channels: usize,
+    target: f32,
+    min_gain: f32,
+    max_gain: f32,
+    gate: f32,
+    gain: f32,
+}
+
+impl Agc {
+    pub fn new(format: InputFormat, target_db: f32, max_gain_db: f32) -> Self {
+        let channels = format.channels.max(1) as usize;
+        let frames = (format.sample_rate as usize * WINDOW_MS / 1000).max(1);
+        let max_gain_db = max_gain_db.abs();
+        Self {
+            window_len: frames * channels,
+            channels,
+            target: from_db(target_db),
+            min_gain: from_db(-max_gain_db),
+            max_gain: from_db(max_gain_db),
+            gate: from_db(GATE_DB),
+            gain: 1.0,
+        }
+    }
+
+    /// Current gain, 1.0 is unchanged.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Applies the gain to interleaved `data` in place.
+    pub fn process(&mut self, data: &mut [f32]) {
+        for window in data.chunks_mut(self.window_len) {
+            let start_gain = self.gain;
+            let level = loudest_rms(window, self.channels);
+            if level >= self.gate {
+                let wanted = (self.target / level).clamp(self.min_gain, self.max_gain);
+                let speed = if wanted < self.gain { ATTACK } else { RELEASE };
+                self.gain += (wanted - self.gain) * speed;
+            }
+
+            let frames = (window.len() / self.channels).max(1) as f32;
+            for (index, frame) in window.chunks_mut(self.channels).enumerate() {
+                let gain = start_gain + (self.gain - start_gain) * (index + 1) as f32 / frames;
+                frame.iter_mut().for_each(|sample| *sample = (*sample * gain).clamp(-1.0, 1.0));
+            }
+        }
+    }
+}
+
+/// RMS of the loudest channel of an interleaved window.
+fn loudest_rms(window: &[f32], channels: usize) -> f32 {
+    let frames = (window.len() / channels).max(1) as f32;
+    (0..channels)
+        .map(|channel| {
+            let power: f32 = window.iter().skip(channel).step_by(channels).map(|sample| sample * sample).sum();
+            (power / frames).sqrt()
+        })
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: InputFormat = InputFormat { sample_rate: 1000, channels: 1 };
+
+    fn tone(len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len).map(|n| if n % 2 == 0 { amplitude } else { -amplitude }).collect()
+    }
+
+    fn level_db(samples: &[f32]) -> f32 {
+        20.0 * loudest_rms(samples, 1).log10()
+    }
+
+    #[test]
+    fn quiet_and_loud_speech_end_up_at_the_target() {
+        for amplitude in [0.01, 0.9] {
+            let mut agc = Agc::new(FORMAT, -20.0, 30.0);
+            let mut audio = tone(2000, amplitude);
+            agc.process(&mut audio);
+            assert!((level_db(&audio[1800..]) + 20.0).abs() < 0.5, "{} dBFS", level_db(&audio[1800..]));
+        }
+    }
+
+    #[test]
+    fn background_noise_keeps_the_gain() {
+        let mut agc = Agc::new(FORMAT, -20.0, 30.0);
+        // -66 dBFS
+        let mut noise = tone(1000, 0.0005);
+        agc.process(&mut noise);
+        assert_eq!(agc.gain(), 1.0);
+    }
+
+    #[test]
+    fn gain_is_limited() {
+        let mut agc = Agc::new(FORMAT, -20.0, 12.0);
+        // -50 dBFS would need 30 dB
+        let mut audio = tone(2000, 0.00316);
+        agc.process(&mut audio);
+        assert!((agc.gain() - from_db(12.0)).abs() < 0.01);
+    }
+}
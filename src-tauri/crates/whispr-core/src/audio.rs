@@ -0,0 +1,1140 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use log::{error, warn, info, debug};
+use cpal::Host;
+use hound::{WavWriter, WavSpec};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use crate::config::{ConfigManager, DspSettings, OnEmpty, ResamplerBackend, WhisprConfig};
+use crate::dsp;
+use crate::ephemeral;
+use crate::input_device::{self, CpalInputDevice, DataCallback, DeviceEntry, ErrorCallback, InputDevice, InputError, InputFormat, InputStream};
+use crate::resample::{self, Resampler};
+use crate::storage;
+use crate::agc::Agc;
+use crate::vad::EnergyVad;
+use chrono::Local;
+use anyhow::Error;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+#[derive(Clone, PartialEq)]
+pub struct SilenceConfig {
+    enabled: bool,
+    threshold_db: f32,
+    window_ms: u32,
+    hangover_ms: u32,
+}
+
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -50.0,
+            window_ms: 20,
+            hangover_ms: 300,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct AgcConfig {
+    enabled: bool,
+    target_db: f32,
+    max_gain_db: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_db: -20.0,
+            max_gain_db: 30.0,
+        }
+    }
+}
+
+// Shortest and longest audio kept from before the dictation key
+const PRE_ROLL_RANGE: (f32, f32) = (0.5, 2.0);
+
+/// The last moments of audio before a capture, see `AudioManager::set_pre_roll`.
+#[derive(Default)]
+struct PreRoll {
+    samples: VecDeque<f32>,
+    /// Interleaved samples kept, 0 while off
+    capacity: usize,
+}
+
+// Callbacks arriving this much later than the previous buffer's duration
+// count as a gap in the captured audio
+const GAP_TOLERANCE: Duration = Duration::from_millis(50);
+// Samples this close to full scale were most likely cut off by the converter
+const CLIP_LEVEL: f32 = 0.99;
+
+/// Receives interleaved device audio with its format, see `AudioManager::set_tap`.
+pub type AudioTap = Box<dyn FnMut(&[f32], InputFormat) + Send>;
+
+/// Health of the current or last capture, to diagnose missing words.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CaptureStats {
+    pub callbacks: u64,
+    /// Samples delivered by the device, across all channels
+    pub total_samples: u64,
+    /// Samples kept after silence removal
+    pub kept_samples: u64,
+    /// Errors reported by the stream, e.g. overruns
+    pub stream_errors: u64,
+    /// Callbacks that arrived noticeably later than expected
+    pub gaps: u64,
+    pub longest_gap_ms: u64,
+    /// Samples at or near full scale, across all channels
+    pub clipped_samples: u64,
+}
+
+impl CaptureStats {
+    /// Share of the delivered samples that were clipping, in percent.
+    pub fn clip_percent(&self) -> f32 {
+        if self.total_samples == 0 {
+            return 0.0;
+        }
+        self.clipped_samples as f32 / self.total_samples as f32 * 100.0
+    }
+}
+
+impl std::fmt::Display for CaptureStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} callbacks, {} samples ({} kept, {:.2}% clipped), {} stream errors, {} gaps (longest {} ms)",
+            self.callbacks, self.total_samples, self.kept_samples, self.clip_percent(), self.stream_errors, self.gaps, self.longest_gap_ms
+        )
+    }
+}
+
+enum AudioCommand {
+    Start {
+        device: Arc<dyn InputDevice>,
+        on_data: DataCallback,
+        on_error: ErrorCallback,
+        reply: mpsc::Sender<Result<(), Error>>,
+    },
+    Stop {
+        reply: mpsc::Sender<()>,
+    },
+}
+
+/// Owns the input stream on a dedicated thread, since streams can't be moved
+/// between threads. Exits once the `AudioThread` is dropped.
+struct AudioThread {
+    commands: mpsc::Sender<AudioCommand>,
+}
+
+impl AudioThread {
+    fn spawn() -> Self {
+        let (commands, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("audio".to_string())
+            .spawn(move || {
+                let mut stream: Option<Box<dyn InputStream>> = None;
+                for command in receiver {
+                    match command {
+                        AudioCommand::Start { device, on_data, on_error, reply } => {
+                            stream = None;
+                            let result = device.open(on_data, on_error).map(|opened| stream = Some(opened));
+                            let _ = reply.send(result);
+                        }
+                        AudioCommand::Stop { reply } => {
+                            // Pause the stream before dropping to ensure clean shutdown
+                            if let Some(stream) = stream.take() {
+                                if let Err(e) = stream.pause() {
+                                    error!("Error pausing stream: {}", e);
+                                }
+                            }
+                            let _ = reply.send(());
+                        }
+                    }
+                }
+                debug!("Audio thread exiting");
+            })
+            .expect("Failed to spawn audio thread");
+        Self { commands }
+    }
+
+    fn start(&self, device: Arc<dyn InputDevice>, on_data: DataCallback, on_error: ErrorCallback) -> Result<(), Error> {
+        let (reply, response) = mpsc::channel();
+        self.commands.send(AudioCommand::Start { device, on_data, on_error, reply })
+            .map_err(|_| anyhow::anyhow!("Audio thread is not running"))?;
+        response.recv()
+            .map_err(|_| anyhow::anyhow!("Audio thread is not running"))?
+    }
+
+    fn stop(&self) {
+        let (reply, response) = mpsc::channel();
+        if self.commands.send(AudioCommand::Stop { reply }).is_ok() {
+            let _ = response.recv();
+        }
+    }
+}
+
+pub struct AudioManager {
+    /// Where devices are looked up, `None` with only a synthetic device
+    host: Option<Host>,
+    input_device: Arc<dyn InputDevice>,
+    /// Id of the selected device, `None` for the default device
+    input_device_id: Option<String>,
+    audio_thread: AudioThread,
+    is_capturing: Arc<Mutex<bool>>,
+    wav_writer: Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>,
+    /// Writer of the unprocessed recording, with `developer.save_raw_audio`
+    raw_wav_writer: Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>,
+    silence_config: Arc<Mutex<SilenceConfig>>,
+    agc_config: Arc<Mutex<AgcConfig>>,
+    pre_roll: Arc<Mutex<PreRoll>>,
+    /// Seconds of `pre_roll`, 0 for off
+    pre_roll_secs: f32,
+    /// The stream is open between captures to fill `pre_roll`
+    armed: bool,
+    /// The device of the open stream went away
+    stream_lost: Arc<AtomicBool>,
+    _start_time: Arc<Mutex<Option<Instant>>>,
+    captured_audio: Arc<Mutex<VecDeque<f32>>>,
+    /// Copy of the audio before silence removal, only kept when a retry on
+    /// empty transcriptions is configured
+    unfiltered_audio: Arc<Mutex<Option<VecDeque<f32>>>>,
+    resampler: Arc<dyn Resampler>,
+    dsp_settings: DspSettings,
+    stats: Arc<Mutex<CaptureStats>>,
+    /// Format of the running capture, `captured_audio` is in this format
+    capture_format: Option<InputFormat>,
+    /// Audio captured on a device that went away mid-recording, with its format
+    earlier_segments: Vec<(InputFormat, Vec<f32>)>,
+    device_lost_handler: Option<Arc<dyn Fn() + Send + Sync>>,
+    /// Told when the pre-roll stream opens and closes
+    armed_handler: Option<Arc<dyn Fn(bool) + Send + Sync>>,
+    /// Receives every buffer of device audio while capturing
+    tap: Arc<Mutex<Option<AudioTap>>>,
+    /// WAV file of the current or last capture, if recordings are saved
+    recording_path: Option<PathBuf>,
+}
+
+fn zero_deque(deque: &mut VecDeque<f32>) {
+    // Filled up to capacity, the slices cover every slot of the ring buffer
+    deque.resize(deque.capacity(), 0.0);
+    let (front, back) = deque.as_mut_slices();
+    front.zeroize();
+    back.zeroize();
+    deque.clear();
+}
+
+// Set by the first manager on the default host
+static HOST_OPENED: AtomicBool = AtomicBool::new(false);
+
+impl AudioManager {
+    /// Opens the default host. Keep exactly one of these and share it, every
+    /// other one opens the devices again.
+    pub fn new() -> Result<Self, Error> {
+        if HOST_OPENED.swap(true, Ordering::SeqCst) {
+            warn!("Another AudioManager was created, share the existing one instead");
+        }
+        let host = cpal::default_host();
+        let input_device = host
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
+        
+        info!("Using input device: {}", input_device.name()?);
+
+        let mut manager = Self::with_input_device(Arc::new(CpalInputDevice::new(input_device)));
+        manager.host = Some(host);
+        Ok(manager)
+    }
+
+    /// Creates a manager capturing from `input_device`, which doesn't have to
+    /// be backed by real hardware. Without a host, switching devices fails.
+    pub fn with_input_device(input_device: Arc<dyn InputDevice>) -> Self {
+        Self {
+            host: None,
+            input_device,
+            input_device_id: None,
+            audio_thread: AudioThread::spawn(),
+            is_capturing: Arc::new(Mutex::new(false)),
+            wav_writer: Arc::new(Mutex::new(None)),
+            raw_wav_writer: Arc::new(Mutex::new(None)),
+            silence_config: Arc::new(Mutex::new(SilenceConfig::default())),
+            agc_config: Arc::new(Mutex::new(AgcConfig::default())),
+            pre_roll: Arc::new(Mutex::new(PreRoll::default())),
+            pre_roll_secs: 0.0,
+            armed: false,
+            stream_lost: Arc::new(AtomicBool::new(false)),
+            _start_time: Arc::new(Mutex::new(None)),
+            captured_audio: Arc::new(Mutex::new(VecDeque::new())),
+            unfiltered_audio: Arc::new(Mutex::new(None)),
+            resampler: resample::create(ResamplerBackend::default()).into(),
+            dsp_settings: DspSettings::default(),
+            stats: Arc::new(Mutex::new(CaptureStats::default())),
+            capture_format: None,
+            earlier_segments: Vec::new(),
+            device_lost_handler: None,
+            armed_handler: None,
+            tap: Arc::new(Mutex::new(None)),
+            recording_path: None,
+        }
+    }
+
+    /// Called from the audio callback thread when the capturing device
+    /// disappears, once per stream.
+    pub fn set_device_lost_handler<F>(&mut self, handler: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.device_lost_handler = Some(Arc::new(handler));
+    }
+
+    /// Called whenever the microphone is opened or closed for the pre-roll
+    /// between captures, so it can be shown as in use.
+    pub fn set_armed_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(bool) + Send + Sync + 'static,
+    {
+        self.armed_handler = Some(Arc::new(handler));
+    }
+
+    fn set_armed(&mut self, armed: bool) {
+        if std::mem::replace(&mut self.armed, armed) != armed {
+            if let Some(handler) = &self.armed_handler {
+                handler(armed);
+            }
+        }
+    }
+
+    /// Passes the audio to `tap` as the device delivers it, before any
+    /// processing, until replaced or cleared with `None`. Called on the audio
+    /// callback thread, so it must not block.
+    pub fn set_tap(&self, tap: Option<AudioTap>) {
+        *self.tap.lock().unwrap() = tap;
+    }
+
+    fn host(&self) -> Result<&Host, Error> {
+        self.host.as_ref().ok_or_else(|| anyhow::anyhow!("No audio host to look up input devices on"))
+    }
+
+    /// Switches to the system default input device, returning its name.
+    pub fn use_default_input_device(&mut self) -> Result<String, Error> {
+        let device = self.host()?
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
+        let name = device.name()?;
+        self.input_device = Arc::new(CpalInputDevice::new(device));
+        self.input_device_id = None;
+        info!("Using input device: {}", name);
+        if self.armed {
+            self.disarm();
+            self.arm();
+        }
+        Ok(name)
+    }
+
+    /// Statistics of the running capture, or the last one once stopped.
+    pub fn capture_stats(&self) -> CaptureStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Switches to the device with `device_id`, see `DeviceEntry`.
+    pub fn set_input_device(&mut self, device_id: &str) -> Result<(), Error> {
+        let device = input_device::find_cpal_device(self.host()?, device_id)?
+            .ok_or_else(|| anyhow::anyhow!("Device not found: {}", device_id))?;
+        self.input_device = Arc::new(CpalInputDevice::new(device));
+        self.input_device_id = Some(device_id.to_string());
+        if self.armed {
+            self.disarm();
+            self.arm();
+        }
+        Ok(())
+    }
+
+    /// The device with `device_id`, or the default one without, looked up
+    /// on the shared host for captures of their own like the rolling buffer.
+    pub fn find_input_device(&self, device_id: Option<&str>) -> Result<cpal::Device, Error> {
+        let host = self.host()?;
+        match device_id {
+            Some(id) => input_device::find_cpal_device(host, id)?,
+            None => None,
+        }
+        .or_else(|| host.default_input_device())
+        .ok_or_else(|| anyhow::anyhow!("No input device available"))
+    }
+
+    /// The loopback device `input_device::pick_loopback` picks.
+    pub fn find_loopback_device(&self, preferred: Option<&str>) -> Result<(DeviceEntry, cpal::Device), Error> {
+        let devices = input_device::list_cpal_devices(self.host()?)?;
+        let entries: Vec<DeviceEntry> = devices.iter().map(|(entry, _)| entry.clone()).collect();
+        let entry = input_device::pick_loopback(&entries, preferred)
+            .ok_or_else(|| anyhow::anyhow!("No loopback device found, install one such as BlackHole"))?
+            .clone();
+        devices.into_iter().find(|(candidate, _)| candidate.id == entry.id)
+            .ok_or_else(|| anyhow::anyhow!("Loopback device {} disappeared", entry.name))
+    }
+
+    /// Switches to a loopback device to capture what the Mac plays, see
+    /// `input_device::pick_loopback`. Returns its name.
+    pub fn use_loopback_device(&mut self, preferred: Option<&str>) -> Result<String, Error> {
+        let (entry, _) = self.find_loopback_device(preferred)?;
+        self.set_input_device(&entry.id)?;
+        info!("Capturing system audio from {}", entry.name);
+        Ok(entry.name)
+    }
+
+    pub fn get_current_device_name(&self) -> Result<String, Error> {
+        self.input_device.name()
+    }
+
+    /// Id of the capturing device. The default device is taken to be the
+    /// first one with its name.
+    pub fn get_current_device_id(&self) -> Result<String, Error> {
+        match &self.input_device_id {
+            Some(id) => Ok(id.clone()),
+            None => self.input_device.name(),
+        }
+    }
+
+    pub fn configure_silence_removal(&self, enabled: bool, threshold_db: Option<f32>, window_ms: Option<u32>, hangover_ms: Option<u32>) {
+        let mut config = self.silence_config.lock().unwrap();
+        config.enabled = enabled;
+        if let Some(t) = threshold_db {
+            config.threshold_db = t;
+        }
+        if let Some(w) = window_ms {
+            config.window_ms = w;
+        }
+        if let Some(h) = hangover_ms {
+            config.hangover_ms = h;
+        }
+    }
+
+    pub fn configure_agc(&self, enabled: bool, target_db: f32, max_gain_db: f32) {
+        *self.agc_config.lock().unwrap() = AgcConfig { enabled, target_db, max_gain_db };
+    }
+
+    /// Keeps the microphone open between dictations and holds on to its last
+    /// `secs` (0.5 to 2, 0 turns it off), which the next capture starts with.
+    /// Catches the first words of people who start speaking just before they
+    /// press the dictation key.
+    pub fn set_pre_roll(&mut self, secs: f32) {
+        self.pre_roll_secs = if secs > 0.0 { secs.clamp(PRE_ROLL_RANGE.0, PRE_ROLL_RANGE.1) } else { 0.0 };
+        // A running capture picks it up once it stops
+        if self.is_capturing() {
+            return;
+        }
+        self.disarm();
+        self.arm();
+    }
+
+    /// Opens the stream to fill the pre-roll until the next capture.
+    fn arm(&mut self) {
+        if self.pre_roll_secs <= 0.0 {
+            return;
+        }
+        let format = match self.input_device.format() {
+            Ok(format) => format,
+            Err(e) => {
+                warn!("Can't keep audio from before dictations: {}", e);
+                return;
+            }
+        };
+        self.pre_roll.lock().unwrap().capacity =
+            (self.pre_roll_secs * format.sample_rate as f32) as usize * format.channels.max(1) as usize;
+        let result = self.open_stream(
+            format,
+            self.stats.clone(),
+            self.is_capturing.clone(),
+            self.wav_writer.clone(),
+            self.silence_config.clone(),
+            self._start_time.clone(),
+            self.captured_audio.clone(),
+            self.unfiltered_audio.clone(),
+        );
+        match result {
+            Ok(()) => {
+                self.set_armed(true);
+                debug!("Keeping the last {:.1}s before dictations", self.pre_roll_secs);
+            }
+            Err(e) => warn!("Can't keep audio from before dictations: {}", e),
+        }
+    }
+
+    /// Closes the pre-roll stream and wipes what it held.
+    fn disarm(&mut self) {
+        if self.armed {
+            self.set_armed(false);
+            self.audio_thread.stop();
+        }
+        let mut pre_roll = self.pre_roll.lock().unwrap();
+        pre_roll.capacity = 0;
+        zero_deque(&mut pre_roll.samples);
+    }
+
+    pub fn is_silence_removal_enabled(&self) -> bool {
+        self.silence_config.lock().unwrap().enabled
+    }
+
+    pub fn list_input_devices(&self) -> Result<Vec<DeviceEntry>, Error> {
+        Ok(input_device::list_cpal_devices(self.host()?)?.into_iter().map(|(entry, _)| entry).collect())
+    }
+
+    pub fn start_capture(&mut self) -> Result<(), Error> {
+        let format = self.input_device.format()?;
+        debug!("Using input format: {:?}", format);
+
+        let spec = WavSpec {
+            channels: format.channels,
+            sample_rate: format.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+
+        let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+        let whispr_config = config_manager.load_config("settings").expect("Failed to load configuration");
+
+        let save_recordings = whispr_config.developer.save_recordings && !ephemeral::is_active() && match storage::check_free_space(config_manager.get_config_dir(), 0, &whispr_config) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Not saving recording: {}", e);
+                false
+            }
+        };
+
+        let (writer, raw_writer) = if save_recordings {
+            let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+            let recordings_dir = config_manager.get_config_dir().join("recordings");
+            let file_path = recordings_dir.join(format!("{}.wav", timestamp));
+            std::fs::create_dir_all(&recordings_dir).expect("Failed to create recordings directory");
+            info!("Saving recording to: {}", file_path.display());
+            let writer = WavWriter::create(&file_path, spec)?;
+            // Tells capture problems apart from what silence removal made of them
+            let raw_writer = if whispr_config.developer.save_raw_audio {
+                let raw_path = recordings_dir.join(format!("{}.raw.wav", timestamp));
+                info!("Saving raw audio to: {}", raw_path.display());
+                Some(WavWriter::create(&raw_path, spec)?)
+            } else {
+                None
+            };
+            self.recording_path = Some(file_path);
+            (Some(writer), raw_writer)
+        } else {
+            self.recording_path = None;
+            (None, None)
+        };
+
+        let keep_unfiltered = whispr_config.output.on_empty == OnEmpty::Retry;
+        self.begin_capture(format, writer, raw_writer, keep_unfiltered)
+    }
+
+    /// Starts capturing `format` from the input device into the given
+    /// writers, the part of `start_capture` that doesn't need the settings.
+    fn begin_capture(
+        &mut self,
+        format: InputFormat,
+        writer: Option<WavWriter<BufWriter<File>>>,
+        raw_writer: Option<WavWriter<BufWriter<File>>>,
+        keep_unfiltered: bool,
+    ) -> Result<(), Error> {
+        *self.wav_writer.lock().unwrap() = writer;
+        *self.raw_wav_writer.lock().unwrap() = raw_writer;
+        *self.unfiltered_audio.lock().unwrap() = keep_unfiltered.then(VecDeque::new);
+        *self._start_time.lock().unwrap() = Some(Instant::now());
+
+        let is_capturing = self.is_capturing.clone();
+        let wav_writer = self.wav_writer.clone();
+        let silence_config = self.silence_config.clone();
+        let _start_time = self._start_time.clone();
+        let captured_audio = self.captured_audio.clone();
+        let unfiltered_audio = self.unfiltered_audio.clone();
+        *self.stats.lock().unwrap() = CaptureStats::default();
+        let stats = self.stats.clone();
+
+        // Clear any existing audio data before starting new capture
+        captured_audio.lock().unwrap().clear();
+        self.earlier_segments.clear();
+
+        // The pre-roll stream carries on as the capture, the audio it holds
+        // goes first
+        let reuse = self.armed && self.capture_format == Some(format) && !self.stream_lost.load(Ordering::SeqCst);
+        if reuse {
+            self.set_armed(false);
+            debug!("Capturing on the pre-roll stream");
+        } else {
+            self.disarm();
+            self.open_stream(format, stats, is_capturing, wav_writer, silence_config, _start_time, captured_audio, unfiltered_audio)?;
+        }
+        *self.is_capturing.lock().unwrap() = true;
+
+        info!("Capture started");
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn open_stream(
+        &mut self,
+        format: InputFormat,
+        stats: Arc<Mutex<CaptureStats>>,
+        is_capturing: Arc<Mutex<bool>>,
+        wav_writer: Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>,
+        silence_config: Arc<Mutex<SilenceConfig>>,
+        _start_time: Arc<Mutex<Option<Instant>>>,
+        captured_audio: Arc<Mutex<VecDeque<f32>>>,
+        unfiltered_audio: Arc<Mutex<Option<VecDeque<f32>>>>,
+    ) -> Result<(), Error> {
+        let input_data_fn = self.build_input_callback(format, stats, is_capturing, wav_writer, silence_config, _start_time, captured_audio, unfiltered_audio);
+        let error_stats = self.stats.clone();
+        let device_lost_handler = self.device_lost_handler.clone();
+        self.stream_lost.store(false, Ordering::SeqCst);
+        let stream_lost = self.stream_lost.clone();
+        // Only this stream's first loss is reported, a device going away
+        // raises errors until the stream is stopped
+        let reported = AtomicBool::new(false);
+        let input_error_fn: ErrorCallback = Box::new(move |error| {
+            error_stats.lock().unwrap().stream_errors += 1;
+            if let InputError::DeviceLost = error {
+                stream_lost.store(true, Ordering::SeqCst);
+                if reported.swap(true, Ordering::SeqCst) {
+                    return;
+                }
+                if let Some(handler) = &device_lost_handler {
+                    handler();
+                }
+            }
+        });
+        self.audio_thread.start(self.input_device.clone(), input_data_fn, input_error_fn)?;
+        self.capture_format = Some(format);
+        Ok(())
+    }
+
+    /// Continues a running capture on the default input device after the
+    /// previous one disappeared, keeping the audio captured so far. Returns
+    /// the name of the new device.
+    pub fn restart_capture_on_default_device(&mut self) -> Result<String, Error> {
+        self.audio_thread.stop();
+        if let Some(format) = self.capture_format.take() {
+            let captured: Vec<f32> = self.captured_audio.lock().unwrap().drain(..).collect();
+            if !captured.is_empty() {
+                self.earlier_segments.push((format, captured));
+            }
+        }
+        // The unfiltered copy can't be stitched together reliably, skip the retry
+        self.unfiltered_audio.lock().unwrap().take();
+
+        let name = self.use_default_input_device()?;
+        let format = self.input_device.format()?;
+
+        // A WAV file can't change its format midway
+        let mut wav_writer = self.wav_writer.lock().unwrap();
+        let format_changed = wav_writer.as_ref()
+            .is_some_and(|writer| writer.spec().channels != format.channels || writer.spec().sample_rate != format.sample_rate);
+        if format_changed {
+            warn!("New input device uses a different format, no longer saving this recording");
+            self.recording_path = None;
+            if let Some(writer) = wav_writer.take() {
+                if let Err(e) = writer.finalize() {
+                    error!("Error finalizing WAV file: {}", e);
+                }
+            }
+            self.finalize_raw_recording();
+        }
+        drop(wav_writer);
+
+        self.open_stream(
+            format,
+            self.stats.clone(),
+            self.is_capturing.clone(),
+            self.wav_writer.clone(),
+            self.silence_config.clone(),
+            self._start_time.clone(),
+            self.captured_audio.clone(),
+            self.unfiltered_audio.clone(),
+        )?;
+        info!("Capture continued on {}", name);
+        Ok(name)
+    }
+
+    pub fn stop_capture(&mut self) {
+        // First mark as not capturing to prevent any new data from being processed
+        *self.is_capturing.lock().unwrap() = false;
+
+        // Ensure proper stream shutdown
+        self.audio_thread.stop();
+        
+        // Clean up WAV writer
+        if let Some(writer) = self.wav_writer.lock().unwrap().take() {
+            if let Err(e) = writer.finalize() {
+                error!("Error finalizing WAV file: {}", e);
+            }
+        }
+        self.finalize_raw_recording();
+
+        // Log timing information
+        if let Some(start_time) = self._start_time.lock().unwrap().take() {
+            let duration = start_time.elapsed();
+            info!("Recording stopped after: {:.2}s", duration.as_secs_f32());
+            info!("Capture stats: {}", self.stats.lock().unwrap());
+            self.write_sidecar();
+        }
+        
+        // Small delay to ensure all audio data has been processed
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // Log audio buffer size but don't clear it yet - it will be cleared when get_captured_audio is called
+        let samples = self.captured_audio.lock().unwrap().len();
+        debug!("Audio buffer contains {} samples", samples);
+
+        // Additional delay to ensure complete cleanup
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        self.set_armed(false);
+        self.arm();
+    }
+
+    fn finalize_raw_recording(&self) {
+        if let Some(writer) = self.raw_wav_writer.lock().unwrap().take() {
+            if let Err(e) = writer.finalize() {
+                error!("Error finalizing raw WAV file: {}", e);
+            }
+        }
+    }
+
+    fn build_input_callback(
+        &self,
+        format: InputFormat,
+        stats: Arc<Mutex<CaptureStats>>,
+        is_capturing: Arc<Mutex<bool>>,
+        wav_writer: Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>,
+        silence_config: Arc<Mutex<SilenceConfig>>,
+        _start_time: Arc<Mutex<Option<Instant>>>,
+        captured_audio: Arc<Mutex<VecDeque<f32>>>,
+        unfiltered_audio: Arc<Mutex<Option<VecDeque<f32>>>>,
+    ) -> DataCallback {
+        // Rebuilt when the settings change, e.g. from the tray menu
+        let mut vad: Option<(SilenceConfig, EnergyVad)> = None;
+        let mut agc: Option<(AgcConfig, Agc)> = None;
+        let agc_config = self.agc_config.clone();
+        let mut last_callback: Option<(Instant, Duration)> = None;
+        let samples_per_second = format.sample_rate as f64 * format.channels.max(1) as f64;
+        let raw_wav_writer = self.raw_wav_writer.clone();
+        let tap = self.tap.clone();
+        let pre_roll = self.pre_roll.clone();
+
+        let input_data_fn = move |data: &[f32]| {
+            if !*is_capturing.lock().unwrap() {
+                let mut pre_roll = pre_roll.lock().unwrap();
+                if pre_roll.capacity > 0 {
+                    pre_roll.samples.extend(data);
+                    let overflow = pre_roll.samples.len().saturating_sub(pre_roll.capacity);
+                    pre_roll.samples.drain(..overflow);
+                }
+                // The next capture starts afresh
+                last_callback = None;
+                vad = None;
+                return;
+            }
+
+            let mut with_pre_roll;
+            let data = {
+                let mut pre_roll = pre_roll.lock().unwrap();
+                if pre_roll.samples.is_empty() {
+                    data
+                } else {
+                    with_pre_roll = Vec::with_capacity(pre_roll.samples.len() + data.len());
+                    with_pre_roll.extend(pre_roll.samples.drain(..));
+                    with_pre_roll.extend_from_slice(data);
+                    &with_pre_roll[..]
+                }
+            };
+
+            if let Some(tap) = tap.lock().unwrap().as_mut() {
+                tap(data, format);
+            }
+            if let Some(writer) = raw_wav_writer.lock().unwrap().as_mut() {
+                for &sample in data {
+                    writer.write_sample(sample).unwrap_or_else(|e| error!("Error writing raw sample: {}", e));
+                }
+            }
+
+            let now = Instant::now();
+            {
+                let mut stats = stats.lock().unwrap();
+                stats.callbacks += 1;
+                stats.total_samples += data.len() as u64;
+                stats.clipped_samples += data.iter().filter(|sample| sample.abs() >= CLIP_LEVEL).count() as u64;
+                if let Some((previous, expected)) = last_callback {
+                    let elapsed = now.duration_since(previous);
+                    if elapsed > expected + GAP_TOLERANCE {
+                        stats.gaps += 1;
+                        stats.longest_gap_ms = stats.longest_gap_ms.max((elapsed - expected).as_millis() as u64);
+                    }
+                }
+            }
+            last_callback = Some((now, Duration::from_secs_f64(data.len() as f64 / samples_per_second)));
+
+            // Gain control comes before silence removal, its threshold then
+            // fits every microphone
+            let agc_cfg = agc_config.lock().unwrap().clone();
+            let mut leveled;
+            let data = if agc_cfg.enabled {
+                if agc.as_ref().map(|(current, _)| current) != Some(&agc_cfg) {
+                    let control = Agc::new(format, agc_cfg.target_db, agc_cfg.max_gain_db);
+                    agc = Some((agc_cfg, control));
+                }
+                leveled = data.to_vec();
+                if let Some((_, control)) = agc.as_mut() {
+                    control.process(&mut leveled);
+                }
+                &leveled[..]
+            } else {
+                data
+            };
+
+            let silence_cfg = silence_config.lock().unwrap().clone();
+
+            // Process samples without holding locks
+            let mut samples_to_keep = Vec::with_capacity(data.len());
+            
+            if silence_cfg.enabled {
+                if let Some(unfiltered) = unfiltered_audio.lock().unwrap().as_mut() {
+                    unfiltered.extend(data);
+                }
+                if vad.as_ref().map(|(current, _)| current) != Some(&silence_cfg) {
+                    let detector = EnergyVad::new(format, silence_cfg.threshold_db, silence_cfg.window_ms, silence_cfg.hangover_ms);
+                    vad = Some((silence_cfg, detector));
+                }
+                if let Some((_, detector)) = vad.as_mut() {
+                    detector.process(data, &mut samples_to_keep);
+                }
+            } else {
+                samples_to_keep.extend_from_slice(data);
+            }
+
+            // Write samples in a single batch with minimal lock time
+            {
+                let mut writer_guard = wav_writer.lock().unwrap();
+                if let Some(ref mut writer) = *writer_guard {
+                    // Write all samples at once to minimize lock time
+                    for &sample in &samples_to_keep {
+                        writer.write_sample(sample).unwrap_or_else(|e| error!("Error writing sample: {}", e));
+                    }
+                }
+            } // writer lock is released here
+
+            stats.lock().unwrap().kept_samples += samples_to_keep.len() as u64;
+
+            // Update audio buffer in a single batch with minimal lock time
+            {
+                let mut audio_buffer = captured_audio.lock().unwrap();
+                audio_buffer.extend(samples_to_keep);
+            } // audio buffer lock is released here
+        };
+
+        Box::new(input_data_fn)
+    }
+
+    /// Writes the capture stats next to the saved recording, as
+    /// `<recording>.json`.
+    fn write_sidecar(&self) {
+        let Some(recording_path) = &self.recording_path else {
+            return;
+        };
+        let stats = self.capture_stats();
+        let sidecar = serde_json::json!({
+            "stats": stats,
+            "clip_percent": stats.clip_percent(),
+        });
+        let path = recording_path.with_extension("json");
+        if let Err(e) = std::fs::write(&path, sidecar.to_string()) {
+            error!("Failed to write recording sidecar {}: {}", path.display(), e);
+        }
+    }
+
+    /// The saved WAV file of the last capture. It holds exactly the audio
+    /// that was transcribed, so segment timestamps map onto it.
+    pub fn recording_path(&self) -> Option<PathBuf> {
+        self.recording_path.clone()
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        *self.is_capturing.lock().unwrap()
+    }
+
+    /// Drops any audio captured so far without processing it.
+    pub fn discard_captured_audio(&mut self) {
+        self.captured_audio.lock().unwrap().clear();
+        self.earlier_segments.clear();
+        self.unfiltered_audio.lock().unwrap().take();
+    }
+
+    /// RMS level of the audio captured so far in dBFS, before any DSP.
+    pub fn captured_level_db(&self) -> Option<f32> {
+        let captured_audio = self.captured_audio.lock().unwrap();
+        let earlier = self.earlier_segments.iter().flat_map(|(_, data)| data.iter());
+        dsp::rms_db(earlier.chain(captured_audio.iter()))
+    }
+
+    /// Overwrites every buffer of the last capture and the pre-roll with
+    /// zeros, spare capacity included, and empties them.
+    pub fn scrub_buffers(&mut self) {
+        zero_deque(&mut self.captured_audio.lock().unwrap());
+        if let Some(unfiltered_audio) = self.unfiltered_audio.lock().unwrap().as_mut() {
+            zero_deque(unfiltered_audio);
+        }
+        for (_, data) in &mut self.earlier_segments {
+            data.zeroize();
+        }
+        self.earlier_segments.clear();
+        zero_deque(&mut self.pre_roll.lock().unwrap().samples);
+    }
+
+    /// The last recording without silence removal, if it was kept. Consumes
+    /// the copy, so it can only be retrieved once.
+    pub fn get_unfiltered_audio(&self, desired_sample_rate: u32, desired_channels: u16) -> Result<Option<Vec<f32>>, Error> {
+        let Some(audio_data) = self.unfiltered_audio.lock().unwrap().take() else {
+            return Ok(None);
+        };
+        if audio_data.is_empty() {
+            return Ok(None);
+        }
+        self.convert_audio(vec![(self.capture_format()?, audio_data.into())], desired_sample_rate, desired_channels)
+    }
+
+    pub fn set_resampler(&mut self, backend: ResamplerBackend) {
+        self.resampler = resample::create(backend).into();
+        debug!("Using {} resampler", self.resampler.name());
+    }
+
+    pub fn set_dsp(&mut self, settings: DspSettings) {
+        self.dsp_settings = settings;
+    }
+
+    pub fn set_remove_silence(&mut self, remove_silence: bool) {
+        self.configure_silence_removal(remove_silence, None, None, None);
+    }
+
+    /// Format of the current or last capture.
+    fn capture_format(&self) -> Result<InputFormat, Error> {
+        match self.capture_format {
+            Some(format) => Ok(format),
+            None => self.input_device.format(),
+        }
+    }
+
+    /// The captured audio converted to the desired format, including audio
+    /// from a device that went away mid-recording. `Ok(None)` if nothing was
+    /// captured, errors if the conversion failed.
+    pub fn get_captured_audio(&mut self, desired_sample_rate: u32, desired_channels: u16) -> Result<Option<Vec<f32>>, Error> {
+        let mut segments = std::mem::take(&mut self.earlier_segments);
+        let audio_data: Vec<f32> = self.captured_audio.lock().unwrap().drain(..).collect();
+        if !audio_data.is_empty() {
+            segments.push((self.capture_format()?, audio_data));
+        }
+
+        if segments.is_empty() {
+            debug!("Audio buffer is empty");
+            return Ok(None);
+        }
+        let buffer_len: usize = segments.iter().map(|(_, data)| data.len()).sum();
+        debug!("Processing {} samples from audio buffer", buffer_len);
+        self.convert_audio(segments, desired_sample_rate, desired_channels)
+    }
+
+    /// The last `secs` of the running capture converted to the desired format,
+    /// leaving the capture untouched, to check what was just said.
+    pub fn recent_audio(&self, secs: f32, desired_sample_rate: u32, desired_channels: u16) -> Result<Option<Vec<f32>>, Error> {
+        let format = self.capture_format()?;
+        let wanted = (secs * format.sample_rate as f32) as usize * format.channels as usize;
+        let recent: Vec<f32> = {
+            let captured_audio = self.captured_audio.lock().unwrap();
+            let skip = captured_audio.len().saturating_sub(wanted);
+            captured_audio.iter().skip(skip).copied().collect()
+        };
+        if recent.is_empty() {
+            return Ok(None);
+        }
+        self.convert_audio(vec![(format, recent)], desired_sample_rate, desired_channels)
+    }
+
+    /// Converts saved recordings the same way as captured audio, e.g. to
+    /// transcribe them again, without holding the `AudioManager` meanwhile.
+    pub fn recording_converter(&self) -> RecordingConverter {
+        RecordingConverter { resampler: self.resampler.clone(), dsp_settings: self.dsp_settings.clone() }
+    }
+
+    fn convert_audio(&self, segments: Vec<(InputFormat, Vec<f32>)>, desired_sample_rate: u32, desired_channels: u16) -> Result<Option<Vec<f32>>, Error> {
+        convert_audio(&*self.resampler, &self.dsp_settings, segments, desired_sample_rate, desired_channels)
+    }
+}
+
+/// The resampler and DSP settings of an `AudioManager` at the time it was
+/// asked, see `AudioManager::recording_converter`.
+pub struct RecordingConverter {
+    resampler: Arc<dyn Resampler>,
+    dsp_settings: DspSettings,
+}
+
+impl RecordingConverter {
+    pub fn convert(&self, format: InputFormat, data: Vec<f32>, desired_sample_rate: u32, desired_channels: u16) -> Result<Option<Vec<f32>>, Error> {
+        convert_audio(&*self.resampler, &self.dsp_settings, vec![(format, data)], desired_sample_rate, desired_channels)
+    }
+}
+
+/// Downmixes and resamples each segment of raw device audio to the desired
+/// format, joins them and runs the DSP stage.
+fn convert_audio(
+    resampler: &dyn Resampler,
+    dsp_settings: &DspSettings,
+    segments: Vec<(InputFormat, Vec<f32>)>,
+    desired_sample_rate: u32,
+    desired_channels: u16,
+) -> Result<Option<Vec<f32>>, Error> {
+    let mut processed_audio = Vec::new();
+    for (format, audio_data) in segments {
+        processed_audio.extend(convert_segment(resampler, dsp_settings, audio_data, format, desired_sample_rate, desired_channels)?);
+    }
+
+    if desired_channels == 1 {
+        dsp::process(&mut processed_audio, desired_sample_rate, dsp_settings);
+    }
+
+    if processed_audio.is_empty() {
+        warn!("Processed audio is empty after conversion");
+        Ok(None)
+    } else {
+        debug!("Successfully processed {} samples", processed_audio.len());
+        Ok(Some(processed_audio))
+    }
+}
+
+fn convert_segment(
+    resampler: &dyn Resampler,
+    dsp_settings: &DspSettings,
+    audio_data: Vec<f32>,
+    format: InputFormat,
+    desired_sample_rate: u32,
+    desired_channels: u16,
+) -> Result<Vec<f32>, Error> {
+    let captured_sample_rate = format.sample_rate;
+    let captured_channels = format.channels;
+    debug!("Captured format: {}Hz, {} channels", captured_sample_rate, captured_channels);
+    debug!("Desired format: {}Hz, {} channels", desired_sample_rate, desired_channels);
+
+    let mut processed_audio = audio_data;
+    let initial_len = processed_audio.len();
+
+    if captured_channels > 1 && desired_channels == 1 {
+        processed_audio = dsp::downmix(&processed_audio, captured_channels);
+        debug!("Converted {} channels to mono: {} -> {} samples", captured_channels, initial_len, processed_audio.len());
+    }
+
+    // RNNoise runs at 48 kHz, so denoising resamples twice unless the
+    // device records at that rate
+    let mut sample_rate = captured_sample_rate;
+    if dsp_settings.denoise && desired_channels == 1 {
+        if sample_rate != dsp::DENOISE_SAMPLE_RATE {
+            processed_audio = resampler.resample(&processed_audio, sample_rate, dsp::DENOISE_SAMPLE_RATE, desired_channels)?;
+            sample_rate = dsp::DENOISE_SAMPLE_RATE;
+        }
+        processed_audio = dsp::denoise(&processed_audio);
+        debug!("Denoised {} samples", processed_audio.len());
+    }
+
+    // Resample if needed
+    if sample_rate != desired_sample_rate {
+        let before_resample = processed_audio.len();
+        processed_audio = resampler.resample(
+            &processed_audio,
+            sample_rate,
+            desired_sample_rate,
+            desired_channels,
+        )?;
+        debug!("Resampled audio: {} -> {} samples", before_resample, processed_audio.len());
+    }
+
+    Ok(processed_audio)
+}
+
+impl Drop for AudioManager {
+    fn drop(&mut self) {
+        self.pre_roll_secs = 0.0;
+        self.stop_capture();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_device::SyntheticInputDevice;
+
+    fn manager(sample_rate: u32, channels: u16) -> (AudioManager, Arc<SyntheticInputDevice>) {
+        let device = Arc::new(SyntheticInputDevice::new(sample_rate, channels));
+        let mut manager = AudioManager::with_input_device(device.clone());
+        // Keeps constant test signals as they are
+        manager.set_dsp(DspSettings { remove_dc_offset: false, ..DspSettings::default() });
+        (manager, device)
+    }
+
+    fn capture(manager: &mut AudioManager, device: &SyntheticInputDevice, samples: &[f32]) -> Option<Vec<f32>> {
+        let format = manager.input_device.format().unwrap();
+        manager.begin_capture(format, None, None, false).unwrap();
+        device.feed(samples, 512);
+        *manager.is_capturing.lock().unwrap() = false;
+        manager.audio_thread.stop();
+        manager.get_captured_audio(16000, 1).unwrap()
+    }
+
+    #[test]
+    fn downmixes_to_mono() {
+        let (mut manager, device) = manager(16000, 2);
+        let stereo: Vec<f32> = (0..1600).flat_map(|_| [0.2, 0.4]).collect();
+        let mono = capture(&mut manager, &device, &stereo).unwrap();
+        assert_eq!(mono.len(), 1600);
+        assert!(mono.iter().all(|sample| (sample - 0.3).abs() < 1e-6));
+    }
+
+    #[test]
+    fn resamples_to_16_khz() {
+        let (mut manager, device) = manager(48000, 1);
+        let tone: Vec<f32> = (0..48000).map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() * 0.5).collect();
+        let resampled = capture(&mut manager, &device, &tone).unwrap();
+        assert!(resampled.len().abs_diff(16000) < 160, "{} samples", resampled.len());
+    }
+
+    #[test]
+    fn trims_silence() {
+        let (mut manager, device) = manager(16000, 1);
+        manager.configure_silence_removal(true, Some(-50.0), Some(20), Some(300));
+        let silence = vec![0.0; 16000];
+        let tone: Vec<f32> = (0..16000).map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin() * 0.5).collect();
+        let samples: Vec<f32> = [&silence[..], &tone[..], &silence[..]].concat();
+        let kept = capture(&mut manager, &device, &samples).unwrap();
+        // The tone and the hangover after it
+        assert!((16000..=16000 + 4800 + 320).contains(&kept.len()), "{} samples", kept.len());
+        assert_eq!(manager.capture_stats().total_samples, 48000);
+    }
+
+    #[test]
+    fn caps_the_pre_roll_buffer() {
+        let (mut manager, device) = manager(16000, 1);
+        manager.set_pre_roll(0.5);
+        device.feed(&[0.1; 32000], 512);
+        assert_eq!(manager.pre_roll.lock().unwrap().samples.len(), 8000);
+
+        // Goes in front of the capture
+        let captured = capture(&mut manager, &device, &[0.1; 1600]).unwrap();
+        assert_eq!(captured.len(), 8000 + 1600);
+    }
+
+    #[test]
+    fn switching_devices_needs_a_host() {
+        let (mut manager, _) = manager(16000, 1);
+        assert!(manager.set_input_device("USB Mic").is_err());
+        assert_eq!(manager.get_current_device_name().unwrap(), "Synthetic");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::config::CjkSettings;
+
+/// Languages the transforms apply to unless turned off. Korean separates
+/// words with spaces and uses ASCII punctuation, so only its Hanja are
+/// affected.
+const CJK_LANGUAGES: &[&str] = &["zh", "yue", "ja", "ko"];
+
+/// Han, kana and their punctuation, but not Hangul.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        // CJK symbols and punctuation, hiragana, katakana
+        '\u{3000}'..='\u{30FF}'
+        // CJK unified ideographs and extension A
+        | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+        // Compatibility ideographs
+        | '\u{F900}'..='\u{FAFF}'
+        // Full-width forms, but not the half-width katakana and Hangul after them
+        | '\u{FF01}'..='\u{FF60}'
+        // Extensions B and later
+        | '\u{20000}'..='\u{3FFFF}'
+    )
+}
+
+/// The full-width form of ASCII punctuation after Chinese or Japanese text.
+fn full_width(c: char, japanese: bool) -> Option<char> {
+    Some(match c {
+        '.' => '。',
+        ',' if japanese => '、',
+        ',' => '，',
+        '?' => '？',
+        '!' => '！',
+        ':' => '：',
+        ';' => '；',
+        '(' => '（',
+        ')' => '）',
+        _ => return None,
+    })
+}
+
+/// Whether a transform set to `setting` applies to a dictation in `language`,
+/// `None` applies it to Chinese, Japanese and Korean.
+fn enabled(setting: Option<bool>, language: Option<&str>) -> bool {
+    setting.unwrap_or_else(|| language.is_some_and(|language| CJK_LANGUAGES.contains(&language)))
+}
+
+/// Whisper often puts spaces between Chinese or Japanese characters and uses
+/// ASCII punctuation where they take full-width forms. Removes spaces
+/// between two CJK characters, or after one at the end, and turns ASCII
+/// punctuation that follows a CJK character full-width, as set in
+/// `output.cjk`.
+pub fn format(text: &str, language: Option<&str>, settings: &CjkSettings) -> String {
+    let remove_spaces = enabled(settings.remove_spaces, language);
+    let full_width_punctuation = enabled(settings.full_width_punctuation, language);
+    if !remove_spaces && !full_width_punctuation {
+        return text.to_string();
+    }
+    let japanese = language == Some("ja");
+
+    let mut chars: Vec<char> = text.chars().collect();
+    if full_width_punctuation {
+        for i in 1..chars.len() {
+            if is_cjk(chars[i - 1]) {
+                if let Some(wide) = full_width(chars[i], japanese) {
+                    chars[i] = wide;
+                }
+            }
+        }
+    }
+    if !remove_spaces {
+        return chars.into_iter().collect();
+    }
+
+    let mut formatted = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == ' ' {
+            let end = chars[i..].iter().position(|c| *c != ' ').map_or(chars.len(), |offset| i + offset);
+            let after_cjk = formatted.chars().last().is_some_and(is_cjk);
+            let before_cjk = match chars.get(end) {
+                Some(next) => is_cjk(*next),
+                None => true,
+            };
+            if !(after_cjk && before_cjk) {
+                formatted.extend(&chars[i..end]);
+            }
+            i = end;
+            continue;
+        }
+        formatted.push(chars[i]);
+        i += 1;
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chinese_loses_spaces_and_gets_full_width_punctuation() {
+        let settings = CjkSettings::default();
+        assert_eq!(format("你好 世界, 今天 天气 怎么样? ", Some("zh"), &settings), "你好世界，今天天气怎么样？");
+        // Spaces next to Latin words and decimals stay as they are
+        assert_eq!(format("我用 iPhone 15 拍的, 3.5 秒.", Some("zh"), &settings), "我用 iPhone 15 拍的，3.5 秒。");
+    }
+
+    #[test]
+    fn japanese_uses_its_own_comma() {
+        assert_eq!(format("はい, そうです. ", Some("ja"), &CjkSettings::default()), "はい、そうです。");
+    }
+
+    #[test]
+    fn korean_keeps_its_spaces() {
+        assert_eq!(format("안녕하세요, 반갑습니다. ", Some("ko"), &CjkSettings::default()), "안녕하세요, 반갑습니다. ");
+    }
+
+    #[test]
+    fn other_languages_only_when_turned_on() {
+        assert_eq!(format("你好 世界.", Some("en"), &CjkSettings::default()), "你好 世界.");
+        assert_eq!(format("你好 世界.", None, &CjkSettings::default()), "你好 世界.");
+        let settings = CjkSettings { remove_spaces: Some(true), full_width_punctuation: Some(false) };
+        assert_eq!(format("你好 世界.", None, &settings), "你好世界.");
+        let settings = CjkSettings { remove_spaces: Some(false), full_width_punctuation: None };
+        assert_eq!(format("你好 世界.", Some("zh"), &settings), "你好 世界。");
+    }
+}
@@ -0,0 +1,1152 @@
+use anyhow::Result;
+use log::info;
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use serde_json::Value;
+
+const BASE_PATH: &str = ".whispr";
+const SETTINGS_FILE: &str = "settings";
+
+/// Expands a leading `~` to the home directory and `$VAR` or `${VAR}` to
+/// environment variables in a configured path. Unset variables are kept as
+/// written.
+pub fn expand_path(path: &str) -> PathBuf {
+    expand_path_with(path, |name| std::env::var(name).ok(), dirs::home_dir())
+}
+
+fn expand_path_with(path: &str, var: impl Fn(&str) -> Option<String>, home: Option<PathBuf>) -> PathBuf {
+    let mut expanded = String::new();
+    let mut rest = path.trim();
+    if let Some(home) = &home {
+        if rest == "~" || rest.starts_with("~/") {
+            expanded.push_str(&home.to_string_lossy());
+            rest = &rest[1..];
+        }
+    }
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        // A lone `$` has an empty name and stays as well
+        match var(name).filter(|_| !name.is_empty()) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&rest[start..rest.len() - remainder.len()]),
+        }
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Model {
+    pub display_name: String,
+    pub url: String,
+    pub filename: String,
+    /// Published SHA-256 of the file, checked after downloads and at launch
+    pub sha256: Option<String>,
+}
+
+impl Model {
+    /// The checksum to hold the file to: `sha256` if set, else the one the
+    /// catalog ships for the file. Custom models without either are checked
+    /// against the hash recorded when they were downloaded.
+    pub fn published_sha256(&self) -> Option<String> {
+        self.sha256.clone()
+            .or_else(|| CatalogModel::for_model(self).and_then(|entry| entry.sha256).map(str::to_string))
+    }
+}
+
+const MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/";
+
+/// A whisper.cpp model offered in the tray menu. Quantized variants (q8_0,
+/// q5_x) trade a little accuracy for a fraction of the memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatalogModel {
+    pub id: &'static str,
+    pub display_name: &'static str,
+    pub filename: &'static str,
+    /// Installed memory below which the model is not recommended
+    pub min_ram_gb: u64,
+    /// Published SHA-256 of the file, the `lfs.oid` Hugging Face lists for
+    /// it. Without one, downloads are checked against the hash Hugging Face
+    /// sends with the file and later launches against the recorded one.
+    pub sha256: Option<&'static str>,
+}
+
+/// Best first, so the first model that fits the memory is the recommendation.
+pub const MODEL_CATALOG: [CatalogModel; 7] = [
+    CatalogModel { id: "large-v3-turbo", display_name: "Whisper Large v3 Turbo", filename: "ggml-large-v3-turbo.bin", min_ram_gb: 16, sha256: None },
+    CatalogModel { id: "large-v3-turbo-q8_0", display_name: "Whisper Large v3 Turbo (q8_0)", filename: "ggml-large-v3-turbo-q8_0.bin", min_ram_gb: 8, sha256: None },
+    CatalogModel { id: "large-v3-turbo-q5_0", display_name: "Whisper Large v3 Turbo (q5_0)", filename: "ggml-large-v3-turbo-q5_0.bin", min_ram_gb: 6, sha256: None },
+    CatalogModel { id: "medium-q5_0", display_name: "Whisper Medium (q5_0)", filename: "ggml-medium-q5_0.bin", min_ram_gb: 4, sha256: None },
+    CatalogModel { id: "small-q5_1", display_name: "Whisper Small (q5_1)", filename: "ggml-small-q5_1.bin", min_ram_gb: 2, sha256: None },
+    CatalogModel { id: "base-q5_1", display_name: "Whisper Base (q5_1)", filename: "ggml-base-q5_1.bin", min_ram_gb: 1, sha256: None },
+    CatalogModel { id: "tiny-q5_1", display_name: "Whisper Tiny (quantized)", filename: "ggml-tiny-q5_1.bin", min_ram_gb: 0, sha256: None },
+];
+
+impl CatalogModel {
+    pub fn from_id(id: &str) -> Option<Self> {
+        MODEL_CATALOG.iter().find(|model| model.id == id).copied()
+    }
+
+    /// The catalog entry for a configured model, matched by file name.
+    pub fn for_model(model: &Model) -> Option<Self> {
+        MODEL_CATALOG.iter().find(|entry| entry.filename == model.filename).copied()
+    }
+
+    /// The best model for a Mac with `ram_gb` of memory.
+    pub fn recommended(ram_gb: u64) -> Self {
+        MODEL_CATALOG.iter()
+            .find(|model| model.min_ram_gb <= ram_gb)
+            .copied()
+            .unwrap_or(MODEL_CATALOG[MODEL_CATALOG.len() - 1])
+    }
+
+    pub fn model(&self) -> Model {
+        Model {
+            display_name: self.display_name.to_string(),
+            url: format!("{}{}", MODEL_BASE_URL, self.filename),
+            filename: self.filename.to_string(),
+            sha256: self.sha256.map(str::to_string),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConfigManager<T> where T: Serialize + for<'de> Deserialize<'de> + Default {
+    config_dir: PathBuf,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> ConfigManager<T> where T: Serialize + for<'de> Deserialize<'de> + Default {
+    pub fn new(_config_name: &str) -> Result<Self> {
+        let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        let config_dir = home_dir.join(BASE_PATH);
+        
+        if !config_dir.exists() {
+            fs::create_dir_all(&config_dir)?;
+        }
+
+        Ok(Self {
+            config_dir,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn save_config(&self, config: &T, _name: &str) -> Result<()> {
+        let config_path = self.config_dir.join(format!("{}.json", SETTINGS_FILE));
+        let config_str = serde_json::to_string_pretty(config)?;
+        fs::write(config_path, config_str)?;
+        Ok(())
+    }
+
+    pub fn load_config(&self, _name: &str) -> Result<T> {
+        let config_path = self.config_dir.join(format!("{}.json", SETTINGS_FILE));
+        
+        if !config_path.exists() {
+            let default_config = T::default();
+            self.save_config(&default_config, _name)?;
+            return Ok(default_config);
+        }
+
+        let config_str = fs::read_to_string(&config_path)?;
+        let stored_config: Value = serde_json::from_str(&config_str)?;
+        let default_config = T::default();
+        let default_value = serde_json::to_value(&default_config)?;
+
+        let (merged_value, had_missing_fields) = merge_json_values(stored_config, default_value);
+        
+        if had_missing_fields {
+            info!("Config file had missing fields, updating with default values");
+            let config: T = serde_json::from_value(merged_value.clone())?;
+            self.save_config(&config, _name)?;
+        }
+        
+        let config: T = serde_json::from_value(merged_value)?;
+        Ok(config)
+    }
+
+    pub fn config_exists(&self, _name: &str) -> bool {
+        self.config_dir.join(format!("{}.json", SETTINGS_FILE)).exists()
+    }
+
+    pub fn get_config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// When the settings were last saved, to reload them only on changes.
+    pub fn modified(&self, _name: &str) -> Option<SystemTime> {
+        fs::metadata(self.config_dir.join(format!("{}.json", SETTINGS_FILE)))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+fn merge_json_values(stored: Value, default: Value) -> (Value, bool) {
+    match (stored, default) {
+        (Value::Object(mut stored_map), Value::Object(default_map)) => {
+            let mut had_missing_fields = false;
+            
+            for (key, default_value) in default_map {
+                match stored_map.get(&key) {
+                    None => {
+                        info!("Missing config field: {}", key);
+                        had_missing_fields = true;
+                        stored_map.insert(key, default_value);
+                    }
+                    Some(stored_value) => {
+                        if let Value::Object(_) = default_value {
+                            let (merged, missing) = merge_json_values(stored_value.clone(), default_value);
+                            if missing {
+                                had_missing_fields = true;
+                                stored_map.insert(key, merged);
+                            }
+                        }
+                    }
+                }
+            }
+            
+            (Value::Object(stored_map), had_missing_fields)
+        }
+        (stored, _) => (stored, false),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct WhisprConfig {
+    pub audio: AudioSettings,
+    pub developer: DeveloperSettings,
+    pub whisper: WhisperSettings,
+    pub start_at_login: bool,
+    pub keyboard_shortcut: String,
+    pub model: Model,
+    pub fallback_model: Model,
+    pub models_dir: Option<String>,
+    /// The preferred model file, anywhere, e.g. on an external drive or a
+    /// shared volume. Defaults to `model.bin` in `models_dir`
+    pub model_path: Option<String>,
+    pub storage: StorageSettings,
+    pub network: NetworkSettings,
+    pub output: OutputSettings,
+    pub integrations: IntegrationsSettings,
+    pub rolling_buffer: RollingBufferSettings,
+    /// Show a floating push-to-talk button as an alternative to the shortcut
+    pub record_button: bool,
+    /// Treat every dictation as a voice command instead of text
+    pub command_mode: bool,
+    /// Global shortcut that types the last transcription again
+    pub paste_last_shortcut: Option<String>,
+    /// Saying this ends a dictation started from the API or `whisprctl
+    /// toggle`, e.g. "stop dictation"
+    pub stop_phrase: Option<String>,
+    pub history: HistorySettings,
+    pub sync: SyncSettings,
+    pub analytics: AnalyticsSettings,
+    pub startup: StartupSettings,
+    pub ui: UiSettings,
+    pub api: ApiSettings,
+    pub daemon: DaemonSettings,
+    pub llm: LlmSettings,
+}
+
+impl Default for WhisprConfig {
+    fn default() -> Self {
+        Self {
+            audio: AudioSettings::default(),
+            developer: DeveloperSettings::default(),
+            whisper: WhisperSettings::default(),
+            start_at_login: false,
+            keyboard_shortcut: "right_command_key".to_string(),
+            model: MODEL_CATALOG[0].model(),
+            fallback_model: MODEL_CATALOG[MODEL_CATALOG.len() - 1].model(),
+            models_dir: None,
+            model_path: None,
+            storage: StorageSettings::default(),
+            network: NetworkSettings::default(),
+            output: OutputSettings::default(),
+            integrations: IntegrationsSettings::default(),
+            rolling_buffer: RollingBufferSettings::default(),
+            record_button: false,
+            command_mode: false,
+            paste_last_shortcut: Some("ctrl+cmd+KeyV".to_string()),
+            stop_phrase: None,
+            history: HistorySettings::default(),
+            sync: SyncSettings::default(),
+            analytics: AnalyticsSettings::default(),
+            startup: StartupSettings::default(),
+            ui: UiSettings::default(),
+            api: ApiSettings::default(),
+            daemon: DaemonSettings::default(),
+            llm: LlmSettings::default(),
+        }
+    }
+}
+
+/// Sample rate converter used to bring device audio to 16kHz.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResamplerBackend {
+    /// libsamplerate (C library)
+    #[default]
+    Samplerate,
+    /// Pure Rust
+    Rubato,
+}
+
+/// What dictations are captured from.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioSource {
+    /// The selected input device
+    #[default]
+    Microphone,
+    /// What the Mac plays, e.g. a call or a video, through a loopback device
+    /// such as BlackHole
+    SystemAudio,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct AudioSettings {
+    pub source: AudioSource,
+    /// Device to capture system audio from, by default the first of
+    /// BlackHole, Loopback Audio, Soundflower and VB-Cable found
+    pub loopback_device: Option<String>,
+    pub device_name: Option<String>,
+    /// Tells apart devices with the same name, see `input_device::DeviceEntry`
+    pub device_id: Option<String>,
+    pub remove_silence: bool,
+    /// Windows with a lower RMS level (dBFS) count as silence
+    pub silence_threshold_db: f32,
+    /// Length of the windows silence removal keeps or drops whole
+    pub silence_window_ms: u32,
+    /// Silence kept after speech, for word endings and short pauses
+    pub silence_hangover_ms: u32,
+    /// Automatic gain control, brings speech to `agc_target_db` before
+    /// silence removal and whisper
+    pub agc: bool,
+    /// RMS level (dBFS) speech is brought to
+    pub agc_target_db: f32,
+    /// The most the gain control amplifies or attenuates, in dB
+    pub agc_max_gain_db: f32,
+    /// Seconds of audio from just before the dictation key (0.5 - 2) put in
+    /// front of every recording, 0 for none. Keeps the microphone open.
+    pub pre_roll_secs: f32,
+    pub recordings_dir: Option<String>,
+    pub resampler: ResamplerBackend,
+    pub dsp: DspSettings,
+    /// Continue on the default device when the microphone disappears mid-recording
+    pub restart_on_device_change: bool,
+    /// Recordings quieter than this RMS level (dBFS) are skipped without transcribing
+    pub min_rms_db: f32,
+    /// Warn after a dictation when more than this percentage of samples clipped
+    pub clip_warning_percent: f32,
+}
+
+/// Processing applied to the recording right before it is transcribed.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DspSettings {
+    /// Suppresses steady background noise like fans and typing with RNNoise
+    pub denoise: bool,
+    pub remove_dc_offset: bool,
+    pub normalize_loudness: bool,
+    pub target_lufs: f32,
+}
+
+impl Default for DspSettings {
+    fn default() -> Self {
+        Self {
+            denoise: false,
+            remove_dc_offset: true,
+            normalize_loudness: false,
+            target_lufs: -20.0,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// Id of the configured input device. Configs from before ids only have
+    /// the name, which is the id of the first device with that name.
+    pub fn selected_device(&self) -> Option<&str> {
+        self.device_id.as_deref().or(self.device_name.as_deref())
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            source: AudioSource::default(),
+            loopback_device: None,
+            device_name: None,
+            device_id: None,
+            remove_silence: true,
+            silence_threshold_db: -50.0,
+            silence_window_ms: 20,
+            silence_hangover_ms: 300,
+            agc: false,
+            agc_target_db: -20.0,
+            agc_max_gain_db: 30.0,
+            pre_roll_secs: 0.0,
+            recordings_dir: Some(BASE_PATH.to_string()),
+            resampler: ResamplerBackend::default(),
+            dsp: DspSettings::default(),
+            restart_on_device_change: true,
+            min_rms_db: -60.0,
+            clip_warning_percent: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DeveloperSettings {
+    pub save_recordings: bool,
+    /// With `save_recordings`, also keeps the audio as the device delivered
+    /// it (all channels, full rate, silence included) as `<recording>.raw.wav`
+    pub save_raw_audio: bool,
+    pub whisper_logging: bool,
+    pub logging: bool,
+    /// Transcriptions of the sample per Benchmark Model run
+    pub benchmark_runs: usize,
+}
+
+impl Default for DeveloperSettings {
+    fn default() -> Self {
+        Self {
+            save_recordings: false,
+            save_raw_audio: false,
+            whisper_logging: false,
+            logging: true, // Logging enabled by default
+            benchmark_runs: 5,
+        }
+    }
+}
+
+/// Decoding presets trading speed for accuracy, switchable per dictation.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    Fast,
+    #[default]
+    Balanced,
+    Accurate,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 3] = [Quality::Fast, Quality::Balanced, Quality::Accurate];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Quality::Fast => "fast",
+            Quality::Balanced => "balanced",
+            Quality::Accurate => "accurate",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Quality::Fast => "Fast",
+            Quality::Balanced => "Balanced",
+            Quality::Accurate => "Accurate",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|quality| quality.id() == id)
+    }
+
+    /// Beam size for beam search decoding, `None` decodes greedily.
+    pub fn beam_size(&self) -> Option<i32> {
+        match self {
+            Quality::Fast | Quality::Balanced => None,
+            Quality::Accurate => Some(5),
+        }
+    }
+
+    /// Fast decodes with the small fallback model when it is available.
+    pub fn uses_fallback_model(&self) -> bool {
+        matches!(self, Quality::Fast)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct WhisperSettings {
+    pub model_name: String,
+    pub language: Option<String>,
+    pub translate: bool,
+    pub dictionary: Option<Vec<String>>,
+    pub quality: Quality,
+    /// Decodes again at a higher temperature when the result is empty or
+    /// stuck in a loop, at most this often
+    pub max_retries: u32,
+    /// Languages auto-detection may pick, e.g. ["en", "de"]. Empty allows all.
+    pub allowed_languages: Vec<String>,
+    /// Offers to redo a dictation in the usual language when auto-detection
+    /// picks another one than for this many dictations before. 0 disables.
+    pub language_switch_window: usize,
+    /// Runs the encoder through Core ML on Apple Silicon, downloading it
+    /// next to the model
+    pub coreml: bool,
+    /// Runs whisper on the GPU through Metal, off runs it on the CPU only.
+    /// Takes effect the next time the model is loaded
+    pub use_gpu: bool,
+    /// GPU to use on Macs with more than one, by Metal's device order
+    pub gpu_device: i32,
+    /// Decoding threads, 0 picks them by the quality preset
+    pub n_threads: usize,
+    /// Words spelled with "spell that" join `dictionary` once this many
+    /// dictations used them. 0 keeps them for the session only
+    pub promote_spelled_after: u32,
+    /// Shows a quick draft from the fallback model while the main model is
+    /// still transcribing
+    pub draft: DraftMode,
+}
+
+impl WhisperSettings {
+    /// Whether whisper picks the language itself ("auto" or unset).
+    pub fn detects_language(&self) -> bool {
+        matches!(self.language.as_deref(), None | Some("auto"))
+    }
+}
+
+impl Default for WhisperSettings {
+    fn default() -> Self {
+        Self {
+            model_name: "base.en".to_string(),
+            language: None,
+            translate: false,
+            dictionary: None,
+            quality: Quality::default(),
+            max_retries: 2,
+            allowed_languages: Vec::new(),
+            language_switch_window: 3,
+            coreml: true,
+            use_gpu: true,
+            gpu_device: 0,
+            n_threads: 0,
+            promote_spelled_after: 3,
+            draft: DraftMode::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct StorageSettings {
+    pub min_free_space_mb: u64,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            min_free_space_mb: 1024,
+        }
+    }
+}
+
+/// Opt-in mode that keeps the last few seconds of microphone audio in memory
+/// so they can be transcribed after the fact. Never written to disk.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct RollingBufferSettings {
+    pub enabled: bool,
+    pub seconds: u32,
+    /// Global shortcut that transcribes the buffer while it is enabled, like
+    /// "What Did They Just Say?". Unset disables it
+    pub shortcut: Option<String>,
+    /// Also keeps what the Mac plays, through the loopback device used for
+    /// system audio (see `audio.loopback_device`)
+    pub include_system_audio: bool,
+}
+
+impl Default for RollingBufferSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seconds: 30,
+            shortcut: Some("ctrl+cmd+KeyR".to_string()),
+            include_system_audio: false,
+        }
+    }
+}
+
+/// Where the fallback model's draft of a dictation goes while the main
+/// model refines it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftMode {
+    #[default]
+    Off,
+    /// Typed right away, then corrected where the main model differs
+    Type,
+    /// Shown in the overlay until the main model's text is delivered
+    Overlay,
+}
+
+/// Direction transcribed text is shown in.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDirection {
+    /// Right-to-left for languages written that way (Arabic, Hebrew, …), from
+    /// the detected language or, if unknown, the text itself
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+/// Optional sections of the tray menu. Quit, About and the model status are
+/// always shown.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MenuSection {
+    KeyboardShortcut,
+    AudioDevice,
+    Quality,
+    Language,
+    Output,
+    Models,
+    RollingBuffer,
+    DeveloperOptions,
+}
+
+impl MenuSection {
+    pub const ALL: [MenuSection; 8] = [
+        MenuSection::KeyboardShortcut,
+        MenuSection::AudioDevice,
+        MenuSection::Quality,
+        MenuSection::Language,
+        MenuSection::Output,
+        MenuSection::Models,
+        MenuSection::RollingBuffer,
+        MenuSection::DeveloperOptions,
+    ];
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct UiSettings {
+    /// Tray menu sections to show, in their fixed order
+    pub menu_sections: Vec<MenuSection>,
+    /// Language submenu shows native names first, "Deutsch (German)"
+    pub native_language_names: bool,
+    /// Overrides macOS "Reduce Motion", `None` follows the system setting
+    pub reduce_motion: Option<bool>,
+    /// Overrides macOS "Reduce Transparency", `None` follows the system setting
+    pub reduce_transparency: Option<bool>,
+    pub text_direction: TextDirection,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            menu_sections: MenuSection::ALL.to_vec(),
+            native_language_names: false,
+            reduce_motion: None,
+            reduce_transparency: None,
+            text_direction: TextDirection::default(),
+        }
+    }
+}
+
+/// Versioned events and the local HTTP API for companion tools, see
+/// docs/api.md.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ApiSettings {
+    pub events_enabled: bool,
+    pub server_enabled: bool,
+    pub port: u16,
+    /// Also serves the API to the local network on `lan_port`, over HTTPS
+    /// only, which needs `tls_cert` and `tls_key`
+    pub lan_enabled: bool,
+    pub lan_port: u16,
+    /// PEM certificate chain for the network listener
+    pub tls_cert: Option<String>,
+    /// PEM private key of `tls_cert`
+    pub tls_key: Option<String>,
+    /// Transcriptions requested over HTTP decoded at the same time
+    pub max_concurrent_jobs: usize,
+    /// Transcriptions that may wait for a slot, more are turned away
+    pub max_queued_jobs: usize,
+    /// Seconds a transcription request may wait and decode before it fails
+    pub job_timeout_secs: u64,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            events_enabled: false,
+            server_enabled: false,
+            port: 43210,
+            lan_enabled: false,
+            lan_port: 43211,
+            tls_cert: None,
+            tls_key: None,
+            max_concurrent_jobs: 1,
+            max_queued_jobs: 8,
+            job_timeout_secs: 300,
+        }
+    }
+}
+
+/// Keeps the model loaded in `whisprd`, a background process of its own, so
+/// the menubar app can be quit and started again without loading it anew.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DaemonSettings {
+    pub enabled: bool,
+    /// Loopback port `whisprd` listens on
+    pub port: u16,
+    /// `whisprd` records dictations as well, not only decodes them. The
+    /// stop phrase, pre-roll and the spectrogram window need the recording
+    /// in the app
+    pub capture: bool,
+}
+
+impl DaemonSettings {
+    /// Whether dictations are recorded by `whisprd`.
+    pub fn captures(&self) -> bool {
+        self.enabled && self.capture
+    }
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        Self { enabled: false, port: 43212, capture: true }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct NetworkSettings {
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<String>,
+    pub mirror_url: Option<String>,
+}
+
+/// Destinations a transcription can be delivered to. Several can be active
+/// at once and run in the configured order.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    Keyboard,
+    Clipboard,
+    File,
+    Webhook,
+    Obsidian,
+    AppleNotes,
+    AppleReminders,
+    Obs,
+    Chat,
+    Email,
+}
+
+impl SinkKind {
+    pub const ALL: [SinkKind; 10] = [
+        SinkKind::Keyboard,
+        SinkKind::Clipboard,
+        SinkKind::File,
+        SinkKind::Webhook,
+        SinkKind::Obsidian,
+        SinkKind::AppleNotes,
+        SinkKind::AppleReminders,
+        SinkKind::Obs,
+        SinkKind::Chat,
+        SinkKind::Email,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            SinkKind::Keyboard => "keyboard",
+            SinkKind::Clipboard => "clipboard",
+            SinkKind::File => "file",
+            SinkKind::Webhook => "webhook",
+            SinkKind::Obsidian => "obsidian",
+            SinkKind::AppleNotes => "apple_notes",
+            SinkKind::AppleReminders => "apple_reminders",
+            SinkKind::Obs => "obs",
+            SinkKind::Chat => "chat",
+            SinkKind::Email => "email",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            SinkKind::Keyboard => "Type into Focused App",
+            SinkKind::Clipboard => "Copy to Clipboard",
+            SinkKind::File => "Append to Journal File",
+            SinkKind::Webhook => "Send to Webhook",
+            SinkKind::Obsidian => "Obsidian Daily Note",
+            SinkKind::AppleNotes => "Apple Notes",
+            SinkKind::AppleReminders => "Apple Reminders",
+            SinkKind::Obs => "OBS Captions",
+            SinkKind::Chat => "Send to Slack/Discord",
+            SinkKind::Email => "Draft Email",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sink| sink.id() == id)
+    }
+}
+
+/// How text gets into terminals and modal editors, which mangle fast
+/// synthetic keystrokes.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalStrategy {
+    #[default]
+    Paste,
+    SlowTyping,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct InjectionSettings {
+    pub terminal_strategy: TerminalStrategy,
+    pub typing_delay_ms: u64,
+    /// Additional bundle identifiers treated as terminals
+    pub terminal_apps: Vec<String>,
+    /// Bundle identifiers that always get regular typing, even if known terminals
+    pub typing_apps: Vec<String>,
+    /// Copy to the clipboard instead of typing when no text field is focused
+    pub focus_guard: bool,
+    /// Check that typing changed the focused field and paste the text if it
+    /// didn't
+    pub verify_typing: bool,
+    /// Bundle identifiers that ignore synthetic typing and get pasted into,
+    /// added when the user confirms after `verify_typing` had to paste
+    pub paste_apps: Vec<String>,
+}
+
+impl Default for InjectionSettings {
+    fn default() -> Self {
+        Self {
+            terminal_strategy: TerminalStrategy::default(),
+            typing_delay_ms: 8,
+            terminal_apps: Vec::new(),
+            typing_apps: Vec::new(),
+            focus_guard: true,
+            verify_typing: false,
+            paste_apps: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct WebhookSettings {
+    pub url: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+/// What to do when a dictation produces no text.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OnEmpty {
+    #[default]
+    Ignore,
+    /// Briefly show "Nothing heard" in the overlay
+    Toast,
+    /// Play the system error sound
+    Sound,
+    /// Transcribe again without silence removal, then fall back to a toast
+    Retry,
+}
+
+/// Chinese and Japanese post-processing, `None` applies a transform to
+/// dictations in Chinese, Japanese and Korean only.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default)]
+pub struct CjkSettings {
+    /// Removes the spaces whisper puts between CJK characters
+    pub remove_spaces: Option<bool>,
+    /// Turns ASCII punctuation after CJK characters full-width, "。" for "."
+    pub full_width_punctuation: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct OutputSettings {
+    /// Ordered list of destinations every dictation is delivered to
+    pub sinks: Vec<SinkKind>,
+    pub injection: InjectionSettings,
+    pub file_path: Option<String>,
+    pub webhook: WebhookSettings,
+    pub on_empty: OnEmpty,
+    /// Transcriptions with a lower confidence (0.0 - 1.0) are shown for
+    /// confirmation instead of being delivered directly, 0 disables the check
+    pub min_confidence: f32,
+    /// Color every word of a low confidence transcription by its confidence
+    /// in the review window instead of marking only those below
+    /// `min_confidence`
+    pub review_window: bool,
+    /// Pauses between segments of at least this many seconds start a new
+    /// paragraph, 0 keeps everything on one line
+    pub paragraph_pause_secs: f32,
+    pub cjk: CjkSettings,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            sinks: vec![SinkKind::Keyboard],
+            injection: InjectionSettings::default(),
+            file_path: None,
+            webhook: WebhookSettings::default(),
+            on_empty: OnEmpty::default(),
+            min_confidence: 0.4,
+            review_window: false,
+            paragraph_pause_secs: 0.0,
+            cjk: CjkSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct IntegrationsSettings {
+    pub obsidian: ObsidianSettings,
+    pub apple: AppleSettings,
+    pub obs: ObsSettings,
+    pub chat: ChatSettings,
+    pub email: EmailSettings,
+    pub time_tracking: TimeTrackingSettings,
+    pub home_assistant: HomeAssistantSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ObsidianSettings {
+    pub vault_path: Option<String>,
+    pub daily_notes_folder: String,
+    pub date_format: String,
+    pub template: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Default for ObsidianSettings {
+    fn default() -> Self {
+        Self {
+            vault_path: None,
+            daily_notes_folder: "Daily Notes".to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            template: None,
+            tags: vec!["dictation".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct AppleSettings {
+    pub notes_folder: Option<String>,
+    pub reminders_list: Option<String>,
+    /// Turns dictations starting with "remind me to" into reminders instead
+    /// of delivering them
+    pub voice_commands: bool,
+}
+
+impl Default for AppleSettings {
+    fn default() -> Self {
+        Self {
+            notes_folder: None,
+            reminders_list: None,
+            voice_commands: false,
+        }
+    }
+}
+
+/// Captions for OBS, written to a file a "Text (FreeType 2)" source reads
+/// with "Read from file".
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ObsSettings {
+    pub caption_file: Option<String>,
+    /// Also show the text while it is being transcribed
+    pub live: bool,
+    /// Clear the caption this long after the last dictation, 0 keeps it
+    pub clear_after_secs: u64,
+}
+
+impl Default for ObsSettings {
+    fn default() -> Self {
+        Self {
+            caption_file: None,
+            live: true,
+            clear_after_secs: 5,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatService {
+    #[default]
+    Slack,
+    Discord,
+}
+
+/// Incoming webhook of a Slack or Discord channel, used by the chat output
+/// and by releasing the dictation key with Control held.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct ChatSettings {
+    pub service: ChatService,
+    pub webhook_url: Option<String>,
+    /// Shown in bold above every message, e.g. "Standup"
+    pub prefix: Option<String>,
+    /// Overrides the webhook's name, Discord only
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailClient {
+    /// Opens a mailto: link in the default mail app
+    #[default]
+    Mailto,
+    /// Opens a compose window in Mail.app via AppleScript
+    Mail,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct EmailSettings {
+    pub client: EmailClient,
+    /// Recipient filled in for every draft, comma separated
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeTrackingService {
+    #[default]
+    Toggl,
+    Clockify,
+}
+
+/// "Start timer …" and "stop timer" voice commands. The API token is read
+/// from the Keychain, never from this file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct TimeTrackingSettings {
+    pub enabled: bool,
+    pub service: TimeTrackingService,
+    /// Defaults to the account's default workspace
+    pub workspace_id: Option<String>,
+}
+
+/// Conversation API commands unknown to whispr are forwarded to in command
+/// mode. The long-lived access token is read from the Keychain.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct HomeAssistantSettings {
+    /// e.g. "http://homeassistant.local:8123"
+    pub url: Option<String>,
+    pub forward_unmatched: bool,
+    /// Language for the conversation agent, defaults to Home Assistant's own
+    pub language: Option<String>,
+}
+
+/// Past transcriptions kept in `~/.whispr/history.jsonl`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    pub max_entries: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 1000,
+        }
+    }
+}
+
+/// Keeps settings consistent across Macs through a folder synced by iCloud
+/// Drive, Dropbox or similar. Device, models and developer options stay local.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct SyncSettings {
+    /// e.g. "/Users/me/Library/Mobile Documents/com~apple~CloudDocs"
+    pub folder: Option<String>,
+    /// Also share the transcription history, without recordings
+    pub history: bool,
+}
+
+/// A local LLM behind an OpenAI compatible chat completions endpoint, e.g.
+/// Ollama or LM Studio, for summaries in history.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct LlmSettings {
+    pub endpoint: String,
+    pub model: String,
+    /// System prompt for "Summarize", the transcript follows as the user message
+    pub summary_prompt: String,
+    /// Local models can take a while for an hour of meeting
+    pub timeout_secs: u64,
+}
+
+impl Default for LlmSettings {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:11434/v1/chat/completions".to_string(),
+            model: "llama3.2".to_string(),
+            summary_prompt: "Summarize this transcript of a meeting or voice note in Markdown: \
+                a short overview paragraph, then the key points, decisions and action items as bullet lists. \
+                Answer in the language of the transcript and only with the summary."
+                .to_string(),
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// Per-dictation word count, target app, latency and language kept in
+/// `~/.whispr/stats.jsonl` for the monthly report. Nothing leaves the Mac.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct AnalyticsSettings {
+    pub enabled: bool,
+}
+
+/// What happens at launch.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct StartupSettings {
+    /// Check the model against the SHA-256 recorded when it was downloaded
+    /// before loading it
+    pub verify_model: bool,
+    /// Briefly show "Ready" in the overlay once the model is loaded
+    pub show_ready: bool,
+    /// Wait this long before the dictation key starts recording, e.g. while
+    /// other apps started at login still grab the keyboard
+    pub arm_delay_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recommends_quantized_models_on_small_macs() {
+        assert_eq!(CatalogModel::recommended(32).id, "large-v3-turbo");
+        assert_eq!(CatalogModel::recommended(16).id, "large-v3-turbo");
+        assert_eq!(CatalogModel::recommended(8).id, "large-v3-turbo-q8_0");
+        assert_eq!(CatalogModel::recommended(4).id, "medium-q5_0");
+        assert_eq!(CatalogModel::recommended(0).id, "tiny-q5_1");
+    }
+
+    #[test]
+    fn paths_expand_home_and_variables() {
+        let var = |name: &str| (name == "VOLUME").then(|| "/Volumes/Models".to_string());
+        let home = Some(PathBuf::from("/Users/me"));
+        assert_eq!(expand_path_with("~/models/model.bin", var, home.clone()), PathBuf::from("/Users/me/models/model.bin"));
+        assert_eq!(expand_path_with("$VOLUME/model.bin", var, home.clone()), PathBuf::from("/Volumes/Models/model.bin"));
+        assert_eq!(expand_path_with("${VOLUME}_team/m.bin", var, home.clone()), PathBuf::from("/Volumes/Models_team/m.bin"));
+        // Unset variables, lone dollars and tildes elsewhere stay
+        assert_eq!(expand_path_with("$UNSET/a$/~b", var, home.clone()), PathBuf::from("$UNSET/a$/~b"));
+        assert_eq!(expand_path_with("${VOLUME", var, home), PathBuf::from("${VOLUME"));
+    }
+
+    #[test]
+    fn defaults_are_in_the_catalog() {
+        let config = WhisprConfig::default();
+        assert_eq!(CatalogModel::for_model(&config.model).unwrap().model().url, config.model.url);
+        assert_eq!(CatalogModel::for_model(&config.fallback_model).unwrap().model().url, config.fallback_model.url);
+    }
+}
+
@@ -0,0 +1,221 @@
+use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+use crate::config::WhisprConfig;
+
+// Comment lines are wrapped at this many characters, indentation excluded
+const WIDTH: usize = 76;
+
+/// Walks the settings schema next to the default settings.
+struct Writer<'a> {
+    definitions: &'a schemars::Map<String, Schema>,
+    out: String,
+}
+
+impl<'a> Writer<'a> {
+    /// The schema behind references, `Option`s and the wrappers that carry
+    /// a field's own doc comment.
+    fn resolve(&self, schema: &'a SchemaObject) -> &'a SchemaObject {
+        if let Some(reference) = &schema.reference {
+            let name = reference.trim_start_matches("#/definitions/");
+            if let Some(Schema::Object(definition)) = self.definitions.get(name) {
+                return self.resolve(definition);
+            }
+        }
+        if let Some(subschemas) = &schema.subschemas {
+            let wrapped = subschemas.all_of.iter().chain(&subschemas.any_of).flatten()
+                .filter_map(|schema| match schema {
+                    Schema::Object(object) if !is_null(object) => Some(object),
+                    _ => None,
+                })
+                .next();
+            if let Some(wrapped) = wrapped {
+                return self.resolve(wrapped);
+            }
+        }
+        schema
+    }
+
+    /// The field's doc comment, or that of its type.
+    fn description(&self, schema: &'a SchemaObject) -> Option<String> {
+        let own = schema.metadata.as_ref().and_then(|metadata| {
+            let text: Vec<&str> = [metadata.title.as_deref(), metadata.description.as_deref()].into_iter().flatten().collect();
+            (!text.is_empty()).then(|| text.join(" "))
+        });
+        own.or_else(|| {
+            let resolved = self.resolve(schema);
+            (!std::ptr::eq(resolved, schema)).then(|| self.description(resolved)).flatten()
+        })
+    }
+
+    /// Values an enum, or the items of a list of one, may take, with the
+    /// doc comments of the variants.
+    fn allowed(&self, schema: &'a SchemaObject) -> Vec<(Value, Option<String>)> {
+        let resolved = self.resolve(schema);
+        if let Some(SingleOrVec::Single(item)) = resolved.array.as_ref().and_then(|array| array.items.as_ref()) {
+            return match item.as_ref() {
+                Schema::Object(item) => self.allowed(item),
+                Schema::Bool(_) => Vec::new(),
+            };
+        }
+        if let Some(values) = &resolved.enum_values {
+            return values.iter().map(|value| (value.clone(), None)).collect();
+        }
+        let variants = resolved.subschemas.as_ref().and_then(|subschemas| subschemas.one_of.as_ref());
+        variants.into_iter().flatten()
+            .filter_map(|variant| match variant {
+                Schema::Object(variant) => Some(variant),
+                Schema::Bool(_) => None,
+            })
+            .flat_map(|variant| {
+                let description = self.description(variant);
+                let values = variant.enum_values.clone().unwrap_or_default().into_iter().chain(variant.const_value.clone());
+                values.map(move |value| (value, description.clone()))
+            })
+            .collect()
+    }
+
+    fn line(&mut self, indent: usize, text: &str) {
+        self.out.push_str(&"  ".repeat(indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn comments(&mut self, schema: &'a SchemaObject, value: &Value, indent: usize) {
+        if let Some(description) = self.description(schema) {
+            for line in wrap(&description) {
+                self.line(indent, &format!("// {}", line));
+            }
+        }
+        let allowed = self.allowed(schema);
+        let label = if value.is_array() { "Any of" } else { "One of" };
+        if allowed.iter().any(|(_, description)| description.is_some()) {
+            self.line(indent, &format!("// {}:", label));
+            for (value, description) in allowed {
+                match description {
+                    Some(description) => self.line(indent, &format!("//   {}: {}", value, description)),
+                    None => self.line(indent, &format!("//   {}", value)),
+                }
+            }
+        } else if !allowed.is_empty() {
+            let values: Vec<String> = allowed.iter().map(|(value, _)| value.to_string()).collect();
+            self.line(indent, &format!("// {}: {}", label, values.join(", ")));
+        } else if value.is_null() {
+            if let Some(kind) = kind(self.resolve(schema)) {
+                self.line(indent, &format!("// Unset by default, or {}", kind));
+            }
+        }
+    }
+
+    /// Writes `value`, a section with a commented line per setting if the
+    /// schema has fields for it.
+    fn value(&mut self, schema: &'a SchemaObject, value: &Value, indent: usize) {
+        let properties = self.resolve(schema).object.as_ref().map(|object| &object.properties);
+        let (Some(properties), Value::Object(values)) = (properties, value) else {
+            self.out.push_str(&value.to_string());
+            return;
+        };
+        if properties.is_empty() {
+            self.out.push_str(&value.to_string());
+            return;
+        }
+        self.out.push_str("{\n");
+        for (index, (name, property)) in properties.iter().enumerate() {
+            let value = values.get(name).unwrap_or(&Value::Null);
+            if let Schema::Object(property) = property {
+                self.comments(property, value, indent + 1);
+            }
+            self.out.push_str(&"  ".repeat(indent + 1));
+            self.out.push_str(&format!("{}: ", Value::from(name.as_str())));
+            match property {
+                Schema::Object(property) => self.value(property, value, indent + 1),
+                Schema::Bool(_) => self.out.push_str(&value.to_string()),
+            }
+            if index + 1 < properties.len() {
+                self.out.push(',');
+            }
+            self.out.push('\n');
+        }
+        self.out.push_str(&"  ".repeat(indent));
+        self.out.push('}');
+    }
+}
+
+fn is_null(schema: &SchemaObject) -> bool {
+    matches!(&schema.instance_type, Some(SingleOrVec::Single(kind)) if **kind == InstanceType::Null)
+}
+
+/// What an unset setting takes instead, e.g. "a string".
+fn kind(schema: &SchemaObject) -> Option<&'static str> {
+    let kinds: Vec<InstanceType> = match schema.instance_type.as_ref()? {
+        SingleOrVec::Single(kind) => vec![**kind],
+        SingleOrVec::Vec(kinds) => kinds.clone(),
+    };
+    kinds.into_iter().find(|kind| *kind != InstanceType::Null).map(|kind| match kind {
+        InstanceType::Boolean => "true or false",
+        InstanceType::Integer => "a whole number",
+        InstanceType::Number => "a number",
+        InstanceType::String => "a string",
+        InstanceType::Array => "a list",
+        InstanceType::Object | InstanceType::Null => "an object",
+    })
+}
+
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= WIDTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// The settings file with every setting at its default, each under `//`
+/// comments with its description and the values it allows. Generated from
+/// the config types, so settings added later show up without extra work.
+/// The comments have to go before the file is used as `settings.json`.
+pub fn commented_example() -> String {
+    let root = schemars::schema_for!(WhisprConfig);
+    let defaults = serde_json::to_value(WhisprConfig::default()).unwrap_or_default();
+    let mut writer = Writer { definitions: &root.definitions, out: String::new() };
+    writer.value(&root.schema, &defaults, 0);
+    writer.out.push('\n');
+    writer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_comments_it_is_the_default_config() {
+        let example = commented_example();
+        let json: String = example.lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let parsed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, serde_json::to_value(WhisprConfig::default()).unwrap());
+    }
+
+    #[test]
+    fn documents_settings_and_their_values() {
+        let example = commented_example();
+        assert!(example.contains("// Suppresses steady background noise like fans and typing with RNNoise\n      \"denoise\": false,\n"));
+        assert!(example.contains("// One of: \"fast\", \"balanced\", \"accurate\"\n"));
+        assert!(example.contains("//   \"rubato\": Pure Rust\n"));
+        assert!(example.contains("// Unset by default, or a string\n  \"models_dir\": null,\n"));
+    }
+
+    #[test]
+    fn wraps_long_descriptions() {
+        let text = "word ".repeat(40);
+        let lines = wrap(&text);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= WIDTH));
+    }
+}
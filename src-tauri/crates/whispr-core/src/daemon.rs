@@ -0,0 +1,281 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::config::{AudioSettings, WhisperSettings};
+use crate::whisper::{Decoding, ProgressCallback, SegmentCallback, TranscriptionProgress};
+
+pub const STATUS_PATH: &str = "/v1/status";
+pub const MODEL_PATH: &str = "/v1/model";
+pub const DECODE_PATH: &str = "/v1/decode";
+pub const CAPTURE_START_PATH: &str = "/v1/capture/start";
+pub const CAPTURE_STOP_PATH: &str = "/v1/capture/stop";
+pub const CAPTURE_DISCARD_PATH: &str = "/v1/capture/discard";
+pub const DECODE_CAPTURED_PATH: &str = "/v1/decode/captured";
+
+// Answering status and model requests, a decode may take much longer
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What `whisprd` is doing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub pid: u32,
+    /// The model decodes run with, `None` until one is loaded
+    pub model: Option<PathBuf>,
+    /// Being loaded to replace `model`
+    pub loading: Option<PathBuf>,
+    /// Why the last model failed to load
+    pub error: Option<String>,
+}
+
+/// Asks `whisprd` to load another model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadModel {
+    pub path: PathBuf,
+}
+
+/// Result of a decode, what the app otherwise gets from its
+/// `WhisperProcessor`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decoded {
+    #[serde(flatten)]
+    pub decoding: Decoding,
+    /// Seconds of audio decoded
+    pub audio_secs: f32,
+}
+
+/// Asks `whisprd` to capture a dictation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartCapture {
+    pub audio: AudioSettings,
+    /// Part of an ephemeral session: no recording is saved and the audio is
+    /// zeroed once decoded
+    pub ephemeral: bool,
+}
+
+/// What `whisprd` captured, for the checks the app runs before decoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Captured {
+    /// RMS level in dBFS, `None` if nothing was captured
+    pub level_db: Option<f32>,
+    pub clip_percent: f32,
+    /// The saved WAV file, if recordings are saved
+    pub recording: Option<PathBuf>,
+}
+
+/// Asks `whisprd` to decode what it captured last.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodeCaptured {
+    pub settings: WhisperSettings,
+    /// The copy from before silence removal, to retry an empty transcription
+    pub unfiltered: bool,
+}
+
+/// A line of a decode response, which `whisprd` streams as NDJSON so
+/// progress and partial text show while it decodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeEvent {
+    Progress(TranscriptionProgress),
+    Segment(String),
+    Done(Decoded),
+    Failed(String),
+}
+
+/// Body of a decode request: the length of the settings JSON as a
+/// little-endian u32, the settings, then the 16 kHz mono samples as
+/// little-endian f32.
+pub fn encode_decode_request(settings: &WhisperSettings, audio: &[f32]) -> Result<Vec<u8>> {
+    let settings = serde_json::to_vec(settings)?;
+    let mut body = Vec::with_capacity(4 + settings.len() + audio.len() * 4);
+    body.extend_from_slice(&(settings.len() as u32).to_le_bytes());
+    body.extend_from_slice(&settings);
+    for sample in audio {
+        body.extend_from_slice(&sample.to_le_bytes());
+    }
+    Ok(body)
+}
+
+pub fn parse_decode_request(body: &[u8]) -> Result<(WhisperSettings, Vec<f32>)> {
+    let length = body.get(..4).context("Decode request too short")?;
+    let length = u32::from_le_bytes(length.try_into()?) as usize;
+    let settings = body.get(4..4 + length).context("Decode request too short")?;
+    let settings = serde_json::from_slice(settings).context("Invalid whisper settings")?;
+    let samples = &body[4 + length..];
+    anyhow::ensure!(samples.len() % 4 == 0, "Audio isn't a whole number of samples");
+    let audio = samples.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect();
+    Ok((settings, audio))
+}
+
+/// Talks to `whisprd` on the loopback port with the API token, a
+/// connection per request.
+#[derive(Debug, Clone)]
+pub struct Client {
+    port: u16,
+    token: String,
+}
+
+impl Client {
+    pub fn new(port: u16, token: String) -> Self {
+        Self { port, token }
+    }
+
+    /// Sends a request and reads up to the body, returning the status.
+    fn send(&self, method: &str, path: &str, body: &[u8], timeout: Option<Duration>) -> Result<(u16, BufReader<TcpStream>)> {
+        let mut stream = TcpStream::connect_timeout(&([127, 0, 0, 1], self.port).into(), TIMEOUT)
+            .with_context(|| format!("whisprd is not listening on port {}", self.port))?;
+        stream.set_read_timeout(timeout)?;
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method, path, self.token, body.len()
+        )?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let status: u16 = status_line.split_whitespace().nth(1)
+            .and_then(|status| status.parse().ok())
+            .context("Invalid response from whisprd")?;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+        }
+        Ok((status, reader))
+    }
+
+    fn call(&self, method: &str, path: &str, body: &[u8], timeout: Option<Duration>) -> Result<Vec<u8>> {
+        let (status, mut reader) = self.send(method, path, body, timeout)?;
+        let mut response = Vec::new();
+        reader.read_to_end(&mut response)?;
+        if status != 200 {
+            anyhow::bail!("{}", error_message(&response));
+        }
+        Ok(response)
+    }
+
+    /// Passes the streamed progress and segments on until the result arrives.
+    fn stream_decode(
+        &self,
+        path: &str,
+        body: &[u8],
+        mut on_progress: Option<ProgressCallback>,
+        mut on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoded> {
+        let (status, mut reader) = self.send("POST", path, body, None)?;
+        if status != 200 {
+            let mut response = Vec::new();
+            reader.read_to_end(&mut response)?;
+            anyhow::bail!("{}", error_message(&response));
+        }
+        for line in reader.lines() {
+            match serde_json::from_str(&line?).context("Invalid response from whisprd")? {
+                DecodeEvent::Progress(progress) => {
+                    if let Some(on_progress) = on_progress.as_mut() {
+                        on_progress(progress);
+                    }
+                }
+                DecodeEvent::Segment(text) => {
+                    if let Some(on_segment) = on_segment.as_mut() {
+                        on_segment(&text);
+                    }
+                }
+                DecodeEvent::Done(decoded) => return Ok(decoded),
+                DecodeEvent::Failed(e) => anyhow::bail!("{}", e),
+            }
+        }
+        anyhow::bail!("whisprd closed the connection before the decode finished")
+    }
+
+    pub fn status(&self) -> Result<Status> {
+        Ok(serde_json::from_slice(&self.call("GET", STATUS_PATH, &[], Some(TIMEOUT))?)?)
+    }
+
+    /// Starts loading `path`, decodes keep using the previous model until it
+    /// is ready.
+    pub fn load_model(&self, path: PathBuf) -> Result<Status> {
+        let body = serde_json::to_vec(&LoadModel { path })?;
+        Ok(serde_json::from_slice(&self.call("POST", MODEL_PATH, &body, Some(TIMEOUT))?)?)
+    }
+
+    /// Transcribes 16 kHz mono `audio`, waiting as long as whisper takes.
+    pub fn decode(
+        &self,
+        audio: &[f32],
+        settings: &WhisperSettings,
+        on_progress: Option<ProgressCallback>,
+        on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoded> {
+        let body = encode_decode_request(settings, audio)?;
+        self.stream_decode(DECODE_PATH, &body, on_progress, on_segment)
+    }
+
+    /// Starts capturing from the device `audio` selects, processed as it
+    /// configures.
+    pub fn start_capture(&self, audio: &AudioSettings, ephemeral: bool) -> Result<()> {
+        let body = serde_json::to_vec(&StartCapture { audio: audio.clone(), ephemeral })?;
+        self.call("POST", CAPTURE_START_PATH, &body, Some(TIMEOUT))?;
+        Ok(())
+    }
+
+    pub fn stop_capture(&self) -> Result<Captured> {
+        Ok(serde_json::from_slice(&self.call("POST", CAPTURE_STOP_PATH, &[], Some(TIMEOUT))?)?)
+    }
+
+    /// Stops a running capture and drops what it captured.
+    pub fn discard_capture(&self) -> Result<()> {
+        self.call("POST", CAPTURE_DISCARD_PATH, &[], Some(TIMEOUT))?;
+        Ok(())
+    }
+
+    /// Transcribes the last capture, see `decode`.
+    pub fn decode_captured(
+        &self,
+        request: &DecodeCaptured,
+        on_progress: Option<ProgressCallback>,
+        on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoded> {
+        self.stream_decode(DECODE_CAPTURED_PATH, &serde_json::to_vec(request)?, on_progress, on_segment)
+    }
+}
+
+fn error_message(response: &[u8]) -> String {
+    let error: Value = serde_json::from_slice(response).unwrap_or_default();
+    error["error"].as_str().unwrap_or("Request to whisprd failed").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_requests_round_trip() {
+        let settings = WhisperSettings { translate: true, ..WhisperSettings::default() };
+        let audio = [0.0, -1.0, 0.25, f32::MIN_POSITIVE];
+        let (parsed_settings, parsed_audio) = parse_decode_request(&encode_decode_request(&settings, &audio).unwrap()).unwrap();
+        assert!(parsed_settings.translate);
+        assert_eq!(parsed_audio, audio);
+    }
+
+    #[test]
+    fn decode_events_round_trip() {
+        let line = serde_json::to_string(&DecodeEvent::Segment("Hello".to_string())).unwrap();
+        assert_eq!(line, r#"{"segment":"Hello"}"#);
+        assert!(matches!(serde_json::from_str(&line).unwrap(), DecodeEvent::Segment(text) if text == "Hello"));
+    }
+
+    #[test]
+    fn rejects_truncated_requests() {
+        let body = encode_decode_request(&WhisperSettings::default(), &[0.5]).unwrap();
+        assert!(parse_decode_request(&body[..body.len() - 1]).is_err());
+        assert!(parse_decode_request(&body[..10]).is_err());
+        assert!(parse_decode_request(&[]).is_err());
+    }
+}
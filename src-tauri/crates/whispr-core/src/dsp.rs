@@ -0,0 +1,192 @@
+use log::info;
+use nnnoiseless::DenoiseState;
+use crate::config::DspSettings;
+
+/// RNNoise only works on 48 kHz audio
+pub const DENOISE_SAMPLE_RATE: u32 = 48000;
+// RNNoise expects samples in the range of 16 bit integers
+const DENOISE_SCALE: f32 = 32767.0;
+
+// BS.1770 block gating
+const BLOCK_DURATION: f32 = 0.4;
+const BLOCK_STEP: f32 = 0.1;
+const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+const RELATIVE_GATE_LU: f32 = -10.0;
+// Keep a little headroom so normalization never clips
+const PEAK_CEILING: f32 = 0.891; // -1 dBFS
+const MAX_GAIN_DB: f32 = 30.0;
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&self, samples: &[f32]) -> Vec<f32> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        samples.iter()
+            .map(|&sample| {
+                let x0 = sample as f64;
+                let y0 = self.b[0] * x0 + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+                x2 = x1;
+                x1 = x0;
+                y2 = y1;
+                y1 = y0;
+                y0 as f32
+            })
+            .collect()
+    }
+}
+
+/// The two K-weighting filters (high shelf and RLB high pass) for an
+/// arbitrary sample rate, as in libebur128.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    [shelf, high_pass]
+}
+
+fn power_to_lufs(power: f64) -> f32 {
+    (-0.691 + 10.0 * power.log10()) as f32
+}
+
+/// Gated integrated loudness of mono audio in LUFS, `None` if the audio is
+/// shorter than one block or entirely below the absolute gate.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let [shelf, high_pass] = k_weighting(sample_rate);
+    let weighted = high_pass.process(&shelf.process(samples));
+
+    let block_len = (BLOCK_DURATION * sample_rate as f32) as usize;
+    let step = (BLOCK_STEP * sample_rate as f32) as usize;
+    if block_len == 0 || weighted.len() < block_len {
+        return None;
+    }
+
+    let block_powers: Vec<f64> = (0..=weighted.len() - block_len)
+        .step_by(step)
+        .map(|start| {
+            let block = &weighted[start..start + block_len];
+            block.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / block_len as f64
+        })
+        .filter(|&power| power > 0.0 && power_to_lufs(power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if block_powers.is_empty() {
+        return None;
+    }
+
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+    let relative_gate = power_to_lufs(mean(&block_powers)) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = block_powers.into_iter()
+        .filter(|&power| power_to_lufs(power) > relative_gate)
+        .collect();
+    if gated.is_empty() {
+        return None;
+    }
+    Some(power_to_lufs(mean(&gated)))
+}
+
+/// Subtracts the mean and returns it.
+pub fn remove_dc_offset(samples: &mut [f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let offset = (samples.iter().map(|&s| s as f64).sum::<f64>() / samples.len() as f64) as f32;
+    samples.iter_mut().for_each(|sample| *sample -= offset);
+    offset
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+}
+
+fn to_db(value: f32) -> f32 {
+    20.0 * value.max(f32::MIN_POSITIVE).log10()
+}
+
+/// Overall RMS level in dBFS, `None` for no samples.
+pub fn rms_db<'a>(samples: impl IntoIterator<Item = &'a f32>) -> Option<f32> {
+    let (sum, count) = samples.into_iter().fold((0.0f64, 0usize), |(sum, count), &sample| {
+        (sum + (sample as f64).powi(2), count + 1)
+    });
+    (count > 0).then(|| to_db((sum / count as f64).sqrt() as f32))
+}
+
+/// Averages interleaved frames of `channels` samples into mono.
+pub fn downmix(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    samples.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Suppresses background noise in mono audio at `DENOISE_SAMPLE_RATE`.
+/// The output has the length of the input, the first frame is dropped as
+/// RNNoise fades it in.
+pub fn denoise(samples: &[f32]) -> Vec<f32> {
+    let mut state = DenoiseState::new();
+    let mut denoised = Vec::with_capacity(samples.len() + DenoiseState::FRAME_SIZE);
+    let mut input = [0.0f32; DenoiseState::FRAME_SIZE];
+    let mut output = [0.0f32; DenoiseState::FRAME_SIZE];
+    for (index, frame) in samples.chunks(DenoiseState::FRAME_SIZE).enumerate() {
+        input.fill(0.0);
+        input.iter_mut().zip(frame).for_each(|(scaled, &sample)| *scaled = sample * DENOISE_SCALE);
+        state.process_frame(&mut output, &input);
+        if index == 0 {
+            denoised.resize(frame.len(), 0.0);
+        } else {
+            denoised.extend(output[..frame.len()].iter().map(|&sample| sample / DENOISE_SCALE));
+        }
+    }
+    denoised
+}
+
+/// Runs the configured stages on mono audio right before it goes to whisper
+/// and logs what was measured and changed.
+pub fn process(samples: &mut [f32], sample_rate: u32, settings: &DspSettings) {
+    if !settings.remove_dc_offset && !settings.normalize_loudness {
+        return;
+    }
+
+    let offset = if settings.remove_dc_offset { remove_dc_offset(samples) } else { 0.0 };
+    let loudness = integrated_loudness(samples, sample_rate);
+    let input_peak = peak(samples);
+
+    let mut gain_db = 0.0;
+    if settings.normalize_loudness {
+        if let Some(loudness) = loudness {
+            let peak_limit_db = to_db(PEAK_CEILING) - to_db(input_peak);
+            gain_db = (settings.target_lufs - loudness).min(MAX_GAIN_DB).min(peak_limit_db);
+            let gain = 10f32.powf(gain_db / 20.0);
+            samples.iter_mut().for_each(|sample| *sample *= gain);
+        }
+    }
+
+    info!(
+        "DSP: DC offset {:.4}, loudness {}, peak {:.1} dBFS, gain {:+.1} dB",
+        offset,
+        loudness.map_or("n/a".to_string(), |l| format!("{:.1} LUFS", l)),
+        to_db(input_peak),
+        gain_db
+    );
+}
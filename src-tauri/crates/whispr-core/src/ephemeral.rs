@@ -0,0 +1,30 @@
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroize;
+
+// Per session, never saved to the settings
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether dictations of this session leave no traces: no history, stats,
+/// recordings or log file lines, and buffers are zeroed after each one.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+pub fn set_active(active: bool) {
+    // Both are logged while file logging is on
+    if active {
+        info!("Ephemeral session started");
+    }
+    ACTIVE.store(active, Ordering::SeqCst);
+    if !active {
+        info!("Ephemeral session ended");
+    }
+}
+
+/// Zeroes `buffer`, spare capacity included, in an ephemeral session.
+pub fn scrub<T: Zeroize + ?Sized>(buffer: &mut T) {
+    if is_active() {
+        buffer.zeroize();
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Stable error codes for integrations and the UI to branch on instead of
+/// parsing messages. Serialized as e.g. `"E_AUDIO_DEVICE"`. Codes are only
+/// ever added, never renamed or reused, see docs/api.md.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// No input device, or capture couldn't start or read it
+    #[serde(rename = "E_AUDIO_DEVICE")]
+    AudioDevice,
+    /// The model file is missing
+    #[serde(rename = "E_MODEL_MISSING")]
+    ModelMissing,
+    /// The model file couldn't be loaded
+    #[serde(rename = "E_MODEL_LOAD")]
+    ModelLoad,
+    /// The model is still loading
+    #[serde(rename = "E_MODEL_NOT_READY")]
+    ModelNotReady,
+    /// The model file doesn't match its checksum
+    #[serde(rename = "E_MODEL_CORRUPT")]
+    ModelCorrupt,
+    /// Whisper failed to transcribe the recording
+    #[serde(rename = "E_TRANSCRIPTION")]
+    Transcription,
+    /// Delivering the text to an output failed
+    #[serde(rename = "E_OUTPUT")]
+    Output,
+    /// Typing needs the Accessibility permission
+    #[serde(rename = "E_PERMISSION_AX")]
+    PermissionAx,
+    #[serde(rename = "E_CONFIG")]
+    Config,
+    /// The dictation key can't be monitored
+    #[serde(rename = "E_HOTKEY")]
+    Hotkey,
+    #[serde(rename = "E_DOWNLOAD")]
+    Download,
+    /// HTTP API: missing or wrong token
+    #[serde(rename = "E_UNAUTHORIZED")]
+    Unauthorized,
+    /// HTTP API: malformed request
+    #[serde(rename = "E_BAD_REQUEST")]
+    BadRequest,
+    /// HTTP API: unknown endpoint
+    #[serde(rename = "E_NOT_FOUND")]
+    NotFound,
+    /// HTTP API: too many transcriptions are waiting already
+    #[serde(rename = "E_BUSY")]
+    Busy,
+    /// HTTP API: the transcription didn't finish within `api.job_timeout_secs`
+    #[serde(rename = "E_TIMEOUT")]
+    Timeout,
+    #[serde(rename = "E_SYSTEM")]
+    System,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::AudioDevice => "E_AUDIO_DEVICE",
+            ErrorCode::ModelMissing => "E_MODEL_MISSING",
+            ErrorCode::ModelLoad => "E_MODEL_LOAD",
+            ErrorCode::ModelNotReady => "E_MODEL_NOT_READY",
+            ErrorCode::ModelCorrupt => "E_MODEL_CORRUPT",
+            ErrorCode::Transcription => "E_TRANSCRIPTION",
+            ErrorCode::Output => "E_OUTPUT",
+            ErrorCode::PermissionAx => "E_PERMISSION_AX",
+            ErrorCode::Config => "E_CONFIG",
+            ErrorCode::Hotkey => "E_HOTKEY",
+            ErrorCode::Download => "E_DOWNLOAD",
+            ErrorCode::Unauthorized => "E_UNAUTHORIZED",
+            ErrorCode::BadRequest => "E_BAD_REQUEST",
+            ErrorCode::NotFound => "E_NOT_FOUND",
+            ErrorCode::Busy => "E_BUSY",
+            ErrorCode::Timeout => "E_TIMEOUT",
+            ErrorCode::System => "E_SYSTEM",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_as_its_code() {
+        for code in [ErrorCode::AudioDevice, ErrorCode::PermissionAx, ErrorCode::ModelNotReady, ErrorCode::System] {
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+            assert_eq!(serde_json::from_value::<ErrorCode>(code.as_str().into()).unwrap(), code);
+        }
+    }
+}
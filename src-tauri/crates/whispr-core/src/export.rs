@@ -0,0 +1,82 @@
+use anyhow::{bail, Result};
+use std::fmt::Write;
+use std::str::FromStr;
+use crate::pipeline::Transcription;
+
+/// File formats a transcription can be written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Txt,
+    Srt,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Txt => "txt",
+            Format::Srt => "srt",
+        }
+    }
+
+    pub fn render(self, transcription: &Transcription) -> String {
+        match self {
+            Format::Txt => format!("{}\n", transcription.text.trim_end()),
+            Format::Srt => srt(&transcription.segments),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "txt" => Ok(Format::Txt),
+            "srt" => Ok(Format::Srt),
+            other => bail!("Unknown format {}, expected txt or srt", other),
+        }
+    }
+}
+
+/// `00:01:02,340` for a time in centiseconds, as whisper reports them.
+fn srt_timestamp(centis: f32) -> String {
+    let millis = (centis.max(0.0) * 10.0).round() as u64;
+    format!("{:02}:{:02}:{:02},{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+/// SubRip subtitles with one cue per segment, empty segments left out.
+pub fn srt(segments: &[(f32, f32, String)]) -> String {
+    let mut out = String::new();
+    let cues = segments.iter().filter(|(_, _, text)| !text.trim().is_empty());
+    for (index, (start, end, text)) in cues.enumerate() {
+        let _ = writeln!(out, "{}", index + 1);
+        let _ = writeln!(out, "{} --> {}", srt_timestamp(*start), srt_timestamp(end.max(*start)));
+        let _ = writeln!(out, "{}\n", text.trim());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srt_numbers_cues_and_formats_times() {
+        let segments = vec![
+            (0.0, 250.0, " Hello there.".to_string()),
+            (250.0, 260.0, " ".to_string()),
+            (6234.0, 372_345.5, " How are you?".to_string()),
+        ];
+        assert_eq!(
+            srt(&segments),
+            "1\n00:00:00,000 --> 00:00:02,500\nHello there.\n\n2\n00:01:02,340 --> 01:02:03,455\nHow are you?\n\n"
+        );
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(" SRT".parse::<Format>().unwrap(), Format::Srt);
+        assert_eq!("txt".parse::<Format>().unwrap().extension(), "txt");
+        assert!("vtt".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use log::{info, debug, error};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::history_index::{self, SearchHit};
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+// Appends and pruning rewrite the same file
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// One whisper segment with its offsets in seconds into the transcribed
+/// audio, which is also what the saved recording contains.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySegment {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub text: String,
+}
+
+/// A later transcription of the same recording, e.g. with a better model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryVersion {
+    pub timestamp: String,
+    pub model: String,
+    pub text: String,
+    pub segments: Vec<HistorySegment>,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Milliseconds since the epoch when the entry was recorded
+    pub id: i64,
+    pub timestamp: String,
+    pub text: String,
+    pub segments: Vec<HistorySegment>,
+    pub confidence: f32,
+    /// Saved WAV file, only with Developer Options → Save Recordings
+    pub recording: Option<String>,
+    /// Re-transcriptions, oldest first. The fields above stay the original.
+    #[serde(default)]
+    pub versions: Vec<HistoryVersion>,
+    /// Lowercase, without the leading #
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
+    /// App frontmost when the text was delivered
+    #[serde(default)]
+    pub app: Option<String>,
+    #[serde(default)]
+    pub bundle_id: Option<String>,
+    /// Whisper got stuck in a loop and the repetitions were trimmed
+    #[serde(default)]
+    pub low_confidence: bool,
+    /// Markdown summary from the local LLM, see `llm`
+    #[serde(default)]
+    pub summary: Option<String>,
+}
+
+fn to_segments(segments: &[(f32, f32, String)]) -> Vec<HistorySegment> {
+    segments.iter()
+        .map(|(start, end, text)| HistorySegment {
+            start_secs: start / 100.0,
+            end_secs: end / 100.0,
+            text: text.clone(),
+        })
+        .collect()
+}
+
+impl HistoryVersion {
+    /// `segments` as returned by whisper, timestamps in centiseconds.
+    pub fn new(model: &str, segments: &[(f32, f32, String)], confidence: f32) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            model: model.to_string(),
+            text: segments.iter().map(|(_, _, text)| text.as_str()).collect::<Vec<_>>().join(" "),
+            segments: to_segments(segments),
+            confidence,
+        }
+    }
+}
+
+impl HistoryEntry {
+    /// `segments` as returned by whisper, timestamps in centiseconds.
+    pub fn new(text: &str, segments: &[(f32, f32, String)], confidence: f32, recording: Option<&Path>) -> Self {
+        let now = Local::now();
+        Self {
+            id: now.timestamp_millis(),
+            timestamp: now.to_rfc3339(),
+            text: text.trim().to_string(),
+            segments: to_segments(segments),
+            confidence,
+            recording: recording.map(|path| path.display().to_string()),
+            versions: Vec::new(),
+            tags: Vec::new(),
+            favorite: false,
+            app: None,
+            bundle_id: None,
+            low_confidence: false,
+            summary: None,
+        }
+    }
+
+    /// Notes the app the text was delivered to, empty values are left out.
+    pub fn with_app(mut self, name: &str, bundle_id: &str) -> Self {
+        self.app = Some(name.to_string()).filter(|name| !name.is_empty());
+        self.bundle_id = Some(bundle_id.to_string()).filter(|bundle_id| !bundle_id.is_empty());
+        self
+    }
+}
+
+fn history_path() -> Result<PathBuf> {
+    let config_manager = ConfigManager::<WhisprConfig>::new("settings")?;
+    Ok(config_manager.get_config_dir().join(HISTORY_FILE))
+}
+
+fn read_entries(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // One broken line (e.g. a crash mid-write) shouldn't hide the rest
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => debug!("Skipping unreadable history line: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+fn write_entries(path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&temp_path)?;
+    for entry in entries {
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    }
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// All entries, oldest first.
+pub fn load() -> Result<Vec<HistoryEntry>> {
+    read_entries(&history_path()?)
+}
+
+/// Appends a transcription to `~/.whispr/history.jsonl`, dropping the oldest
+/// entries beyond `history.max_entries`. Returns false if history is off or
+/// the session is ephemeral.
+pub fn record(config: &WhisprConfig, entry: &HistoryEntry) -> Result<bool> {
+    if !config.history.enabled || crate::ephemeral::is_active() {
+        return Ok(false);
+    }
+    let path = history_path()?;
+    let _lock = WRITE_LOCK.lock().unwrap();
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    drop(file);
+
+    let mut entries = read_entries(&path)?;
+    let mut removed = Vec::new();
+    if entries.len() > config.history.max_entries {
+        removed = entries.drain(..entries.len() - config.history.max_entries).map(|entry| entry.id).collect();
+        write_entries(&path, &entries)?;
+        debug!("Pruned history to {} entries", entries.len());
+    }
+    reindex(&path, std::slice::from_ref(entry), &removed);
+    Ok(true)
+}
+
+/// Keeps the search index in sync. It can always be rebuilt from the
+/// history file, so failures are only logged.
+fn reindex(path: &Path, changed: &[HistoryEntry], removed: &[i64]) {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    if let Err(e) = history_index::update(dir, || read_entries(path), changed, removed) {
+        error!("Failed to update history index: {}", e);
+    }
+}
+
+/// Adds entries synced from other Macs, keeping the file in time order and
+/// within `max_entries`.
+pub fn merge(new_entries: Vec<HistoryEntry>, max_entries: usize) -> Result<()> {
+    let path = history_path()?;
+    let _lock = WRITE_LOCK.lock().unwrap();
+    let mut entries = read_entries(&path)?;
+    entries.extend(new_entries);
+    entries.sort_by_key(|entry| entry.id);
+    entries.dedup_by_key(|entry| entry.id);
+    let excess = entries.len().saturating_sub(max_entries);
+    let removed: Vec<i64> = entries.drain(..excess).map(|entry| entry.id).collect();
+    write_entries(&path, &entries)?;
+    reindex(&path, &entries, &removed);
+    Ok(())
+}
+
+/// Changes one entry in place.
+pub fn update<F>(id: i64, change: F) -> Result<()>
+where
+    F: FnOnce(&mut HistoryEntry),
+{
+    let path = history_path()?;
+    let _lock = WRITE_LOCK.lock().unwrap();
+    let mut entries = read_entries(&path)?;
+    let entry = entries.iter_mut().find(|entry| entry.id == id).context("Unknown history entry")?;
+    change(entry);
+    let changed = entry.clone();
+    write_entries(&path, &entries)?;
+    reindex(&path, &[changed], &[]);
+    Ok(())
+}
+
+/// "#Meeting " and "meeting" are the same tag.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim().to_lowercase()
+}
+
+/// Adds a tag to the most recent entry, for the "tag that as …" command.
+pub fn tag_latest(tag: &str) -> Result<()> {
+    let latest = load()?.last().map(|entry| entry.id).context("No transcription to tag yet")?;
+    let tag = normalize_tag(tag);
+    update(latest, |entry| {
+        if !entry.tags.contains(&tag) {
+            entry.tags.push(tag);
+        }
+    })
+}
+
+/// Writes the entries with `tag` (all entries without one) and their
+/// summaries as Markdown to `~/.whispr/exports` and returns the file.
+pub fn export(tag: Option<&str>) -> Result<PathBuf> {
+    let tag = tag.map(normalize_tag).filter(|tag| !tag.is_empty());
+    let entries: Vec<HistoryEntry> = load()?
+        .into_iter()
+        .filter(|entry| match &tag {
+            Some(tag) => entry.tags.contains(tag),
+            None => true,
+        })
+        .collect();
+
+    let title = tag.as_deref().map_or("All dictations".to_string(), |tag| format!("#{}", tag));
+    let mut markdown = format!("# {}\n", title);
+    for entry in &entries {
+        let text = entry.versions.last().map_or(&entry.text, |version| &version.text);
+        let tags: Vec<String> = entry.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        markdown.push_str(&format!("\n## {}{}\n\n{}\n", entry.timestamp, if entry.favorite { " ★" } else { "" }, text));
+        if let Some(summary) = &entry.summary {
+            markdown.push_str(&format!("\n### Summary\n\n{}\n", summary.trim()));
+        }
+        if !tags.is_empty() {
+            markdown.push_str(&format!("\n{}\n", tags.join(" ")));
+        }
+    }
+
+    let exports_dir = history_path()?.with_file_name("exports");
+    fs::create_dir_all(&exports_dir)?;
+    let name = tag.as_deref().unwrap_or("history");
+    let path = exports_dir.join(format!("{}-{}.md", name, Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    fs::write(&path, markdown)?;
+    info!("Exported {} history entries to {}", entries.len(), path.display());
+    Ok(path)
+}
+
+/// Saved recording of an entry.
+pub fn recording_path(id: i64) -> Result<PathBuf> {
+    let entries = load()?;
+    let entry = entries.iter().find(|entry| entry.id == id).context("Unknown history entry")?;
+    let recording = entry.recording.as_deref().context("No recording saved for this entry")?;
+    Ok(PathBuf::from(recording))
+}
+
+/// Full-text search over history, see `history_index::search`.
+pub fn search(query: &str) -> Result<Vec<SearchHit>> {
+    let path = history_path()?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    history_index::search(dir, || read_entries(&path), query)
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use log::{info, debug};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::history::HistoryEntry;
+
+const INDEX_FILE: &str = "history_index.sqlite";
+const MAX_HITS: usize = 200;
+// Control characters around matches, the UI highlights what's in between
+const MATCH_START: &str = "\u{2}";
+const MATCH_END: &str = "\u{3}";
+
+// The open index and the directory it belongs to, the history directory can
+// change at runtime
+static INDEX: Mutex<Option<(PathBuf, Connection)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub id: i64,
+    /// Excerpt with matches wrapped in \u{2} … \u{3}
+    pub snippet: String,
+}
+
+/// Text a search should find, the latest version wins over the original.
+fn indexed_text(entry: &HistoryEntry) -> &str {
+    entry.versions.last().map_or(&entry.text, |version| &version.text)
+}
+
+fn open(dir: &Path) -> Result<Connection> {
+    let connection = Connection::open(dir.join(INDEX_FILE)).context("Failed to open history index")?;
+    connection.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS entries USING fts5(
+            text, tags, id UNINDEXED, tokenize = 'unicode61 remove_diacritics 2'
+        );",
+    )?;
+    Ok(connection)
+}
+
+fn insert(connection: &Connection, entry: &HistoryEntry) -> Result<()> {
+    connection.execute("DELETE FROM entries WHERE id = ?1", params![entry.id])?;
+    connection.execute(
+        "INSERT INTO entries (text, tags, id) VALUES (?1, ?2, ?3)",
+        params![indexed_text(entry), entry.tags.join(" "), entry.id],
+    )?;
+    Ok(())
+}
+
+/// Runs `action` on the index in `dir`, opening it first if needed or if the
+/// open one belongs to another directory. A freshly opened
+/// index that doesn't match `entries` (e.g. history.jsonl was edited or
+/// synced from another Mac) is rebuilt.
+fn with_index<T>(dir: &Path, entries: impl FnOnce() -> Result<Vec<HistoryEntry>>, action: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    let mut index = INDEX.lock().unwrap();
+    if index.as_ref().is_none_or(|(open_dir, _)| open_dir != dir) {
+        // Close the other directory's index first
+        *index = None;
+        let connection = open(dir)?;
+        let entries = entries()?;
+        let indexed: i64 = connection.query_row("SELECT count(*) FROM entries", [], |row| row.get(0))?;
+        if indexed != entries.len() as i64 {
+            info!("Rebuilding history index ({} indexed, {} entries)", indexed, entries.len());
+            let transaction = connection.unchecked_transaction()?;
+            transaction.execute("DELETE FROM entries", [])?;
+            for entry in &entries {
+                insert(&transaction, entry)?;
+            }
+            transaction.commit()?;
+        }
+        *index = Some((dir.to_path_buf(), connection));
+    }
+    action(&index.as_ref().unwrap().1)
+}
+
+/// Adds or replaces entries in the index and drops `removed`.
+pub fn update(dir: &Path, all: impl FnOnce() -> Result<Vec<HistoryEntry>>, changed: &[HistoryEntry], removed: &[i64]) -> Result<()> {
+    with_index(dir, all, |connection| {
+        let transaction = connection.unchecked_transaction()?;
+        for entry in changed {
+            insert(&transaction, entry)?;
+        }
+        for id in removed {
+            transaction.execute("DELETE FROM entries WHERE id = ?1", params![id])?;
+        }
+        transaction.commit()?;
+        Ok(())
+    })
+}
+
+fn run_query(connection: &Connection, query: &str) -> rusqlite::Result<Vec<SearchHit>> {
+    let mut statement = connection.prepare(&format!(
+        "SELECT id, snippet(entries, 0, '{}', '{}', '…', 16) FROM entries WHERE entries MATCH ?1 ORDER BY rank LIMIT {}",
+        MATCH_START, MATCH_END, MAX_HITS
+    ))?;
+    let hits = statement
+        .query_map(params![query], |row| Ok(SearchHit { id: row.get(0)?, snippet: row.get(1)? }))?
+        .collect();
+    hits
+}
+
+/// Quotes every word, so text that isn't valid FTS5 syntax still searches.
+fn literal_query(query: &str) -> String {
+    query.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Best matches first. Supports FTS5 syntax: "exact phrases", prefix* and
+/// AND/OR/NOT.
+pub fn search(dir: &Path, all: impl FnOnce() -> Result<Vec<HistoryEntry>>, query: &str) -> Result<Vec<SearchHit>> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    with_index(dir, all, |connection| {
+        match run_query(connection, query) {
+            Ok(hits) => Ok(hits),
+            Err(e) => {
+                debug!("Not a valid search query ({}), searching literally", e);
+                Ok(run_query(connection, &literal_query(query))?)
+            }
+        }
+    })
+}
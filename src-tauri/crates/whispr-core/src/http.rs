@@ -0,0 +1,238 @@
+//! The bit of HTTP/1.1 the API server and `whisprd` speak: a request per
+//! connection, the token checked before the body is read, and a cap on the
+//! connections served at once.
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const MAX_HEADERS: usize = 64;
+// The request line and every header, read_line would otherwise buffer a line without end
+const MAX_LINE_BYTES: u64 = 8 * 1024;
+/// Per read once a request arrives, and per write
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// For the request line and headers as a whole, READ_TIMEOUT alone lets a
+// client keep a connection by sending a byte every few seconds
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Fails reads once `until` has passed.
+struct Deadline<R> {
+    inner: R,
+    until: Option<Instant>,
+}
+
+impl<R: Read> Read for Deadline<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.until.is_some_and(|until| Instant::now() > until) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Request took too long"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Reads a line of the head, failing on lines over `MAX_LINE_BYTES`.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<()> {
+    reader.take(MAX_LINE_BYTES).read_line(line)?;
+    anyhow::ensure!(line.ends_with('\n'), "Request head ended early or has a line that is too long");
+    Ok(())
+}
+
+/// Reads a request, or only its head if it lacks the token: the body isn't
+/// read for clients that can't use it. `max_body` is the largest body
+/// accepted for a path.
+pub fn read_request(stream: &mut impl Read, token: &str, max_body: impl Fn(&str) -> usize) -> Result<Option<Request>> {
+    let mut reader = BufReader::new(Deadline { inner: stream, until: Some(Instant::now() + HEAD_TIMEOUT) });
+    let mut request_line = String::new();
+    read_line(&mut reader, &mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().context("Missing method")?.to_string();
+    let path = parts.next().context("Missing path")?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        read_line(&mut reader, &mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            anyhow::bail!("Too many headers");
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let mut request = Request { method, path, headers, body: Vec::new() };
+    if !is_authorized(&request, token) {
+        return Ok(None);
+    }
+    // Uploads from authorized clients may take longer, each read still times out
+    reader.get_mut().until = None;
+
+    let length: usize = request.headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+    if length > max_body(&request.path) {
+        anyhow::bail!("Body too large");
+    }
+    // Grows as the body arrives rather than trusting Content-Length up front
+    reader.take(length as u64).read_to_end(&mut request.body)?;
+    anyhow::ensure!(request.body.len() == length, "Body shorter than Content-Length");
+
+    Ok(Some(request))
+}
+
+/// Whether the request carries `token` as `Authorization: Bearer`.
+pub fn is_authorized(request: &Request, token: &str) -> bool {
+    let Some(provided) = request.headers.get("authorization").and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    // Compare in constant time so the token can't be guessed byte by byte
+    let provided = provided.trim().as_bytes();
+    provided.len() == token.len()
+        && provided.iter().zip(token.as_bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Writes a complete response and closes the exchange.
+pub fn write_response(stream: &mut impl Write, status: u16, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Counts a connection as open until dropped.
+pub struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    pub fn take(open: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        if open.fetch_add(1, Ordering::SeqCst) >= max {
+            open.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Self(open.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Serves every connection to `listener` on a thread of its own with
+/// `READ_TIMEOUT`, at most `max_connections` at once. `serve` gets the slot
+/// the connection holds, e.g. to trade it for another kind. Blocks for as
+/// long as the listener accepts.
+pub fn accept(listener: TcpListener, max_connections: usize, serve: impl Fn(TcpStream, ConnectionSlot) + Send + Sync + 'static) {
+    let open = Arc::new(AtomicUsize::new(0));
+    let serve = Arc::new(serve);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                // Closing the connection right away is all a client over the limit gets
+                let Some(slot) = ConnectionSlot::take(&open, max_connections) else {
+                    debug!("Too many connections, dropping one");
+                    continue;
+                };
+                let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                // A peer that stopped reading can't hold a thread forever either
+                let _ = stream.set_write_timeout(Some(READ_TIMEOUT));
+                let serve = serve.clone();
+                std::thread::spawn(move || serve(stream, slot));
+            }
+            Err(e) => warn!("Failed to accept connection: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn any_size(_: &str) -> usize {
+        usize::MAX
+    }
+
+    #[test]
+    fn rejects_missing_tokens_before_reading_the_body() {
+        let mut stream = Cursor::new(b"POST /v1/transcriptions HTTP/1.1\r\nContent-Length: 100000000\r\n\r\nRIFF".to_vec());
+        assert!(read_request(&mut stream, "secret", any_size).unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_the_body_of_authorized_requests() {
+        let mut stream = Cursor::new(b"POST /v1/recording/toggle HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 2\r\n\r\n{}".to_vec());
+        let request = read_request(&mut stream, "secret", any_size).unwrap().unwrap();
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_limit_of_the_path() {
+        let mut stream = Cursor::new(b"POST /v1/small HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 3\r\n\r\n{ }".to_vec());
+        assert!(read_request(&mut stream, "secret", |path| if path == "/v1/small" { 2 } else { usize::MAX }).is_err());
+    }
+
+    #[test]
+    fn stops_reading_after_the_deadline() {
+        let mut reader = Deadline { inner: Cursor::new(b"GET".to_vec()), until: Some(Instant::now() - Duration::from_secs(1)) };
+        assert_eq!(reader.read(&mut [0; 3]).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn rejects_bodies_shorter_than_announced() {
+        let mut stream = Cursor::new(b"POST /v1/transcriptions HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 10\r\n\r\nRIFF".to_vec());
+        assert!(read_request(&mut stream, "secret", any_size).is_err());
+    }
+
+    #[test]
+    fn rejects_overlong_header_lines() {
+        let mut head = b"GET /v1/status HTTP/1.1\r\nAuthorization: Bearer secret\r\nX-Padding: ".to_vec();
+        head.extend(vec![b'a'; MAX_LINE_BYTES as usize]);
+        head.extend(b"\r\n\r\n");
+        assert!(read_request(&mut Cursor::new(head), "secret", any_size).is_err());
+    }
+
+    #[test]
+    fn connection_slots_are_capped_and_returned() {
+        let open = Arc::new(AtomicUsize::new(0));
+        let first = ConnectionSlot::take(&open, 1);
+        assert!(first.is_some());
+        assert!(ConnectionSlot::take(&open, 1).is_none());
+        drop(first);
+        assert!(ConnectionSlot::take(&open, 1).is_some());
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, Host, Stream, StreamConfig};
+use log::error;
+
+/// Receives interleaved f32 samples from a running input stream.
+pub type DataCallback = Box<dyn FnMut(&[f32]) + Send + 'static>;
+/// Receives errors reported by a running input stream.
+pub type ErrorCallback = Box<dyn FnMut(InputError) + Send + 'static>;
+
+#[derive(Debug, Clone)]
+pub enum InputError {
+    /// The device was unplugged or otherwise disappeared
+    DeviceLost,
+    Other(String),
+}
+
+/// Sample format an input device delivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Source of input audio for `AudioManager`. `CpalInputDevice` wraps a real
+/// device, other implementations can feed synthetic or recorded samples.
+/// Streams are opened on the audio thread, hence `Send + Sync`.
+pub trait InputDevice: Send + Sync {
+    fn name(&self) -> Result<String>;
+    fn format(&self) -> Result<InputFormat>;
+    /// Starts delivering samples to `on_data` until the returned stream is
+    /// dropped. Stream errors (e.g. overruns) go to `on_error`.
+    fn open(&self, on_data: DataCallback, on_error: ErrorCallback) -> Result<Box<dyn InputStream>>;
+}
+
+/// A running capture, stops when dropped.
+pub trait InputStream {
+    fn pause(&self) -> Result<()>;
+}
+
+/// An input device as listed in the menu and kept in the config. Names
+/// aren't unique (two "USB Audio Device"s), so each later device with a name
+/// already taken gets its position among them appended to its id, like
+/// "USB Audio Device#2". The first keeps its plain name as id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceEntry {
+    pub id: String,
+    pub name: String,
+    /// 1 for the first device with this name
+    pub occurrence: usize,
+}
+
+impl DeviceEntry {
+    /// The name, numbered if another device has it too.
+    pub fn label(&self) -> String {
+        if self.occurrence > 1 {
+            format!("{} ({})", self.name, self.occurrence)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Assigns ids to device names in the order the host lists them.
+pub fn identify<I: IntoIterator<Item = String>>(names: I) -> Vec<DeviceEntry> {
+    let mut entries: Vec<DeviceEntry> = Vec::new();
+    for name in names {
+        let occurrence = entries.iter().filter(|entry| entry.name == name).count() + 1;
+        let id = if occurrence > 1 { format!("{}#{}", name, occurrence) } else { name.clone() };
+        entries.push(DeviceEntry { id, name, occurrence });
+    }
+    entries
+}
+
+/// The input devices of `host` with their ids. Devices whose name can't be
+/// read are left out.
+pub fn list_cpal_devices(host: &Host) -> Result<Vec<(DeviceEntry, Device)>> {
+    let devices: Vec<(String, Device)> = host.input_devices()?
+        .filter_map(|device| device.name().ok().map(|name| (name, device)))
+        .collect();
+    let entries = identify(devices.iter().map(|(name, _)| name.clone()));
+    Ok(entries.into_iter().zip(devices.into_iter().map(|(_, device)| device)).collect())
+}
+
+/// The input device of `host` with `id`, if it is connected.
+pub fn find_cpal_device(host: &Host, id: &str) -> Result<Option<Device>> {
+    Ok(list_cpal_devices(host)?.into_iter().find(|(entry, _)| entry.id == id).map(|(_, device)| device))
+}
+
+/// Virtual devices that pass what the Mac plays back to an input, in the
+/// order they are preferred.
+pub const LOOPBACK_DEVICES: &[&str] = &["BlackHole", "Loopback Audio", "Soundflower", "VB-Cable"];
+
+/// The loopback device to capture system audio from: the one named
+/// `preferred` if given and connected, otherwise the first known one.
+pub fn pick_loopback<'a>(devices: &'a [DeviceEntry], preferred: Option<&str>) -> Option<&'a DeviceEntry> {
+    if let Some(preferred) = preferred {
+        return devices.iter().find(|device| device.id == preferred || device.name == preferred);
+    }
+    LOOPBACK_DEVICES.iter().find_map(|known| {
+        let known = known.to_lowercase();
+        devices.iter().find(|device| device.name.to_lowercase().contains(&known))
+    })
+}
+
+pub struct CpalInputDevice {
+    device: Device,
+}
+
+impl CpalInputDevice {
+    pub fn new(device: Device) -> Self {
+        Self { device }
+    }
+}
+
+impl InputDevice for CpalInputDevice {
+    fn name(&self) -> Result<String> {
+        Ok(self.device.name()?)
+    }
+
+    fn format(&self) -> Result<InputFormat> {
+        let config = self.device.default_input_config()?;
+        Ok(InputFormat {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+        })
+    }
+
+    fn open(&self, mut on_data: DataCallback, mut on_error: ErrorCallback) -> Result<Box<dyn InputStream>> {
+        let format = self.format()?;
+        let config = StreamConfig {
+            channels: format.channels,
+            sample_rate: cpal::SampleRate(format.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let stream = self.device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| on_data(data),
+            move |err| {
+                error!("An error occurred on the audio stream: {}", err);
+                on_error(match err {
+                    cpal::StreamError::DeviceNotAvailable => InputError::DeviceLost,
+                    err => InputError::Other(err.to_string()),
+                });
+            },
+            None,
+        )?;
+        stream.play()?;
+        Ok(Box::new(CpalInputStream { stream }))
+    }
+}
+
+struct CpalInputStream {
+    stream: Stream,
+}
+
+impl InputStream for CpalInputStream {
+    fn pause(&self) -> Result<()> {
+        Ok(self.stream.pause()?)
+    }
+}
+
+/// Feeds the samples passed to `feed` to an open stream, so the capture
+/// pipeline runs without audio hardware.
+#[cfg(test)]
+pub(crate) struct SyntheticInputDevice {
+    format: InputFormat,
+    on_data: std::sync::Arc<std::sync::Mutex<Option<DataCallback>>>,
+}
+
+#[cfg(test)]
+impl SyntheticInputDevice {
+    pub(crate) fn new(sample_rate: u32, channels: u16) -> Self {
+        Self { format: InputFormat { sample_rate, channels }, on_data: Default::default() }
+    }
+
+    /// Delivers interleaved `samples` in callbacks of `chunk` samples, as a
+    /// device would. Nothing happens without an open stream.
+    pub(crate) fn feed(&self, samples: &[f32], chunk: usize) {
+        if let Some(on_data) = self.on_data.lock().unwrap().as_mut() {
+            samples.chunks(chunk).for_each(|chunk| on_data(chunk));
+        }
+    }
+}
+
+#[cfg(test)]
+impl InputDevice for SyntheticInputDevice {
+    fn name(&self) -> Result<String> {
+        Ok("Synthetic".to_string())
+    }
+
+    fn format(&self) -> Result<InputFormat> {
+        Ok(self.format)
+    }
+
+    fn open(&self, on_data: DataCallback, _on_error: ErrorCallback) -> Result<Box<dyn InputStream>> {
+        *self.on_data.lock().unwrap() = Some(on_data);
+        Ok(Box::new(SyntheticInputStream { on_data: self.on_data.clone() }))
+    }
+}
+
+#[cfg(test)]
+struct SyntheticInputStream {
+    on_data: std::sync::Arc<std::sync::Mutex<Option<DataCallback>>>,
+}
+
+#[cfg(test)]
+impl InputStream for SyntheticInputStream {
+    fn pause(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Drop for SyntheticInputStream {
+    fn drop(&mut self) {
+        self.on_data.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_a_known_loopback_device() {
+        let devices = identify(["MacBook Pro Microphone", "Soundflower (2ch)", "BlackHole 2ch"].map(String::from));
+        assert_eq!(pick_loopback(&devices, None).unwrap().name, "BlackHole 2ch");
+        assert_eq!(pick_loopback(&devices[..2], None).unwrap().name, "Soundflower (2ch)");
+        assert!(pick_loopback(&devices[..1], None).is_none());
+    }
+
+    #[test]
+    fn configured_loopback_device_wins() {
+        let devices = identify(["BlackHole 2ch", "Aggregate Device"].map(String::from));
+        assert_eq!(pick_loopback(&devices, Some("Aggregate Device")).unwrap().name, "Aggregate Device");
+        assert!(pick_loopback(&devices, Some("Unplugged Device")).is_none());
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use log::{info, debug};
+use std::fs::File;
+use std::io::Read;
+
+/// Keychain service of the local API token
+pub const API_SERVICE: &str = "com.whispr.app.api";
+const ACCOUNT: &str = "whispr";
+const TOKEN_BYTES: usize = 32;
+
+/// Reads a password stored under `service` for the whispr account.
+#[cfg(target_os = "macos")]
+pub fn find_password(service: &str) -> Option<String> {
+    let password = security_framework::passwords::get_generic_password(service, ACCOUNT).ok()?;
+    let password = String::from_utf8(password).ok()?.trim().to_string();
+    (!password.is_empty()).then_some(password)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn find_password(_service: &str) -> Option<String> {
+    None
+}
+
+/// Stores `password` through the Security framework, `security` would need
+/// it as an argument that every local process can read with `ps`.
+#[cfg(target_os = "macos")]
+fn store_password(service: &str, password: &str) -> Result<()> {
+    security_framework::passwords::set_generic_password(service, ACCOUNT, password.as_bytes())
+        .with_context(|| format!("Failed to store {} in the Keychain", service))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn store_password(service: &str, _password: &str) -> Result<()> {
+    anyhow::bail!("Failed to store {}, the Keychain is only available on macOS", service)
+}
+
+fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .context("Failed to read random bytes")?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Token clients of the local API authenticate with. Generated on first use
+/// and stored in the login Keychain, where plugins can read it as well.
+pub fn api_token() -> Result<String> {
+    if let Some(token) = find_password(API_SERVICE) {
+        debug!("Using API token from the Keychain");
+        return Ok(token);
+    }
+
+    let token = generate_token()?;
+    store_password(API_SERVICE, &token)?;
+    info!("Generated a new API token and stored it in the Keychain");
+    Ok(token)
+}
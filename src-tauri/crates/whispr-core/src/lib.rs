@@ -0,0 +1,46 @@
+//! The parts of whispr that don't need Tauri or Cocoa: config, audio capture
+//! and conversion, the whisper backend, post-processing, history and the
+//! Keychain. The app, the command line tools and the tests all build on it.
+//! Everything but the config and the Keychain needs the `engine` feature.
+
+#[cfg(feature = "engine")]
+pub mod agc;
+#[cfg(feature = "engine")]
+pub mod audio;
+#[cfg(feature = "engine")]
+pub mod cjk;
+pub mod config;
+pub mod config_schema;
+#[cfg(feature = "engine")]
+pub mod daemon;
+#[cfg(feature = "engine")]
+pub mod dsp;
+#[cfg(feature = "engine")]
+pub mod ephemeral;
+pub mod error;
+#[cfg(feature = "engine")]
+pub mod export;
+#[cfg(feature = "engine")]
+pub mod history;
+#[cfg(feature = "engine")]
+pub mod history_index;
+pub mod http;
+#[cfg(feature = "engine")]
+pub mod input_device;
+pub mod keychain;
+#[cfg(feature = "engine")]
+pub mod pipeline;
+#[cfg(feature = "engine")]
+pub mod quality;
+#[cfg(feature = "engine")]
+pub mod resample;
+#[cfg(feature = "engine")]
+pub mod spectrogram;
+#[cfg(feature = "engine")]
+pub mod stop_phrase;
+#[cfg(feature = "engine")]
+pub mod storage;
+#[cfg(feature = "engine")]
+pub mod vad;
+#[cfg(feature = "engine")]
+pub mod whisper;
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavReader};
+use log::{info, debug};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use crate::cjk;
+use crate::config::WhisprConfig;
+use crate::dsp;
+use crate::input_device::InputFormat;
+use crate::quality;
+use crate::resample::{self, Resampler};
+use crate::whisper::{Decoding, WhisperProcessor};
+
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// What the pipeline made of a recording.
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub text: String,
+    /// Start and end in centiseconds with the text, after trimming loops
+    pub segments: Vec<(f32, f32, String)>,
+    pub confidence: f32,
+    pub language: Option<String>,
+    /// Repetition loops were trimmed, the text is less trustworthy
+    pub repetitions_trimmed: bool,
+}
+
+/// Samples of a WAV file, integer formats scaled to -1.0..1.0.
+pub fn read_wav(path: &Path) -> Result<(InputFormat, Vec<f32>)> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    read_wav_from(BufReader::new(file))
+}
+
+/// Like `read_wav`, for WAV data from anywhere, e.g. an HTTP request.
+pub fn read_wav_from<R: Read>(reader: R) -> Result<(InputFormat, Vec<f32>)> {
+    let mut reader = WavReader::new(reader).context("Not a WAV file")?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>().map(|sample| sample.map(|sample| sample as f32 / scale)).collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok((InputFormat { sample_rate: spec.sample_rate, channels: spec.channels }, samples))
+}
+
+/// Joins segments into the delivered text. A pause of at least
+/// `paragraph_pause` seconds between two segments starts a new paragraph, 0
+/// never does. A trailing space after closing punctuation lets the next
+/// dictation chain on.
+pub fn join_segments(segments: &[(f32, f32, String)], paragraph_pause: f32) -> String {
+    let mut text = String::new();
+    let mut previous_end = None;
+    for (start, end, segment) in segments {
+        if let Some(previous_end) = previous_end {
+            // Segment times are in whisper's 10 ms steps
+            let pause = (start - previous_end) / 100.0;
+            text.push_str(if paragraph_pause > 0.0 && pause >= paragraph_pause { "\n\n" } else { " " });
+        }
+        text.push_str(segment);
+        previous_end = Some(*end);
+    }
+    if text.chars().last().is_some_and(|last| last.is_ascii_punctuation()) {
+        text.push(' ');
+    }
+    text
+}
+
+/// Audio file in, transcript out: the dictation steps that don't need the
+/// app, for integration tests, benchmarks and the command line.
+pub struct Pipeline {
+    whisper: WhisperProcessor,
+    resampler: Box<dyn Resampler>,
+    config: WhisprConfig,
+}
+
+impl Pipeline {
+    pub fn load(model_path: &Path, config: WhisprConfig) -> Result<Self> {
+        let whisper = WhisperProcessor::load_with_progress(model_path, config.clone(), |_| {})
+            .map_err(|e| anyhow::anyhow!(e))?;
+        Ok(Self::new(whisper, config))
+    }
+
+    pub fn new(whisper: WhisperProcessor, config: WhisprConfig) -> Self {
+        let resampler = resample::create(config.audio.resampler);
+        Self { whisper, resampler, config }
+    }
+
+    pub fn transcribe_file(&self, path: &Path) -> Result<Transcription> {
+        let (format, samples) = read_wav(path)?;
+        self.transcribe(format, samples)
+    }
+
+    /// Downmixes and resamples `samples` for whisper, runs the DSP stage and
+    /// decodes them.
+    pub fn transcribe(&self, format: InputFormat, samples: Vec<f32>) -> Result<Transcription> {
+        let audio = self.prepare(format, samples)?;
+        let Decoding { mut segments, confidence, language, .. } = self.whisper
+            .process_audio_with_confidence(&audio, &self.config.whisper, None, None)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        let repetitions_trimmed = quality::trim_segments(&mut segments);
+        let text = join_segments(&segments, self.config.output.paragraph_pause_secs);
+        let text = cjk::format(&text, language.as_deref(), &self.config.output.cjk);
+        info!("Transcribed {} characters", text.chars().count());
+        Ok(Transcription { text, segments, confidence, language, repetitions_trimmed })
+    }
+
+    fn prepare(&self, format: InputFormat, samples: Vec<f32>) -> Result<Vec<f32>> {
+        let mut audio = if format.channels > 1 { dsp::downmix(&samples, format.channels) } else { samples };
+        if format.sample_rate != WHISPER_SAMPLE_RATE {
+            audio = self.resampler.resample(&audio, format.sample_rate, WHISPER_SAMPLE_RATE, 1)?;
+        }
+        dsp::process(&mut audio, WHISPER_SAMPLE_RATE, &self.config.audio.dsp);
+        debug!("Prepared {} samples for whisper", audio.len());
+        anyhow::ensure!(!audio.is_empty(), "Recording is empty");
+        Ok(audio)
+    }
+}
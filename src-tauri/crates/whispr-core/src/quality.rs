@@ -0,0 +1,180 @@
+use log::warn;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// Words per window the entropy is measured over
+const WINDOW_WORDS: usize = 12;
+// Shorter texts are measured as a whole, below this not at all
+const MIN_WORDS: usize = 6;
+// Window entropy relative to the maximum for its size, normal speech is around 0.9
+const MIN_ENTROPY_RATIO: f32 = 0.6;
+const MAX_LOOP_PHRASE_WORDS: usize = 4;
+// A phrase (or segment) repeated this often in a row is a decoding loop
+const MIN_LOOP_REPEATS: usize = 3;
+
+/// Lowercase without punctuation, so "Thank you." and "thank you" match.
+fn normalize(token: &str) -> String {
+    let word = token.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    if word.is_empty() { token.to_string() } else { word }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(normalize).collect()
+}
+
+/// Shannon entropy of the word distribution divided by its maximum, 0 for
+/// one word repeated, 1 when every word is different.
+fn entropy_ratio(words: &[String]) -> f32 {
+    if words.len() < 2 {
+        return 1.0;
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in words {
+        *counts.entry(word).or_default() += 1;
+    }
+    let total = words.len() as f32;
+    let entropy: f32 = counts.values()
+        .map(|&count| {
+            let p = count as f32 / total;
+            -p * p.log2()
+        })
+        .sum();
+    entropy / total.log2()
+}
+
+/// Start and end of every window whose entropy is suspiciously low.
+fn low_entropy_spans(words: &[String]) -> Vec<(usize, usize)> {
+    if words.len() < MIN_WORDS {
+        return Vec::new();
+    }
+    let window = WINDOW_WORDS.min(words.len());
+    (0..=words.len() - window)
+        .filter(|&start| entropy_ratio(&words[start..start + window]) < MIN_ENTROPY_RATIO)
+        .map(|start| (start, start + window))
+        .collect()
+}
+
+/// Phrase length and repeat count of the longest loop starting at `start`.
+fn loop_at(words: &[String], start: usize) -> Option<(usize, usize)> {
+    (1..=MAX_LOOP_PHRASE_WORDS)
+        .filter_map(|length| {
+            let phrase = words.get(start..start + length)?;
+            let repeats = (1..)
+                .take_while(|repeat| {
+                    let offset = start + repeat * length;
+                    words.get(offset..offset + length) == Some(phrase)
+                })
+                .count() + 1;
+            (repeats >= MIN_LOOP_REPEATS).then_some((length, repeats))
+        })
+        .max_by_key(|&(length, repeats)| (length * repeats, Reverse(length)))
+}
+
+/// Whether the text is stuck in a loop ("the the the…", "thank you thank
+/// you…"): a window with low word entropy that repeats a short phrase.
+pub fn is_degenerate(text: &str) -> bool {
+    let words = words(text);
+    low_entropy_spans(&words).iter().any(|&(start, end)| (start..end).any(|i| loop_at(&words, i).is_some()))
+}
+
+/// Collapses repeated phrases inside low entropy windows to one occurrence,
+/// returning `None` if there was nothing to trim.
+pub fn trim_repetitions(text: &str) -> Option<String> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let words = words(text);
+    let spans = low_entropy_spans(&words);
+    if spans.is_empty() {
+        return None;
+    }
+    let suspicious = |i: usize| spans.iter().any(|&(start, end)| (start..end).contains(&i));
+
+    let mut kept = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        match loop_at(&words, i).filter(|_| suspicious(i)) {
+            Some((length, repeats)) => {
+                kept.extend_from_slice(&tokens[i..i + length]);
+                i += length * repeats;
+            }
+            None => {
+                kept.push(tokens[i]);
+                i += 1;
+            }
+        }
+    }
+    (kept.len() < tokens.len()).then(|| kept.join(" "))
+}
+
+/// Post-decoding pass over whisper segments (timestamps in centiseconds):
+/// trims loops within segments and drops runs of the same segment repeated,
+/// keeping the first. Returns whether anything was trimmed.
+pub fn trim_segments(segments: &mut Vec<(f32, f32, String)>) -> bool {
+    let mut trimmed = false;
+    for (_, _, text) in segments.iter_mut() {
+        if let Some(shorter) = trim_repetitions(text) {
+            warn!("Trimmed repetition loop: \"{}\" -> \"{}\"", text, shorter);
+            *text = shorter;
+            trimmed = true;
+        }
+    }
+
+    let mut kept: Vec<(f32, f32, String)> = Vec::with_capacity(segments.len());
+    let mut i = 0;
+    while i < segments.len() {
+        let text = words(&segments[i].2);
+        let run = segments[i..].iter().take_while(|(_, _, other)| words(other) == text).count();
+        if run >= MIN_LOOP_REPEATS && !text.is_empty() {
+            warn!("Dropped {} repetitions of segment \"{}\"", run - 1, segments[i].2);
+            // The kept segment covers the time of the whole run
+            let end = segments[i + run - 1].1;
+            kept.push((segments[i].0, end, segments[i].2.clone()));
+            trimmed = true;
+        } else {
+            kept.extend_from_slice(&segments[i..i + run]);
+        }
+        i += run;
+    }
+    *segments = kept;
+    trimmed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_loops() {
+        assert!(is_degenerate("Thank you. Thank you. Thank you. Thank you."));
+        assert!(is_degenerate("so we need to go go go go go go go go go now"));
+        assert!(is_degenerate("and then I said and then I said and then I said and then I said"));
+    }
+
+    #[test]
+    fn accepts_normal_text() {
+        assert!(!is_degenerate("Thank you, thank you, thank you very much."));
+        assert!(!is_degenerate("Please send the report to the team by Friday."));
+        assert!(!is_degenerate(""));
+    }
+
+    #[test]
+    fn trims_loops_only() {
+        assert_eq!(
+            trim_repetitions("we should the the the the the the the the meet tomorrow").as_deref(),
+            Some("we should the meet tomorrow")
+        );
+        assert_eq!(trim_repetitions("Please send the report to the team by Friday."), None);
+    }
+
+    #[test]
+    fn drops_repeated_segments() {
+        let mut segments = vec![
+            (0.0, 100.0, "Hello there.".to_string()),
+            (100.0, 200.0, "Thank you.".to_string()),
+            (200.0, 300.0, "Thank you.".to_string()),
+            (300.0, 400.0, "Thank you.".to_string()),
+        ];
+        assert!(trim_segments(&mut segments));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1], (100.0, 400.0, "Thank you.".to_string()));
+    }
+}
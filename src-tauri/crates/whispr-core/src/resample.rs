@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use rubato::{
+    Resampler as _, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
+};
+use samplerate::{convert, ConverterType};
+use crate::config::ResamplerBackend;
+
+const RUBATO_CHUNK_SIZE: usize = 1024;
+
+/// Sample rate conversion for interleaved f32 audio.
+pub trait Resampler: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn resample(&self, data: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Result<Vec<f32>>;
+}
+
+/// libsamplerate through the `samplerate` crate.
+pub struct LibSamplerate;
+
+impl Resampler for LibSamplerate {
+    fn name(&self) -> &'static str {
+        "libsamplerate"
+    }
+
+    fn resample(&self, data: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Result<Vec<f32>> {
+        convert(from_rate, to_rate, channels as usize, ConverterType::SincBestQuality, data)
+            .context("libsamplerate conversion failed")
+    }
+}
+
+/// Pure Rust sinc resampler, no C dependency.
+pub struct Rubato;
+
+impl Resampler for Rubato {
+    fn name(&self) -> &'static str {
+        "rubato"
+    }
+
+    fn resample(&self, data: &[f32], from_rate: u32, to_rate: u32, channels: u16) -> Result<Vec<f32>> {
+        let channels = channels as usize;
+        let frames = data.len() / channels;
+        let ratio = to_rate as f64 / from_rate as f64;
+        let params = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let mut resampler = SincFixedIn::<f32>::new(ratio, 1.0, params, RUBATO_CHUNK_SIZE, channels)
+            .context("Failed to create rubato resampler")?;
+
+        let input: Vec<Vec<f32>> = (0..channels)
+            .map(|channel| data.iter().skip(channel).step_by(channels).copied().collect())
+            .collect();
+        let mut output: Vec<Vec<f32>> = vec![Vec::new(); channels];
+        let mut append = |chunk: Vec<Vec<f32>>| {
+            for (channel, samples) in output.iter_mut().zip(chunk) {
+                channel.extend(samples);
+            }
+        };
+
+        let mut position = 0;
+        while position + RUBATO_CHUNK_SIZE <= frames {
+            let chunk: Vec<&[f32]> = input.iter().map(|c| &c[position..position + RUBATO_CHUNK_SIZE]).collect();
+            append(resampler.process(&chunk, None).context("rubato conversion failed")?);
+            position += RUBATO_CHUNK_SIZE;
+        }
+        if position < frames {
+            let chunk: Vec<&[f32]> = input.iter().map(|c| &c[position..]).collect();
+            append(resampler.process_partial(Some(&chunk), None).context("rubato conversion failed")?);
+        }
+        // Flush the samples still held back by the filter delay
+        append(resampler.process_partial::<&[f32]>(None, None).context("rubato conversion failed")?);
+
+        let delay = resampler.output_delay();
+        let expected = (frames as f64 * ratio).round() as usize;
+        let mut interleaved = Vec::with_capacity(expected * channels);
+        for frame in delay..(delay + expected).min(output[0].len()) {
+            interleaved.extend(output.iter().map(|channel| channel[frame]));
+        }
+        Ok(interleaved)
+    }
+}
+
+pub fn create(backend: ResamplerBackend) -> Box<dyn Resampler> {
+    match backend {
+        ResamplerBackend::Samplerate => Box::new(LibSamplerate),
+        ResamplerBackend::Rubato => Box::new(Rubato),
+    }
+}
@@ -0,0 +1,143 @@
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use std::sync::Arc;
+
+// Window and hop as in whisper's own input features: 25 ms and 10 ms
+const WINDOW_SECS: f32 = 0.025;
+const HOP_SECS: f32 = 0.01;
+// Whisper only looks at the audio below 8 kHz
+const MAX_FREQUENCY: f32 = 8000.0;
+// Levels map onto 0..1 between this and full scale
+const FLOOR_DB: f32 = -100.0;
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Log-mel spectrogram of a mono stream, computed frame by frame as audio
+/// arrives. Each frame holds one level per mel band, lowest band first,
+/// between 0 (at or below -100 dBFS) and 1 (full scale).
+pub struct MelSpectrogram {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Per mel band the FFT bins it covers with their triangle weights
+    filters: Vec<Vec<(usize, f32)>>,
+    hop: usize,
+    /// Power of a full scale sine in its FFT bin
+    reference_power: f32,
+    pending: Vec<f32>,
+}
+
+impl MelSpectrogram {
+    pub fn new(sample_rate: u32, mel_bands: usize) -> Self {
+        let size = ((sample_rate as f32 * WINDOW_SECS) as usize).max(2);
+        let hop = ((sample_rate as f32 * HOP_SECS) as usize).max(1);
+        let window: Vec<f32> = (0..size)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / size as f32).cos())
+            .collect();
+        let window_sum: f32 = window.iter().sum();
+
+        let max_frequency = MAX_FREQUENCY.min(sample_rate as f32 / 2.0);
+        let max_mel = hz_to_mel(max_frequency);
+        let edges: Vec<f32> = (0..mel_bands + 2)
+            .map(|i| mel_to_hz(max_mel * i as f32 / (mel_bands + 1) as f32))
+            .collect();
+        let bin_hz = sample_rate as f32 / size as f32;
+        let filters = edges.windows(3)
+            .map(|edge| {
+                let (low, center, high) = (edge[0], edge[1], edge[2]);
+                (0..=size / 2)
+                    .filter_map(|bin| {
+                        let hz = bin as f32 * bin_hz;
+                        let weight = if hz <= low || hz >= high {
+                            0.0
+                        } else if hz <= center {
+                            (hz - low) / (center - low)
+                        } else {
+                            (high - hz) / (high - center)
+                        };
+                        (weight > 0.0).then_some((bin, weight))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(size),
+            window,
+            filters,
+            hop,
+            reference_power: (window_sum / 2.0).powi(2),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Appends `samples` and returns the frames they completed.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let size = self.window.len();
+        let mut frames = Vec::new();
+        while self.pending.len() >= size {
+            frames.push(self.frame(&self.pending[..size]));
+            self.pending.drain(..self.hop);
+        }
+        frames
+    }
+
+    fn frame(&self, samples: &[f32]) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = samples.iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        self.filters.iter()
+            .map(|filter| {
+                let power: f32 = filter.iter().map(|(bin, weight)| buffer[*bin].norm_sqr() * weight).sum();
+                let db = 10.0 * (power / self.reference_power).max(1e-12).log10();
+                ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, secs: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * secs) as usize)
+            .map(|n| (2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn one_frame_per_hop() {
+        let mut spectrogram = MelSpectrogram::new(16000, 80);
+        // 400 sample window, 160 sample hop
+        assert_eq!(spectrogram.push(&[0.0; 399]).len(), 0);
+        assert_eq!(spectrogram.push(&[0.0; 1]).len(), 1);
+        assert_eq!(spectrogram.push(&[0.0; 320]).len(), 2);
+    }
+
+    #[test]
+    fn tones_light_up_their_band() {
+        let mut spectrogram = MelSpectrogram::new(48000, 80);
+        let frames = spectrogram.push(&sine(1000.0, 48000, 0.1));
+        let frame = &frames[frames.len() / 2];
+        let loudest = frame.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap().0;
+        // 1 kHz sits a bit above a third of the mel scale up to 8 kHz
+        assert!((26..=29).contains(&loudest), "loudest band {}", loudest);
+        assert!(frame[loudest] > 0.9);
+        assert!(frame[79] < frame[loudest] - 0.3);
+    }
+
+    #[test]
+    fn silence_is_zero() {
+        let mut spectrogram = MelSpectrogram::new(16000, 40);
+        let frames = spectrogram.push(&[0.0; 1600]);
+        assert!(frames.iter().flatten().all(|level| *level == 0.0));
+    }
+}
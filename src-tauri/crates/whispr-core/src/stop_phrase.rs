@@ -0,0 +1,140 @@
+/// A spoken phrase like "stop dictation" that ends a hands-free dictation.
+/// Matching ignores case, punctuation and word breaks and tolerates about
+/// one wrong letter in four, so "Stop dictating." still counts.
+#[derive(Debug, Clone)]
+pub struct StopPhrase {
+    /// The phrase lowercased without spaces or punctuation
+    letters: Vec<char>,
+    words: usize,
+}
+
+/// Lowercase letters and digits of `word`.
+fn normalize(word: &str) -> impl Iterator<Item = char> + '_ {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase)
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Words of `text` with their byte offsets.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    text.split_whitespace()
+        .map(|word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+        .collect()
+}
+
+impl StopPhrase {
+    /// `None` for a phrase without any letters.
+    pub fn new(phrase: &str) -> Option<Self> {
+        let letters: Vec<char> = normalize(phrase).collect();
+        let words = phrase.split_whitespace().count();
+        (!letters.is_empty()).then_some(Self { letters, words })
+    }
+
+    fn matches(&self, words: &[(usize, &str)]) -> bool {
+        let candidate: Vec<char> = words.iter().flat_map(|(_, word)| normalize(word)).collect();
+        !candidate.is_empty() && edit_distance(&candidate, &self.letters) * 4 <= self.letters.len()
+    }
+
+    /// Byte offset of the last occurrence of the phrase in `text`. Whisper
+    /// may split or merge words, so runs of one word more or less count too.
+    pub fn find(&self, text: &str) -> Option<usize> {
+        let words = words(text);
+        (0..words.len()).rev().find_map(|start| {
+            let counts = self.words.saturating_sub(1).max(1)..=self.words + 1;
+            counts
+                .filter(|count| start + count <= words.len())
+                .any(|count| self.matches(&words[start..start + count]))
+                .then_some(words[start].0)
+        })
+    }
+
+    /// `text` up to the last occurrence of the phrase, anything said after it
+    /// dropped too. `None` if the phrase isn't in `text`.
+    pub fn strip(&self, text: &str) -> Option<String> {
+        let offset = self.find(text)?;
+        let kept = text[..offset].trim_end().trim_end_matches([',', ';', ':', '-', '—']);
+        Some(kept.trim_end().to_string())
+    }
+
+    /// Removes the phrase from the end of a transcription. Only the last two
+    /// segments are searched so an earlier mention in the dictation stays.
+    /// Returns whether it was found.
+    pub fn strip_segments(&self, segments: &mut Vec<(f32, f32, String)>) -> bool {
+        let first = segments.len().saturating_sub(2);
+        for index in (first..segments.len()).rev() {
+            if let Some(kept) = self.strip(&segments[index].2) {
+                segments.truncate(index + 1);
+                if kept.is_empty() {
+                    segments.pop();
+                } else {
+                    segments[index].2 = kept;
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase() -> StopPhrase {
+        StopPhrase::new("stop dictation").unwrap()
+    }
+
+    #[test]
+    fn finds_exact_and_misrecognized_phrases() {
+        assert_eq!(phrase().find("That's all. Stop dictation."), Some(12));
+        assert!(phrase().find("that's all, stop dictating").is_some());
+        assert!(phrase().find("stop dick tation").is_some());
+        assert!(phrase().find("stopdictation").is_some());
+    }
+
+    #[test]
+    fn ignores_other_text() {
+        assert!(phrase().find("the bus stop near the station").is_none());
+        assert!(phrase().find("stop").is_none());
+        assert!(phrase().find("").is_none());
+        assert!(StopPhrase::new(" ... ").is_none());
+    }
+
+    #[test]
+    fn strips_phrase_and_what_follows() {
+        assert_eq!(phrase().strip("Send the report, stop dictation. Thank you.").unwrap(), "Send the report");
+        assert_eq!(phrase().strip("Stop dictation.").unwrap(), "");
+        assert!(phrase().strip("Send the report.").is_none());
+    }
+
+    #[test]
+    fn strips_only_from_the_last_segments() {
+        let mut segments = vec![
+            (0.0, 100.0, "Say stop dictation to end.".to_string()),
+            (100.0, 200.0, "Then it ends.".to_string()),
+            (200.0, 300.0, "Stop dictation.".to_string()),
+        ];
+        assert!(phrase().strip_segments(&mut segments));
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].2, "Say stop dictation to end.");
+
+        let mut segments = vec![(0.0, 100.0, "Almost done, stop dictation".to_string())];
+        assert!(phrase().strip_segments(&mut segments));
+        assert_eq!(segments[0].2, "Almost done");
+
+        let mut segments = vec![(0.0, 100.0, "Nothing to see".to_string())];
+        assert!(!phrase().strip_segments(&mut segments));
+        assert_eq!(segments[0].2, "Nothing to see");
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use crate::config::WhisprConfig;
+
+const MB: u64 = 1024 * 1024;
+
+/// Bytes available to unprivileged users on the volume containing `path`.
+pub fn available_space(path: &Path) -> Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Total size of all files below `path`, 0 if it doesn't exist.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const GB: u64 = 1024 * MB;
+    if bytes >= GB {
+        format!("{:.1} GB", bytes as f64 / GB as f64)
+    } else {
+        format!("{:.0} MB", bytes as f64 / MB as f64)
+    }
+}
+
+/// Checks that writing `required_bytes` to the volume of `path` still leaves
+/// the configured minimum free. Returns a user facing message otherwise.
+pub fn check_free_space(path: &Path, required_bytes: u64, config: &WhisprConfig) -> std::result::Result<(), String> {
+    let available = available_space(path)
+        .map_err(|e| format!("Could not determine free disk space: {}", e))?;
+    let min_free = config.storage.min_free_space_mb * MB;
+
+    if available < required_bytes.saturating_add(min_free) {
+        return Err(format!(
+            "Not enough disk space on the volume containing {}: {} free, {} needed plus {} reserve.",
+            path.display(),
+            format_bytes(available),
+            format_bytes(required_bytes),
+            format_bytes(min_free)
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use crate::input_device::InputFormat;
+
+/// Voice activity detection for silence removal by the energy of short
+/// windows. A window whose RMS level reaches the threshold is speech, and
+/// the windows within the hangover after speech are kept too so word endings
+/// and short pauses survive. Windows are always kept or dropped whole, which
+/// avoids the clicks of cutting single samples out of a waveform.
+pub struct EnergyVad {
+    /// Interleaved samples per window
+    window_len: usize,
+    channels: usize,
+    threshold: f32,
+    hangover_windows: usize,
+    /// Windows since the last one with speech
+    silent_windows: usize,
+    /// Start of a window still being filled
+    pending: Vec<f32>,
+}
+
+impl EnergyVad {
+    pub fn new(format: InputFormat, threshold_db: f32, window_ms: u32, hangover_ms: u32) -> Self {
+        let channels = format.channels.max(1) as usize;
+        let frames = (format.sample_rate as usize * window_ms.max(1) as usize / 1000).max(1);
+        let hangover_windows = (hangover_ms as usize).div_ceil(window_ms.max(1) as usize);
+        Self {
+            window_len: frames * channels,
+            channels,
+            threshold: 10f32.powf(threshold_db / 20.0),
+            hangover_windows,
+            // Silence before the first word is removed as well
+            silent_windows: hangover_windows + 1,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Adds interleaved `data` and appends the windows it completed that are
+    /// kept to `kept`.
+    pub fn process(&mut self, data: &[f32], kept: &mut Vec<f32>) {
+        self.pending.extend_from_slice(data);
+        let complete = self.pending.len() - self.pending.len() % self.window_len;
+        for window in self.pending[..complete].chunks(self.window_len) {
+            if rms(window, self.channels) >= self.threshold {
+                self.silent_windows = 0;
+            } else {
+                self.silent_windows += 1;
+            }
+            if self.silent_windows <= self.hangover_windows {
+                kept.extend_from_slice(window);
+            }
+        }
+        self.pending.drain(..complete);
+    }
+}
+
+/// RMS of the loudest channel of an interleaved window.
+fn rms(window: &[f32], channels: usize) -> f32 {
+    let frames = (window.len() / channels).max(1) as f32;
+    (0..channels)
+        .map(|channel| {
+            let power: f32 = window.iter().skip(channel).step_by(channels).map(|sample| sample * sample).sum();
+            (power / frames).sqrt()
+        })
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: InputFormat = InputFormat { sample_rate: 1000, channels: 1 };
+
+    fn tone(len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len).map(|n| if n % 2 == 0 { amplitude } else { -amplitude }).collect()
+    }
+
+    #[test]
+    fn keeps_speech_and_its_hangover_in_whole_windows() {
+        // 20 sample windows, 40 samples of hangover
+        let mut vad = EnergyVad::new(FORMAT, -40.0, 20, 40);
+        let mut kept = Vec::new();
+        vad.process(&vec![0.0; 40], &mut kept);
+        assert!(kept.is_empty());
+        vad.process(&tone(20, 0.5), &mut kept);
+        vad.process(&vec![0.0; 100], &mut kept);
+        assert_eq!(kept.len(), 20 + 40);
+        assert!(kept[..20].iter().all(|sample| sample.abs() == 0.5));
+    }
+
+    #[test]
+    fn windows_span_callbacks() {
+        let mut vad = EnergyVad::new(FORMAT, -40.0, 20, 0);
+        let mut kept = Vec::new();
+        let speech = tone(20, 0.5);
+        vad.process(&speech[..15], &mut kept);
+        assert!(kept.is_empty());
+        vad.process(&speech[15..], &mut kept);
+        assert_eq!(kept, speech);
+    }
+
+    #[test]
+    fn quiet_noise_is_silence() {
+        let mut vad = EnergyVad::new(FORMAT, -40.0, 20, 40);
+        let mut kept = Vec::new();
+        // -46 dBFS
+        vad.process(&tone(200, 0.005), &mut kept);
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn loudest_channel_decides() {
+        let format = InputFormat { sample_rate: 1000, channels: 2 };
+        let mut vad = EnergyVad::new(format, -40.0, 20, 0);
+        let mut kept = Vec::new();
+        // Speech on the right channel only
+        let stereo: Vec<f32> = tone(20, 0.5).into_iter().flat_map(|sample| [0.0, sample]).collect();
+        vad.process(&stereo, &mut kept);
+        assert_eq!(kept.len(), 40);
+    }
+}
@@ -0,0 +1,546 @@
+use whisper_rs::{WhisperContext, WhisperContextParameters, FullParams, SamplingStrategy};
+use crate::config::{Quality, WhisperSettings, WhisprConfig};
+use crate::quality;
+use log::{error, warn, info, debug};
+use std::path::{Path, PathBuf};
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex, Once, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::thread::JoinHandle;
+use std::result::Result;
+
+// Rough speed whisper.cpp loads a model from an SSD at, for the progress
+// estimate while it loads
+const LOAD_BYTES_PER_SEC: f64 = 1.5e9;
+const LOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const WHISPER_SAMPLE_RATE: f32 = 16000.0;
+// Weight of the latest run in the averaged real time factor
+const RTF_SMOOTHING: f32 = 0.3;
+// Temperature added per retry of a failed decode, as in whisper's reference implementation
+const TEMPERATURE_STEP: f32 = 0.2;
+const SAMPLED_CANDIDATES: i32 = 5;
+// Target of forwarded whisper.cpp log lines, shown in place of a source location
+const WHISPER_CPP_LOG_TARGET: &str = "whisper.cpp";
+// ggml_log_level
+const GGML_LOG_LEVEL_DEBUG: u32 = 1;
+const GGML_LOG_LEVEL_INFO: u32 = 2;
+const GGML_LOG_LEVEL_WARN: u32 = 3;
+const GGML_LOG_LEVEL_ERROR: u32 = 4;
+const GGML_LOG_LEVEL_CONT: u32 = 5;
+
+static INSTALL_LOG_CALLBACK: Once = Once::new();
+// `developer.whisper_logging`, errors are forwarded regardless
+static WHISPER_LOGGING: AtomicBool = AtomicBool::new(false);
+// whisper.cpp logs lines in pieces, collected here until the newline
+static PENDING_LOG_LINE: Mutex<(String, Option<log::Level>)> = Mutex::new((String::new(), None));
+
+/// Slot holding the currently active processor, swapped atomically when a new
+/// model finished loading. `None` while the first model is still loading.
+pub type SharedWhisperProcessor = Arc<RwLock<Option<Arc<WhisperProcessor>>>>;
+
+#[derive(Debug, Clone)]
+pub enum ModelStatus {
+    Downloading(u8),
+    DownloadingEncoder(u8),
+    Loading(u8),
+    Ready,
+    Failed(String),
+}
+
+impl std::fmt::Display for ModelStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelStatus::Downloading(percent) => write!(f, "Downloading model… {}%", percent),
+            ModelStatus::DownloadingEncoder(percent) => write!(f, "Downloading Core ML encoder… {}%", percent),
+            ModelStatus::Loading(percent) => write!(f, "Loading model… {}%", percent),
+            ModelStatus::Ready => write!(f, "Model ready"),
+            ModelStatus::Failed(e) => write!(f, "Model failed to load: {}", e),
+        }
+    }
+}
+
+/// Progress of a running transcription, emitted as `transcription-progress`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptionProgress {
+    pub percent: u8,
+    /// Estimated from the real time factor of previous transcriptions
+    pub remaining_secs: Option<f32>,
+}
+
+pub type ProgressCallback = Box<dyn FnMut(TranscriptionProgress) + Send>;
+/// Receives the text of each segment as soon as whisper decoded it.
+pub type SegmentCallback = Box<dyn FnMut(&str) + Send>;
+
+/// A word of a transcription with the lowest probability among its tokens,
+/// to point out likely misrecognitions.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WordConfidence {
+    pub word: String,
+    pub probability: f32,
+}
+
+/// Groups token bytes and probabilities into words, a token starting with a
+/// space begins a new one.
+pub fn group_words(tokens: &[(Vec<u8>, f32)]) -> Vec<WordConfidence> {
+    let mut words: Vec<(Vec<u8>, f32)> = Vec::new();
+    for (bytes, probability) in tokens {
+        match words.last_mut() {
+            Some((word, lowest)) if !bytes.starts_with(b" ") => {
+                word.extend_from_slice(bytes);
+                *lowest = lowest.min(*probability);
+            }
+            _ => words.push((bytes.clone(), *probability)),
+        }
+    }
+    words.into_iter()
+        .map(|(bytes, probability)| WordConfidence { word: String::from_utf8_lossy(&bytes).trim().to_string(), probability })
+        .filter(|word| !word.word.is_empty())
+        .collect()
+}
+
+/// Where whisper.cpp looks for the Core ML encoder of a model: next to it,
+/// with the extension and a quantization suffix replaced, e.g.
+/// `ggml-base-encoder.mlmodelc` for `ggml-base-q5_1.bin`.
+pub fn coreml_encoder_path(model_path: &Path) -> PathBuf {
+    let stem = model_path.file_stem().unwrap_or_default().to_string_lossy();
+    let base = match stem.rsplit_once('-') {
+        Some((base, suffix)) if suffix.len() == 4 && suffix.starts_with('q') && suffix.as_bytes()[2] == b'_' => base,
+        _ => stem.as_ref(),
+    };
+    model_path.with_file_name(format!("{}-encoder.mlmodelc", base))
+}
+
+/// Whether the model at `model_path` runs its encoder through Core ML: on
+/// Apple Silicon with `whisper.coreml` on and the encoder downloaded.
+pub fn uses_coreml(model_path: &Path, config: &WhisprConfig) -> bool {
+    cfg!(target_arch = "aarch64") && config.whisper.coreml && coreml_encoder_path(model_path).is_dir()
+}
+
+/// What a decode produced. Returned rather than kept on the processor, which
+/// dictations, drafts and API jobs share.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Decoding {
+    pub segments: Vec<(f32, f32, String)>,
+    /// Mean probability of all text tokens (0.0 - 1.0)
+    pub confidence: f32,
+    /// Language code, detected or configured
+    pub language: Option<String>,
+    /// Words with their confidence
+    pub words: Vec<WordConfidence>,
+}
+
+pub struct WhisperProcessor {
+    ctx: Arc<WhisperContext>,
+    model_path: PathBuf,
+    /// Processing time divided by audio duration, averaged over past runs
+    realtime_factor: Mutex<Option<f32>>,
+}
+
+fn ggml_level(level: u32) -> Option<log::Level> {
+    match level {
+        GGML_LOG_LEVEL_ERROR => Some(log::Level::Error),
+        GGML_LOG_LEVEL_WARN => Some(log::Level::Warn),
+        GGML_LOG_LEVEL_INFO => Some(log::Level::Info),
+        GGML_LOG_LEVEL_DEBUG => Some(log::Level::Debug),
+        _ => None,
+    }
+}
+
+fn forward_whisper_cpp_line(level: log::Level, line: &str) {
+    if line.trim().is_empty() || (level != log::Level::Error && !WHISPER_LOGGING.load(Ordering::SeqCst)) {
+        return;
+    }
+    log::logger().log(
+        &log::Record::builder()
+            .args(format_args!("{}", line.trim_end()))
+            .level(level)
+            .target(WHISPER_CPP_LOG_TARGET)
+            .module_path_static(Some(WHISPER_CPP_LOG_TARGET))
+            .build(),
+    );
+}
+
+/// Passes whisper.cpp's log output on to the app logger, one record per line.
+unsafe extern "C" fn whisper_cpp_log_trampoline(
+    level: u32, // ggml_log_level
+    text: *const std::os::raw::c_char,
+    _: *mut std::os::raw::c_void, // user_data
+) {
+    if text.is_null() {
+        return;
+    }
+    let text = CStr::from_ptr(text).to_string_lossy();
+    // Panicking here would unwind into C
+    let Ok(mut pending) = PENDING_LOG_LINE.lock() else {
+        return;
+    };
+    let (line, line_level) = &mut *pending;
+    // Continuations keep the level of the piece they continue
+    if level != GGML_LOG_LEVEL_CONT && line.is_empty() {
+        *line_level = ggml_level(level);
+    }
+    line.push_str(&text);
+    while let Some(end) = line.find('\n') {
+        let rest = line.split_off(end + 1);
+        forward_whisper_cpp_line(line_level.unwrap_or(log::Level::Debug), line);
+        *line = rest;
+    }
+}
+
+/// Forwards all of whisper.cpp's log lines instead of only its errors.
+pub fn set_whisper_logging(enabled: bool) {
+    WHISPER_LOGGING.store(enabled, Ordering::SeqCst);
+}
+
+impl WhisperProcessor {
+    /// Routes whisper.cpp's log output through the app logger. Called for
+    /// every model load, which also picks up a changed `whisper_logging`.
+    fn configure_logging(config: &WhisprConfig) {
+        set_whisper_logging(config.developer.whisper_logging);
+        INSTALL_LOG_CALLBACK.call_once(|| unsafe {
+            whisper_rs::set_log_callback(Some(whisper_cpp_log_trampoline), std::ptr::null_mut());
+        });
+    }
+
+    /// Loads the model from its path, reporting progress estimated from the
+    /// file size while whisper.cpp reads it. Reading it into memory first
+    /// for exact progress would hold the model twice.
+    pub fn load_with_progress<F>(model_path: &Path, config: WhisprConfig, on_progress: F) -> Result<Self, String>
+    where
+        F: Fn(u8),
+    {
+        Self::configure_logging(&config);
+
+        debug!("Loading model with use_gpu: {}, gpu_device: {}", config.whisper.use_gpu, config.whisper.gpu_device);
+
+        if uses_coreml(model_path, &config) {
+            // The first load compiles the encoder for the Neural Engine, which takes a while
+            info!("Using Core ML encoder {}", coreml_encoder_path(model_path).display());
+        }
+        let size = std::fs::metadata(model_path)
+            .map_err(|e| format!("Failed to open model {}: {}", model_path.display(), e))?
+            .len();
+        let path = model_path.to_str().ok_or("Model path is not valid UTF-8")?;
+        let expected_secs = (size as f64 / LOAD_BYTES_PER_SEC).max(0.1);
+
+        on_progress(0);
+        let started = Instant::now();
+        let ctx = std::thread::scope(|scope| {
+            let loading = scope.spawn(|| {
+                let mut context_params = WhisperContextParameters::default();
+                context_params.use_gpu = config.whisper.use_gpu;
+                context_params.gpu_device = config.whisper.gpu_device;
+                WhisperContext::new_with_params(path, context_params)
+            });
+            let mut last_percent = 0;
+            while !loading.is_finished() {
+                std::thread::sleep(LOAD_PROGRESS_INTERVAL);
+                // Held at 99 if it takes longer than estimated
+                let percent = (started.elapsed().as_secs_f64() / expected_secs * 99.0).min(99.0) as u8;
+                if percent != last_percent {
+                    last_percent = percent;
+                    on_progress(percent);
+                }
+            }
+            loading.join().map_err(|_| "Loading the model panicked".to_string())
+        })?
+        .map_err(|e| e.to_string())?;
+        on_progress(100);
+
+        Ok(Self::with_context(ctx, model_path))
+    }
+
+    fn with_context(ctx: WhisperContext, model_path: &Path) -> Self {
+        Self {
+            ctx: Arc::new(ctx),
+            model_path: model_path.to_path_buf(),
+            realtime_factor: Mutex::new(None),
+        }
+    }
+
+    /// Loads the model on a background thread and swaps it into `slot` once
+    /// ready. Transcriptions already running keep their own reference to the
+    /// previous processor.
+    pub fn load_in_background<F>(slot: SharedWhisperProcessor, model_path: PathBuf, config: WhisprConfig, on_status: F) -> JoinHandle<()>
+    where
+        F: Fn(ModelStatus) + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            info!("Loading whisper model from {}", model_path.display());
+            let result = Self::load_with_progress(&model_path, config, |percent| {
+                on_status(ModelStatus::Loading(percent));
+            });
+
+            match result {
+                Ok(processor) => {
+                    *slot.write().unwrap() = Some(Arc::new(processor));
+                    info!("Whisper model loaded from {}", model_path.display());
+                    on_status(ModelStatus::Ready);
+                }
+                Err(e) => {
+                    error!("Failed to load whisper model: {}", e);
+                    on_status(ModelStatus::Failed(e));
+                }
+            }
+        })
+    }
+
+    pub fn model_path(&self) -> &Path {
+        &self.model_path
+    }
+
+    /// `whisper.n_threads` if set, otherwise as many as the preset asks for.
+    fn n_threads(settings: &WhisperSettings) -> i32 {
+        if settings.n_threads > 0 {
+            return settings.n_threads as i32;
+        }
+        let available = std::thread::available_parallelism()
+            .map(|n| n.get() as i32)
+            .unwrap_or(4);
+        match settings.quality {
+            Quality::Fast => available.min(4),
+            Quality::Balanced => available.min(8),
+            Quality::Accurate => available,
+        }
+    }
+
+    pub fn process_audio(&self, captured_audio: &[f32], settings: &WhisperSettings) -> Result<Vec<(f32, f32, String)>, String> {
+        self.process_audio_with_confidence(captured_audio, settings, None, None)
+            .map(|decoding| decoding.segments)
+    }
+
+    /// Like `process_audio`, additionally returning the overall confidence,
+    /// language and words, reporting progress to `on_progress` and decoded
+    /// segments to `on_segment`.
+    ///
+    /// With `settings.allowed_languages`, an auto-detected language outside
+    /// the list is decoded again in the most likely allowed language.
+    pub fn process_audio_with_confidence(
+        &self,
+        captured_audio: &[f32],
+        settings: &WhisperSettings,
+        on_progress: Option<ProgressCallback>,
+        on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoding, String> {
+        // Every attempt reports to the same callbacks
+        let on_progress = on_progress.map(|callback| Arc::new(Mutex::new(callback)));
+        let on_segment = on_segment.map(|callback| Arc::new(Mutex::new(callback)));
+
+        let result = self.decode_with_fallback(captured_audio, settings, on_progress.clone(), on_segment.clone())?;
+        if !settings.detects_language() || settings.allowed_languages.is_empty() {
+            return Ok(result);
+        }
+        let Some(detected) = result.language.clone() else {
+            return Ok(result);
+        };
+        if settings.allowed_languages.iter().any(|allowed| allowed.eq_ignore_ascii_case(&detected)) {
+            return Ok(result);
+        }
+
+        let language = self.most_likely_language(captured_audio, settings)
+            .unwrap_or_else(|| settings.allowed_languages[0].clone());
+        warn!("Detected language {} is not allowed, decoding again in {}", detected, language);
+        let forced = WhisperSettings { language: Some(language), ..settings.clone() };
+        self.decode_with_fallback(captured_audio, &forced, on_progress, on_segment)
+    }
+
+    /// The allowed language whisper considers most likely for the audio.
+    fn most_likely_language(&self, captured_audio: &[f32], settings: &WhisperSettings) -> Option<String> {
+        let threads = Self::n_threads(settings) as usize;
+        let mut state = self.ctx.create_state().ok()?;
+        state.pcm_to_mel(captured_audio, threads).ok()?;
+        let probabilities = state.lang_detect(0, threads)
+            .map_err(|e| warn!("Language detection failed: {}", e))
+            .ok()?;
+        settings.allowed_languages.iter()
+            .filter_map(|language| {
+                let id = whisper_rs::get_lang_id(&language.to_lowercase())?;
+                Some((language.clone(), *probabilities.get(id as usize)?))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(language, _)| language)
+    }
+
+    /// Empty or degenerate results (the same words over and over) are decoded
+    /// again at rising temperatures like whisper's reference implementation,
+    /// up to `settings.max_retries` times.
+    fn decode_with_fallback(
+        &self,
+        captured_audio: &[f32],
+        settings: &WhisperSettings,
+        on_progress: Option<Arc<Mutex<ProgressCallback>>>,
+        on_segment: Option<Arc<Mutex<SegmentCallback>>>,
+    ) -> Result<Decoding, String> {
+        let mut attempt = 0;
+        loop {
+            let temperature = (attempt as f32 * TEMPERATURE_STEP).min(1.0);
+            let decoding = self.decode(captured_audio, settings, temperature, on_progress.clone(), on_segment.clone())?;
+            let text = decoding.segments.iter().map(|(_, _, text)| text.as_str()).collect::<Vec<_>>().join(" ");
+            let failed = text.trim().is_empty() || quality::is_degenerate(&text);
+
+            if !failed || attempt >= settings.max_retries || temperature >= 1.0 {
+                if attempt > 0 {
+                    info!(
+                        "Decoding attempt {} at temperature {:.1} {}",
+                        attempt + 1,
+                        temperature,
+                        if failed { "failed too, giving up" } else { "succeeded" }
+                    );
+                }
+                return Ok(decoding);
+            }
+            warn!(
+                "Decoding at temperature {:.1} produced {}, retrying",
+                temperature,
+                if text.trim().is_empty() { "nothing" } else { "repetitions" }
+            );
+            attempt += 1;
+        }
+    }
+
+    fn decode(
+        &self,
+        captured_audio: &[f32],
+        settings: &WhisperSettings,
+        temperature: f32,
+        on_progress: Option<Arc<Mutex<ProgressCallback>>>,
+        on_segment: Option<Arc<Mutex<SegmentCallback>>>,
+    ) -> Result<Decoding, String> {
+        // Above zero the reference implementation samples several candidates instead of beam search
+        let strategy = match settings.quality.beam_size() {
+            Some(beam_size) if temperature == 0.0 => SamplingStrategy::BeamSearch { beam_size, patience: -1.0 },
+            _ if temperature > 0.0 => SamplingStrategy::Greedy { best_of: SAMPLED_CANDIDATES },
+            _ => SamplingStrategy::Greedy { best_of: 1 },
+        };
+        let mut params = FullParams::new(strategy);
+        params.set_temperature(temperature);
+        params.set_n_threads(Self::n_threads(settings));
+        params.set_language(settings.language.as_deref());
+        params.set_translate(settings.translate);
+        if let Some(dict) = &settings.dictionary {
+            if !dict.is_empty() {
+                let prompt = format!("This audio uses specialized terms including: {}. Please use their exact writing.", dict.join(", "));
+                info!("Prompt based on dict: {}", &prompt);
+                params.set_initial_prompt(&prompt);
+            }
+        }
+
+        let audio_secs = captured_audio.len() as f32 / WHISPER_SAMPLE_RATE;
+        let started = Instant::now();
+        if let Some(on_progress) = on_progress {
+            let estimated_secs = self.realtime_factor.lock().unwrap().map(|rtf| rtf * audio_secs);
+            params.set_progress_callback_safe(move |percent: i32| {
+                let elapsed = started.elapsed().as_secs_f32();
+                (on_progress.lock().unwrap())(TranscriptionProgress {
+                    percent: percent.clamp(0, 100) as u8,
+                    remaining_secs: estimated_secs.map(|estimated| (estimated - elapsed).max(0.0)),
+                });
+            });
+        }
+
+        if let Some(on_segment) = on_segment {
+            params.set_segment_callback_safe(move |data: whisper_rs::SegmentCallbackData| {
+                (on_segment.lock().unwrap())(data.text.trim());
+            });
+        }
+
+        let mut state = self.ctx.create_state()
+            .map_err(|e| e.to_string())?;
+        
+        state.full(params, captured_audio)
+            .map_err(|e| e.to_string())?;
+
+        if audio_secs > 0.0 {
+            let rtf = started.elapsed().as_secs_f32() / audio_secs;
+            let mut realtime_factor = self.realtime_factor.lock().unwrap();
+            *realtime_factor = Some(match *realtime_factor {
+                Some(previous) => previous + RTF_SMOOTHING * (rtf - previous),
+                None => rtf,
+            });
+            debug!("Real time factor: {:.2}", rtf);
+        }
+        
+        let language = if settings.detects_language() {
+            state.full_lang_id_from_state().ok()
+                .and_then(whisper_rs::get_lang_str)
+                .map(str::to_string)
+        } else {
+            settings.language.clone()
+        };
+
+        let num_segments = state.full_n_segments()
+            .map_err(|e| e.to_string())?;
+        
+        let mut segments = Vec::new();
+        let mut probability_sum = 0.0;
+        let mut token_count = 0;
+        let mut tokens = Vec::new();
+        for i in 0..num_segments {
+            let segment = state.full_get_segment_text(i)
+                .map_err(|e| e.to_string())?.trim().into();
+            let start = state.full_get_segment_t0(i)
+                .map_err(|e| e.to_string())? as f32;
+            let end = state.full_get_segment_t1(i)
+                .map_err(|e| e.to_string())? as f32;
+
+            let num_tokens = state.full_n_tokens(i)
+                .map_err(|e| e.to_string())?;
+            for j in 0..num_tokens {
+                let token = state.full_get_token_data(i, j)
+                    .map_err(|e| e.to_string())?;
+                // Timestamps, language and other special tokens come after EOT
+                if token.id >= self.ctx.token_eot() {
+                    continue;
+                }
+                probability_sum += token.p;
+                token_count += 1;
+                // Multi-byte characters can be split across tokens, only whole words are decoded
+                let bytes = state.full_get_token_bytes(i, j)
+                    .map_err(|e| e.to_string())?;
+                tokens.push((bytes.to_vec(), token.p));
+            }
+
+            debug!("Segment [{} - {}], {} characters", start, end, segment.chars().count());
+            segments.push((start, end, segment));
+        }
+
+        let confidence = if token_count > 0 { probability_sum / token_count as f32 } else { 0.0 };
+        debug!("Transcription confidence: {:.2}", confidence);
+        Ok(Decoding { segments, confidence, language, words: group_words(&tokens) })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, probability: f32) -> (Vec<u8>, f32) {
+        (text.as_bytes().to_vec(), probability)
+    }
+
+    #[test]
+    fn coreml_encoder_sits_next_to_the_model() {
+        assert_eq!(coreml_encoder_path(Path::new("/m/model.bin")), Path::new("/m/model-encoder.mlmodelc"));
+        assert_eq!(coreml_encoder_path(Path::new("/m/ggml-tiny-q5_1.bin")), Path::new("/m/ggml-tiny-encoder.mlmodelc"));
+        assert_eq!(coreml_encoder_path(Path::new("/m/ggml-large-v3-turbo.bin")), Path::new("/m/ggml-large-v3-turbo-encoder.mlmodelc"));
+    }
+
+    #[test]
+    fn tokens_join_into_words() {
+        let words = group_words(&[token(" Hel", 0.9), token("lo", 0.4), token(",", 0.95), token(" world", 0.8), token(".", 0.99)]);
+        assert_eq!(words, vec![
+            WordConfidence { word: "Hello,".to_string(), probability: 0.4 },
+            WordConfidence { word: "world.".to_string(), probability: 0.8 },
+        ]);
+    }
+
+    #[test]
+    fn split_characters_are_decoded_per_word() {
+        let umlaut = "ü".as_bytes();
+        let tokens = vec![(b" gr".to_vec(), 0.7), (vec![umlaut[0]], 0.6), (vec![umlaut[1]], 0.9), (b"n".to_vec(), 0.8)];
+        assert_eq!(group_words(&tokens), vec![WordConfidence { word: "grün".to_string(), probability: 0.6 }]);
+        assert!(group_words(&[token(" ", 0.5)]).is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::path::{Path, PathBuf};
+use whispr_core::config::WhisprConfig;
+use whispr_core::pipeline::{self, Pipeline};
+
+fn write_wav(path: &Path, spec: WavSpec, samples: &[i16]) {
+    let mut writer = WavWriter::create(path, spec).unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+#[test]
+fn reads_integer_wav_scaled() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stereo.wav");
+    let spec = WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: SampleFormat::Int };
+    write_wav(&path, spec, &[i16::MAX, 0, i16::MIN, 0]);
+
+    let (format, samples) = pipeline::read_wav(&path).unwrap();
+    assert_eq!((format.sample_rate, format.channels), (44100, 2));
+    assert_eq!(samples.len(), 4);
+    assert!((samples[0] - 1.0).abs() < 0.001);
+    assert_eq!(samples[2], -1.0);
+}
+
+#[test]
+fn joined_text_chains_after_punctuation() {
+    let segments = vec![(0.0, 100.0, "Hello there.".to_string()), (100.0, 200.0, "How are you?".to_string())];
+    assert_eq!(pipeline::join_segments(&segments, 0.0), "Hello there. How are you? ");
+    assert_eq!(pipeline::join_segments(&[(0.0, 1.0, "no stop".to_string())], 0.0), "no stop");
+}
+
+#[test]
+fn long_pauses_start_paragraphs() {
+    // Times are in 10 ms steps: 3 s pause, then 0.5 s
+    let segments = vec![
+        (0.0, 400.0, "First point.".to_string()),
+        (700.0, 900.0, "Second point.".to_string()),
+        (950.0, 1100.0, "Still second.".to_string()),
+    ];
+    assert_eq!(pipeline::join_segments(&segments, 2.0), "First point.\n\nSecond point. Still second. ");
+    // The threshold itself counts as a pause
+    assert_eq!(pipeline::join_segments(&segments, 3.0), "First point.\n\nSecond point. Still second. ");
+    assert_eq!(pipeline::join_segments(&segments, 3.5), "First point. Second point. Still second. ");
+}
+
+#[test]
+fn zero_pause_never_breaks() {
+    let segments = vec![(0.0, 100.0, "One".to_string()), (6100.0, 6200.0, "minute later".to_string())];
+    assert_eq!(pipeline::join_segments(&segments, 0.0), "One minute later");
+    assert_eq!(pipeline::join_segments(&[], 2.0), "");
+}
+
+/// Runs the whole pipeline when `WHISPR_TEST_MODEL` points to a model and
+/// `WHISPR_TEST_AUDIO` to a WAV file of speech.
+#[test]
+fn transcribes_a_recording() {
+    let (Some(model), Some(audio)) = (std::env::var_os("WHISPR_TEST_MODEL"), std::env::var_os("WHISPR_TEST_AUDIO")) else {
+        eprintln!("WHISPR_TEST_MODEL or WHISPR_TEST_AUDIO not set, skipping");
+        return;
+    };
+    let pipeline = Pipeline::load(&PathBuf::from(model), WhisprConfig::default()).unwrap();
+    let transcription = pipeline.transcribe_file(&PathBuf::from(audio)).unwrap();
+    assert!(!transcription.text.trim().is_empty());
+    assert!(!transcription.segments.is_empty());
+}
@@ -0,0 +1,141 @@
+//! Talks to a running whispr over its local HTTP API (`api.server_enabled`),
+//! with the token from the Keychain and the port from `~/.whispr/settings.json`.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use whispr_core::config::{ConfigManager, WhisprConfig};
+use whispr_core::config_schema;
+use whispr_core::keychain;
+
+const USAGE: &str = "Usage: whisprctl <command>
+
+Commands:
+  status          Print the dictation state
+  toggle          Start a dictation, or end and transcribe the running one
+  next-quality    Switch to the next quality preset
+  next-language   Switch to the next language of the Language menu
+  last            Print the last transcription
+  events          Print every status change until interrupted
+  config          Print the settings
+  config schema   Print every setting with its default, description and
+                  allowed values
+  config-path     Print where the settings are stored";
+
+fn load_config() -> Result<(ConfigManager<WhisprConfig>, WhisprConfig)> {
+    let manager = ConfigManager::<WhisprConfig>::new("settings")?;
+    let config = manager.load_config("settings")?;
+    Ok((manager, config))
+}
+
+struct Client {
+    port: u16,
+    token: String,
+}
+
+impl Client {
+    fn new(config: &WhisprConfig) -> Result<Self> {
+        anyhow::ensure!(
+            config.api.server_enabled,
+            "The HTTP API is off, set \"api.server_enabled\" to true in the settings and restart whispr"
+        );
+        let token = keychain::find_password(keychain::API_SERVICE)
+            .context("No API token in the Keychain yet, start whispr with the HTTP API enabled first")?;
+        Ok(Self { port: config.api.port, token })
+    }
+
+    /// Sends the request and returns the connection positioned at the status line.
+    fn send(&self, method: &str, path: &str) -> Result<BufReader<TcpStream>> {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port))
+            .with_context(|| format!("whispr is not listening on port {}, is it running?", self.port))?;
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: 127.0.0.1\r\nAuthorization: Bearer {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            method, path, self.token
+        )?;
+        stream.flush()?;
+        Ok(BufReader::new(stream))
+    }
+
+    /// Reads the status line and headers, failing for anything but 200.
+    fn read_head(reader: &mut BufReader<TcpStream>) -> Result<u16> {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let status = status_line.split_whitespace().nth(1)
+            .and_then(|status| status.parse().ok())
+            .context("Invalid response from whispr")?;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                return Ok(status);
+            }
+        }
+    }
+
+    fn call(&self, method: &str, path: &str) -> Result<Value> {
+        let mut reader = self.send(method, path)?;
+        let status = Self::read_head(&mut reader)?;
+        let mut body = String::new();
+        reader.read_to_string(&mut body)?;
+        let body: Value = serde_json::from_str(&body).context("Invalid response from whispr")?;
+        if status != 200 {
+            let message = body["error"].as_str().unwrap_or("Request failed");
+            match body["code"].as_str() {
+                Some(code) => anyhow::bail!("[{}] {}", code, message),
+                None => anyhow::bail!("{}", message),
+            }
+        }
+        Ok(body)
+    }
+
+    fn tail(&self, path: &str) -> Result<()> {
+        let mut reader = self.send("GET", path)?;
+        let status = Self::read_head(&mut reader)?;
+        anyhow::ensure!(status == 200, "Request failed with status {}", status);
+        for line in reader.lines() {
+            let line = line?;
+            // Blank lines only keep the connection alive
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Value = serde_json::from_str(&line)?;
+            println!("{}", event["status"].as_str().unwrap_or_default());
+        }
+        Ok(())
+    }
+}
+
+fn print_field(body: &Value, field: &str) {
+    match &body[field] {
+        Value::String(text) => println!("{}", text),
+        Value::Null => {}
+        other => println!("{}", other),
+    }
+}
+
+fn main() -> Result<()> {
+    let Some(command) = std::env::args().nth(1) else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    // Documents the settings, no need for a settings file
+    if command == "config" && std::env::args().nth(2).as_deref() == Some("schema") {
+        print!("{}", config_schema::commented_example());
+        return Ok(());
+    }
+    let (manager, config) = load_config()?;
+    match command.as_str() {
+        "config" => println!("{}", serde_json::to_string_pretty(&config)?),
+        "config-path" => println!("{}", manager.get_config_dir().join("settings.json").display()),
+        "status" => print_field(&Client::new(&config)?.call("GET", "/v1/status")?, "status"),
+        "toggle" => print_field(&Client::new(&config)?.call("POST", "/v1/recording/toggle")?, "status"),
+        "next-quality" => print_field(&Client::new(&config)?.call("POST", "/v1/quality/next")?, "quality"),
+        "next-language" => print_field(&Client::new(&config)?.call("POST", "/v1/language/next")?, "language"),
+        "last" => print_field(&Client::new(&config)?.call("GET", "/v1/transcription/last")?, "text"),
+        "events" => Client::new(&config)?.tail("/v1/events")?,
+        "-h" | "--help" | "help" => println!("{}", USAGE),
+        other => anyhow::bail!("Unknown command {}\n\n{}", other, USAGE),
+    }
+    Ok(())
+}
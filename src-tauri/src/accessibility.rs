@@ -0,0 +1,48 @@
+use cocoa::base::{id, BOOL, NO};
+use objc::{class, msg_send, sel, sel_impl};
+use serde::Serialize;
+use whispr_core::config::{ConfigManager, UiSettings, WhisprConfig};
+
+/// How the overlay should present itself, from the macOS accessibility
+/// display settings and the `ui` overrides.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DisplayPreferences {
+    /// No fades, slides or animated waveform
+    pub reduce_motion: bool,
+    /// Solid background instead of the blurred HUD material
+    pub reduce_transparency: bool,
+}
+
+impl DisplayPreferences {
+    pub fn resolve(ui: &UiSettings) -> Self {
+        Self {
+            reduce_motion: ui.reduce_motion.unwrap_or_else(system_reduce_motion),
+            reduce_transparency: ui.reduce_transparency.unwrap_or_else(system_reduce_transparency),
+        }
+    }
+}
+
+fn system_reduce_motion() -> bool {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let reduce: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+        reduce != NO
+    }
+}
+
+fn system_reduce_transparency() -> bool {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let reduce: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceTransparency];
+        reduce != NO
+    }
+}
+
+/// Lets the overlay pick its animations and background on load.
+#[tauri::command]
+pub fn display_preferences() -> DisplayPreferences {
+    let config = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default();
+    DisplayPreferences::resolve(&config.ui)
+}
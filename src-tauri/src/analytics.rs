@@ -0,0 +1,274 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{info, debug, error};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::focus::FrontmostApp;
+
+const STATS_FILE: &str = "stats.jsonl";
+const REPORT_LABEL: &str = "whispr:report";
+const TOP_APPS: usize = 5;
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// What is kept about one dictation. Never the text itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictationStats {
+    pub timestamp: String,
+    pub words: usize,
+    /// Name of the app the text was delivered to
+    pub app: Option<String>,
+    pub bundle_id: Option<String>,
+    /// From releasing the dictation key until the text was delivered
+    pub latency_ms: u64,
+    pub language: Option<String>,
+}
+
+impl DictationStats {
+    pub fn new(text: &str, app: Option<&FrontmostApp>, latency_ms: u64, language: Option<String>) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            words: text.split_whitespace().count(),
+            app: app.map(|app| app.name.clone()).filter(|name| !name.is_empty()),
+            bundle_id: app.map(|app| app.bundle_id.clone()).filter(|bundle_id| !bundle_id.is_empty()),
+            latency_ms,
+            language,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppUsage {
+    pub app: String,
+    pub dictations: usize,
+    pub words: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonthlyReport {
+    /// "2026-10"
+    pub month: String,
+    pub dictations: usize,
+    pub words: usize,
+    pub average_latency_ms: u64,
+    /// Most words first
+    pub top_apps: Vec<AppUsage>,
+    pub top_language: Option<String>,
+}
+
+fn stats_path() -> Result<PathBuf> {
+    let config_manager = ConfigManager::<WhisprConfig>::new("settings")?;
+    Ok(config_manager.get_config_dir().join(STATS_FILE))
+}
+
+fn load() -> Result<Vec<DictationStats>> {
+    let file = match fs::File::open(stats_path()?) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut stats = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => stats.push(entry),
+            Err(e) => debug!("Skipping unreadable stats line: {}", e),
+        }
+    }
+    Ok(stats)
+}
+
+/// Appends to `~/.whispr/stats.jsonl`, only with `analytics.enabled` and
+/// outside ephemeral sessions.
+pub fn record(config: &WhisprConfig, stats: DictationStats) {
+    if !config.analytics.enabled || crate::ephemeral::is_active() {
+        return;
+    }
+    let append = || -> Result<()> {
+        let _lock = WRITE_LOCK.lock().unwrap();
+        let mut file = OpenOptions::new().create(true).append(true).open(stats_path()?)?;
+        writeln!(file, "{}", serde_json::to_string(&stats)?)?;
+        Ok(())
+    };
+    if let Err(e) = append() {
+        error!("Failed to record dictation stats: {}", e);
+    }
+}
+
+fn month_of(stats: &DictationStats) -> Option<String> {
+    DateTime::parse_from_rfc3339(&stats.timestamp).ok().map(|timestamp| timestamp.format("%Y-%m").to_string())
+}
+
+/// Aggregates the dictations of `month` ("YYYY-MM").
+pub fn summarize(month: &str, stats: &[DictationStats]) -> MonthlyReport {
+    let in_month: Vec<&DictationStats> = stats.iter().filter(|stats| month_of(stats).as_deref() == Some(month)).collect();
+    let words = in_month.iter().map(|stats| stats.words).sum();
+    let average_latency_ms = match in_month.len() {
+        0 => 0,
+        count => in_month.iter().map(|stats| stats.latency_ms).sum::<u64>() / count as u64,
+    };
+
+    let mut apps: HashMap<&str, AppUsage> = HashMap::new();
+    let mut languages: HashMap<&str, usize> = HashMap::new();
+    for stats in &in_month {
+        let app = stats.app.as_deref().unwrap_or("Unknown");
+        let usage = apps.entry(app).or_insert_with(|| AppUsage { app: app.to_string(), dictations: 0, words: 0 });
+        usage.dictations += 1;
+        usage.words += stats.words;
+        if let Some(language) = &stats.language {
+            *languages.entry(language).or_default() += 1;
+        }
+    }
+    let mut top_apps: Vec<AppUsage> = apps.into_values().collect();
+    top_apps.sort_by(|a, b| b.words.cmp(&a.words).then_with(|| a.app.cmp(&b.app)));
+    top_apps.truncate(TOP_APPS);
+    let top_language = languages.into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(language, _)| language.to_string());
+
+    MonthlyReport {
+        month: month.to_string(),
+        dictations: in_month.len(),
+        words,
+        average_latency_ms,
+        top_apps,
+        top_language,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A self-contained page, no scripts or external resources.
+pub fn render_html(report: &MonthlyReport) -> String {
+    let apps: String = report.top_apps.iter()
+        .map(|usage| format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>", escape_html(&usage.app), usage.dictations, usage.words))
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>whispr report {month}</title>
+<style>
+body {{ font-family: -apple-system, sans-serif; max-width: 40em; margin: 2em auto; color: #222; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ text-align: left; padding: 4px 8px; border-bottom: 1px solid #ddd; }}
+</style>
+</head>
+<body>
+<h1>whispr in {month}</h1>
+<p>{dictations} dictations · {words} words · {latency} ms average latency · most used language: {language}</p>
+<h2>Top apps</h2>
+<table>
+<tr><th>App</th><th>Dictations</th><th>Words</th></tr>
+{apps}
+</table>
+</body>
+</html>
+"#,
+        month = report.month,
+        dictations = report.dictations,
+        words = report.words,
+        latency = report.average_latency_ms,
+        language = escape_html(report.top_language.as_deref().unwrap_or("–")),
+        apps = apps,
+    )
+}
+
+fn current_month() -> String {
+    Local::now().format("%Y-%m").to_string()
+}
+
+/// Months with dictations, newest first.
+#[tauri::command]
+pub fn analytics_months() -> Result<Vec<String>, String> {
+    let stats = load().map_err(|e| e.to_string())?;
+    let months: BTreeSet<String> = stats.iter().filter_map(month_of).collect();
+    Ok(months.into_iter().rev().collect())
+}
+
+#[tauri::command]
+pub fn analytics_report(month: Option<String>) -> Result<MonthlyReport, String> {
+    let stats = load().map_err(|e| e.to_string())?;
+    Ok(summarize(&month.unwrap_or_else(current_month), &stats))
+}
+
+/// Writes the report as HTML to `~/.whispr/exports` and reveals it in Finder.
+#[tauri::command]
+pub fn export_analytics_report(month: Option<String>) -> Result<String, String> {
+    let export = || -> Result<PathBuf> {
+        let report = summarize(&month.unwrap_or_else(current_month), &load()?);
+        let exports_dir = stats_path()?.with_file_name("exports");
+        fs::create_dir_all(&exports_dir)?;
+        let path = exports_dir.join(format!("report-{}.html", report.month));
+        fs::write(&path, render_html(&report))?;
+        info!("Exported report for {} to {}", report.month, path.display());
+        Ok(path)
+    };
+    let path = export().map_err(|e| e.to_string())?;
+    let _ = std::process::Command::new("open").arg("-R").arg(&path).status();
+    Ok(path.display().to_string())
+}
+
+/// Opens the report window, or brings it to the front if it is already open.
+pub fn open_report<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(REPORT_LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let window = WebviewWindowBuilder::new(app, REPORT_LABEL, WebviewUrl::App("index.html".into()))
+        .title("whispr Monthly Report")
+        .inner_size(480.0, 420.0)
+        .min_inner_size(360.0, 300.0)
+        .build();
+    if let Err(e) = window {
+        error!("Failed to open report window: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(timestamp: &str, words: usize, app: &str, latency_ms: u64, language: &str) -> DictationStats {
+        DictationStats {
+            timestamp: timestamp.to_string(),
+            words,
+            app: Some(app.to_string()),
+            bundle_id: None,
+            latency_ms,
+            language: Some(language.to_string()),
+        }
+    }
+
+    #[test]
+    fn summarizes_one_month() {
+        let all = vec![
+            stats("2026-09-30T23:00:00+02:00", 100, "Mail", 100, "en"),
+            stats("2026-10-01T09:00:00+02:00", 10, "Slack", 200, "de"),
+            stats("2026-10-02T09:00:00+02:00", 30, "Notes", 400, "en"),
+            stats("2026-10-03T09:00:00+02:00", 5, "Slack", 300, "de"),
+        ];
+        let report = summarize("2026-10", &all);
+        assert_eq!(report.dictations, 3);
+        assert_eq!(report.words, 45);
+        assert_eq!(report.average_latency_ms, 300);
+        assert_eq!(report.top_apps[0].app, "Notes");
+        assert_eq!(report.top_apps[1].dictations, 2);
+        assert_eq!(report.top_language.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn html_escapes_app_names() {
+        let report = summarize("2026-10", &[stats("2026-10-01T09:00:00+00:00", 1, "<script>", 1, "en")]);
+        assert!(render_html(&report).contains("&lt;script&gt;"));
+    }
+}
@@ -0,0 +1,172 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Listener, Manager, Runtime};
+use tokio::sync::broadcast;
+use log::warn;
+use whispr_core::error::ErrorCode;
+use crate::config::WhisprConfig;
+use crate::trigger::TriggerSource;
+
+/// Version of the events and commands below. Bumped on any incompatible
+/// change, additions keep the version. See docs/api.md.
+pub const API_VERSION: u32 = 1;
+
+pub const STATUS_EVENT: &str = "whispr://status";
+pub const PARTIAL_TEXT_EVENT: &str = "whispr://partial-text";
+pub const FINAL_TEXT_EVENT: &str = "whispr://final-text";
+pub const ERROR_EVENT: &str = "whispr://error";
+
+// Changes a slow subscriber may fall behind before it misses some
+const STATUS_BACKLOG: usize = 16;
+
+static EVENTS_ENABLED: AtomicBool = AtomicBool::new(false);
+static LAST_ERROR: Mutex<Option<LastError>> = Mutex::new(None);
+static STATUS_CHANGES: Lazy<broadcast::Sender<Status>> = Lazy::new(|| broadcast::channel(STATUS_BACKLOG).0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Idle,
+    Recording,
+    Transcribing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct StatusPayload {
+    version: u32,
+    status: Status,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TextPayload<'a> {
+    version: u32,
+    text: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorPayload<'a> {
+    version: u32,
+    code: ErrorCode,
+    message: &'a str,
+}
+
+/// The most recent dictation error, reported in the HTTP status.
+#[derive(Debug, Clone, Serialize)]
+pub struct LastError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// Picks up `api.events_enabled`, called whenever the configuration is loaded.
+pub fn configure(config: &WhisprConfig) {
+    EVENTS_ENABLED.store(config.api.events_enabled, Ordering::SeqCst);
+}
+
+fn emit<R: Runtime, S: Serialize + Clone>(app: &AppHandle<R>, event: &str, payload: S) {
+    if !EVENTS_ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Err(e) = app.emit(event, payload) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Mirrors the overlay's `status-change` events as versioned status events.
+pub fn init<R: Runtime>(app: &AppHandle<R>) {
+    let app_handle = app.clone();
+    app.listen("status-change", move |event| {
+        let status = match serde_json::from_str::<String>(event.payload()).as_deref() {
+            Ok("Listening") => Status::Recording,
+            Ok("Transcribing") => Status::Transcribing,
+            Ok("Ready") => Status::Idle,
+            _ => return,
+        };
+        let _ = STATUS_CHANGES.send(status);
+        emit(&app_handle, STATUS_EVENT, StatusPayload { version: API_VERSION, status });
+    });
+}
+
+/// Receives every status change from now on, for the HTTP event stream.
+/// Unlike the events, regardless of `api.events_enabled`.
+pub fn subscribe_status() -> broadcast::Receiver<Status> {
+    STATUS_CHANGES.subscribe()
+}
+
+pub fn emit_partial_text<R: Runtime>(app: &AppHandle<R>, text: &str) {
+    emit(app, PARTIAL_TEXT_EVENT, TextPayload { version: API_VERSION, text });
+}
+
+pub fn emit_final_text<R: Runtime>(app: &AppHandle<R>, text: &str) {
+    emit(app, FINAL_TEXT_EVENT, TextPayload { version: API_VERSION, text });
+}
+
+/// Emits the error event and remembers the error for the status, which
+/// unlike the event works regardless of `api.events_enabled`.
+pub fn emit_error<R: Runtime>(app: &AppHandle<R>, code: ErrorCode, message: &str) {
+    crate::metrics::record_error(code);
+    *LAST_ERROR.lock().unwrap() = Some(LastError { code, message: message.to_string() });
+    emit(app, ERROR_EVENT, ErrorPayload { version: API_VERSION, code, message });
+}
+
+pub fn last_error() -> Option<LastError> {
+    LAST_ERROR.lock().unwrap().clone()
+}
+
+fn status(state: &crate::AppState) -> Status {
+    if state.recording_start.lock().unwrap().is_some() {
+        Status::Recording
+    } else if state.is_transcribing() {
+        Status::Transcribing
+    } else {
+        Status::Idle
+    }
+}
+
+pub fn current_status<R: Runtime>(app: &AppHandle<R>) -> Status {
+    app.try_state::<crate::AppState>()
+        .map_or(Status::Idle, |state| status(&state))
+}
+
+fn trigger<R: Runtime>(app: &AppHandle<R>, pressed: bool) {
+    crate::trigger::send(app, TriggerSource::Api, pressed);
+    // Nobody holds a key in dictations started from here, listen for the stop phrase
+    if pressed && current_status(app) == Status::Recording {
+        crate::stop_phrase::watch(app);
+    }
+}
+
+/// Starts a dictation, or ends the running one which transcribes and
+/// delivers it.
+pub fn toggle<R: Runtime>(app: &AppHandle<R>) {
+    trigger(app, current_status(app) != Status::Recording);
+}
+
+#[tauri::command]
+pub fn api_version() -> u32 {
+    API_VERSION
+}
+
+#[tauri::command]
+pub fn get_status(app: AppHandle) -> Status {
+    current_status(&app)
+}
+
+/// Starts a dictation as if the dictation key was pressed.
+#[tauri::command]
+pub fn start_recording(app: AppHandle) {
+    trigger(&app, true);
+}
+
+/// Ends a dictation as if the dictation key was released, which transcribes
+/// and delivers it.
+#[tauri::command]
+pub fn stop_recording(app: AppHandle) {
+    trigger(&app, false);
+}
+
+#[tauri::command]
+pub fn toggle_recording(app: AppHandle) {
+    toggle(&app);
+}
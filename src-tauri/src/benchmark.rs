@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::pipeline::{self, WHISPER_SAMPLE_RATE};
+use crate::{menu, models};
+
+// Spoken by macOS's own voice into the sample, so every Mac benchmarks the
+// same ten seconds of speech without shipping a recording
+const SAMPLE_TEXT: &str = "The quick brown fox jumps over the lazy dog. \
+    Please schedule the quarterly review for Thursday at half past two, \
+    and send the updated numbers to everyone on the team before lunch.";
+const SAMPLE_FILE: &str = "benchmark.wav";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Timings of benchmark runs over the same audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub runs: usize,
+    pub audio_secs: f32,
+    pub mean_secs: f32,
+    pub min_secs: f32,
+    pub max_secs: f32,
+}
+
+impl BenchmarkReport {
+    /// `None` without any runs.
+    pub fn new(audio_secs: f32, durations: &[f32]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        Some(Self {
+            runs: durations.len(),
+            audio_secs,
+            mean_secs: durations.iter().sum::<f32>() / durations.len() as f32,
+            min_secs: durations.iter().copied().fold(f32::INFINITY, f32::min),
+            max_secs: durations.iter().copied().fold(0.0, f32::max),
+        })
+    }
+
+    /// Processing time per second of audio, below 1 is faster than real time.
+    pub fn realtime_factor(&self) -> f32 {
+        self.mean_secs / self.audio_secs.max(f32::EPSILON)
+    }
+}
+
+impl std::fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2}s on average for {:.1}s of audio ({:.2}–{:.2}s over {} runs), real-time factor {:.2}",
+            self.mean_secs, self.audio_secs, self.min_secs, self.max_secs, self.runs, self.realtime_factor()
+        )
+    }
+}
+
+/// The benchmark sample in the config directory, recorded with `say` the
+/// first time.
+fn sample_path(config_dir: &Path) -> Result<PathBuf> {
+    let path = config_dir.join(SAMPLE_FILE);
+    if path.exists() {
+        return Ok(path);
+    }
+    info!("Recording benchmark sample to {}", path.display());
+    let status = Command::new("/usr/bin/say")
+        .arg("--file-format=WAVE")
+        .arg(format!("--data-format=LEI16@{}", WHISPER_SAMPLE_RATE))
+        .arg("-o")
+        .arg(&path)
+        .arg(SAMPLE_TEXT)
+        .status()
+        .context("Failed to run say")?;
+    anyhow::ensure!(status.success(), "say failed to record the benchmark sample");
+    Ok(path)
+}
+
+fn benchmark<R: Runtime>(app: &AppHandle<R>, config: &WhisprConfig, config_dir: &Path) -> Result<(String, BenchmarkReport)> {
+    let state = app.try_state::<crate::AppState>().context("App is not ready")?;
+    let whisper = state.whisper.read().unwrap().clone().context("Whisper model is still loading")?;
+    let model_name = models::model_for_path(config, whisper.model_path()).display_name.clone();
+
+    let (format, samples) = pipeline::read_wav(&sample_path(config_dir)?)?;
+    let converter = state.audio.lock().unwrap().recording_converter();
+    let audio = converter.convert(format, samples, WHISPER_SAMPLE_RATE, 1)?
+        .context("Benchmark sample is empty")?;
+    let audio_secs = audio.len() as f32 / WHISPER_SAMPLE_RATE as f32;
+
+    let runs = config.developer.benchmark_runs.max(1);
+    let mut durations = Vec::with_capacity(runs);
+    for run in 1..=runs {
+        menu::set_model_status(app, &format!("Benchmarking… {}/{}", run, runs));
+        let started = Instant::now();
+        whisper.process_audio(&audio, &config.whisper).map_err(|e| anyhow::anyhow!(e))?;
+        durations.push(started.elapsed().as_secs_f32());
+    }
+    let report = BenchmarkReport::new(audio_secs, &durations).context("No benchmark runs")?;
+    Ok((model_name, report))
+}
+
+/// Runs the benchmark sample through the loaded model
+/// `developer.benchmark_runs` times with the current settings and shows the
+/// average latency and real-time factor.
+pub fn run<R: Runtime>(app: &AppHandle<R>) {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+        let config = config_manager.load_config("settings").unwrap_or_default();
+        let result = benchmark(&app, &config, config_manager.get_config_dir());
+        menu::set_model_status(&app, &crate::whisper::ModelStatus::Ready.to_string());
+        RUNNING.store(false, Ordering::SeqCst);
+
+        let (message, kind) = match result {
+            Ok((model_name, report)) => {
+                info!("Benchmark of {} ({:?} quality): {}", model_name, config.whisper.quality, report);
+                (format!("{} ({} quality): {}", model_name, config.whisper.quality.display_name(), report), MessageDialogKind::Info)
+            }
+            Err(e) => {
+                error!("Benchmark failed: {}", e);
+                (format!("The benchmark failed: {}", e), MessageDialogKind::Error)
+            }
+        };
+        app.dialog()
+            .message(message)
+            .kind(kind)
+            .title("Model Benchmark")
+            .show(|_| {});
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_averages_runs() {
+        let report = BenchmarkReport::new(10.0, &[1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(report.runs, 3);
+        assert_eq!(report.mean_secs, 2.0);
+        assert_eq!(report.min_secs, 1.0);
+        assert_eq!(report.max_secs, 3.0);
+        assert!((report.realtime_factor() - 0.2).abs() < 1e-6);
+        assert_eq!(report.to_string(), "2.00s on average for 10.0s of audio (1.00–3.00s over 3 runs), real-time factor 0.20");
+    }
+
+    #[test]
+    fn no_runs_no_report() {
+        assert!(BenchmarkReport::new(10.0, &[]).is_none());
+    }
+}
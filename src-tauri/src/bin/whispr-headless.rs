@@ -0,0 +1,207 @@
+//! Transcribes WAV files with the dictation pipeline and prints the text and
+//! how long each took, e.g. to compare models in CI:
+//!
+//!     whispr-headless ~/.whispr/ggml-base.en.bin sample.wav
+//!
+//! or transcribes every WAV file below a directory into files next to them,
+//! leaving out the ones transcribed before, and writes a summary report:
+//!
+//!     whispr-headless batch ~/.whispr/ggml-base.en.bin recordings --jobs 2 --format srt,txt
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use whispr_core::config::{ConfigManager, WhisprConfig};
+use whispr_core::export::Format;
+use whispr_core::pipeline::{self, Pipeline};
+
+const BATCH_USAGE: &str = "Usage: whispr-headless batch <model> <dir> [--jobs N] [--format srt,txt]";
+const REPORT_FILE: &str = "whispr-batch-report.txt";
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let mut args = std::env::args_os().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg.as_os_str() == "batch") {
+        args.next();
+        return batch(args.collect());
+    }
+    let mut args = args.map(PathBuf::from);
+    let model = args.next().context("Usage: whispr-headless <model> <audio.wav>...")?;
+    let files: Vec<PathBuf> = args.collect();
+    anyhow::ensure!(!files.is_empty(), "No audio files given");
+
+    let pipeline = Pipeline::load(&model, load_config())?;
+
+    for file in files {
+        let (format, samples) = pipeline::read_wav(&file)?;
+        let audio_secs = samples.len() as f32 / (format.sample_rate as f32 * format.channels.max(1) as f32);
+        let started = Instant::now();
+        let transcription = pipeline.transcribe(format, samples)?;
+        let elapsed = started.elapsed().as_secs_f32();
+        println!("{}: {}", file.display(), transcription.text.trim_end());
+        println!(
+            "  {:.2}s for {:.2}s of audio (real-time factor {:.2}), confidence {:.2}",
+            elapsed,
+            audio_secs,
+            elapsed / audio_secs.max(f32::EPSILON),
+            transcription.confidence
+        );
+    }
+    Ok(())
+}
+
+/// The settings of this Mac if there are any, so results match the app.
+fn load_config() -> WhisprConfig {
+    ConfigManager::<WhisprConfig>::new("settings")
+        .ok()
+        .filter(|manager| manager.config_exists("settings"))
+        .and_then(|manager| manager.load_config("settings").ok())
+        .unwrap_or_default()
+}
+
+struct BatchArgs {
+    model: PathBuf,
+    dir: PathBuf,
+    jobs: usize,
+    formats: Vec<Format>,
+}
+
+fn parse_batch_args(args: Vec<OsString>) -> Result<BatchArgs> {
+    let mut positional = Vec::new();
+    let mut jobs = 1;
+    let mut formats = vec![Format::Txt];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--jobs") => {
+                let value = args.next().context(BATCH_USAGE)?;
+                jobs = value.to_string_lossy().parse().context("--jobs needs a number")?;
+            }
+            Some("--format") => {
+                let value = args.next().context(BATCH_USAGE)?;
+                formats = value.to_string_lossy().split(',').map(str::parse).collect::<Result<_>>()?;
+            }
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+    let [model, dir]: [PathBuf; 2] = positional.try_into().map_err(|_| anyhow::anyhow!(BATCH_USAGE))?;
+    anyhow::ensure!(!formats.is_empty(), "No output formats given");
+    Ok(BatchArgs { model, dir, jobs: jobs.max(1), formats })
+}
+
+/// WAV files below `dir`.
+fn find_audio(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_audio(&path, found)?;
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("wav")) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+enum Outcome {
+    Done { audio_secs: f32, decode: Duration },
+    Failed(anyhow::Error),
+}
+
+fn transcribe_into(pipeline: &Pipeline, file: &Path, formats: &[Format]) -> Result<(f32, Duration)> {
+    let (format, samples) = pipeline::read_wav(file)?;
+    let audio_secs = samples.len() as f32 / (format.sample_rate as f32 * format.channels.max(1) as f32);
+    let started = Instant::now();
+    let transcription = pipeline.transcribe(format, samples)?;
+    let decode = started.elapsed();
+    for format in formats {
+        let output = file.with_extension(format.extension());
+        fs::write(&output, format.render(&transcription))
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+    }
+    Ok((audio_secs, decode))
+}
+
+fn batch(args: Vec<OsString>) -> Result<()> {
+    let args = parse_batch_args(args)?;
+    let mut files = Vec::new();
+    find_audio(&args.dir, &mut files)?;
+    files.sort();
+    // Transcribed before if every requested output is there
+    let (skipped, pending): (Vec<PathBuf>, Vec<PathBuf>) = files.into_iter()
+        .partition(|file| args.formats.iter().all(|format| file.with_extension(format.extension()).exists()));
+    println!("{} files to transcribe, {} transcribed before", pending.len(), skipped.len());
+
+    let started = Instant::now();
+    let mut outcomes = Vec::new();
+    if !pending.is_empty() {
+        let pipeline = Pipeline::load(&args.model, load_config())?;
+        // Every worker decodes with a whisper state of its own on the one model
+        let next = Mutex::new(pending.iter());
+        let results = Mutex::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..args.jobs.min(pending.len()) {
+                scope.spawn(|| loop {
+                    let Some(file) = next.lock().unwrap().next() else {
+                        break;
+                    };
+                    let outcome = match transcribe_into(&pipeline, file, &args.formats) {
+                        Ok((audio_secs, decode)) => {
+                            println!("{}: {:.2}s for {:.2}s of audio", file.display(), decode.as_secs_f32(), audio_secs);
+                            Outcome::Done { audio_secs, decode }
+                        }
+                        Err(e) => {
+                            eprintln!("{}: {:#}", file.display(), e);
+                            Outcome::Failed(e)
+                        }
+                    };
+                    results.lock().unwrap().push((file.clone(), outcome));
+                });
+            }
+        });
+        outcomes = results.into_inner().unwrap();
+        outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    let report = report(&outcomes, skipped.len(), started.elapsed());
+    let report_path = args.dir.join(REPORT_FILE);
+    fs::write(&report_path, &report).with_context(|| format!("Failed to write {}", report_path.display()))?;
+    print!("{}", report);
+    let failed = outcomes.iter().filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_))).count();
+    anyhow::ensure!(failed == 0, "{} of {} files failed", failed, outcomes.len());
+    Ok(())
+}
+
+fn report(outcomes: &[(PathBuf, Outcome)], skipped: usize, elapsed: Duration) -> String {
+    let mut audio_secs = 0.0;
+    let mut decode_secs = 0.0;
+    let mut failures = Vec::new();
+    for (file, outcome) in outcomes {
+        match outcome {
+            Outcome::Done { audio_secs: audio, decode } => {
+                audio_secs += audio;
+                decode_secs += decode.as_secs_f32();
+            }
+            Outcome::Failed(e) => failures.push((file, e)),
+        }
+    }
+    let mut out = String::new();
+    let _ = writeln!(out, "Transcribed: {}", outcomes.len() - failures.len());
+    let _ = writeln!(out, "Skipped: {}", skipped);
+    let _ = writeln!(out, "Failed: {}", failures.len());
+    let _ = writeln!(out, "Audio: {:.1}s", audio_secs);
+    let _ = writeln!(
+        out,
+        "Decoding: {:.1}s (real-time factor {:.2}), {:.1}s wall clock",
+        decode_secs,
+        decode_secs / audio_secs.max(f32::EPSILON),
+        elapsed.as_secs_f32()
+    );
+    for (file, e) in failures {
+        let _ = writeln!(out, "  {}: {:#}", file.display(), e);
+    }
+    out
+}
@@ -0,0 +1,330 @@
+//! Keeps a whisper model loaded in the background for the menubar app, which
+//! starts it with `daemon.enabled` and has it capture and decode every
+//! dictation over the loopback port `daemon.port`. Quitting the app leaves it
+//! running, so the model is ready again right away:
+//!
+//!     whisprd [~/.whispr/ggml-large-v3-turbo.bin]
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde_json::json;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use whispr_core::audio::AudioManager;
+use whispr_core::config::{AudioSettings, AudioSource, ConfigManager, WhisperSettings, WhisprConfig};
+use whispr_core::daemon::{self, Captured, DecodeCaptured, DecodeEvent, Decoded, LoadModel, StartCapture, Status};
+use whispr_core::http::{self, Request};
+use whispr_core::whisper::{Decoding, ModelStatus, ProgressCallback, SegmentCallback, SharedWhisperProcessor, WhisperProcessor};
+use whispr_core::{ephemeral, keychain};
+
+const MAX_BODY_BYTES: usize = 64 * 1024;
+// About an hour of 16 kHz mono f32
+const MAX_AUDIO_BYTES: usize = 256 * 1024 * 1024;
+// Only the app talks to whisprd, a status check may overlap a decode
+const MAX_CONNECTIONS: usize = 4;
+
+struct Daemon {
+    whisper: SharedWhisperProcessor,
+    config: WhisprConfig,
+    token: String,
+    loading: Mutex<Option<PathBuf>>,
+    error: Mutex<Option<String>>,
+    /// Opened with the first capture, which is when macOS asks for the microphone
+    audio: Mutex<Option<AudioManager>>,
+    /// `audio.restart_on_device_change` of the running capture
+    restart_on_device_change: AtomicBool,
+    /// A lost device is being handled, the handler fires more than once per loss
+    device_lost: AtomicBool,
+}
+
+/// What a request is answered with.
+enum Reply {
+    Json(u16, String),
+    /// Streamed as `DecodeEvent`s while whisper runs
+    Decode(Arc<WhisperProcessor>, Vec<f32>, WhisperSettings),
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    Reply::Json(status, json!({ "error": message }).to_string())
+}
+
+impl Daemon {
+    fn status(&self) -> Status {
+        Status {
+            pid: std::process::id(),
+            model: self.whisper.read().unwrap().as_ref().map(|whisper| whisper.model_path().to_path_buf()),
+            loading: self.loading.lock().unwrap().clone(),
+            error: self.error.lock().unwrap().clone(),
+        }
+    }
+
+    fn load_model(self: &Arc<Self>, path: PathBuf) {
+        *self.loading.lock().unwrap() = Some(path.clone());
+        self.error.lock().unwrap().take();
+        let daemon = self.clone();
+        WhisperProcessor::load_in_background(self.whisper.clone(), path, self.config.clone(), move |status| match status {
+            ModelStatus::Ready => {
+                daemon.loading.lock().unwrap().take();
+            }
+            ModelStatus::Failed(e) => {
+                daemon.loading.lock().unwrap().take();
+                *daemon.error.lock().unwrap() = Some(e);
+            }
+            status => debug!("{}", status),
+        });
+    }
+
+    fn whisper(&self) -> Result<Arc<WhisperProcessor>, Reply> {
+        self.whisper.read().unwrap().clone().ok_or_else(|| error(503, "No model loaded yet"))
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let whisper = self.whisper()?;
+        let (settings, audio) = daemon::parse_decode_request(body).map_err(|e| error(400, &e.to_string()))?;
+        Ok(Reply::Decode(whisper, audio, settings))
+    }
+
+    /// Selects the device and processing like the app does for its own
+    /// captures.
+    fn configure_audio(audio: &mut AudioManager, settings: &AudioSettings) {
+        if settings.source == AudioSource::SystemAudio {
+            if let Err(e) = audio.use_loopback_device(settings.loopback_device.as_deref()) {
+                warn!("{}, capturing from the microphone instead", e);
+            }
+        } else if let Some(device_id) = settings.selected_device() {
+            if let Err(e) = audio.set_input_device(device_id) {
+                warn!("{}, using the default input device", e);
+                if let Err(e) = audio.use_default_input_device() {
+                    warn!("Failed to switch to the default input device: {}", e);
+                }
+            }
+        }
+        audio.configure_silence_removal(
+            settings.remove_silence,
+            Some(settings.silence_threshold_db),
+            Some(settings.silence_window_ms),
+            Some(settings.silence_hangover_ms),
+        );
+        audio.configure_agc(settings.agc, settings.agc_target_db, settings.agc_max_gain_db);
+        audio.set_resampler(settings.resampler);
+        audio.set_dsp(settings.dsp.clone());
+    }
+
+    fn start_capture(self: &Arc<Self>, body: &[u8]) -> Reply {
+        let start: StartCapture = match serde_json::from_slice(body) {
+            Ok(start) => start,
+            Err(e) => return error(400, &format!("Invalid request: {}", e)),
+        };
+        if ephemeral::is_active() != start.ephemeral {
+            ephemeral::set_active(start.ephemeral);
+        }
+        let mut audio = self.audio.lock().unwrap();
+        if audio.is_none() {
+            match AudioManager::new() {
+                Ok(mut manager) => {
+                    let daemon = Arc::downgrade(self);
+                    manager.set_device_lost_handler(move || {
+                        // Called on the audio callback thread, which must not block
+                        let Some(daemon) = daemon.upgrade() else {
+                            return;
+                        };
+                        if !daemon.device_lost.swap(true, Ordering::SeqCst) {
+                            std::thread::spawn(move || daemon.handle_device_lost());
+                        }
+                    });
+                    *audio = Some(manager);
+                }
+                Err(e) => return error(503, &format!("No audio input: {}", e)),
+            }
+        }
+        let audio = audio.as_mut().unwrap();
+        if audio.is_capturing() {
+            return error(409, "Already capturing");
+        }
+        Self::configure_audio(audio, &start.audio);
+        self.restart_on_device_change.store(start.audio.restart_on_device_change, Ordering::SeqCst);
+        match audio.start_capture() {
+            Ok(()) => Reply::Json(200, "{}".to_string()),
+            Err(e) => error(500, &format!("Failed to start capturing: {}", e)),
+        }
+    }
+
+    /// Continues on the default device or gives up on the capture, which the
+    /// app then finds empty.
+    fn handle_device_lost(&self) {
+        let mut audio = self.audio.lock().unwrap();
+        if let Some(audio) = audio.as_mut().filter(|audio| audio.is_capturing()) {
+            let restarted = self.restart_on_device_change.load(Ordering::SeqCst)
+                && match audio.restart_capture_on_default_device() {
+                    Ok(name) => {
+                        warn!("Input device lost, continuing the capture on {}", name);
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Failed to continue capturing on the default device: {}", e);
+                        false
+                    }
+                };
+            if !restarted {
+                warn!("Input device lost, dropping the capture");
+                audio.stop_capture();
+                audio.discard_captured_audio();
+            }
+        }
+        self.device_lost.store(false, Ordering::SeqCst);
+    }
+
+    fn stop_capture(&self) -> Reply {
+        let mut audio = self.audio.lock().unwrap();
+        let Some(audio) = audio.as_mut() else {
+            return error(409, "Not capturing");
+        };
+        audio.stop_capture();
+        let captured = Captured {
+            level_db: audio.captured_level_db(),
+            clip_percent: audio.capture_stats().clip_percent(),
+            recording: audio.recording_path(),
+        };
+        Reply::Json(200, json!(captured).to_string())
+    }
+
+    fn discard_capture(&self) -> Reply {
+        if let Some(audio) = self.audio.lock().unwrap().as_mut() {
+            audio.stop_capture();
+            audio.discard_captured_audio();
+            if ephemeral::is_active() {
+                audio.scrub_buffers();
+            }
+        }
+        Reply::Json(200, "{}".to_string())
+    }
+
+    fn decode_captured(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let whisper = self.whisper()?;
+        let request: DecodeCaptured = serde_json::from_slice(body).map_err(|e| error(400, &format!("Invalid request: {}", e)))?;
+        let mut audio = self.audio.lock().unwrap();
+        let audio = audio.as_mut().ok_or_else(|| error(409, "Nothing captured"))?;
+        let captured = if request.unfiltered {
+            audio.get_unfiltered_audio(16000, 1)
+        } else {
+            audio.get_captured_audio(16000, 1)
+        };
+        if ephemeral::is_active() {
+            audio.scrub_buffers();
+        }
+        match captured {
+            Ok(Some(captured)) => Ok(Reply::Decode(whisper, captured, request.settings)),
+            // Answered like a decode that heard nothing
+            Ok(None) => {
+                let decoded = Decoded { decoding: Decoding::default(), audio_secs: 0.0 };
+                Ok(Reply::Json(200, json!(DecodeEvent::Done(decoded)).to_string()))
+            }
+            Err(e) => Err(error(500, &format!("Failed to convert captured audio: {}", e))),
+        }
+    }
+
+    fn route(self: &Arc<Self>, request: &Request) -> Reply {
+        debug!("Request: {} {}", request.method, request.path);
+        let result = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", daemon::STATUS_PATH) => Ok(Reply::Json(200, json!(self.status()).to_string())),
+            ("POST", daemon::MODEL_PATH) => match serde_json::from_slice::<LoadModel>(&request.body) {
+                Ok(load) => {
+                    info!("Loading {}", load.path.display());
+                    self.load_model(load.path);
+                    Ok(Reply::Json(200, json!(self.status()).to_string()))
+                }
+                Err(e) => Err(error(400, &format!("Invalid request: {}", e))),
+            },
+            ("POST", daemon::DECODE_PATH) => self.decode(&request.body),
+            ("POST", daemon::CAPTURE_START_PATH) => Ok(self.start_capture(&request.body)),
+            ("POST", daemon::CAPTURE_STOP_PATH) => Ok(self.stop_capture()),
+            ("POST", daemon::CAPTURE_DISCARD_PATH) => Ok(self.discard_capture()),
+            ("POST", daemon::DECODE_CAPTURED_PATH) => self.decode_captured(&request.body),
+            _ => Err(error(404, "Unknown endpoint")),
+        };
+        result.unwrap_or_else(|reply| reply)
+    }
+}
+
+fn write_event(stream: &Mutex<TcpStream>, event: &DecodeEvent) -> Result<()> {
+    let mut stream = stream.lock().unwrap();
+    writeln!(stream, "{}", json!(event))?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Decodes `audio`, streaming progress and each segment as whisper gets
+/// there and the result at the end.
+fn stream_decode(stream: TcpStream, whisper: &WhisperProcessor, mut audio: Vec<f32>, settings: &WhisperSettings) -> Result<()> {
+    let stream = Arc::new(Mutex::new(stream));
+    write!(stream.lock().unwrap(), "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n")?;
+    debug!("Decoding {:.1}s of audio", audio.len() as f32 / 16000.0);
+    let progress_stream = stream.clone();
+    let on_progress: ProgressCallback = Box::new(move |progress| {
+        let _ = write_event(&progress_stream, &DecodeEvent::Progress(progress));
+    });
+    let segment_stream = stream.clone();
+    let on_segment: SegmentCallback = Box::new(move |segment| {
+        let _ = write_event(&segment_stream, &DecodeEvent::Segment(segment.to_string()));
+    });
+    let audio_secs = audio.len() as f32 / 16000.0;
+    let result = whisper.process_audio_with_confidence(&audio, settings, Some(on_progress), Some(on_segment));
+    ephemeral::scrub(&mut audio);
+    let event = match result {
+        Ok(decoding) => DecodeEvent::Done(Decoded { decoding, audio_secs }),
+        Err(e) => {
+            warn!("Decode failed: {}", e);
+            DecodeEvent::Failed(e)
+        }
+    };
+    write_event(&stream, &event)
+}
+
+fn handle(daemon: &Arc<Daemon>, mut stream: TcpStream) -> Result<()> {
+    let max_body = |path: &str| if path == daemon::DECODE_PATH { MAX_AUDIO_BYTES } else { MAX_BODY_BYTES };
+    let reply = match http::read_request(&mut stream, &daemon.token, max_body)? {
+        Some(request) => daemon.route(&request),
+        None => error(401, "Missing or invalid token"),
+    };
+    match reply {
+        Reply::Json(status, body) => http::write_response(&mut stream, status, "application/json", &body),
+        Reply::Decode(whisper, audio, settings) => stream_decode(stream, &whisper, audio, &settings),
+    }
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+    let model = std::env::args_os().nth(1).map(PathBuf::from);
+    let config = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default();
+    let port = config.daemon.port;
+    // A second whisprd finds the port taken and leaves the first one be
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Port {} is taken, is whisprd already running?", port))?;
+    let token = keychain::api_token()?;
+    info!("whisprd {} listening on 127.0.0.1:{}", std::process::id(), port);
+
+    let daemon = Arc::new(Daemon {
+        whisper: Arc::new(RwLock::new(None)),
+        config,
+        token,
+        loading: Mutex::new(None),
+        error: Mutex::new(None),
+        audio: Mutex::new(None),
+        restart_on_device_change: AtomicBool::new(false),
+        device_lost: AtomicBool::new(false),
+    });
+    if let Some(model) = model {
+        daemon.load_model(model);
+    }
+    http::accept(listener, MAX_CONNECTIONS, move |stream, _slot| {
+        if let Err(e) = handle(&daemon, stream) {
+            debug!("Request failed: {}", e);
+        }
+    });
+    Ok(())
+}
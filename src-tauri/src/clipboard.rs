@@ -0,0 +1,93 @@
+use anyhow::Result;
+use std::ffi::c_void;
+use cocoa::appkit::NSPasteboardTypeString;
+use cocoa::base::{id, nil, BOOL, NO};
+use cocoa::foundation::NSString;
+use objc::{class, msg_send, sel, sel_impl};
+use crate::focus::nsstring_to_string;
+
+fn general_pasteboard() -> id {
+    unsafe { msg_send![class!(NSPasteboard), generalPasteboard] }
+}
+
+pub fn set_text(text: &str) -> Result<()> {
+    unsafe {
+        let pasteboard = general_pasteboard();
+        let _: i64 = msg_send![pasteboard, clearContents];
+        let string = NSString::alloc(nil).init_str(text);
+        let success: BOOL = msg_send![pasteboard, setString: string forType: NSPasteboardTypeString];
+        let _: () = msg_send![string, release];
+        if success == NO {
+            anyhow::bail!("Failed to write to the pasteboard");
+        }
+    }
+    Ok(())
+}
+
+/// Everything on the pasteboard, every item with all of its types, so
+/// images, files and rich text survive a paste that borrows it.
+pub struct Contents {
+    items: Vec<Vec<(String, Vec<u8>)>>,
+}
+
+/// Copies out what's on the pasteboard.
+pub fn save() -> Contents {
+    let mut items = Vec::new();
+    unsafe {
+        let pasteboard_items: id = msg_send![general_pasteboard(), pasteboardItems];
+        if pasteboard_items == nil {
+            return Contents { items };
+        }
+        let count: usize = msg_send![pasteboard_items, count];
+        for i in 0..count {
+            let item: id = msg_send![pasteboard_items, objectAtIndex: i];
+            let types: id = msg_send![item, types];
+            let type_count: usize = if types == nil { 0 } else { msg_send![types, count] };
+            let mut representations = Vec::new();
+            for j in 0..type_count {
+                let pasteboard_type: id = msg_send![types, objectAtIndex: j];
+                let data: id = msg_send![item, dataForType: pasteboard_type];
+                let (Some(pasteboard_type), false) = (nsstring_to_string(pasteboard_type), data == nil) else {
+                    continue;
+                };
+                let bytes: *const c_void = msg_send![data, bytes];
+                let length: usize = msg_send![data, length];
+                let bytes = if bytes.is_null() { Vec::new() } else { std::slice::from_raw_parts(bytes as *const u8, length).to_vec() };
+                representations.push((pasteboard_type, bytes));
+            }
+            items.push(representations);
+        }
+    }
+    Contents { items }
+}
+
+/// Puts back what `save` copied out, leaving an empty pasteboard empty.
+pub fn restore(contents: &Contents) -> Result<()> {
+    unsafe {
+        let pasteboard = general_pasteboard();
+        let _: i64 = msg_send![pasteboard, clearContents];
+        if contents.items.is_empty() {
+            return Ok(());
+        }
+        let objects: id = msg_send![class!(NSMutableArray), new];
+        for representations in &contents.items {
+            let item: id = msg_send![class!(NSPasteboardItem), new];
+            for (pasteboard_type, bytes) in representations {
+                let pasteboard_type = NSString::alloc(nil).init_str(pasteboard_type);
+                let data: id = msg_send![class!(NSData), alloc];
+                let data: id = msg_send![data, initWithBytes: bytes.as_ptr() as *const c_void length: bytes.len()];
+                let _: BOOL = msg_send![item, setData: data forType: pasteboard_type];
+                let _: () = msg_send![data, release];
+                let _: () = msg_send![pasteboard_type, release];
+            }
+            let _: () = msg_send![objects, addObject: item];
+            let _: () = msg_send![item, release];
+        }
+        let success: BOOL = msg_send![pasteboard, writeObjects: objects];
+        let _: () = msg_send![objects, release];
+        if success == NO {
+            anyhow::bail!("Failed to write to the pasteboard");
+        }
+    }
+    Ok(())
+}
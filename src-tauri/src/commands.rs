@@ -0,0 +1,170 @@
+use anyhow::Result;
+use log::{info, debug};
+use crate::config::WhisprConfig;
+use crate::history;
+use crate::session_dictionary;
+use crate::integrations::{apple, home_assistant, time_tracking};
+
+const TAG_PREFIXES: &[&str] = &["tag that as ", "tag this as "];
+const SPELL_PREFIXES: &[&str] = &["spell that", "spell it"];
+
+/// A spoken command that replaces delivering the dictation.
+pub trait VoiceCommand: Send {
+    fn name(&self) -> &'static str;
+
+    /// Returns the command's argument if `text` invokes this command.
+    fn parse(&self, text: &str) -> Option<String>;
+
+    /// Runs the command, returning a short confirmation to show the user.
+    fn run(&self, argument: &str) -> Result<String>;
+}
+
+/// Strips `prefix` from the start of `text`, ignoring case, and returns the
+/// rest without a leading comma or colon and trailing punctuation. The
+/// prefix has to end at a word boundary, "start timers" doesn't start with
+/// "start timer".
+pub fn strip_command_prefix<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let trimmed = text.trim();
+    let start = trimmed.get(..prefix.len())?;
+    if !start.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let rest = &trimmed[prefix.len()..];
+    if rest.chars().next().is_some_and(char::is_alphanumeric) && !prefix.ends_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.trim().trim_start_matches([',', ':']).trim().trim_end_matches(['.', '!', '?']))
+}
+
+struct ReminderCommand {
+    settings: crate::config::AppleSettings,
+}
+
+impl VoiceCommand for ReminderCommand {
+    fn name(&self) -> &'static str {
+        "reminder"
+    }
+
+    fn parse(&self, text: &str) -> Option<String> {
+        apple::parse_reminder_command(text)
+    }
+
+    fn run(&self, reminder: &str) -> Result<String> {
+        apple::create_reminder(&self.settings, reminder)?;
+        Ok(format!("Reminder: {}", reminder))
+    }
+}
+
+/// "tag that as idea" tags the previous dictation.
+struct TagCommand;
+
+impl VoiceCommand for TagCommand {
+    fn name(&self) -> &'static str {
+        "tag"
+    }
+
+    fn parse(&self, text: &str) -> Option<String> {
+        TAG_PREFIXES.iter()
+            .find_map(|prefix| strip_command_prefix(text, prefix))
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+    }
+
+    fn run(&self, tag: &str) -> Result<String> {
+        history::tag_latest(tag)?;
+        Ok(format!("Tagged #{}", history::normalize_tag(tag)))
+    }
+}
+
+/// "spell that: K-U-B-E-R-N-E-T-E-S" adds the word to the session's
+/// dictionary.
+struct SpellCommand;
+
+impl VoiceCommand for SpellCommand {
+    fn name(&self) -> &'static str {
+        "spell"
+    }
+
+    fn parse(&self, text: &str) -> Option<String> {
+        SPELL_PREFIXES.iter()
+            .find_map(|prefix| strip_command_prefix(text, prefix))
+            .and_then(session_dictionary::parse_spelling)
+    }
+
+    fn run(&self, word: &str) -> Result<String> {
+        if session_dictionary::add(word) {
+            Ok(format!("Added {} for this session", word))
+        } else {
+            Ok(format!("{} is already known", word))
+        }
+    }
+}
+
+/// Voice commands enabled in the configuration, matched in order.
+pub struct CommandRegistry {
+    commands: Vec<Box<dyn VoiceCommand>>,
+}
+
+impl CommandRegistry {
+    pub fn from_config(config: &WhisprConfig) -> Self {
+        let mut commands: Vec<Box<dyn VoiceCommand>> = vec![Box::new(SpellCommand)];
+        if config.integrations.apple.voice_commands {
+            commands.push(Box::new(ReminderCommand { settings: config.integrations.apple.clone() }));
+        }
+        if config.history.enabled {
+            commands.push(Box::new(TagCommand));
+        }
+        if config.integrations.time_tracking.enabled {
+            commands.extend(time_tracking::commands(&config.integrations.time_tracking, &config.network));
+        }
+        Self { commands }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        self.commands.iter().any(|command| command.parse(text).is_some())
+    }
+
+    /// Runs the first command `text` invokes. Returns `None` if it isn't a
+    /// command, so the dictation is delivered as text.
+    pub fn execute(&self, text: &str) -> Option<Result<String>> {
+        self.commands.iter().find_map(|command| {
+            let argument = command.parse(text)?;
+            info!("Voice command {} detected", command.name());
+            debug!("Voice command argument: {}", argument);
+            Some(command.run(&argument))
+        })
+    }
+}
+
+/// Command mode: the whole dictation is a command. Commands whispr doesn't
+/// know are forwarded to Home Assistant if enabled.
+pub fn run_command(text: &str, config: &WhisprConfig) -> Result<String> {
+    if let Some(result) = CommandRegistry::from_config(config).execute(text) {
+        return result;
+    }
+    let home_assistant = &config.integrations.home_assistant;
+    if home_assistant.forward_unmatched {
+        return home_assistant::process(home_assistant, &config.network, text);
+    }
+    info!("Unknown voice command");
+    Ok(format!("Unknown command: {}", text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_end_at_a_word_boundary() {
+        assert_eq!(strip_command_prefix("Start timer writing docs.", "start timer"), Some("writing docs"));
+        assert_eq!(strip_command_prefix("Start timer", "start timer"), Some(""));
+        assert_eq!(strip_command_prefix("Start timers at noon", "start timer"), None);
+        assert_eq!(strip_command_prefix("Tag that as idea", "tag that as "), Some("idea"));
+    }
+
+    #[test]
+    fn drops_a_comma_or_colon_after_the_prefix() {
+        assert_eq!(strip_command_prefix("Start timer, five minutes", "start timer"), Some("five minutes"));
+        assert_eq!(strip_command_prefix("Spell that: K-U-B-E", "spell that"), Some("K-U-B-E"));
+    }
+}
@@ -0,0 +1,57 @@
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+use tokio::sync::broadcast::error::RecvError;
+use log::{debug, error};
+use crate::logging::{self, LogLine};
+
+const CONSOLE_LABEL: &str = "whispr:console";
+
+/// Lines logged before the console was opened.
+#[tauri::command]
+pub fn console_backlog() -> Vec<LogLine> {
+    logging::backlog()
+}
+
+/// Opens the developer console, or brings it to the front if it is already
+/// open. Log lines are streamed to it as `log-line` events until it closes.
+pub fn open_console<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(CONSOLE_LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+
+    // Subscribe before the window loads so no line falls between the backlog and the stream
+    let mut lines = logging::subscribe();
+    let window = WebviewWindowBuilder::new(app, CONSOLE_LABEL, WebviewUrl::App("index.html".into()))
+        .title("whispr Console")
+        .inner_size(900.0, 500.0)
+        .min_inner_size(400.0, 200.0)
+        .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(e) => {
+            error!("Failed to open console window: {}", e);
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        loop {
+            match lines.blocking_recv() {
+                Ok(line) => {
+                    if window.emit_to(CONSOLE_LABEL, "log-line", line).is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    let _ = window.emit_to(CONSOLE_LABEL, "log-lagged", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+            if window.app_handle().get_webview_window(CONSOLE_LABEL).is_none() {
+                break;
+            }
+        }
+        debug!("Console closed, stopped streaming log lines");
+    });
+}
@@ -0,0 +1,305 @@
+use log::{info, debug, warn, error};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use whispr_core::error::ErrorCode;
+use crate::trigger::TriggerEvent;
+
+/// Why a dictation step failed, with the code integrations branch on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictationError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl DictationError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// Where the dictation is. Only one runs at a time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DictationState {
+    Idle,
+    Recording { started: Instant },
+    Transcribing,
+    Injecting,
+    /// The last dictation failed, the next press starts over
+    Error(DictationError),
+}
+
+/// The work behind each step of a dictation, done by the app for real and
+/// by fakes in tests.
+pub trait Dictation {
+    /// What transcription hands on to injection.
+    type Transcript;
+
+    fn start_capture(&mut self) -> Result<(), DictationError>;
+    /// Stops capturing, false if the recording was aborted in the meantime
+    /// (e.g. by sleep) and there is nothing to transcribe.
+    fn stop_capture(&mut self) -> bool;
+    /// Transcribes and post-processes the captured audio, `None` if nothing
+    /// was heard.
+    fn transcribe(&mut self, event: &TriggerEvent) -> Result<Option<Self::Transcript>, DictationError>;
+    fn inject(&mut self, transcript: Self::Transcript) -> Result<(), DictationError>;
+    /// Called on every transition to update status, overlay and tray.
+    fn state_changed(&mut self, state: &DictationState);
+}
+
+/// Turns trigger presses and releases into record → validate duration →
+/// transcribe → inject. The state is only locked for transitions, so a press
+/// while the last dictation is still being transcribed or typed is turned
+/// away right away instead of waiting for it.
+pub struct DictationController<D: Dictation> {
+    // Locked after `state` when both are needed, `release` holds it alone
+    // while transcribing and injecting
+    dictation: Mutex<D>,
+    state: Mutex<DictationState>,
+    min_duration: Duration,
+}
+
+impl<D: Dictation> DictationController<D> {
+    /// Recordings shorter than `min_duration` are discarded.
+    pub fn new(dictation: D, min_duration: Duration) -> Self {
+        Self { dictation: Mutex::new(dictation), state: Mutex::new(DictationState::Idle), min_duration }
+    }
+
+    #[cfg(test)]
+    pub fn state(&self) -> DictationState {
+        self.state.lock().unwrap().clone()
+    }
+
+    #[cfg(test)]
+    fn dictation(&self) -> std::sync::MutexGuard<'_, D> {
+        self.dictation.lock().unwrap()
+    }
+
+    /// Handles a press or release. A release transcribes and injects before
+    /// returning, so callers on the main thread hand it to another thread.
+    pub fn handle(&self, event: TriggerEvent, now: Instant) {
+        if event.pressed {
+            self.press(now);
+        } else {
+            self.release(&event, now);
+        }
+    }
+
+    fn press(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            DictationState::Idle | DictationState::Error(_) => {
+                let mut dictation = self.dictation.lock().unwrap();
+                match dictation.start_capture() {
+                    Ok(()) => Self::set(&mut state, &mut dictation, DictationState::Recording { started: now }),
+                    Err(e) => Self::fail(&mut state, &mut dictation, e, "Failed to start audio capture"),
+                }
+            }
+            DictationState::Recording { .. } => warn!("Recording already in progress"),
+            DictationState::Transcribing | DictationState::Injecting => warn!("Still busy with the last dictation"),
+        }
+    }
+
+    fn release(&self, event: &TriggerEvent, now: Instant) {
+        let mut dictation = {
+            let mut state = self.state.lock().unwrap();
+            let DictationState::Recording { started } = *state else {
+                debug!("No recording in progress, ignoring key release");
+                return;
+            };
+            let mut dictation = self.dictation.lock().unwrap();
+            if !dictation.stop_capture() {
+                // Whatever aborted it already cleaned up
+                debug!("Recording was aborted, ignoring key release");
+                *state = DictationState::Idle;
+                return;
+            }
+
+            let duration = now.saturating_duration_since(started);
+            if duration < self.min_duration {
+                debug!("Recording too short ({:.2}s), discarding", duration.as_secs_f32());
+                Self::set(&mut state, &mut dictation, DictationState::Idle);
+                return;
+            }
+            Self::set(&mut state, &mut dictation, DictationState::Transcribing);
+            dictation
+        };
+
+        let transcript = match dictation.transcribe(event) {
+            Ok(Some(transcript)) => transcript,
+            Ok(None) => {
+                info!("Nothing to deliver");
+                self.finish(&mut dictation, DictationState::Idle);
+                return;
+            }
+            Err(e) => {
+                Self::fail(&mut self.state.lock().unwrap(), &mut dictation, e, "Failed to process audio");
+                return;
+            }
+        };
+
+        self.finish(&mut dictation, DictationState::Injecting);
+        match dictation.inject(transcript) {
+            Ok(()) => self.finish(&mut dictation, DictationState::Idle),
+            Err(e) => Self::fail(&mut self.state.lock().unwrap(), &mut dictation, e, "Failed to output transcription"),
+        }
+    }
+
+    /// A transition while `release` holds only the dictation.
+    fn finish(&self, dictation: &mut D, next: DictationState) {
+        Self::set(&mut self.state.lock().unwrap(), dictation, next);
+    }
+
+    fn set(state: &mut DictationState, dictation: &mut D, next: DictationState) {
+        *state = next;
+        dictation.state_changed(state);
+    }
+
+    /// Logs the failed step with its code, e.g. "[E_OUTPUT] Failed to output
+    /// transcription: …", and moves to the error state.
+    fn fail(state: &mut DictationState, dictation: &mut D, error: DictationError, step: &str) {
+        let error = DictationError::new(error.code, format!("{}: {}", step, error.message));
+        error!("[{}] {}", error.code, error.message);
+        Self::set(state, dictation, DictationState::Error(error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trigger::TriggerSource;
+
+    const MIN_DURATION: Duration = Duration::from_secs(1);
+
+    #[derive(Default)]
+    struct FakeDictation {
+        captures: usize,
+        aborted: bool,
+        no_device: bool,
+        heard: Option<String>,
+        injected: Vec<String>,
+        states: Vec<DictationState>,
+    }
+
+    impl Dictation for FakeDictation {
+        type Transcript = String;
+
+        fn start_capture(&mut self) -> Result<(), DictationError> {
+            self.captures += 1;
+            if self.no_device {
+                return Err(DictationError::new(ErrorCode::AudioDevice, "No input device"));
+            }
+            Ok(())
+        }
+
+        fn stop_capture(&mut self) -> bool {
+            !self.aborted
+        }
+
+        fn transcribe(&mut self, _event: &TriggerEvent) -> Result<Option<String>, DictationError> {
+            Ok(self.heard.clone())
+        }
+
+        fn inject(&mut self, transcript: String) -> Result<(), DictationError> {
+            self.injected.push(transcript);
+            Ok(())
+        }
+
+        fn state_changed(&mut self, state: &DictationState) {
+            self.states.push(state.clone());
+        }
+    }
+
+    fn controller(heard: Option<&str>) -> DictationController<FakeDictation> {
+        let dictation = FakeDictation { heard: heard.map(str::to_string), ..Default::default() };
+        DictationController::new(dictation, MIN_DURATION)
+    }
+
+    fn press(controller: &DictationController<FakeDictation>, at: Instant) {
+        controller.handle(TriggerEvent::new(TriggerSource::Hotkey, true), at);
+    }
+
+    fn release(controller: &DictationController<FakeDictation>, at: Instant) {
+        controller.handle(TriggerEvent::new(TriggerSource::Hotkey, false), at);
+    }
+
+    #[test]
+    fn delivers_a_dictation() {
+        let controller = controller(Some("hello"));
+        let start = Instant::now();
+        press(&controller, start);
+        release(&controller, start + Duration::from_secs(2));
+        assert_eq!(controller.dictation().injected, vec!["hello".to_string()]);
+        assert_eq!(controller.dictation().states, vec![
+            DictationState::Recording { started: start },
+            DictationState::Transcribing,
+            DictationState::Injecting,
+            DictationState::Idle,
+        ]);
+    }
+
+    #[test]
+    fn discards_early_release() {
+        let controller = controller(Some("hello"));
+        let start = Instant::now();
+        press(&controller, start);
+        release(&controller, start + Duration::from_millis(300));
+        assert!(controller.dictation().injected.is_empty());
+        assert_eq!(controller.state(), DictationState::Idle);
+        assert!(!controller.dictation().states.contains(&DictationState::Transcribing));
+    }
+
+    #[test]
+    fn ignores_double_press() {
+        let controller = controller(Some("hello"));
+        let start = Instant::now();
+        press(&controller, start);
+        press(&controller, start + Duration::from_millis(500));
+        assert_eq!(controller.dictation().captures, 1);
+        // The recording still counts from the first press
+        release(&controller, start + Duration::from_millis(1200));
+        assert_eq!(controller.dictation().injected.len(), 1);
+    }
+
+    #[test]
+    fn empty_audio_injects_nothing() {
+        let controller = controller(None);
+        let start = Instant::now();
+        press(&controller, start);
+        release(&controller, start + Duration::from_secs(2));
+        assert!(controller.dictation().injected.is_empty());
+        assert_eq!(controller.state(), DictationState::Idle);
+    }
+
+    #[test]
+    fn ignores_release_without_recording() {
+        let controller = controller(Some("hello"));
+        release(&controller, Instant::now());
+        assert!(controller.dictation().states.is_empty());
+
+        // Aborted by sleep between press and release
+        let start = Instant::now();
+        press(&controller, start);
+        controller.dictation().aborted = true;
+        release(&controller, start + Duration::from_secs(2));
+        assert!(controller.dictation().injected.is_empty());
+        assert_eq!(controller.state(), DictationState::Idle);
+    }
+
+    #[test]
+    fn failures_carry_their_code() {
+        let controller = controller(Some("hello"));
+        controller.dictation().no_device = true;
+        press(&controller, Instant::now());
+        match controller.state() {
+            DictationState::Error(error) => {
+                assert_eq!(error.code, ErrorCode::AudioDevice);
+                assert_eq!(error.message, "Failed to start audio capture: No input device");
+            }
+            other => panic!("unexpected state {:?}", other),
+        }
+        // The next press starts over
+        controller.dictation().no_device = false;
+        press(&controller, Instant::now());
+        assert!(matches!(controller.state(), DictationState::Recording { .. }));
+    }
+}
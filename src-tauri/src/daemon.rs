@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Runtime};
+use whispr_core::daemon::{Captured, Client, DecodeCaptured, Decoded};
+use whispr_core::ephemeral;
+use crate::config::{AudioSettings, WhisperSettings, WhisprConfig};
+use crate::keychain;
+use crate::menu;
+use crate::whisper::{Decoding, ModelStatus, ProgressCallback, SegmentCallback, SharedWhisperProcessor, WhisperProcessor};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Until a freshly started whisprd answers
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+// Until the model is loaded, which includes compiling the Core ML encoder the first time
+const LOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The model `whisprd` holds for the app.
+pub struct Remote {
+    client: Client,
+    model_path: PathBuf,
+    /// Dictations are recorded by whisprd too, see `daemon.capture`
+    captures: bool,
+}
+
+impl Remote {
+    pub fn captures(&self) -> bool {
+        self.captures
+    }
+
+    pub fn start_capture(&self, audio: &AudioSettings) -> Result<(), String> {
+        self.client.start_capture(audio, ephemeral::is_active()).map_err(|e| format!("whisprd: {:#}", e))
+    }
+
+    pub fn stop_capture(&self) -> Result<Captured, String> {
+        self.client.stop_capture().map_err(|e| format!("whisprd: {:#}", e))
+    }
+
+    pub fn discard_capture(&self) -> Result<(), String> {
+        self.client.discard_capture().map_err(|e| format!("whisprd: {:#}", e))
+    }
+
+    /// Decodes what whisprd recorded.
+    pub fn decode_captured(
+        &self,
+        settings: &WhisperSettings,
+        unfiltered: bool,
+        on_progress: Option<ProgressCallback>,
+        on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoded, String> {
+        let request = DecodeCaptured { settings: settings.clone(), unfiltered };
+        self.client.decode_captured(&request, on_progress, on_segment).map_err(|e| format!("whisprd: {:#}", e))
+    }
+}
+
+// Set once whisprd confirmed it loaded the app's model
+static REMOTE: Mutex<Option<Arc<Remote>>> = Mutex::new(None);
+
+/// Where dictations are decoded: the model loaded in the app, or the one
+/// `whisprd` keeps loaded for it. Mirrors `WhisperProcessor`.
+#[derive(Clone)]
+pub enum Decoder {
+    Local(Arc<WhisperProcessor>),
+    Daemon(Arc<Remote>),
+}
+
+impl Decoder {
+    pub fn process_audio_with_confidence(
+        &self,
+        audio: &[f32],
+        settings: &WhisperSettings,
+        on_progress: Option<ProgressCallback>,
+        on_segment: Option<SegmentCallback>,
+    ) -> Result<Decoding, String> {
+        match self {
+            Decoder::Local(whisper) => whisper.process_audio_with_confidence(audio, settings, on_progress, on_segment),
+            Decoder::Daemon(remote) => remote.client.decode(audio, settings, on_progress, on_segment)
+                .map(|decoded| decoded.decoding)
+                .map_err(|e| format!("whisprd: {:#}", e)),
+        }
+    }
+
+    pub fn process_audio(&self, audio: &[f32], settings: &WhisperSettings) -> Result<Vec<(f32, f32, String)>, String> {
+        self.process_audio_with_confidence(audio, settings, None, None)
+            .map(|decoding| decoding.segments)
+    }
+
+    pub fn model_path(&self) -> &Path {
+        match self {
+            Decoder::Local(whisper) => whisper.model_path(),
+            Decoder::Daemon(remote) => &remote.model_path,
+        }
+    }
+
+    /// The model loaded in the app, e.g. to skip drafts when there is none.
+    pub fn local(&self) -> Option<&Arc<WhisperProcessor>> {
+        match self {
+            Decoder::Local(whisper) => Some(whisper),
+            Decoder::Daemon(_) => None,
+        }
+    }
+}
+
+pub fn remote() -> Option<Arc<Remote>> {
+    REMOTE.lock().unwrap().clone()
+}
+
+/// Decodes in the app again, e.g. after `daemon.enabled` was turned off.
+/// whisprd keeps running until it is quit.
+pub fn disconnect() {
+    if REMOTE.lock().unwrap().take().is_some() {
+        info!("No longer decoding in whisprd");
+    }
+}
+
+/// whisprd next to the app's executable, where a bundle puts it, or on the
+/// `PATH`.
+fn executable() -> PathBuf {
+    std::env::current_exe().ok()
+        .map(|exe| exe.with_file_name("whisprd"))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("whisprd"))
+}
+
+fn wait_until<T>(timeout: Duration, mut check: impl FnMut() -> Result<Option<T>>) -> Result<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(value) = check()? {
+            return Ok(value);
+        }
+        anyhow::ensure!(Instant::now() < deadline, "Timed out waiting for whisprd");
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Starts whisprd unless it runs already and has it load `model_path`.
+fn connect<R: Runtime>(app: &AppHandle<R>, model_path: &Path, config: &WhisprConfig) -> Result<Remote> {
+    let client = Client::new(config.daemon.port, keychain::api_token()?);
+    if client.status().is_err() {
+        let executable = executable();
+        info!("Starting {}", executable.display());
+        // A process group of its own, so it outlives the app
+        Command::new(&executable)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", executable.display()))?;
+        wait_until(START_TIMEOUT, || Ok(client.status().ok()))?;
+    }
+
+    let status = client.status()?;
+    if status.model.as_deref() != Some(model_path) && status.loading.as_deref() != Some(model_path) {
+        client.load_model(model_path.to_path_buf())?;
+    }
+    menu::set_model_status(app, "Loading model in whisprd…");
+    wait_until(LOAD_TIMEOUT, || {
+        let status = client.status()?;
+        if status.model.as_deref() == Some(model_path) {
+            return Ok(Some(()));
+        }
+        if status.loading.is_none() {
+            anyhow::bail!("{}", status.error.unwrap_or_else(|| "whisprd didn't load the model".to_string()));
+        }
+        Ok(None)
+    })?;
+    Ok(Remote { client, model_path: model_path.to_path_buf(), captures: config.daemon.capture })
+}
+
+/// Has whisprd load `model_path` in the background and decodes through it
+/// from then on. If whisprd can't be started or reached, the model is loaded
+/// in the app into `slot` as without `daemon.enabled`.
+pub fn load_model<R: Runtime>(app: &AppHandle<R>, slot: SharedWhisperProcessor, model_path: PathBuf, config: WhisprConfig) -> JoinHandle<()> {
+    let app = app.clone();
+    std::thread::spawn(move || match connect(&app, &model_path, &config) {
+        Ok(remote) => {
+            info!("whisprd loaded {}", model_path.display());
+            *REMOTE.lock().unwrap() = Some(Arc::new(remote));
+            // The app's own copy is no longer needed
+            slot.write().unwrap().take();
+            menu::set_model_status(&app, &ModelStatus::Ready.to_string());
+        }
+        Err(e) => {
+            warn!("Failed to use whisprd, loading the model in the app: {:#}", e);
+            disconnect();
+            let _ = crate::load_model_into(slot, &app, model_path, config).join();
+        }
+    })
+}
@@ -0,0 +1,164 @@
+use log::{debug, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager, Runtime};
+use crate::config::{AudioSource, ConfigManager, WhisprConfig};
+use crate::input_device::DeviceEntry;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Capturing from the default device because the configured one is gone
+static ON_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    FallBack,
+    Restore,
+}
+
+/// Whether to leave the configured device or go back to it, given the
+/// devices present.
+fn change(configured: &str, available: &[DeviceEntry], on_fallback: bool) -> Option<Change> {
+    let present = available.iter().any(|device| device.id == configured);
+    match (present, on_fallback) {
+        (false, false) => Some(Change::FallBack),
+        (true, true) => Some(Change::Restore),
+        _ => None,
+    }
+}
+
+/// Records that the configured device couldn't be used and the default one
+/// stands in, so it is switched back to once it reappears.
+pub fn mark_fallback() {
+    ON_FALLBACK.store(true, Ordering::SeqCst);
+}
+
+/// The device in use is the configured one again, e.g. picked from the tray.
+pub fn clear_fallback() {
+    ON_FALLBACK.store(false, Ordering::SeqCst);
+}
+
+fn apply<R: Runtime>(app: &AppHandle<R>, change: Change, configured: &str, devices: &[DeviceEntry]) {
+    let Some(state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+    let mut audio = state.audio.lock().unwrap();
+    // Losing the device mid-recording is handled by `handle_device_lost`
+    if audio.is_capturing() {
+        return;
+    }
+    let configured_name = devices.iter().find(|device| device.id == configured)
+        .map_or_else(|| configured.to_string(), DeviceEntry::label);
+    let message = match change {
+        Change::FallBack => match audio.use_default_input_device() {
+            Ok(name) => {
+                mark_fallback();
+                warn!("Input device {} is gone, using {}", configured, name);
+                format!("{} disconnected, using {}", configured_name, name)
+            }
+            Err(e) => {
+                debug!("No input device to fall back to: {}", e);
+                return;
+            }
+        },
+        Change::Restore => match audio.set_input_device(configured) {
+            Ok(()) => {
+                clear_fallback();
+                info!("Input device {} is back", configured);
+                format!("Switched back to {}", configured_name)
+            }
+            Err(e) => {
+                debug!("Input device {} is listed but can't be used yet: {}", configured, e);
+                return;
+            }
+        },
+    };
+    let current = audio.get_current_device_id().ok();
+    drop(audio);
+    if let Some(current) = current {
+        crate::menu::check_input_device(app, &current);
+    }
+    crate::show_toast(app, &message);
+}
+
+/// The settings, reloaded only once the file changed since the last call.
+struct Settings {
+    config: WhisprConfig,
+    modified: Option<SystemTime>,
+}
+
+impl Settings {
+    fn new() -> Self {
+        Self { config: WhisprConfig::default(), modified: None }
+    }
+
+    fn current(&mut self) -> &WhisprConfig {
+        if let Ok(manager) = ConfigManager::<WhisprConfig>::new("settings") {
+            let modified = manager.modified("settings");
+            if modified.is_none() || modified != self.modified {
+                self.config = manager.load_config("settings").unwrap_or_default();
+                self.modified = modified;
+            }
+        }
+        &self.config
+    }
+}
+
+/// Polls the input devices and moves to the default one while the
+/// configured device is unplugged, and back once it returns, updating the
+/// Audio Device submenu and telling the overlay.
+pub fn start<R: Runtime>(app: AppHandle<R>) {
+    std::thread::spawn(move || {
+        let mut settings = Settings::new();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let config = settings.current();
+            // System audio comes from a loopback device, which isn't unplugged
+            if config.audio.source != AudioSource::Microphone {
+                continue;
+            }
+            // Without a configured device the default one is used anyway
+            let Some(configured) = config.audio.selected_device() else {
+                continue;
+            };
+            let Some(state) = app.try_state::<crate::AppState>() else {
+                continue;
+            };
+            // Listed on the capture's host rather than opening another one
+            let devices = state.audio.lock().unwrap().list_input_devices();
+            let devices = match devices {
+                Ok(devices) => devices,
+                Err(e) => {
+                    debug!("Failed to list input devices: {}", e);
+                    continue;
+                }
+            };
+            if let Some(change) = change(configured, &devices, ON_FALLBACK.load(Ordering::SeqCst)) {
+                apply(&app, change, configured, &devices);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str) -> DeviceEntry {
+        DeviceEntry { id: id.to_string(), name: id.to_string(), occurrence: 1 }
+    }
+
+    #[test]
+    fn falls_back_while_the_configured_device_is_gone() {
+        let builtin = [device("MacBook Pro Microphone")];
+        assert_eq!(change("USB Mic", &builtin, false), Some(Change::FallBack));
+        assert_eq!(change("USB Mic", &builtin, true), None);
+    }
+
+    #[test]
+    fn restores_the_configured_device_when_it_returns() {
+        let both = [device("MacBook Pro Microphone"), device("USB Mic")];
+        assert_eq!(change("USB Mic", &both, true), Some(Change::Restore));
+        assert_eq!(change("USB Mic", &both, false), None);
+    }
+}
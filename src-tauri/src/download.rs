@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use log::{info, debug, warn};
+use reqwest::blocking::{Client, ClientBuilder, Response};
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::config::{NetworkSettings, WhisprConfig};
+use crate::storage;
+
+const HUGGING_FACE_BASE: &str = "https://huggingface.co";
+const DOWNLOAD_CHUNK_SIZE: usize = 256 * 1024;
+
+// Built once and shared, so webhooks and chat messages reuse its connections
+// and the CA bundle isn't read for every request. Rebuilt when the proxy or
+// CA bundle it was built for changes.
+static CLIENT: Mutex<Option<(ClientKey, Client)>> = Mutex::new(None);
+
+type ClientKey = (Option<String>, Option<String>);
+
+/// The HTTP client for the `network` settings. Proxies from the environment
+/// (HTTPS_PROXY, ALL_PROXY, NO_PROXY) and the macOS system configuration are
+/// picked up by reqwest unless an explicit proxy is set. Cheap to call,
+/// clones share one connection pool.
+pub fn shared_client(network: &NetworkSettings) -> Result<Client> {
+    let key = (network.proxy.clone(), network.ca_bundle.clone());
+    let mut cached = CLIENT.lock().unwrap();
+    if let Some((cached_key, client)) = cached.as_ref() {
+        if *cached_key == key {
+            return Ok(client.clone());
+        }
+    }
+    let client = client_builder(network)?.build()?;
+    *cached = Some((key, client.clone()));
+    Ok(client)
+}
+
+fn client_builder(network: &NetworkSettings) -> Result<ClientBuilder> {
+    let mut builder = Client::builder()
+        .user_agent(concat!("whispr/", env!("CARGO_PKG_VERSION")));
+
+    if let Some(proxy) = network.proxy.as_deref().filter(|p| !p.is_empty()) {
+        info!("Using proxy {}", proxy);
+        builder = builder.proxy(reqwest::Proxy::all(proxy).context("Invalid proxy URL")?);
+    }
+
+    if let Some(ca_bundle) = network.ca_bundle.as_deref().filter(|p| !p.is_empty()) {
+        let pem = fs::read(ca_bundle)
+            .with_context(|| format!("Failed to read CA bundle {}", ca_bundle))?;
+        for certificate in reqwest::Certificate::from_pem_bundle(&pem).context("Invalid CA bundle")? {
+            builder = builder.add_root_certificate(certificate);
+        }
+        info!("Using custom CA bundle {}", ca_bundle);
+    }
+
+    Ok(builder)
+}
+
+/// The SHA-256 Hugging Face publishes for an LFS file, sent as
+/// `X-Linked-Etag` with the redirect to where it is stored. `None` for
+/// other hosts or if it can't be asked for.
+pub fn published_sha256(url: &str, network: &NetworkSettings) -> Option<String> {
+    let client = client_builder(network).ok()?
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .ok()?;
+    let response = client.head(resolve_url(url, network)).send()
+        .map_err(|e| warn!("Failed to look up the checksum of {}: {}", url, e))
+        .ok()?;
+    let etag = response.headers().get("x-linked-etag")?.to_str().ok()?;
+    let hash = etag.trim().trim_start_matches("W/").trim_matches('"');
+    (hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit())).then(|| hash.to_ascii_lowercase())
+}
+
+/// Rewrites Hugging Face URLs to the configured mirror, if any.
+fn resolve_url(url: &str, network: &NetworkSettings) -> String {
+    match network.mirror_url.as_deref().filter(|m| !m.is_empty()) {
+        Some(mirror) if url.starts_with(HUGGING_FACE_BASE) => {
+            format!("{}{}", mirror.trim_end_matches('/'), &url[HUGGING_FACE_BASE.len()..])
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+fn content_length(response: &Response) -> Option<u64> {
+    response.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Downloads `url` to `dest`, resuming a previous `.part` file if the server
+/// supports range requests. `on_progress` receives (downloaded, total) bytes.
+pub fn download_file<F>(url: &str, dest: &Path, config: &WhisprConfig, mut on_progress: F) -> Result<()>
+where
+    F: FnMut(u64, u64),
+{
+    let url = resolve_url(url, &config.network);
+    let client = shared_client(&config.network)?;
+    let part_path = partial_path(dest);
+    let existing = fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(&url);
+    if existing > 0 {
+        info!("Resuming download of {} at {} bytes", url, existing);
+        request = request.header(RANGE, format!("bytes={}-", existing));
+    } else {
+        info!("Downloading {}", url);
+    }
+
+    let mut response = request.send()?.error_for_status()?;
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    if existing > 0 && !resumed {
+        warn!("Server does not support resuming, restarting download");
+    }
+
+    let mut downloaded = if resumed { existing } else { 0 };
+    let remaining = content_length(&response).unwrap_or(0);
+    let total = downloaded + remaining;
+
+    let parent = dest.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent)?;
+    storage::check_free_space(parent, remaining, config).map_err(anyhow::Error::msg)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part_path)?;
+
+    let mut buffer = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+    loop {
+        let read = response.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        downloaded += read as u64;
+        on_progress(downloaded, total);
+    }
+    file.flush()?;
+
+    if total > 0 && downloaded < total {
+        anyhow::bail!("Download incomplete: {} of {} bytes", downloaded, total);
+    }
+
+    fs::rename(&part_path, dest)?;
+    debug!("Download finished: {} bytes written to {}", downloaded, dest.display());
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use log::{debug, error, info};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::commands::CommandRegistry;
+use crate::config::{ConfigManager, DraftMode, InjectionSettings, WhisperSettings, WhisprConfig};
+use crate::whisper::{Decoding, ModelStatus, SharedWhisperProcessor, WhisperProcessor};
+use crate::{cjk, ephemeral, focus, models, output, pipeline, text_direction};
+
+static LOADING: AtomicBool = AtomicBool::new(false);
+
+/// A draft typed into the focused app, to be corrected once the main model
+/// is done.
+pub struct TypedDraft {
+    text: String,
+    /// Where the draft went, backspaces anywhere else would delete the user's text
+    target: Option<focus::TypingTarget>,
+}
+
+impl TypedDraft {
+    /// Deletes the draft again, e.g. when the main model heard nothing or a
+    /// voice command.
+    pub fn erase(mut self) -> Result<()> {
+        let result = self.ensure_target().and_then(|_| output::erase_text(self.text.graphemes(true).count()));
+        ephemeral::scrub(&mut self.text);
+        result
+    }
+
+    /// Turns the typed draft into `text`, only retyping from the first
+    /// grapheme that differs.
+    pub fn replace_with(mut self, text: &str, settings: &InjectionSettings) -> Result<()> {
+        let (erase, insert) = patch(&self.text, text);
+        let target = self.ensure_target();
+        ephemeral::scrub(&mut self.text);
+        if erase == 0 && insert.is_empty() {
+            debug!("Draft matches the final transcription");
+            return Ok(());
+        }
+        target?;
+        debug!("Correcting draft: {} graphemes erased, {} typed", erase, insert.graphemes(true).count());
+        if erase > 0 {
+            output::erase_text(erase)?;
+        }
+        if !insert.is_empty() {
+            output::type_text(insert, settings)?;
+        }
+        Ok(())
+    }
+
+    fn ensure_target(&self) -> Result<()> {
+        match &self.target {
+            Some(target) if target.is_current() => Ok(()),
+            _ => anyhow::bail!("Focus moved since the draft was typed, leaving it as is"),
+        }
+    }
+}
+
+/// The graphemes to erase from the end of `draft` and the text to type
+/// after them to turn it into `text`.
+pub fn patch<'a>(draft: &str, text: &'a str) -> (usize, &'a str) {
+    let common = draft.graphemes(true).zip(text.graphemes(true)).take_while(|(a, b)| a == b).count();
+    let offset = text.grapheme_indices(true).nth(common).map_or(text.len(), |(offset, _)| offset);
+    (draft.graphemes(true).count() - common, &text[offset..])
+}
+
+/// The draft model if `whisper.draft` is on and it is loaded. The first call
+/// starts loading the fallback model in the background and drafts from the
+/// next dictation on.
+pub fn processor<R: Runtime>(app: &AppHandle<R>, config: &WhisprConfig, main: &WhisperProcessor) -> Option<Arc<WhisperProcessor>> {
+    if config.whisper.draft == DraftMode::Off {
+        return None;
+    }
+    let state = app.try_state::<crate::AppState>()?;
+    let loaded = state.draft_whisper.read().unwrap().clone();
+    if let Some(draft) = loaded {
+        return Some(draft);
+    }
+    let config_manager = ConfigManager::<WhisprConfig>::new("settings").ok()?;
+    let model_path = models::fallback_model_path(config, config_manager.get_config_dir());
+    // Drafting with the model that transcribes anyway only doubles the work
+    if model_path == main.model_path() || !model_path.exists() {
+        return None;
+    }
+    if !LOADING.swap(true, Ordering::SeqCst) {
+        load(state.draft_whisper.clone(), model_path, config.clone());
+    }
+    None
+}
+
+fn load(slot: SharedWhisperProcessor, model_path: PathBuf, config: WhisprConfig) {
+    WhisperProcessor::load_in_background(slot, model_path, config, |status| {
+        debug!("Draft model: {}", status);
+        if matches!(status, ModelStatus::Ready | ModelStatus::Failed(_)) {
+            LOADING.store(false, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Transcribes `audio` with the draft model and types the draft or shows it
+/// in the overlay, as set in `whisper.draft`. Only drafts that can be
+/// corrected later are typed, so not voice commands, nothing outside a text
+/// field and nothing unless `can_type`. Returns the typed draft.
+pub fn deliver<R: Runtime>(
+    app: &AppHandle<R>,
+    draft_whisper: &WhisperProcessor,
+    audio: &[f32],
+    settings: &WhisperSettings,
+    config: &WhisprConfig,
+    can_type: bool,
+) -> Option<TypedDraft> {
+    let Decoding { segments, language, .. } = match draft_whisper.process_audio_with_confidence(audio, settings, None, None) {
+        Ok(decoding) => decoding,
+        Err(e) => {
+            error!("Failed to transcribe draft: {}", e);
+            return None;
+        }
+    };
+    let text = pipeline::join_segments(&segments, config.output.paragraph_pause_secs);
+    let text = cjk::format(&text, language.as_deref(), &config.output.cjk);
+    if text.trim().is_empty() {
+        return None;
+    }
+    info!("Drafted {} characters", text.chars().count());
+
+    let types = config.whisper.draft == DraftMode::Type
+        && can_type
+        && !config.command_mode
+        && !CommandRegistry::from_config(config).matches(&text)
+        && focus::text_focus() != focus::TextFocus::NotEditable;
+    if !types {
+        let _ = app.emit("draft-text", text_direction::DirectedText::new(text.trim_end()));
+        return None;
+    }
+    let direction = text_direction::resolve(config.ui.text_direction, language.as_deref(), &text);
+    let text = text_direction::mark_for_injection(&text, direction).into_owned();
+    let target = focus::typing_target();
+    match output::type_erasable(&text, &config.output.injection) {
+        Ok(true) => Some(TypedDraft { text, target }),
+        Ok(false) => {
+            let _ = app.emit("draft-text", text_direction::DirectedText::new(text.trim_end()));
+            None
+        }
+        Err(e) => {
+            error!("Failed to type draft: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_from_the_first_difference() {
+        assert_eq!(patch("Hello word. ", "Hello world. "), (3, "ld. "));
+        assert_eq!(patch("Same. ", "Same. "), (0, ""));
+        assert_eq!(patch("Short", "Shorter"), (0, "er"));
+        assert_eq!(patch("Longer", "Long"), (2, ""));
+        assert_eq!(patch("Grüße", "Grütze"), (2, "tze"));
+        assert_eq!(patch("", "New"), (0, "New"));
+    }
+
+    #[test]
+    fn patches_whole_graphemes() {
+        assert_eq!(patch("Thumbs 👍🏽", "Thumbs 👍🏽 up"), (0, " up"));
+        assert_eq!(patch("Thumbs 👍🏽", "Thumbs 👍🏻"), (1, "👍🏻"));
+        assert_eq!(patch("Cafe\u{301}s", "Cafe\u{301}!"), (1, "!"));
+    }
+}
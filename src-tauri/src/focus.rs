@@ -0,0 +1,217 @@
+use cocoa::base::{id, nil};
+use core_foundation::base::{CFEqual, CFRelease, CFTypeRef, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::string::{CFString, CFStringRef};
+use objc::{class, msg_send, sel, sel_impl};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+type AXUIElementRef = CFTypeRef;
+type AXError = i32;
+
+const AX_ERROR_SUCCESS: AXError = 0;
+
+// Roles that take typed text even without a settable AXValue
+const EDITABLE_ROLES: &[&str] = &["AXTextField", "AXTextArea", "AXComboBox", "AXSearchField"];
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXUIElementCreateSystemWide() -> AXUIElementRef;
+    fn AXUIElementCopyAttributeValue(element: AXUIElementRef, attribute: CFStringRef, value: *mut CFTypeRef) -> AXError;
+    fn AXUIElementIsAttributeSettable(element: AXUIElementRef, attribute: CFStringRef, settable: *mut u8) -> AXError;
+    fn AXIsProcessTrusted() -> u8;
+}
+
+/// Whether whispr has the Accessibility permission it needs to type.
+pub fn is_trusted() -> bool {
+    unsafe { AXIsProcessTrusted() != 0 }
+}
+
+/// The application that currently has keyboard focus.
+#[derive(Debug, Clone)]
+pub struct FrontmostApp {
+    pub bundle_id: String,
+    pub name: String,
+}
+
+pub(crate) fn nsstring_to_string(string: id) -> Option<String> {
+    if string == nil {
+        return None;
+    }
+    unsafe {
+        let utf8: *const c_char = msg_send![string, UTF8String];
+        if utf8.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+}
+
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let app: id = msg_send![workspace, frontmostApplication];
+        if app == nil {
+            return None;
+        }
+        let bundle_id: id = msg_send![app, bundleIdentifier];
+        let name: id = msg_send![app, localizedName];
+        Some(FrontmostApp {
+            bundle_id: nsstring_to_string(bundle_id).unwrap_or_default(),
+            name: nsstring_to_string(name).unwrap_or_default(),
+        })
+    }
+}
+
+/// Deactivates whispr so keyboard focus goes back to the previously active
+/// app, e.g. after a click on one of our windows.
+pub fn yield_focus() {
+    unsafe {
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let _: () = msg_send![app, deactivate];
+    }
+}
+
+/// Whether the focused UI element accepts typed text, as far as the
+/// Accessibility API can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFocus {
+    Editable,
+    NotEditable,
+    /// The app doesn't expose its focus (e.g. Electron apps, missing
+    /// Accessibility permission)
+    Unknown,
+}
+
+unsafe fn copy_attribute(element: AXUIElementRef, attribute: &str) -> Option<CFTypeRef> {
+    let attribute = CFString::new(attribute);
+    let mut value: CFTypeRef = std::ptr::null();
+    let error = AXUIElementCopyAttributeValue(element, attribute.as_concrete_TypeRef(), &mut value);
+    (error == AX_ERROR_SUCCESS && !value.is_null()).then_some(value)
+}
+
+unsafe fn string_attribute(element: AXUIElementRef, attribute: &str) -> Option<String> {
+    let value = copy_attribute(element, attribute)?;
+    if core_foundation::base::CFGetTypeID(value) != CFString::type_id() {
+        CFRelease(value);
+        return None;
+    }
+    Some(CFString::wrap_under_create_rule(value as CFStringRef).to_string())
+}
+
+unsafe fn bool_attribute(element: AXUIElementRef, attribute: &str) -> Option<bool> {
+    let value = copy_attribute(element, attribute)?;
+    if core_foundation::base::CFGetTypeID(value) != CFBoolean::type_id() {
+        CFRelease(value);
+        return None;
+    }
+    Some(CFBoolean::wrap_under_create_rule(value as _).into())
+}
+
+unsafe fn is_settable(element: AXUIElementRef, attribute: &str) -> bool {
+    let attribute = CFString::new(attribute);
+    let mut settable = 0u8;
+    AXUIElementIsAttributeSettable(element, attribute.as_concrete_TypeRef(), &mut settable) == AX_ERROR_SUCCESS
+        && settable != 0
+}
+
+/// Checks the system wide focused element before typing into it.
+pub fn text_focus() -> TextFocus {
+    unsafe {
+        let system = AXUIElementCreateSystemWide();
+        if system.is_null() {
+            return TextFocus::Unknown;
+        }
+        let focused = copy_attribute(system, "AXFocusedUIElement");
+        CFRelease(system);
+        let Some(focused) = focused else {
+            return TextFocus::Unknown;
+        };
+
+        let role = string_attribute(focused, "AXRole");
+        let editable = role.as_deref().is_some_and(|role| EDITABLE_ROLES.contains(&role))
+            // Web views mark contenteditable elements instead of using a text role
+            || bool_attribute(focused, "AXEditable").unwrap_or(false)
+            || is_settable(focused, "AXSelectedText");
+        CFRelease(focused);
+
+        match role {
+            None => TextFocus::Unknown,
+            Some(_) if editable => TextFocus::Editable,
+            Some(_) => TextFocus::NotEditable,
+        }
+    }
+}
+
+/// Where text was typed: the frontmost app and its focused element, if it
+/// exposes one. Lets a later correction check it would land in the same place.
+pub struct TypingTarget {
+    pid: i32,
+    element: Option<AXUIElementRef>,
+}
+
+// AXUIElement references may be used and released from any thread
+unsafe impl Send for TypingTarget {}
+
+impl TypingTarget {
+    /// Whether typing would still go to this target.
+    pub fn is_current(&self) -> bool {
+        let Some(current) = typing_target() else {
+            return false;
+        };
+        if current.pid != self.pid {
+            return false;
+        }
+        match (self.element, current.element) {
+            (Some(element), Some(current)) => unsafe { CFEqual(element, current) != 0 },
+            // The app doesn't expose its focus, the process is all there is to check
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Drop for TypingTarget {
+    fn drop(&mut self) {
+        if let Some(element) = self.element.take() {
+            unsafe { CFRelease(element) };
+        }
+    }
+}
+
+/// The app and element typed text goes to right now.
+pub fn typing_target() -> Option<TypingTarget> {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let app: id = msg_send![workspace, frontmostApplication];
+        if app == nil {
+            return None;
+        }
+        let pid: i32 = msg_send![app, processIdentifier];
+        let system = AXUIElementCreateSystemWide();
+        let element = if system.is_null() {
+            None
+        } else {
+            let focused = copy_attribute(system, "AXFocusedUIElement");
+            CFRelease(system);
+            focused
+        };
+        Some(TypingTarget { pid, element })
+    }
+}
+
+/// The value of the focused text field, if the app exposes one.
+pub fn focused_value() -> Option<String> {
+    unsafe {
+        let system = AXUIElementCreateSystemWide();
+        if system.is_null() {
+            return None;
+        }
+        let focused = copy_attribute(system, "AXFocusedUIElement");
+        CFRelease(system);
+        let focused = focused?;
+        let value = string_attribute(focused, "AXValue");
+        CFRelease(focused);
+        value
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use log::error;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+use whispr_core::history_index::SearchHit;
+use crate::config::WhisprConfig;
+
+pub use whispr_core::history::*;
+
+const HISTORY_LABEL: &str = "whispr:history";
+
+/// Records an entry and copies it to the sync folder.
+pub fn record(config: &WhisprConfig, entry: HistoryEntry) -> Result<()> {
+    if whispr_core::history::record(config, &entry)? {
+        crate::sync::publish_history_entry(config, &entry);
+    }
+    Ok(())
+}
+
+/// Plays one segment of an entry's recording, stopping any earlier playback.
+pub fn play_segment(id: i64, segment: usize) -> Result<()> {
+    let entries = load()?;
+    let entry = entries.iter().find(|entry| entry.id == id).context("Unknown history entry")?;
+    let recording = entry.recording.as_deref().context("No recording saved for this entry")?;
+    let segment = entry.segments.get(segment).context("Unknown segment")?;
+
+    let start = Duration::from_secs_f32(segment.start_secs.max(0.0));
+    let length = Duration::from_secs_f32((segment.end_secs - segment.start_secs).max(0.0));
+    crate::playback::play(id, PathBuf::from(recording), start, Some(length))
+}
+
+#[tauri::command]
+pub fn history_entries() -> Result<Vec<HistoryEntry>, String> {
+    load().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn play_history_segment(id: i64, segment: usize) -> Result<(), String> {
+    play_segment(id, segment).map_err(|e| {
+        error!("Failed to play segment: {}", e);
+        e.to_string()
+    })
+}
+
+#[tauri::command]
+pub fn history_search(query: String) -> Result<Vec<SearchHit>, String> {
+    search(&query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_history_tags(id: i64, tags: Vec<String>) -> Result<(), String> {
+    let mut tags: Vec<String> = tags.iter().map(|tag| normalize_tag(tag)).filter(|tag| !tag.is_empty()).collect();
+    tags.dedup();
+    update(id, |entry| entry.tags = tags).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_history_favorite(id: i64, favorite: bool) -> Result<(), String> {
+    update(id, |entry| entry.favorite = favorite).map_err(|e| e.to_string())
+}
+
+/// Exports and reveals the file in Finder.
+#[tauri::command]
+pub fn export_history(tag: Option<String>) -> Result<String, String> {
+    let path = export(tag.as_deref()).map_err(|e| e.to_string())?;
+    let _ = std::process::Command::new("open").arg("-R").arg(&path).status();
+    Ok(path.display().to_string())
+}
+
+/// Opens the history window, or brings it to the front if it is already open.
+pub fn open_history<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(HISTORY_LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let window = WebviewWindowBuilder::new(app, HISTORY_LABEL, WebviewUrl::App("index.html".into()))
+        .title("whispr History")
+        .inner_size(700.0, 600.0)
+        .min_inner_size(400.0, 300.0)
+        .build();
+    if let Err(e) = window {
+        error!("Failed to open history window: {}", e);
+    }
+}
@@ -1,36 +1,166 @@
-use cocoa::base::id;
-use log::{info, debug};
+use cocoa::base::{id, nil};
+use log::{info, debug, warn};
 use objc::{class, msg_send, sel, sel_impl};
 use objc::runtime::Sel;
 use anyhow::Result;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use crate::config::WhisprConfig;
+use crate::focus::nsstring_to_string;
+use crate::trigger::{RecordingController, Trigger, TriggerEvent, TriggerSource};
 
 type NSUInteger = libc::c_ulong;
+type CGEventRef = *mut std::ffi::c_void;
 
 const NSEVENT_MASK_FLAGS_CHANGED: NSUInteger = 1 << 12;
+const CG_EVENT_FLAGS_CHANGED: u32 = 12;
+const CG_HID_EVENT_TAP: u32 = 0;
+const CG_EVENT_SOURCE_STATE_HID_SYSTEM: i32 = 1;
+const SELF_TEST_TIMEOUT: Duration = Duration::from_millis(750);
+
+// Apps known to remap modifier keys before they reach us
+const KEY_REMAPPERS: &[(&str, &str)] = &[
+    ("org.pqrs.Karabiner-Menu", "Karabiner-Elements"),
+    ("org.pqrs.Karabiner-Elements.Settings", "Karabiner-Elements"),
+    ("com.hegenberg.BetterTouchTool", "BetterTouchTool"),
+    ("com.knollsoft.Hyperkey", "Hyperkey"),
+];
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventCreateKeyboardEvent(source: *mut std::ffi::c_void, keycode: u16, key_down: bool) -> CGEventRef;
+    fn CGEventSetType(event: CGEventRef, event_type: u32);
+    fn CGEventSetFlags(event: CGEventRef, flags: u64);
+    fn CGEventPost(tap: u32, event: CGEventRef);
+    fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+}
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn IsSecureEventInputEnabled() -> u8;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *const std::ffi::c_void);
+}
+
+/// Names of running apps that commonly remap modifier keys.
+pub fn running_key_remappers() -> Vec<&'static str> {
+    let mut remappers = Vec::new();
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let apps: id = msg_send![workspace, runningApplications];
+        let count: NSUInteger = msg_send![apps, count];
+        for i in 0..count {
+            let app: id = msg_send![apps, objectAtIndex: i];
+            let bundle_id: id = msg_send![app, bundleIdentifier];
+            if bundle_id == nil {
+                continue;
+            }
+            if let Some(bundle_id) = nsstring_to_string(bundle_id) {
+                for (remapper_id, name) in KEY_REMAPPERS {
+                    if bundle_id == *remapper_id && !remappers.contains(name) {
+                        remappers.push(*name);
+                    }
+                }
+            }
+        }
+    }
+    remappers
+}
+
+/// How long ago any keyboard changed a modifier, whether or not our
+/// monitors heard of it.
+pub fn since_last_flags_changed() -> Duration {
+    let secs = unsafe { CGEventSourceSecondsSinceLastEventType(CG_EVENT_SOURCE_STATE_HID_SYSTEM, CG_EVENT_FLAGS_CHANGED) };
+    Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+}
+
+/// Whether a password field (or an app like Terminal) holds Secure Input,
+/// which keeps key events from global monitors.
+pub fn secure_input_enabled() -> bool {
+    unsafe { IsSecureEventInputEnabled() != 0 }
+}
+
+/// When the monitors last heard a flagsChanged event, shared with the
+/// watchdog that re-registers them once they stop hearing any.
+#[derive(Clone)]
+pub struct Heartbeat {
+    epoch: Instant,
+    // Milliseconds after `epoch`
+    last_beat_ms: Arc<AtomicU64>,
+    events: Arc<AtomicUsize>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self { epoch: Instant::now(), last_beat_ms: Arc::new(AtomicU64::new(0)), events: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    fn beat(&self) {
+        self.last_beat_ms.store(self.epoch.elapsed().as_millis() as u64, Ordering::SeqCst);
+    }
+
+    fn event(&self) {
+        self.events.fetch_add(1, Ordering::SeqCst);
+        self.beat();
+    }
+
+    /// Since the monitors last observed an event or were registered.
+    pub fn silent_for(&self) -> Duration {
+        self.epoch.elapsed().saturating_sub(Duration::from_millis(self.last_beat_ms.load(Ordering::SeqCst)))
+    }
+
+    /// Events observed so far, of any key.
+    pub fn events(&self) -> usize {
+        self.events.load(Ordering::SeqCst)
+    }
+}
+
+fn post_flags_changed(key_code: u16, flags: u64) {
+    unsafe {
+        let event = CGEventCreateKeyboardEvent(std::ptr::null_mut(), key_code, flags != 0);
+        if event.is_null() {
+            warn!("HotkeyManager: Failed to create synthetic event");
+            return;
+        }
+        CGEventSetType(event, CG_EVENT_FLAGS_CHANGED);
+        CGEventSetFlags(event, flags);
+        CGEventPost(CG_HID_EVENT_TAP, event);
+        CFRelease(event as *const std::ffi::c_void);
+    }
+}
 
 pub struct HotkeyManager {
     monitors: Vec<*mut std::ffi::c_void>,
-    callback: Arc<dyn Fn(bool) + Send + Sync>,
+    controller: Option<RecordingController>,
     key_code: u16,
     key_mask: NSUInteger,
+    self_test_active: Arc<AtomicBool>,
+    self_test_events: Arc<AtomicUsize>,
+    heartbeat: Heartbeat,
+    /// Last state of the configured key, shared by the monitors so only its
+    /// changes are logged
+    key_pressed: Arc<AtomicBool>,
 }
 
 impl HotkeyManager {
-    pub fn new<F>(callback: F, config: WhisprConfig) -> Self 
-    where
-        F: Fn(bool) + Send + Sync + 'static,
-    {
+    pub fn new(config: WhisprConfig) -> Self {
         debug!("HotkeyManager: Initializing");
         let (key_code, key_mask) = Self::get_key_code_and_mask(&config.keyboard_shortcut);
         debug!("HotkeyManager: Using key_code: {}, key_mask: {}, and shortcut: {}", key_code, key_mask, config.keyboard_shortcut);
         HotkeyManager {
             monitors: Vec::new(),
-            callback: Arc::new(callback),
+            controller: None,
             key_code,
             key_mask,
+            self_test_active: Arc::new(AtomicBool::new(false)),
+            self_test_events: Arc::new(AtomicUsize::new(0)),
+            heartbeat: Heartbeat::new(),
+            key_pressed: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -39,6 +169,8 @@ impl HotkeyManager {
             // Key mappings for different shortcuts
             ("right_option_key", (61, 1 << 19)), // Right Option key
             ("right_command_key", (54, 1 << 20)), // Right Command key
+            // Globe/fn key, Touch Bar keys never reach flagsChanged monitors
+            ("globe_key", (63, 1 << 23)),
             // Add more key mappings as needed
         ]
         .iter()
@@ -49,18 +181,31 @@ impl HotkeyManager {
     }
 
     fn add_monitor(&mut self, monitor_selector: Sel) -> Result<()> {
-        let callback = self.callback.clone();
+        let controller = self.controller.clone()
+            .ok_or_else(|| anyhow::anyhow!("No recording controller attached"))?;
         let key_code = self.key_code;
         let key_mask = self.key_mask;
+        let self_test_active = self.self_test_active.clone();
+        let self_test_events = self.self_test_events.clone();
+        let heartbeat = self.heartbeat.clone();
+        let key_pressed = self.key_pressed.clone();
         let monitor: id = unsafe {
             let handler = block::ConcreteBlock::new(move |event: id| {
                 if !event.is_null() {
+                    heartbeat.event();
                     let event_key_code: u16 = msg_send![event, keyCode];
+                    let flags: NSUInteger = msg_send![event, modifierFlags];
                     if event_key_code == key_code {
-                        let flags: NSUInteger = msg_send![event, modifierFlags];
+                        if self_test_active.load(Ordering::SeqCst) {
+                            self_test_events.fetch_add(1, Ordering::SeqCst);
+                            return;
+                        }
                         let is_pressed = flags & key_mask != 0;
-                        debug!("HotkeyManager: Key - pressed: {}", is_pressed);
-                        callback(is_pressed);
+                        // Flags help debug remapped modifiers, other keys aren't logged
+                        if key_pressed.swap(is_pressed, Ordering::SeqCst) != is_pressed {
+                            debug!("HotkeyManager: Key - pressed: {}, flags: {:#x}", is_pressed, flags);
+                        }
+                        controller.send(TriggerEvent { source: TriggerSource::Hotkey, pressed: is_pressed, modifier_flags: flags });
                     }
                 }
             })
@@ -84,6 +229,78 @@ impl HotkeyManager {
         info!("HotkeyManager: Starting event monitors");
         self.add_monitor(sel!(addGlobalMonitorForEventsMatchingMask:handler:))?;
         self.add_monitor(sel!(addLocalMonitorForEventsMatchingMask:handler:))?;
+        // Modifier changes from before don't count against the new monitors
+        self.heartbeat.beat();
         Ok(())
     }
+
+    pub fn heartbeat(&self) -> Heartbeat {
+        self.heartbeat.clone()
+    }
+
+    pub fn stop(&mut self) {
+        for monitor in self.monitors.drain(..) {
+            unsafe {
+                let _: () = msg_send![class!(NSEvent), removeMonitor: monitor as id];
+            }
+        }
+        debug!("HotkeyManager: Event monitors removed");
+    }
+
+    /// Recreates the event monitors, e.g. after they stopped delivering
+    /// events across sleep.
+    pub fn restart(&mut self) -> Result<()> {
+        self.stop();
+        self.start()
+    }
+
+    /// Posts a synthetic press/release of the configured key and reports
+    /// whether our monitors observed it. If not, another app (Karabiner,
+    /// BetterTouchTool) is swallowing the key or input monitoring is denied.
+    pub fn run_self_test<F>(&self, on_result: F)
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        let key_code = self.key_code;
+        let key_mask = self.key_mask as u64;
+        let active = self.self_test_active.clone();
+        let events = self.self_test_events.clone();
+
+        // The monitors run on the main thread, so wait for them elsewhere
+        std::thread::spawn(move || {
+            events.store(0, Ordering::SeqCst);
+            active.store(true, Ordering::SeqCst);
+            post_flags_changed(key_code, key_mask);
+            post_flags_changed(key_code, 0);
+            std::thread::sleep(SELF_TEST_TIMEOUT);
+            active.store(false, Ordering::SeqCst);
+
+            let seen = events.load(Ordering::SeqCst);
+            if seen > 0 {
+                info!("HotkeyManager: Self test passed ({} events observed)", seen);
+            } else {
+                warn!("HotkeyManager: Self test failed, no events observed for key_code {}", key_code);
+            }
+            on_result(seen > 0);
+        });
+    }
+}
+
+impl Trigger for HotkeyManager {
+    fn name(&self) -> &'static str {
+        "dictation key"
+    }
+
+    fn attach(&mut self, controller: RecordingController) -> Result<()> {
+        self.controller = Some(controller);
+        self.start()
+    }
+
+    fn detach(&mut self) {
+        self.stop();
+    }
+
+    fn restart(&mut self) -> Result<()> {
+        HotkeyManager::restart(self)
+    }
 }
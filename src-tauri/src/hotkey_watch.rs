@@ -0,0 +1,95 @@
+use log::{debug, error, info, warn};
+use std::time::Duration;
+use tauri::{AppHandle, Runtime};
+use crate::hotkey::{self, Heartbeat};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Covers a modifier change still on its way to the monitors
+const GRACE: Duration = Duration::from_secs(1);
+// Re-registering won't help once Input Monitoring is denied, stop trying
+// until the monitors hear something again
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Recovery {
+    MissedEvents,
+    SecureInputEnded,
+}
+
+/// Whether the monitors need re-registering: macOS saw a modifier change
+/// they didn't, or a Secure Input session that may have silenced them ended.
+fn recovery(silent_for: Duration, since_flags_changed: Duration, secure_input: bool, was_secure_input: bool) -> Option<Recovery> {
+    // Nothing reaches global monitors while Secure Input is on
+    if secure_input {
+        return None;
+    }
+    if was_secure_input {
+        return Some(Recovery::SecureInputEnded);
+    }
+    (silent_for > since_flags_changed.saturating_add(GRACE)).then_some(Recovery::MissedEvents)
+}
+
+/// Checks that the dictation key monitors still observe flagsChanged events
+/// and re-registers them when they stopped, e.g. after a permission change
+/// or a Secure Input session.
+pub fn start<R: Runtime>(app: AppHandle<R>, heartbeat: Heartbeat) {
+    std::thread::spawn(move || {
+        let mut was_secure_input = false;
+        let mut attempts = 0;
+        let mut events_at_recovery = heartbeat.events();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            // The last recovery worked
+            if heartbeat.events() != events_at_recovery {
+                attempts = 0;
+            }
+            let secure_input = hotkey::secure_input_enabled();
+            if secure_input != was_secure_input {
+                debug!("Secure Input {}", if secure_input { "started" } else { "ended" });
+            }
+            let recovery = recovery(heartbeat.silent_for(), hotkey::since_last_flags_changed(), secure_input, was_secure_input);
+            was_secure_input = secure_input;
+            match recovery {
+                Some(Recovery::MissedEvents) if attempts >= MAX_ATTEMPTS => continue,
+                Some(Recovery::MissedEvents) => {
+                    attempts += 1;
+                    warn!("Hotkey monitors missed a modifier change, re-registering them (attempt {} of {})", attempts, MAX_ATTEMPTS);
+                    if attempts == MAX_ATTEMPTS {
+                        warn!("Giving up until the hotkey monitors observe an event again, check Input Monitoring in System Settings");
+                    }
+                }
+                Some(Recovery::SecureInputEnded) => info!("Secure Input ended, re-registering the hotkey monitors"),
+                None => continue,
+            }
+            events_at_recovery = heartbeat.events();
+            // NSEvent monitors are added on the main thread
+            if let Err(e) = app.run_on_main_thread(crate::restart_triggers) {
+                error!("Failed to re-register the hotkey monitors: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn recovers_when_macos_saw_a_change_the_monitors_missed() {
+        assert_eq!(recovery(secs(30), secs(10), false, false), Some(Recovery::MissedEvents));
+        assert_eq!(recovery(secs(10), secs(10), false, false), None);
+        assert_eq!(recovery(secs(5), secs(30), false, false), None);
+        assert_eq!(recovery(secs(30), Duration::MAX, false, false), None);
+    }
+
+    #[test]
+    fn waits_for_secure_input_to_end() {
+        assert_eq!(recovery(secs(30), secs(10), true, false), None);
+        assert_eq!(recovery(secs(30), secs(10), true, true), None);
+        assert_eq!(recovery(secs(0), secs(10), false, true), Some(Recovery::SecureInputEnded));
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use log::{info, debug};
+use std::process::Command;
+use crate::config::AppleSettings;
+
+const REMINDER_PREFIX: &str = "remind me to ";
+const NOTE_TITLE_LENGTH: usize = 40;
+
+/// Runs `script`, which reads `args` in its `on run argv` handler. Text goes
+/// in as arguments rather than into the script, so it needs no escaping and
+/// the script logged holds no dictated text.
+pub(crate) fn run_applescript(script: &str, args: &[&str]) -> Result<()> {
+    debug!("Running AppleScript: {}", script);
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .args(args)
+        .output()
+        .context("Failed to run osascript")?;
+    if !output.status.success() {
+        anyhow::bail!("AppleScript failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Returns the reminder text if the transcription is a "remind me to …"
+/// voice command.
+pub fn parse_reminder_command(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    let prefix = trimmed.get(..REMINDER_PREFIX.len())?;
+    if !prefix.eq_ignore_ascii_case(REMINDER_PREFIX) {
+        return None;
+    }
+
+    let reminder = trimmed[REMINDER_PREFIX.len()..].trim().trim_end_matches(['.', '!']);
+    let mut chars = reminder.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+pub fn create_note(settings: &AppleSettings, text: &str) -> Result<()> {
+    let body = text.trim();
+    let title: String = body.chars().take(NOTE_TITLE_LENGTH).collect();
+    let (script, folder) = match &settings.notes_folder {
+        Some(folder) => (
+            "on run argv\n\
+            tell application \"Notes\" to make new note at folder (item 3 of argv) with properties {name:item 1 of argv, body:item 2 of argv}\n\
+            end run",
+            Some(folder.as_str()),
+        ),
+        None => (
+            "on run argv\n\
+            tell application \"Notes\" to make new note with properties {name:item 1 of argv, body:item 2 of argv}\n\
+            end run",
+            None,
+        ),
+    };
+    let mut args = vec![title.as_str(), body];
+    args.extend(folder);
+    run_applescript(script, &args)?;
+    info!("Created Apple Note ({} characters)", body.chars().count());
+    Ok(())
+}
+
+pub fn create_reminder(settings: &AppleSettings, text: &str) -> Result<()> {
+    let (script, list) = match &settings.reminders_list {
+        Some(list) => (
+            "on run argv\n\
+            tell application \"Reminders\" to make new reminder at list (item 2 of argv) with properties {name:item 1 of argv}\n\
+            end run",
+            Some(list.as_str()),
+        ),
+        None => (
+            "on run argv\n\
+            tell application \"Reminders\" to make new reminder with properties {name:item 1 of argv}\n\
+            end run",
+            None,
+        ),
+    };
+    let mut args = vec![text.trim()];
+    args.extend(list);
+    run_applescript(script, &args)?;
+    info!("Created reminder");
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde_json::{json, Value};
+use crate::config::{ChatService, ChatSettings, NetworkSettings};
+use crate::download;
+
+// Discord rejects messages longer than this
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+/// Slack treats these as control characters in message text.
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn payload(settings: &ChatSettings, text: &str) -> Value {
+    let text = text.trim();
+    let prefix = settings.prefix.as_deref().filter(|prefix| !prefix.is_empty());
+    match settings.service {
+        ChatService::Slack => {
+            let text = escape_slack(text);
+            let text = match prefix {
+                Some(prefix) => format!("*{}*\n{}", escape_slack(prefix), text),
+                None => text,
+            };
+            json!({ "text": text })
+        }
+        ChatService::Discord => {
+            let text = match prefix {
+                Some(prefix) => format!("**{}**\n{}", prefix, text),
+                None => text.to_string(),
+            };
+            let mut payload = json!({
+                "content": truncate(&text, DISCORD_MAX_LENGTH),
+                // Never ping anyone because a name was dictated
+                "allowed_mentions": { "parse": [] },
+            });
+            if let Some(username) = settings.username.as_deref().filter(|name| !name.is_empty()) {
+                payload["username"] = json!(username);
+            }
+            payload
+        }
+    }
+}
+
+/// Posts the transcription to the configured Slack or Discord incoming webhook.
+pub fn send(settings: &ChatSettings, network: &NetworkSettings, text: &str) -> Result<()> {
+    let url = settings.webhook_url.as_deref()
+        .filter(|url| !url.is_empty())
+        .context("No chat webhook configured (integrations.chat.webhook_url)")?;
+    download::shared_client(network)?
+        .post(url)
+        .json(&payload(settings, text))
+        .send()?
+        .error_for_status()?;
+    debug!("Posted transcription to {:?}", settings.service);
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::process::Command;
+use crate::config::{EmailClient, EmailSettings};
+use super::apple::run_applescript;
+
+const SUBJECT_MARKER: &str = "subject";
+const BODY_MARKER: &str = "body";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub subject: String,
+    pub body: String,
+}
+
+/// Finds `marker` as a whole word, case insensitive, and returns the byte
+/// range it covers.
+fn find_marker(text: &str, marker: &str, from: usize) -> Option<(usize, usize)> {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths outside ASCII, don't guess offsets then
+    if lower.len() != text.len() {
+        return None;
+    }
+    let mut offset = from;
+    while let Some(position) = lower[offset..].find(marker) {
+        let start = offset + position;
+        let end = start + marker.len();
+        let boundary_before = !matches!(lower[..start].chars().last(), Some(c) if c.is_alphanumeric());
+        let boundary_after = !matches!(lower[end..].chars().next(), Some(c) if c.is_alphanumeric());
+        if boundary_before && boundary_after {
+            return Some((start, end));
+        }
+        offset = end;
+    }
+    None
+}
+
+fn clean(text: &str) -> String {
+    text.trim_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ':' | ';'))
+        .to_string()
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Splits a dictation into subject and body. "Subject … body …" markers win,
+/// otherwise the first sentence becomes the subject.
+pub fn parse_email(text: &str) -> Email {
+    let text = text.trim();
+
+    if let Some((_, subject_start)) = find_marker(text, SUBJECT_MARKER, 0).filter(|(start, _)| *start == 0) {
+        if let Some((body_start, body_end)) = find_marker(text, BODY_MARKER, subject_start) {
+            let subject = clean(&text[subject_start..body_start]);
+            return Email {
+                subject: capitalize(subject.trim_end_matches('.')),
+                body: capitalize(&clean(&text[body_end..])),
+            };
+        }
+    }
+
+    match text.find(['.', '?', '!']) {
+        Some(end) => Email {
+            subject: clean(&text[..end]),
+            body: clean(&text[end + 1..]),
+        },
+        None => Email { subject: text.to_string(), body: String::new() },
+    }
+}
+
+/// Percent-encodes everything but unreserved characters, as mailto: needs.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+pub fn mailto_url(settings: &EmailSettings, email: &Email) -> String {
+    format!(
+        "mailto:{}?subject={}&body={}",
+        // Addresses keep their @ and separating commas
+        settings.to.as_deref().map(|to| encode(to).replace("%40", "@").replace("%2C", ",")).unwrap_or_default(),
+        encode(&email.subject),
+        encode(&email.body)
+    )
+}
+
+fn compose_in_mail(settings: &EmailSettings, email: &Email) -> Result<()> {
+    let to = settings.to.as_deref().filter(|to| !to.is_empty());
+    // The recipient is item 3, only passed when there is one
+    let script = "on run argv\n\
+        tell application \"Mail\"\n\
+        set message to make new outgoing message with properties {subject:item 1 of argv, content:item 2 of argv, visible:true}\n\
+        if (count of argv) > 2 then\n\
+        tell message to make new to recipient at end of to recipients with properties {address:item 3 of argv}\n\
+        end if\n\
+        activate\n\
+        end tell\n\
+        end run";
+    let mut args = vec![email.subject.as_str(), email.body.as_str()];
+    args.extend(to);
+    run_applescript(script, &args)
+}
+
+/// Opens a compose window pre-filled with the dictated email. Nothing is
+/// sent, the user reviews and sends it.
+pub fn compose(settings: &EmailSettings, text: &str) -> Result<()> {
+    let email = parse_email(text);
+    match settings.client {
+        EmailClient::Mailto => {
+            let status = Command::new("open")
+                .arg(mailto_url(settings, &email))
+                .status()
+                .context("Failed to run open")?;
+            if !status.success() {
+                anyhow::bail!("No mail app handles mailto: links");
+            }
+        }
+        EmailClient::Mail => compose_in_mail(settings, &email)?,
+    }
+    info!("Opened email draft ({} characters)", email.body.chars().count());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_sentence_becomes_subject() {
+        let email = parse_email("Lunch on Friday. Are you free at noon? Let me know.");
+        assert_eq!(email.subject, "Lunch on Friday");
+        assert_eq!(email.body, "Are you free at noon? Let me know.");
+    }
+
+    #[test]
+    fn markers_split_subject_and_body() {
+        let email = parse_email("Subject: quarterly report, body: the numbers are attached. Thanks.");
+        assert_eq!(email.subject, "Quarterly report");
+        assert_eq!(email.body, "The numbers are attached. Thanks.");
+    }
+
+    #[test]
+    fn marker_words_inside_text_are_ignored() {
+        let email = parse_email("Somebody said hi. The subject came up.");
+        assert_eq!(email.subject, "Somebody said hi");
+        assert_eq!(email.body, "The subject came up.");
+    }
+
+    #[test]
+    fn mailto_url_is_encoded() {
+        let settings = EmailSettings { to: Some("team@example.com".to_string()), ..Default::default() };
+        let email = Email { subject: "Hi there".to_string(), body: "A & B".to_string() };
+        assert_eq!(mailto_url(&settings, &email), "mailto:team@example.com?subject=Hi%20there&body=A%20%26%20B");
+    }
+}
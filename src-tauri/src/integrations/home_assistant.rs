@@ -0,0 +1,37 @@
+use anyhow::{Context, Result};
+use log::{info, debug};
+use serde_json::{json, Value};
+use crate::config::{HomeAssistantSettings, NetworkSettings};
+use crate::{download, keychain};
+
+const KEYCHAIN_SERVICE: &str = "com.whispr.app.home_assistant";
+
+/// Hands the spoken command to Home Assistant's conversation agent and
+/// returns its spoken response, e.g. "Turned off the lights".
+pub fn process(settings: &HomeAssistantSettings, network: &NetworkSettings, text: &str) -> Result<String> {
+    let url = settings.url.as_deref()
+        .filter(|url| !url.is_empty())
+        .context("No Home Assistant configured (integrations.home_assistant.url)")?;
+    let token = keychain::find_password(KEYCHAIN_SERVICE)
+        .with_context(|| format!("No Home Assistant token in the Keychain, add it as {} (account whispr)", KEYCHAIN_SERVICE))?;
+
+    let mut body = json!({ "text": text.trim() });
+    if let Some(language) = settings.language.as_deref().filter(|language| !language.is_empty()) {
+        body["language"] = json!(language);
+    }
+    let response: Value = download::shared_client(network)?
+        .post(format!("{}/api/conversation/process", url.trim_end_matches('/')))
+        .bearer_auth(token)
+        .json(&body)
+        .send()?
+        .error_for_status()?
+        .json()?;
+    debug!("Home Assistant response: {}", response);
+
+    let speech = response["response"]["speech"]["plain"]["speech"]
+        .as_str()
+        .unwrap_or("Done")
+        .to_string();
+    info!("Home Assistant handled a command");
+    Ok(speech)
+}
@@ -0,0 +1,7 @@
+pub mod obsidian;
+pub mod apple;
+pub mod obs;
+pub mod chat;
+pub mod email;
+pub mod time_tracking;
+pub mod home_assistant;
@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use log::{debug, error};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::config::ObsSettings;
+
+// Bumped on every write so a pending clear doesn't wipe a newer caption
+static CAPTION_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn caption_path(settings: &ObsSettings) -> Result<PathBuf> {
+    settings.caption_file.as_deref()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .context("No caption file configured (integrations.obs.caption_file)")
+}
+
+/// Replaces the caption. Written to a temporary file and renamed so OBS never
+/// reads a half written caption.
+pub fn write_caption(settings: &ObsSettings, text: &str) -> Result<()> {
+    let path = caption_path(settings)?;
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, text.trim())?;
+    fs::rename(&temp_path, &path)?;
+    CAPTION_GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Shows the final caption and clears it after `clear_after_secs` unless a
+/// newer caption replaced it in the meantime.
+pub fn show_caption(settings: &ObsSettings, text: &str) -> Result<()> {
+    write_caption(settings, text)?;
+    debug!("Updated OBS caption");
+    if settings.clear_after_secs == 0 {
+        return Ok(());
+    }
+
+    let generation = CAPTION_GENERATION.load(Ordering::SeqCst);
+    let settings = settings.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(settings.clear_after_secs));
+        if CAPTION_GENERATION.load(Ordering::SeqCst) == generation {
+            if let Err(e) = write_caption(&settings, "") {
+                error!("Failed to clear OBS caption: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use log::info;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use crate::config::ObsidianSettings;
+
+fn daily_note_path(settings: &ObsidianSettings) -> Result<PathBuf> {
+    let vault = settings.vault_path.as_deref()
+        .filter(|path| !path.is_empty())
+        .context("No Obsidian vault configured (integrations.obsidian.vault_path)")?;
+    let filename = format!("{}.md", Local::now().format(&settings.date_format));
+    Ok(PathBuf::from(vault).join(&settings.daily_notes_folder).join(filename))
+}
+
+/// `value` as a double-quoted YAML scalar, so tags with `,`, `]`, `#` or
+/// quotes keep the front matter parseable.
+fn yaml_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Content for a new daily note: the configured template with `{{date}}`
+/// replaced, or a minimal note with YAML frontmatter.
+fn new_note_content(settings: &ObsidianSettings) -> Result<String> {
+    let date = Local::now().format(&settings.date_format).to_string();
+
+    if let (Some(vault), Some(template)) = (&settings.vault_path, &settings.template) {
+        let template_path = PathBuf::from(vault).join(template);
+        let template = fs::read_to_string(&template_path)
+            .with_context(|| format!("Failed to read template {}", template_path.display()))?;
+        return Ok(template.replace("{{date}}", &date));
+    }
+
+    let mut content = format!("---\ndate: {}\n", date);
+    if !settings.tags.is_empty() {
+        let tags: Vec<String> = settings.tags.iter().map(|tag| yaml_quote(tag)).collect();
+        content.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    }
+    content.push_str("---\n\n");
+    Ok(content)
+}
+
+/// Appends the transcription as a timestamped list item to today's daily
+/// note, creating the note if it doesn't exist yet.
+pub fn append_to_daily_note(settings: &ObsidianSettings, text: &str) -> Result<()> {
+    let note_path = daily_note_path(settings)?;
+
+    if !note_path.exists() {
+        if let Some(parent) = note_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&note_path, new_note_content(settings)?)?;
+        info!("Created daily note {}", note_path.display());
+    }
+
+    let tags: String = settings.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+    let mut file = OpenOptions::new().append(true).open(&note_path)?;
+    writeln!(file, "- {} {}{}", Local::now().format("%H:%M"), text.trim(), tags)?;
+    info!("Appended transcription to {}", note_path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_tags_for_front_matter() {
+        assert_eq!(yaml_quote("dictation"), "\"dictation\"");
+        assert_eq!(yaml_quote("a, b]"), "\"a, b]\"");
+        assert_eq!(yaml_quote("say \"hi\" \\o/"), "\"say \\\"hi\\\" \\\\o/\"");
+        assert_eq!(yaml_quote("line\nbreak"), "\"line\\nbreak\"");
+    }
+
+    #[test]
+    fn new_note_lists_quoted_tags() {
+        let settings = ObsidianSettings { tags: vec!["voice".to_string(), "#inbox".to_string()], ..ObsidianSettings::default() };
+        let content = new_note_content(&settings).unwrap();
+        assert!(content.contains("tags: [\"voice\", \"#inbox\"]\n"));
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use log::info;
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::{json, Value};
+use crate::commands::{strip_command_prefix, VoiceCommand};
+use crate::config::{NetworkSettings, TimeTrackingService, TimeTrackingSettings};
+use crate::{download, keychain};
+
+const TOGGL_API: &str = "https://api.track.toggl.com/api/v9";
+const CLOCKIFY_API: &str = "https://api.clockify.me/api/v1";
+const START_PREFIX: &str = "start timer";
+const STOP_PREFIX: &str = "stop timer";
+
+fn keychain_service(service: TimeTrackingService) -> &'static str {
+    match service {
+        TimeTrackingService::Toggl => "com.whispr.app.toggl",
+        TimeTrackingService::Clockify => "com.whispr.app.clockify",
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+struct TimeTracker {
+    settings: TimeTrackingSettings,
+    client: Client,
+    token: String,
+}
+
+impl TimeTracker {
+    fn new(settings: &TimeTrackingSettings, network: &NetworkSettings) -> Result<Self> {
+        let service = keychain_service(settings.service);
+        let token = keychain::find_password(service)
+            .with_context(|| format!("No API token in the Keychain, add it as {} (account whispr)", service))?;
+        Ok(Self {
+            settings: settings.clone(),
+            client: download::shared_client(network)?,
+            token,
+        })
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.settings.service {
+            TimeTrackingService::Toggl => request.basic_auth(&self.token, Some("api_token")),
+            TimeTrackingService::Clockify => request.header("X-Api-Key", &self.token),
+        }
+    }
+
+    fn get(&self, url: &str) -> Result<Value> {
+        Ok(self.authorize(self.client.get(url)).send()?.error_for_status()?.json()?)
+    }
+
+    /// Workspace from the settings, or the account's default one.
+    fn workspace_id(&self, user: &Value) -> Result<String> {
+        if let Some(workspace_id) = self.settings.workspace_id.as_deref().filter(|id| !id.is_empty()) {
+            return Ok(workspace_id.to_string());
+        }
+        let field = match self.settings.service {
+            TimeTrackingService::Toggl => "default_workspace_id",
+            TimeTrackingService::Clockify => "activeWorkspace",
+        };
+        match &user[field] {
+            Value::Number(id) => Ok(id.to_string()),
+            Value::String(id) => Ok(id.clone()),
+            _ => anyhow::bail!("Account has no default workspace, set integrations.time_tracking.workspace_id"),
+        }
+    }
+
+    fn user(&self) -> Result<Value> {
+        match self.settings.service {
+            TimeTrackingService::Toggl => self.get(&format!("{}/me", TOGGL_API)),
+            TimeTrackingService::Clockify => self.get(&format!("{}/user", CLOCKIFY_API)),
+        }
+    }
+
+    fn start(&self, description: &str) -> Result<()> {
+        let user = self.user()?;
+        let workspace_id = self.workspace_id(&user)?;
+        let request = match self.settings.service {
+            TimeTrackingService::Toggl => self.client
+                .post(format!("{}/workspaces/{}/time_entries", TOGGL_API, workspace_id))
+                .json(&json!({
+                    "created_with": "whispr",
+                    "description": description,
+                    "workspace_id": workspace_id.parse::<u64>().context("Invalid Toggl workspace id")?,
+                    "start": now(),
+                    // Negative durations mark a running entry
+                    "duration": -1,
+                })),
+            TimeTrackingService::Clockify => self.client
+                .post(format!("{}/workspaces/{}/time-entries", CLOCKIFY_API, workspace_id))
+                .json(&json!({
+                    "description": description,
+                    "start": now(),
+                })),
+        };
+        self.authorize(request).send()?.error_for_status()?;
+        info!("Started {:?} timer \"{}\"", self.settings.service, description);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<()> {
+        let request = match self.settings.service {
+            TimeTrackingService::Toggl => {
+                let current = self.get(&format!("{}/me/time_entries/current", TOGGL_API))?;
+                let (Some(entry_id), Some(workspace_id)) = (current["id"].as_u64(), current["workspace_id"].as_u64()) else {
+                    anyhow::bail!("No timer running");
+                };
+                self.client.patch(format!("{}/workspaces/{}/time_entries/{}/stop", TOGGL_API, workspace_id, entry_id))
+            }
+            TimeTrackingService::Clockify => {
+                let user = self.user()?;
+                let workspace_id = self.workspace_id(&user)?;
+                let user_id = user["id"].as_str().context("Clockify returned no user id")?;
+                self.client
+                    .patch(format!("{}/workspaces/{}/user/{}/time-entries", CLOCKIFY_API, workspace_id, user_id))
+                    .json(&json!({ "end": now() }))
+            }
+        };
+        let response = self.authorize(request).send()?;
+        // Clockify answers 404 when nothing is running
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("No timer running");
+        }
+        response.error_for_status()?;
+        info!("Stopped {:?} timer", self.settings.service);
+        Ok(())
+    }
+}
+
+/// "start timer writing report"
+struct StartTimerCommand {
+    settings: TimeTrackingSettings,
+    network: NetworkSettings,
+}
+
+impl VoiceCommand for StartTimerCommand {
+    fn name(&self) -> &'static str {
+        "start_timer"
+    }
+
+    fn parse(&self, text: &str) -> Option<String> {
+        strip_command_prefix(text, START_PREFIX).map(str::to_string)
+    }
+
+    fn run(&self, description: &str) -> Result<String> {
+        TimeTracker::new(&self.settings, &self.network)?.start(description)?;
+        Ok(if description.is_empty() { "Timer started".to_string() } else { format!("Timer started: {}", description) })
+    }
+}
+
+/// "stop timer"
+struct StopTimerCommand {
+    settings: TimeTrackingSettings,
+    network: NetworkSettings,
+}
+
+impl VoiceCommand for StopTimerCommand {
+    fn name(&self) -> &'static str {
+        "stop_timer"
+    }
+
+    fn parse(&self, text: &str) -> Option<String> {
+        strip_command_prefix(text, STOP_PREFIX).filter(|rest| rest.is_empty()).map(str::to_string)
+    }
+
+    fn run(&self, _: &str) -> Result<String> {
+        TimeTracker::new(&self.settings, &self.network)?.stop()?;
+        Ok("Timer stopped".to_string())
+    }
+}
+
+pub fn commands(settings: &TimeTrackingSettings, network: &NetworkSettings) -> Vec<Box<dyn VoiceCommand>> {
+    vec![
+        Box::new(StartTimerCommand { settings: settings.clone(), network: network.clone() }),
+        Box::new(StopTimerCommand { settings: settings.clone(), network: network.clone() }),
+    ]
+}
@@ -0,0 +1,244 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use whispr_core::error::ErrorCode;
+use crate::api::{self, Status};
+use crate::config::{ApiSettings, ConfigManager, WhisprConfig};
+use crate::input_device::InputFormat;
+use crate::metrics;
+use crate::pipeline::{self, WHISPER_SAMPLE_RATE};
+use crate::whisper::Decoding;
+
+// How often waiting jobs check whether the dictation holding them up ended
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Transcriptions requested over the HTTP API, started in the order they
+/// arrived.
+struct Queue {
+    waiting: VecDeque<u64>,
+    running: usize,
+    next_ticket: u64,
+}
+
+impl Queue {
+    const fn new() -> Self {
+        Self { waiting: VecDeque::new(), running: 0, next_ticket: 0 }
+    }
+
+    /// Lines up a new job, `None` if `max_queued` are waiting already.
+    fn enqueue(&mut self, max_queued: usize) -> Option<u64> {
+        if self.waiting.len() >= max_queued {
+            return None;
+        }
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.waiting.push_back(ticket);
+        Some(ticket)
+    }
+
+    /// Starts the job if it is next in line and fewer than `max_concurrent`
+    /// run. Nothing starts while a dictation is recorded or transcribed, the
+    /// hotkey always goes first.
+    fn try_start(&mut self, ticket: u64, max_concurrent: usize, dictating: bool) -> bool {
+        if dictating || self.running >= max_concurrent.max(1) || self.waiting.front() != Some(&ticket) {
+            return false;
+        }
+        self.waiting.pop_front();
+        self.running += 1;
+        true
+    }
+
+    fn abandon(&mut self, ticket: u64) {
+        self.waiting.retain(|waiting| *waiting != ticket);
+    }
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue::new());
+static QUEUE_CHANGED: Condvar = Condvar::new();
+
+/// A decode slot, given back when dropped.
+struct Slot;
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        QUEUE.lock().unwrap().running -= 1;
+        QUEUE_CHANGED.notify_all();
+    }
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    InvalidAudio(anyhow::Error),
+    QueueFull,
+    TimedOut,
+    ModelNotReady,
+    Failed(anyhow::Error),
+}
+
+impl JobError {
+    pub fn status(&self) -> u16 {
+        match self {
+            JobError::InvalidAudio(_) => 400,
+            JobError::QueueFull | JobError::ModelNotReady => 503,
+            JobError::TimedOut => 504,
+            JobError::Failed(_) => 500,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            JobError::InvalidAudio(_) => ErrorCode::BadRequest,
+            JobError::QueueFull => ErrorCode::Busy,
+            JobError::TimedOut => ErrorCode::Timeout,
+            JobError::ModelNotReady => ErrorCode::ModelNotReady,
+            JobError::Failed(_) => ErrorCode::Transcription,
+        }
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::InvalidAudio(e) => write!(f, "Invalid audio: {}", e),
+            JobError::QueueFull => f.write_str("Too many transcriptions are waiting, try again later"),
+            JobError::TimedOut => f.write_str("The transcription didn't finish in time"),
+            JobError::ModelNotReady => f.write_str("Whisper model is still loading"),
+            JobError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobResult {
+    pub text: String,
+    pub language: Option<String>,
+    pub confidence: f32,
+}
+
+/// Waits in line for a decode slot until `deadline`.
+fn wait_for_slot(app: &AppHandle, settings: &ApiSettings, deadline: Instant) -> Result<Slot, JobError> {
+    let mut queue = QUEUE.lock().unwrap();
+    let ticket = queue.enqueue(settings.max_queued_jobs).ok_or(JobError::QueueFull)?;
+    loop {
+        let dictating = api::current_status(app) != Status::Idle;
+        if queue.try_start(ticket, settings.max_concurrent_jobs, dictating) {
+            return Ok(Slot);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            queue.abandon(ticket);
+            QUEUE_CHANGED.notify_all();
+            return Err(JobError::TimedOut);
+        }
+        queue = QUEUE_CHANGED.wait_timeout(queue, POLL_INTERVAL.min(deadline - now)).unwrap().0;
+    }
+}
+
+fn decode(app: &AppHandle, format: InputFormat, samples: Vec<f32>) -> Result<JobResult, JobError> {
+    let state = app.try_state::<crate::AppState>().ok_or(JobError::ModelNotReady)?;
+    let whisper = state.decoder().ok_or(JobError::ModelNotReady)?;
+    let config = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default();
+    // Converting may take a while, dictations shouldn't wait for the audio lock meanwhile
+    let converter = state.audio.lock().unwrap().recording_converter();
+    let audio = converter.convert(format, samples, WHISPER_SAMPLE_RATE, 1)
+        .map_err(|e| JobError::Failed(e.into()))?
+        .context("Audio is empty")
+        .map_err(JobError::InvalidAudio)?;
+
+    let decode_started = Instant::now();
+    let Decoding { mut segments, confidence, language, .. } = whisper
+        .process_audio_with_confidence(&audio, &config.whisper, None, None)
+        .map_err(|e| JobError::Failed(anyhow::anyhow!(e)))?;
+    metrics::record_decode(metrics::Source::Api, audio.len() as f32 / WHISPER_SAMPLE_RATE as f32, decode_started.elapsed());
+    crate::quality::trim_segments(&mut segments);
+    let text = pipeline::join_segments(&segments, config.output.paragraph_pause_secs);
+    let text = crate::cjk::format(&text, language.as_deref(), &config.output.cjk);
+    Ok(JobResult { text: text.trim_end().to_string(), language, confidence })
+}
+
+/// Transcribes a WAV file sent over the HTTP API. At most
+/// `api.max_concurrent_jobs` decode at a time and `api.max_queued_jobs` wait
+/// for their turn, none start while a dictation is in progress. Waiting and
+/// decoding together may take `api.job_timeout_secs`, a decode that runs
+/// over keeps its slot until it ends so the limit holds.
+pub fn transcribe(app: &AppHandle, wav: &[u8], settings: &ApiSettings) -> Result<JobResult, JobError> {
+    let deadline = Instant::now() + Duration::from_secs(settings.job_timeout_secs);
+    let (format, samples) = pipeline::read_wav_from(Cursor::new(wav)).map_err(JobError::InvalidAudio)?;
+    let slot = wait_for_slot(app, settings, deadline)?;
+    let secs = samples.len() as f32 / format.channels.max(1) as f32 / format.sample_rate as f32;
+    info!("Transcribing {:.1}s of audio for the API", secs);
+
+    let (sender, receiver) = mpsc::channel();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let result = decode(&app, format, samples);
+        drop(slot);
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            warn!("API transcription timed out, it keeps its slot until it finishes");
+            Err(JobError::TimedOut)
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            debug!("API transcription thread ended without a result");
+            Err(JobError::Failed(anyhow::anyhow!("Transcription failed")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_in_order_up_to_the_limit() {
+        let mut queue = Queue::new();
+        let first = queue.enqueue(8).unwrap();
+        let second = queue.enqueue(8).unwrap();
+        let third = queue.enqueue(8).unwrap();
+        assert!(!queue.try_start(second, 2, false));
+        assert!(queue.try_start(first, 2, false));
+        assert!(queue.try_start(second, 2, false));
+        assert!(!queue.try_start(third, 2, false));
+        queue.running -= 1;
+        assert!(queue.try_start(third, 2, false));
+    }
+
+    #[test]
+    fn turns_jobs_away_when_full() {
+        let mut queue = Queue::new();
+        assert!(queue.enqueue(2).is_some());
+        let second = queue.enqueue(2).unwrap();
+        assert_eq!(queue.enqueue(2), None);
+        queue.abandon(second);
+        assert!(queue.enqueue(2).is_some());
+    }
+
+    #[test]
+    fn dictation_goes_first() {
+        let mut queue = Queue::new();
+        let ticket = queue.enqueue(8).unwrap();
+        assert!(!queue.try_start(ticket, 4, true));
+        assert!(queue.try_start(ticket, 4, false));
+    }
+
+    #[test]
+    fn abandoned_jobs_let_the_next_one_start() {
+        let mut queue = Queue::new();
+        let first = queue.enqueue(8).unwrap();
+        let second = queue.enqueue(8).unwrap();
+        queue.abandon(first);
+        assert!(queue.try_start(second, 1, false));
+    }
+}
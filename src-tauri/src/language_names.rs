@@ -0,0 +1,107 @@
+use cocoa::base::{id, nil};
+use objc::{class, msg_send, sel, sel_impl};
+use once_cell::sync::Lazy;
+
+/// UI locales with a column in [`NAMES`], English first as the fallback.
+pub const UI_LOCALES: [&str; 5] = ["en", "de", "fr", "es", "it"];
+
+/// Language names as (whisper language code, native name, names in each of
+/// [`UI_LOCALES`]). "auto" has no native name.
+const NAMES: &[(&str, Option<&str>, [&str; 5])] = &[
+    ("auto", None, ["Automatic", "Automatisch", "Automatique", "Automático", "Automatico"]),
+    ("en", Some("English"), ["English", "Englisch", "Anglais", "Inglés", "Inglese"]),
+    ("de", Some("Deutsch"), ["German", "Deutsch", "Allemand", "Alemán", "Tedesco"]),
+    ("fr", Some("Français"), ["French", "Französisch", "Français", "Francés", "Francese"]),
+    ("es", Some("Español"), ["Spanish", "Spanisch", "Espagnol", "Español", "Spagnolo"]),
+    ("it", Some("Italiano"), ["Italian", "Italienisch", "Italien", "Italiano", "Italiano"]),
+    ("pt", Some("Português"), ["Portuguese", "Portugiesisch", "Portugais", "Portugués", "Portoghese"]),
+    ("nl", Some("Nederlands"), ["Dutch", "Niederländisch", "Néerlandais", "Neerlandés", "Olandese"]),
+    ("ja", Some("日本語"), ["Japanese", "Japanisch", "Japonais", "Japonés", "Giapponese"]),
+    ("zh", Some("中文"), ["Chinese", "Chinesisch", "Chinois", "Chino", "Cinese"]),
+];
+
+static UI_LOCALE: Lazy<&'static str> = Lazy::new(|| {
+    let preferred = unsafe {
+        let languages: id = msg_send![class!(NSLocale), preferredLanguages];
+        let first: id = msg_send![languages, firstObject];
+        if first == nil { None } else { crate::focus::nsstring_to_string(first) }
+    };
+    preferred.map_or(UI_LOCALES[0], |tag| ui_locale_for(&tag))
+});
+
+/// The UI locale whispr shows language names in, from the first preferred
+/// macOS language.
+pub fn ui_locale() -> &'static str {
+    &UI_LOCALE
+}
+
+/// Maps a language tag like "de-CH" to one of [`UI_LOCALES`], English if
+/// there is no column for it.
+fn ui_locale_for(tag: &str) -> &'static str {
+    let language = tag.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    UI_LOCALES.iter().find(|locale| **locale == language).copied().unwrap_or(UI_LOCALES[0])
+}
+
+/// "Deutsch" for "de" with the "de" UI locale, `None` if `code` is not in
+/// the table.
+pub fn localized(code: &str, locale: &str) -> Option<&'static str> {
+    let column = UI_LOCALES.iter().position(|ui| *ui == locale).unwrap_or(0);
+    NAMES.iter().find(|(language, ..)| *language == code).map(|(_, _, names)| names[column])
+}
+
+/// Menu label for `code`, with `native` e.g. "Deutsch (German)". Falls back
+/// to the code for languages missing from the table.
+pub fn display_name(code: &str, locale: &str, native: bool) -> String {
+    let Some((_, native_name, _)) = NAMES.iter().find(|(language, ..)| *language == code) else {
+        return code.to_string();
+    };
+    let name = localized(code, locale).unwrap_or(code);
+    match native_name {
+        Some(native_name) if native && *native_name != name => format!("{} ({})", native_name, name),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_ui_locale() {
+        assert_eq!(display_name("de", "en", false), "German");
+        assert_eq!(display_name("de", "fr", false), "Allemand");
+        assert_eq!(display_name("auto", "de", false), "Automatisch");
+        // Unknown UI locales fall back to English
+        assert_eq!(display_name("es", "sv", false), "Spanish");
+    }
+
+    #[test]
+    fn native_names_lead() {
+        assert_eq!(display_name("de", "en", true), "Deutsch (German)");
+        assert_eq!(display_name("ja", "it", true), "日本語 (Giapponese)");
+        // No repetition when the UI is in that language, none for Automatic
+        assert_eq!(display_name("de", "de", true), "Deutsch");
+        assert_eq!(display_name("auto", "en", true), "Automatic");
+    }
+
+    #[test]
+    fn unknown_codes_stay_codes() {
+        assert_eq!(display_name("sw", "en", true), "sw");
+        assert_eq!(localized("sw", "en"), None);
+    }
+
+    #[test]
+    fn language_tags_map_to_ui_locales() {
+        assert_eq!(ui_locale_for("de-CH"), "de");
+        assert_eq!(ui_locale_for("fr_FR"), "fr");
+        assert_eq!(ui_locale_for("en-GB"), "en");
+        assert_eq!(ui_locale_for("zh-Hans-CN"), "en");
+    }
+
+    #[test]
+    fn every_menu_language_has_names() {
+        for (_, code) in crate::menu_spec::LANGUAGES {
+            assert!(localized(code, "en").is_some(), "{} is missing", code);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use log::{info, error};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::config::{ConfigManager, SinkKind, WhisprConfig};
+use crate::output;
+use crate::window::OverlayWindow;
+
+// Long enough to read the chip and move the mouse there
+const CHIP_DURATION: Duration = Duration::from_secs(6);
+
+/// Languages auto-detection picked for the last dictations, newest last.
+pub struct LanguageTracker {
+    recent: VecDeque<String>,
+}
+
+impl LanguageTracker {
+    pub const fn new() -> Self {
+        Self { recent: VecDeque::new() }
+    }
+
+    /// Records `detected` and returns the language of the previous `window`
+    /// dictations if they all agreed on a different one.
+    pub fn observe(&mut self, detected: &str, window: usize) -> Option<String> {
+        let usual = self.recent.front().cloned().filter(|usual| {
+            window > 0
+                && self.recent.len() >= window
+                && usual != detected
+                && self.recent.iter().all(|language| language == usual)
+        });
+        self.recent.push_back(detected.to_string());
+        while self.recent.len() > window.max(1) {
+            self.recent.pop_front();
+        }
+        usual
+    }
+
+    /// The last dictation was redone in `language`, so it never was a switch.
+    pub fn correct(&mut self, language: &str) {
+        if let Some(last) = self.recent.back_mut() {
+            *last = language.to_string();
+        }
+    }
+}
+
+/// Audio and text of a dictation that may have been detected as the wrong
+/// language, kept until the chip disappears.
+struct PendingRedo {
+    audio: Vec<f32>,
+    text: String,
+    language: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LanguageChip {
+    detected: String,
+    usual: String,
+}
+
+static TRACKER: Mutex<LanguageTracker> = Mutex::new(LanguageTracker::new());
+static PENDING: Mutex<Option<PendingRedo>> = Mutex::new(None);
+// Only the timer of the latest chip may hide it
+static CHIP_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// "French" for "fr" in the UI locale, falling back to whisper's own name
+/// for the code.
+pub fn language_name(code: &str) -> String {
+    if let Some(name) = crate::language_names::localized(code, crate::language_names::ui_locale()) {
+        return name.to_string();
+    }
+    let Some(name) = whisper_rs::get_lang_id(code).and_then(whisper_rs::get_lang_str_full) else {
+        return code.to_string();
+    };
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+/// Tracks the detected language of a delivered dictation. When it differs
+/// from the last `whisper.language_switch_window` dictations, shows a chip in
+/// the overlay offering to redo it in the usual language, and returns true.
+pub fn check<R: Runtime>(app: &AppHandle<R>, overlay: &OverlayWindow, config: &WhisprConfig, detected: &str, audio: Vec<f32>, text: &str) -> bool {
+    let Some(usual) = TRACKER.lock().unwrap().observe(detected, config.whisper.language_switch_window) else {
+        return false;
+    };
+    info!("Detected {} after {} dictations in {}, offering a redo", detected, config.whisper.language_switch_window, usual);
+    *PENDING.lock().unwrap() = Some(PendingRedo { audio, text: text.to_string(), language: usual.clone() });
+
+    overlay.show();
+    overlay.set_interactive(true);
+    let _ = app.emit("language-chip", LanguageChip { detected: language_name(detected), usual: language_name(&usual) });
+
+    let generation = CHIP_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(CHIP_DURATION);
+        if CHIP_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let Some(state) = app.try_state::<crate::AppState>() else {
+            return;
+        };
+        // A redo or the next dictation took over the overlay in the meantime
+        if PENDING.lock().unwrap().take().is_none() || state.recording_start.lock().unwrap().is_some() {
+            return;
+        }
+        let overlay = state.overlay.lock().unwrap();
+        overlay.set_interactive(false);
+        overlay.hide();
+    });
+    true
+}
+
+/// Forgets the offered redo, e.g. when a new dictation starts.
+pub fn dismiss() {
+    PENDING.lock().unwrap().take();
+}
+
+fn redo<R: Runtime>(app: &AppHandle<R>, pending: PendingRedo) -> Result<String> {
+    let state = app.try_state::<crate::AppState>().context("App is not ready")?;
+    let whisper = state.decoder().context("Whisper model is still loading")?;
+    let config = ConfigManager::<WhisprConfig>::new("settings")?.load_config("settings")?;
+    let mut settings = config.whisper.clone();
+    settings.language = Some(pending.language.clone());
+
+    let segments = whisper
+        .process_audio(&pending.audio, &settings)
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let text = segments.iter().map(|(_, _, text)| text.as_str()).collect::<Vec<_>>().join(" ");
+    if text.trim().is_empty() {
+        anyhow::bail!("Nothing heard in {}", language_name(&pending.language));
+    }
+
+    // Replace what was typed, other outputs simply get the corrected text too
+    if config.output.sinks.contains(&SinkKind::Keyboard) && state.dictation_file.lock().unwrap().is_none() {
+        output::erase_text(pending.text.graphemes(true).count())?;
+    }
+    output::deliver(&text, &config, None)?;
+    TRACKER.lock().unwrap().correct(&pending.language);
+    *state.last_transcription.lock().unwrap() = Some(text.trim_end().to_string());
+    Ok(text)
+}
+
+/// Decodes the pending dictation again in the usual language and replaces
+/// the delivered text, from a tap on the chip.
+#[tauri::command]
+pub fn redo_in_language(app: AppHandle) {
+    // Tapping the chip activates whispr, hand the keyboard back first
+    crate::focus::yield_focus();
+    let Some(pending) = PENDING.lock().unwrap().take() else {
+        return;
+    };
+    if let Some(state) = app.try_state::<crate::AppState>() {
+        let overlay = state.overlay.lock().unwrap();
+        overlay.set_interactive(false);
+        let _ = app.emit("status-change", "Transcribing");
+    }
+    std::thread::spawn(move || {
+        std::thread::sleep(crate::FOCUS_RETURN_DELAY);
+        let language = language_name(&pending.language);
+        match redo(&app, pending) {
+            Ok(text) => {
+                info!("Redone in {}, {} characters", language, text.chars().count());
+                let _ = app.emit("status-change", "Ready");
+                crate::show_toast(&app, &format!("Redone in {}", language));
+            }
+            Err(e) => {
+                error!("Failed to redo dictation in {}: {}", language, e);
+                let _ = app.emit("status-change", "Ready");
+                crate::show_toast(&app, &e.to_string());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_a_switch_after_a_stable_window() {
+        let mut tracker = LanguageTracker::new();
+        for _ in 0..3 {
+            assert_eq!(tracker.observe("de", 3), None);
+        }
+        assert_eq!(tracker.observe("fr", 3).as_deref(), Some("de"));
+        // Speaking French from now on stops the chip once the window is French
+        assert_eq!(tracker.observe("fr", 3), None);
+    }
+
+    #[test]
+    fn redone_dictations_keep_the_usual_language() {
+        let mut tracker = LanguageTracker::new();
+        for _ in 0..3 {
+            tracker.observe("de", 3);
+        }
+        assert_eq!(tracker.observe("cy", 3).as_deref(), Some("de"));
+        tracker.correct("de");
+        assert_eq!(tracker.observe("cy", 3).as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn stays_quiet_for_mixed_languages() {
+        let mut tracker = LanguageTracker::new();
+        assert_eq!(tracker.observe("de", 3), None);
+        assert_eq!(tracker.observe("en", 3), None);
+        assert_eq!(tracker.observe("de", 3), None);
+        assert_eq!(tracker.observe("fr", 3), None);
+        assert_eq!(tracker.observe("fr", 0), None);
+    }
+}
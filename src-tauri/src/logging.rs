@@ -1,9 +1,54 @@
+use std::collections::VecDeque;
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::sync::Mutex;
 use chrono::Local;
 use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use tokio::sync::broadcast;
+
+// Lines kept for a console opened after they were logged
+const BACKLOG_LINES: usize = 500;
+
+/// A log record as streamed to the developer console.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub timestamp: String,
+    pub level: String,
+    pub location: String,
+    pub message: String,
+}
+
+static LOG_LINES: Lazy<broadcast::Sender<LogLine>> = Lazy::new(|| broadcast::channel(BACKLOG_LINES).0);
+static BACKLOG: Lazy<Mutex<VecDeque<LogLine>>> = Lazy::new(|| Mutex::new(VecDeque::with_capacity(BACKLOG_LINES)));
+
+/// Receives every line logged from now on.
+pub fn subscribe() -> broadcast::Receiver<LogLine> {
+    LOG_LINES.subscribe()
+}
+
+/// The most recently logged lines, oldest first.
+pub fn backlog() -> Vec<LogLine> {
+    BACKLOG.lock().unwrap().iter().cloned().collect()
+}
+
+/// Forgets the lines kept for the console, e.g. after an ephemeral dictation.
+pub fn scrub_backlog() {
+    BACKLOG.lock().unwrap().clear();
+}
+
+fn publish(line: LogLine) {
+    let mut backlog = BACKLOG.lock().unwrap();
+    if backlog.len() == BACKLOG_LINES {
+        backlog.pop_front();
+    }
+    backlog.push_back(line.clone());
+    drop(backlog);
+    // Nobody listening unless the console is open
+    let _ = LOG_LINES.send(line);
+}
 
 struct CombinedLogger {
     file: Mutex<File>,
@@ -18,19 +63,25 @@ impl Log for CombinedLogger {
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
             let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+            // Records from C libraries have no source location, only their target
+            let location = match (record.file(), record.line()) {
+                (Some(file), Some(line)) => format!("{}:{}", file, line),
+                _ => record.target().to_string(),
+            };
             
-            // Write to file
-            let mut file = self.file.lock().unwrap();
-            writeln!(
-                file,
-                "[{} {} {}:{}] {}",
-                timestamp,
-                record.level(),
-                record.file().unwrap_or("unknown"),
-                record.line().unwrap_or(0),
-                record.args()
-            ).unwrap();
-            file.flush().unwrap();
+            // Write to file, an ephemeral session leaves no log lines behind
+            if !crate::ephemeral::is_active() {
+                let mut file = self.file.lock().unwrap();
+                writeln!(
+                    file,
+                    "[{} {} {}] {}",
+                    timestamp,
+                    record.level(),
+                    location,
+                    record.args()
+                ).unwrap();
+                file.flush().unwrap();
+            }
 
             // Write to console with colors
             let mut stdout = StandardStream::stdout(ColorChoice::Always);
@@ -46,14 +97,20 @@ impl Log for CombinedLogger {
             stdout.set_color(ColorSpec::new().set_fg(Some(color))).unwrap();
             writeln!(
                 stdout,
-                "[{} {} {}:{}] {}",
+                "[{} {} {}] {}",
                 console_timestamp,
                 record.level(),
-                record.file().unwrap_or("unknown"),
-                record.line().unwrap_or(0),
+                location,
                 record.args()
             ).unwrap();
             stdout.reset().unwrap();
+
+            publish(LogLine {
+                timestamp: timestamp.to_string(),
+                level: record.level().to_string(),
+                location,
+                message: record.args().to_string(),
+            });
         }
     }
 
@@ -2,87 +2,1266 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod hotkey;
+mod trigger;
+mod controller;
 mod window;
-mod audio;
-mod config;
 mod menu;
-mod whisper;
+mod menu_spec;
+mod language_switch;
 mod logging;
+mod console;
+mod api;
+mod server;
+mod tray;
+mod safemode;
+mod models;
+mod download;
+mod output;
+mod commands;
+mod shortcuts;
+mod history;
+mod playback;
+mod retranscribe;
+mod sync;
+mod analytics;
+mod integrations;
+mod focus;
+mod clipboard;
+mod rolling;
+mod power;
+mod sound;
+mod accessibility;
+mod language_names;
+mod stop_phrase;
+mod review;
+mod spectrogram;
+mod session_dictionary;
+mod benchmark;
+mod text_direction;
+mod draft;
+mod summarize;
+mod jobs;
+mod metrics;
+mod device_watch;
+mod hotkey_watch;
+mod daemon;
 
+use whispr_core::{audio, cjk, config, ephemeral, input_device, keychain, pipeline, quality, resample, storage, whisper};
+use whispr_core::error::ErrorCode;
+use whispr_core::stop_phrase::StopPhrase;
 use log::{error, warn, info, debug};
-use std::sync::{Arc, Mutex};
-use tauri::{Manager, App, Wry, Emitter};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{Manager, App, AppHandle, Runtime, Wry, Emitter};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use tokio::sync::Semaphore;
-use enigo::{Enigo, Keyboard, Settings};
-use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
-use tauri_plugin_shell::ShellExt;
+use enigo::{Enigo, Settings};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 
 use crate::{
     audio::AudioManager,
-    window::OverlayWindow,
+    input_device::DeviceEntry,
+    window::{OverlayWindow, RecordButtonWindow},
     hotkey::HotkeyManager,
-    config::{ConfigManager, WhisprConfig},
+    trigger::{RecordingController, Trigger, TriggerEvent, TriggerSource, Triggers},
+    controller::{Dictation, DictationController, DictationError, DictationState},
+    config::{AudioSource, ConfigManager, OnEmpty, SinkKind, WhisprConfig},
     menu::{create_tray_menu, MenuState},
-    whisper::WhisperProcessor,
+    whisper::{ProgressCallback, SegmentCallback, SharedWhisperProcessor, WhisperProcessor},
+    rolling::RollingBuffer,
 };
 
 const MIN_RECORDING_DURATION: Duration = Duration::from_secs(1);
+const FOCUS_RETURN_DELAY: Duration = Duration::from_millis(200);
+const TOAST_DURATION: Duration = Duration::from_millis(1500);
+// One second of silence, enough to tell whether the whisper context survived sleep
+const WAKE_PROBE_SAMPLES: usize = 16000;
 
 #[derive(thiserror::Error, Debug)]
 pub enum WhisprError {
-    #[error("Audio initialization failed: {0}")]
+    #[error("[{}] Audio initialization failed: {0}", ErrorCode::AudioDevice)]
     AudioError(String),
-    #[error("Configuration error: {0}")]
+    #[error("[{}] Configuration error: {0}", ErrorCode::Config)]
     ConfigError(String),
-    #[error("Hotkey error: {0}")]
+    #[error("[{}] Hotkey error: {0}", ErrorCode::Hotkey)]
     HotkeyError(String),
-    #[error("Whisper model error: {0}")]
+    #[error("[{}] Whisper model error: {0}", ErrorCode::ModelLoad)]
     WhisperError(String),
-    #[error("System error: {0}")]
+    #[error("[{}] System error: {0}", ErrorCode::System)]
     SystemError(String),
 }
 
 type Result<T> = std::result::Result<T, WhisprError>;
 
+thread_local! {
+    // NSEvent monitors are added and removed on the main thread
+    static TRIGGERS: RefCell<Triggers> = RefCell::new(Triggers::default());
+}
+
+/// Recreates the triggers' event sources, e.g. after sleep.
+fn restart_triggers() {
+    TRIGGERS.with(|triggers| triggers.borrow_mut().restart());
+}
+
+/// Stops what exiting would otherwise cut off: the running capture and its
+/// WAV file, the rolling buffer and the triggers, then flushes the log.
+fn shutdown<R: Runtime>(app_handle: &AppHandle<R>) {
+    info!("Shutting down");
+    if let Some(state) = app_handle.try_state::<AppState>() {
+        let mut audio = state.audio.lock().unwrap();
+        // Without a pre-roll stopping closes the microphone instead of
+        // opening it again for the next dictation
+        audio.set_pre_roll(0.0);
+        if audio.is_capturing() {
+            info!("Stopping the active recording");
+            audio.stop_capture();
+            audio.discard_captured_audio();
+        }
+        if let Some(remote) = daemon::remote().filter(|remote| remote.captures()) {
+            if state.recording_start.lock().unwrap().is_some() {
+                let _ = remote.discard_capture();
+            }
+        }
+        drop(audio);
+        state.recording_start.lock().unwrap().take();
+        state.rolling_buffer.lock().unwrap().take();
+        state.overlay.lock().unwrap().hide();
+    }
+    TRIGGERS.with(|triggers| triggers.borrow_mut().detach());
+    log::logger().flush();
+}
+
+/// Pressing and releasing the floating record button behaves like the
+/// dictation key.
+#[tauri::command]
+fn record_button(app_handle: AppHandle, pressed: bool) {
+    // Clicking the button activates whispr, hand the keyboard back to the app being dictated into
+    focus::yield_focus();
+    trigger::send(&app_handle, TriggerSource::RecordButton, pressed);
+}
+
 struct AppState {
-    whisper: WhisperProcessor,
+    whisper: SharedWhisperProcessor,
+    /// The fallback model for drafts, see `whisper.draft`
+    draft_whisper: SharedWhisperProcessor,
     audio: Mutex<AudioManager>,
     overlay: Mutex<OverlayWindow>,
-    recording_semaphore: Arc<Semaphore>,
+    record_button: Mutex<RecordButtonWindow>,
     recording_start: Mutex<Option<Instant>>,
+    transcribing: Mutex<bool>,
+    transcribing_done: Condvar,
+    /// The Mac slept since the whisper context was last checked
+    probe_after_wake: AtomicBool,
+    last_transcription: Mutex<Option<String>>,
+    dictation_file: Mutex<Option<PathBuf>>,
+    rolling_buffer: Mutex<Option<RollingBuffer>>,
 }
 
 impl AppState {
-    fn new(config: WhisprConfig) -> Result<Self> {
+    fn new() -> Result<Self> {
         let audio_manager = AudioManager::new()
-            .map_err(|e| WhisprError::ConfigError(e.to_string()))?;
-        
-        let model_path = dirs::home_dir()
-            .ok_or_else(|| WhisprError::SystemError("Could not find home directory".to_string()))?
-            .join(".whispr")
-            .join("model.bin");
-        let whisper = WhisperProcessor::new(&model_path, config)
-            .map_err(WhisprError::WhisperError)?;
+            .map_err(|e| WhisprError::AudioError(e.to_string()))?;
      
         Ok(Self {
-            whisper,
+            whisper: Arc::new(RwLock::new(None)),
+            draft_whisper: Arc::new(RwLock::new(None)),
             audio: Mutex::new(audio_manager),
             overlay: Mutex::new(OverlayWindow::new()),
-            recording_semaphore: Arc::new(Semaphore::new(1)),
+            record_button: Mutex::new(RecordButtonWindow::new()),
             recording_start: Mutex::new(None),
+            transcribing: Mutex::new(false),
+            transcribing_done: Condvar::new(),
+            probe_after_wake: AtomicBool::new(false),
+            last_transcription: Mutex::new(None),
+            dictation_file: Mutex::new(None),
+            rolling_buffer: Mutex::new(None),
         })
     }
 
     fn configure_audio(&self, config: &WhisprConfig) -> Result<()> {
         let mut audio = self.audio.lock().unwrap();
-        if let Some(device_name) = &config.audio.device_name {
-            audio.set_input_device(device_name)
-                .map_err(|e| WhisprError::AudioError(e.to_string()))?;
+        if config.audio.source == AudioSource::SystemAudio {
+            // Without a loopback device the microphone stands in
+            if let Err(e) = audio.use_loopback_device(config.audio.loopback_device.as_deref()) {
+                warn!("{}, capturing from the microphone instead", e);
+            }
+        } else if let Some(device_id) = config.audio.selected_device() {
+            // An unplugged microphone shouldn't keep whispr from starting
+            match audio.set_input_device(device_id) {
+                Ok(()) => device_watch::clear_fallback(),
+                Err(e) => {
+                    warn!("{}, using the default input device until it is back", e);
+                    device_watch::mark_fallback();
+                }
+            }
+        }
+        audio.configure_silence_removal(
+            config.audio.remove_silence,
+            Some(config.audio.silence_threshold_db),
+            Some(config.audio.silence_window_ms),
+            Some(config.audio.silence_hangover_ms),
+        );
+        audio.configure_agc(config.audio.agc, config.audio.agc_target_db, config.audio.agc_max_gain_db);
+        audio.set_resampler(config.audio.resampler);
+        audio.set_dsp(config.audio.dsp.clone());
+        // whisprd records the dictations, the pre-roll would only keep the microphone open
+        audio.set_pre_roll(if config.daemon.captures() { 0.0 } else { config.audio.pre_roll_secs });
+        Ok(())
+    }
+
+    /// Names of the available input devices, empty if they can't be listed.
+    fn input_devices(&self) -> Vec<DeviceEntry> {
+        self.audio.lock().unwrap().list_input_devices().unwrap_or_else(|e| {
+            error!("Failed to get list of input devices: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Id of the device dictations are captured from.
+    fn input_device_id(&self) -> Option<String> {
+        self.audio.lock().unwrap().get_current_device_id().ok()
+    }
+
+    /// Starts or stops the rolling buffer to match `rolling_buffer.enabled`.
+    /// Stopping drops the buffer, which wipes the captured audio.
+    fn configure_rolling_buffer(&self, config: &WhisprConfig) -> anyhow::Result<()> {
+        let mut rolling_buffer = self.rolling_buffer.lock().unwrap();
+        if !config.rolling_buffer.enabled {
+            rolling_buffer.take();
+            return Ok(());
+        }
+        if rolling_buffer.is_none() {
+            *rolling_buffer = Some(RollingBuffer::start(
+                &self.audio.lock().unwrap(),
+                config.audio.selected_device(),
+                config.rolling_buffer.include_system_audio.then(|| config.audio.loopback_device.as_deref()),
+                config.rolling_buffer.seconds,
+                resample::create(config.audio.resampler),
+            )?);
         }
-        audio.set_remove_silence(config.audio.remove_silence);
         Ok(())
     }
+
+    /// Stops and discards a dictation in progress. The key release that
+    /// follows is ignored since there is no recording start anymore.
+    fn abort_recording<R: Runtime>(&self, app_handle: &AppHandle<R>, overlay: &OverlayWindow, audio: &mut AudioManager) {
+        audio.stop_capture();
+        audio.discard_captured_audio();
+        if let Some(remote) = daemon::remote().filter(|remote| remote.captures()) {
+            if let Err(e) = remote.discard_capture() {
+                warn!("Failed to discard the recording in whisprd: {}", e);
+            }
+        }
+        self.recording_start.lock().unwrap().take();
+        crate::tray::set_recording_indicator(app_handle, false);
+        let _ = app_handle.emit("status-change", "Ready");
+        overlay.hide();
+    }
+
+    /// The dictation work a quit would interrupt, if any.
+    fn active_work(&self) -> Option<&'static str> {
+        if self.recording_start.lock().unwrap().is_some() {
+            Some("recording")
+        } else if self.is_transcribing() {
+            Some("transcription")
+        } else {
+            None
+        }
+    }
+
+    /// Ends a running recording as if the key was released, which transcribes
+    /// and delivers it, and waits for any transcription in flight.
+    ///
+    /// Blocks until then, so call it off the main thread.
+    fn finish_active_work<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        if self.recording_start.lock().unwrap().is_some() {
+            info!("Finishing the active recording before quitting");
+            trigger::send(app_handle, TriggerSource::System, false);
+        }
+        // The release runs on its own thread, so the recording may still be
+        // running when this starts waiting. An aborted recording ends
+        // without a transition, hence the timeout.
+        let mut transcribing = self.transcribing.lock().unwrap();
+        while *transcribing || self.recording_start.lock().unwrap().is_some() {
+            transcribing = self.transcribing_done
+                .wait_timeout(transcribing, Duration::from_millis(500))
+                .unwrap()
+                .0;
+        }
+    }
+
+    pub fn is_transcribing(&self) -> bool {
+        *self.transcribing.lock().unwrap()
+    }
+
+    fn set_transcribing(&self, busy: bool) {
+        *self.transcribing.lock().unwrap() = busy;
+        self.transcribing_done.notify_all();
+    }
+
+    /// Aborts a dictation in progress and stops the rolling buffer, the input
+    /// device may be gone or a different one after wake.
+    fn prepare_for_sleep<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let overlay = self.overlay.lock().unwrap();
+        let mut audio = self.audio.lock().unwrap();
+        // whisprd may be the one recording
+        if audio.is_capturing() || self.recording_start.lock().unwrap().is_some() {
+            info!("Discarding the active recording before sleep");
+            self.abort_recording(app_handle, &overlay, &mut audio);
+        }
+        audio.set_pre_roll(0.0);
+        self.rolling_buffer.lock().unwrap().take();
+    }
+
+    /// The microphone disappeared mid-recording (e.g. a headset was
+    /// unplugged). Continues on the default device or aborts the dictation.
+    fn handle_device_lost<R: Runtime>(&self, app_handle: &AppHandle<R>, config: &WhisprConfig) {
+        let overlay = self.overlay.lock().unwrap();
+        let mut audio = self.audio.lock().unwrap();
+        if !audio.is_capturing() {
+            return;
+        }
+
+        if config.audio.restart_on_device_change {
+            match audio.restart_capture_on_default_device() {
+                Ok(name) => {
+                    warn!("Input device lost, continuing the recording on {}", name);
+                    device_watch::mark_fallback();
+                    drop(audio);
+                    drop(overlay);
+                    show_toast(app_handle, &format!("Switched to {}", name));
+                    return;
+                }
+                Err(e) => error!("Failed to continue recording on the default device: {}", e),
+            }
+        }
+
+        warn!("Input device lost, aborting the recording");
+        self.abort_recording(app_handle, &overlay, &mut audio);
+        drop(audio);
+        drop(overlay);
+        show_toast(app_handle, "Microphone disconnected");
+    }
+
+    /// Re-validates the input device, restarts the rolling buffer and has the
+    /// next dictation check that the whisper context still works.
+    fn recover_after_wake<R: Runtime>(&self, app_handle: &AppHandle<R>, config: WhisprConfig) {
+        if config.audio.source == AudioSource::SystemAudio {
+            if let Err(e) = self.audio.lock().unwrap().use_loopback_device(config.audio.loopback_device.as_deref()) {
+                warn!("{} after wake, capturing from the microphone instead", e);
+            }
+        } else if let Some(device_id) = config.audio.selected_device() {
+            let mut audio = self.audio.lock().unwrap();
+            if let Err(e) = audio.set_input_device(device_id) {
+                warn!("{} after wake, falling back to the default input device", e);
+                match audio.use_default_input_device() {
+                    Ok(_) => device_watch::mark_fallback(),
+                    Err(e) => error!("Failed to reinitialize audio after wake: {}", e),
+                }
+            }
+        }
+        self.audio.lock().unwrap().set_pre_roll(if config.daemon.captures() { 0.0 } else { config.audio.pre_roll_secs });
+
+        if let Err(e) = self.configure_rolling_buffer(&config) {
+            error!("Failed to restart rolling buffer after wake: {}", e);
+        }
+
+        // Probed at the next dictation rather than now, the Mac may wake
+        // many times before anyone dictates
+        self.probe_after_wake.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks that the whisper context survived sleep with a short decode,
+    /// once after each wake, and reloads the model if not. Runs in the
+    /// background while the dictation that calls it records.
+    fn probe_whisper_after_wake<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        if !self.probe_after_wake.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let Some(whisper) = self.whisper.read().unwrap().clone() else {
+            return;
+        };
+        let config = ConfigManager::<WhisprConfig>::new("settings")
+            .and_then(|manager| manager.load_config("settings"))
+            .unwrap_or_default();
+        let app_handle = app_handle.clone();
+        std::thread::spawn(move || {
+            match whisper.process_audio(&vec![0.0; WAKE_PROBE_SAMPLES], &config.whisper) {
+                Ok(_) => debug!("Whisper context is healthy after wake"),
+                Err(e) => {
+                    warn!("Whisper failed after wake ({}), reloading the model", e);
+                    if let Some(state) = app_handle.try_state::<AppState>() {
+                        state.load_model(&app_handle, whisper.model_path().to_path_buf(), config);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Loads `model_path` in the background and swaps it in once ready, so a
+    /// model change doesn't require restarting the app. With `daemon.enabled`
+    /// whisprd loads it instead.
+    fn load_model<R: Runtime>(&self, app_handle: &AppHandle<R>, model_path: PathBuf, config: WhisprConfig) -> JoinHandle<()> {
+        if config.daemon.enabled {
+            return daemon::load_model(app_handle, self.whisper.clone(), model_path, config);
+        }
+        daemon::disconnect();
+        load_model_into(self.whisper.clone(), app_handle, model_path, config)
+    }
+
+    /// whisprd's model if it holds one for the app, otherwise the app's own.
+    fn decoder(&self) -> Option<daemon::Decoder> {
+        daemon::remote().map(daemon::Decoder::Daemon)
+            .or_else(|| self.whisper.read().unwrap().clone().map(daemon::Decoder::Local))
+    }
+
+    /// Loads the model at launch as configured in `startup`: verifies its
+    /// hash first (always if the model has a published checksum) and confirms
+    /// with "Ready" in the overlay once loaded. Fetches a missing Core ML
+    /// encoder afterwards and reloads with it.
+    fn load_startup_model<R: Runtime>(&self, app_handle: &AppHandle<R>, model_path: PathBuf, config: WhisprConfig) {
+        // whisprd may still have it loaded from before
+        if config.daemon.enabled {
+            self.load_model(app_handle, model_path, config);
+            return;
+        }
+        let whisper = self.whisper.clone();
+        let app_handle = app_handle.clone();
+        std::thread::spawn(move || {
+            let model = models::model_for_path(&config, &model_path).clone();
+            let expected = model.published_sha256();
+            // Catalog models, the quantized ones included, are held to the hash Hugging Face published at download
+            let always = expected.is_some() || config::CatalogModel::for_model(&model).is_some();
+            if config.startup.verify_model || always {
+                menu::set_model_status(&app_handle, "Verifying model…");
+                match models::verify_model(&model_path, expected.as_deref()) {
+                    Ok(true) => info!("Model {} verified", model_path.display()),
+                    Ok(false) => {
+                        let message = format!("Model {} does not match its checksum", model_path.display());
+                        error!("[{}] {}", ErrorCode::ModelCorrupt, message);
+                        api::emit_error(&app_handle, ErrorCode::ModelCorrupt, &message);
+                        menu::set_model_status(&app_handle, "Model file is damaged");
+                        let redownload_handle = app_handle.clone();
+                        app_handle.dialog()
+                            .message(format!(
+                                "The model file {} doesn't match its checksum and is probably damaged, \
+                                so whispr didn't load it. Download {} again?",
+                                model_path.display(),
+                                model.display_name
+                            ))
+                            .kind(MessageDialogKind::Error)
+                            .title("Model Damaged")
+                            .buttons(MessageDialogButtons::OkCancelCustom("Download Again".to_string(), "Cancel".to_string()))
+                            .show(move |confirmed| {
+                                if confirmed {
+                                    redownload_model(redownload_handle, model, model_path, config);
+                                }
+                            });
+                        return;
+                    }
+                    // Can't tell, better to dictate with it than not at all
+                    Err(e) => warn!("Failed to verify model {}: {}", model_path.display(), e),
+                }
+            }
+            let _ = load_model_into(whisper.clone(), &app_handle, model_path.clone(), config.clone()).join();
+            let loaded = whisper.read().unwrap().is_some();
+            if config.startup.show_ready && loaded {
+                show_toast(&app_handle, "Ready");
+            }
+            if !loaded {
+                match models::lighter_model_for(&model) {
+                    Some(lighter) => offer_lighter_model(&app_handle, &model, lighter),
+                    // Enough memory, so most likely a half-downloaded or damaged file
+                    None => offer_redownload(&app_handle, model, model_path, config),
+                }
+                return;
+            }
+            // Models downloaded before Core ML support or copied in by hand
+            if models::wants_coreml_encoder(&model, &config) && !whisper::coreml_encoder_path(&model_path).exists() {
+                let result = models::download_coreml_encoder(&model, &model_path, &config, |status| {
+                    menu::set_model_status(&app_handle, &status.to_string());
+                });
+                match result {
+                    Ok(()) => {
+                        info!("Core ML encoder downloaded, reloading {}", model_path.display());
+                        load_model_into(whisper, &app_handle, model_path, config);
+                    }
+                    Err(e) => {
+                        warn!("Failed to download Core ML encoder for {}: {}", model.display_name, e);
+                        menu::set_model_status(&app_handle, &whisper::ModelStatus::Ready.to_string());
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn load_model_into<R: Runtime>(slot: SharedWhisperProcessor, app_handle: &AppHandle<R>, model_path: PathBuf, config: WhisprConfig) -> JoinHandle<()> {
+    let app_handle = app_handle.clone();
+    WhisperProcessor::load_in_background(slot, model_path, config, move |status| {
+        debug!("{}", status);
+        menu::set_model_status(&app_handle, &status.to_string());
+    })
+}
+
+/// Forwards whisper's decoding progress to the overlay.
+fn progress_emitter<R: Runtime>(app_handle: &AppHandle<R>) -> Option<ProgressCallback> {
+    let app_handle = app_handle.clone();
+    Some(Box::new(move |progress| {
+        let _ = app_handle.emit("transcription-progress", progress);
+    }))
+}
+
+/// Emits the text decoded so far as a partial text API event and shows it
+/// as a live OBS caption if enabled.
+fn partial_text_emitter<R: Runtime>(app_handle: &AppHandle<R>, config: &WhisprConfig) -> Option<SegmentCallback> {
+    let app_handle = app_handle.clone();
+    let obs_settings = config.integrations.obs.clone();
+    let live_captions = config.output.sinks.contains(&SinkKind::Obs) && obs_settings.live;
+    let mut text = String::new();
+    Some(Box::new(move |segment| {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(segment);
+        api::emit_partial_text(&app_handle, &text);
+        if live_captions {
+            if let Err(e) = integrations::obs::write_caption(&obs_settings, &text) {
+                debug!("Failed to update live OBS caption: {}", e);
+            }
+        }
+    }))
+}
+
+/// Removes a typed draft the main model's transcription doesn't replace.
+fn erase_draft(draft: Option<draft::TypedDraft>) {
+    if let Some(draft) = draft {
+        if let Err(e) = draft.erase() {
+            error!("Failed to erase draft: {}", e);
+        }
+    }
+}
+
+/// Lets the user know a dictation produced no text, as configured in
+/// `output.on_empty`. Hides the overlay unless it is showing the toast.
+fn notify_empty<R: Runtime>(app_handle: &AppHandle<R>, on_empty: OnEmpty, overlay: &OverlayWindow) {
+    match on_empty {
+        OnEmpty::Ignore => overlay.hide(),
+        OnEmpty::Sound => {
+            overlay.hide();
+            sound::play_system_sound("Basso");
+        }
+        OnEmpty::Toast | OnEmpty::Retry => show_toast_with(app_handle, overlay, "Nothing heard"),
+    }
+}
+
+/// Briefly shows `message` in the overlay, then hides it again or goes back
+/// to the waveform if a dictation is running.
+fn show_toast<R: Runtime>(app_handle: &AppHandle<R>, message: &str) {
+    if let Some(state) = app_handle.try_state::<AppState>() {
+        show_toast_with(app_handle, &state.overlay.lock().unwrap(), message);
+    }
+}
+
+/// `show_toast` for callers that already hold the overlay lock.
+fn show_toast_with<R: Runtime>(app_handle: &AppHandle<R>, overlay: &OverlayWindow, message: &str) {
+    overlay.show();
+    let _ = app_handle.emit("toast", text_direction::DirectedText::new(message));
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(TOAST_DURATION);
+        if let Some(state) = app_handle.try_state::<AppState>() {
+            if state.recording_start.lock().unwrap().is_some() {
+                let _ = app_handle.emit("status-change", "Listening");
+            } else {
+                state.overlay.lock().unwrap().hide();
+            }
+        }
+    });
+}
+
+/// Shows a low confidence transcription in the review window, which only
+/// delivers it once the user confirms.
+fn confirm_low_confidence<R: Runtime>(app_handle: &AppHandle<R>, transcript: &mut Transcript, dictation_file: Option<PathBuf>) {
+    let output = &transcript.config.output;
+    review::open(app_handle, review::PendingReview {
+        text: transcript.text.clone(),
+        words: std::mem::take(&mut transcript.words),
+        confidence: transcript.confidence,
+        // Without `review_window` only the words below the threshold are marked
+        threshold: (!output.review_window).then_some(output.min_confidence),
+        direction: text_direction::resolve(transcript.config.ui.text_direction, transcript.language.as_deref(), &transcript.text),
+        config: transcript.config.clone(),
+        dictation_file,
+    });
+}
+
+/// A transcribed dictation on its way to the target app.
+struct Transcript {
+    text: String,
+    confidence: f32,
+    /// Per word confidence for the review window
+    words: Vec<whisper::WordConfidence>,
+    config: WhisprConfig,
+    quick_send: bool,
+    is_command: bool,
+    target_app: Option<focus::FrontmostApp>,
+    language: Option<String>,
+    /// Kept for redoing the dictation in another language
+    audio: Option<Vec<f32>>,
+    /// Percentage of clipped samples, if above `audio.clip_warning_percent`
+    clipping: Option<f32>,
+    /// The fallback model's draft, already typed
+    draft: Option<draft::TypedDraft>,
+}
+
+impl Drop for Transcript {
+    fn drop(&mut self) {
+        ephemeral::scrub(&mut self.text);
+        for word in &mut self.words {
+            ephemeral::scrub(&mut word.word);
+        }
+    }
+}
+
+/// A dictation's audio run through whisper, before post-processing.
+struct DecodedDictation {
+    result: std::result::Result<whisper::Decoding, String>,
+    recording: Option<PathBuf>,
+    /// Percentage of clipped samples, if above `audio.clip_warning_percent`
+    clipping: Option<f32>,
+    /// Kept for redoing the dictation in another language
+    audio: Option<Vec<f32>>,
+    draft: Option<draft::TypedDraft>,
+}
+
+/// Does the work of each dictation step for the controller: audio capture,
+/// whisper, history, delivery, and the overlay, tray and status events.
+struct AppDictation {
+    app_handle: AppHandle,
+    released: Instant,
+    // A toast, the language chip or a dialog took over the overlay
+    keep_overlay: bool,
+    /// whisprd records the running dictation, see `daemon.capture`
+    remote_capture: Option<Arc<daemon::Remote>>,
+    /// What whisprd recorded once stopped
+    captured: Option<daemon::Captured>,
+}
+
+impl AppDictation {
+    fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle, released: Instant::now(), keep_overlay: false, remote_capture: None, captured: None }
+    }
+
+    /// Clipping worth a warning, logged.
+    fn clipping(clip_percent: f32, config: &WhisprConfig) -> Option<f32> {
+        let clipping = Some(clip_percent).filter(|percent| *percent > config.audio.clip_warning_percent);
+        if let Some(percent) = clipping {
+            warn!("Input was clipping ({:.2}% of samples)", percent);
+        }
+        clipping
+    }
+
+    /// Keyboard noise or an accidental press, not worth a whisper run that
+    /// may hallucinate. Tells the user if so.
+    fn too_quiet(&mut self, level: Option<f32>, config: &WhisprConfig) -> bool {
+        let Some(level) = level.filter(|level| *level < config.audio.min_rms_db) else {
+            return false;
+        };
+        info!("Recording too quiet ({:.1} dBFS < {:.1} dBFS), skipping transcription", level, config.audio.min_rms_db);
+        if let Some(state) = self.app_handle.try_state::<AppState>() {
+            show_toast_with(&self.app_handle, &state.overlay.lock().unwrap(), "Too quiet — skipped");
+        }
+        self.keep_overlay = true;
+        true
+    }
+
+    /// Decodes the app's own recording, typing the fallback model's draft
+    /// first if there is one. `None` if it was too quiet.
+    fn decode_local(
+        &mut self,
+        state: &AppState,
+        whisper: &daemon::Decoder,
+        whisper_settings: &config::WhisperSettings,
+        config: &WhisprConfig,
+        draft_whisper: Option<&WhisperProcessor>,
+        can_type_draft: bool,
+    ) -> Option<DecodedDictation> {
+        let mut audio = state.audio.lock().unwrap();
+        let clipping = Self::clipping(audio.capture_stats().clip_percent(), config);
+        let level = audio.captured_level_db();
+        if level.is_some_and(|level| level < config.audio.min_rms_db) {
+            audio.discard_captured_audio();
+            drop(audio);
+            self.too_quiet(level, config);
+            return None;
+        }
+        let captured_audio = audio.get_captured_audio(16000, 1);
+        let mut recording = audio.recording_path();
+        // Decoding may take a while, captures and API jobs shouldn't wait for it
+        drop(audio);
+        let app_handle = &self.app_handle;
+
+        // Kept for redoing the dictation in another language, never in an ephemeral session
+        let retain_audio = whisper_settings.detects_language()
+            && config.whisper.language_switch_window > 0
+            && !ephemeral::is_active();
+        let mut retained_audio = None;
+        let mut typed_draft = None;
+        let result = match captured_audio {
+            Ok(Some(mut captured_audio)) => {
+                debug!("Got captured audio: {} samples", captured_audio.len());
+                if retain_audio {
+                    retained_audio = Some(captured_audio.clone());
+                }
+                if let Some(draft_whisper) = draft_whisper {
+                    typed_draft = draft::deliver(app_handle, draft_whisper, &captured_audio, whisper_settings, config, can_type_draft);
+                }
+                let decode_started = Instant::now();
+                let result = whisper.process_audio_with_confidence(&captured_audio, whisper_settings, progress_emitter(app_handle), partial_text_emitter(app_handle, config));
+                if result.is_ok() {
+                    metrics::record_decode(metrics::Source::Dictation, captured_audio.len() as f32 / 16000.0, decode_started.elapsed());
+                }
+                ephemeral::scrub(&mut captured_audio);
+                result
+            }
+            Ok(None) => {
+                info!("No audio captured");
+                Ok(whisper::Decoding::default())
+            }
+            Err(e) => Err(format!("Failed to convert captured audio: {}", e)),
+        };
+
+        // Silence removal can swallow quiet speech entirely, give it one more pass without
+        let result = match result {
+            Ok(decoding) if decoding.segments.is_empty() && config.output.on_empty == OnEmpty::Retry => {
+                let unfiltered_audio = state.audio.lock().unwrap().get_unfiltered_audio(16000, 1);
+                match unfiltered_audio {
+                    Ok(Some(mut unfiltered_audio)) => {
+                        info!("Empty transcription, retrying without silence removal");
+                        // The saved recording has the silence removed, timestamps no longer match it
+                        recording = None;
+                        if retain_audio {
+                            retained_audio = Some(unfiltered_audio.clone());
+                        }
+                        let decode_started = Instant::now();
+                        let result = whisper.process_audio_with_confidence(&unfiltered_audio, whisper_settings, progress_emitter(app_handle), partial_text_emitter(app_handle, config));
+                        if result.is_ok() {
+                            metrics::record_decode(metrics::Source::Dictation, unfiltered_audio.len() as f32 / 16000.0, decode_started.elapsed());
+                        }
+                        ephemeral::scrub(&mut unfiltered_audio);
+                        result
+                    }
+                    Ok(None) => Ok(decoding),
+                    Err(e) => Err(format!("Failed to convert captured audio: {}", e)),
+                }
+            }
+            result => result,
+        };
+        Some(DecodedDictation { result, recording, clipping, audio: retained_audio, draft: typed_draft })
+    }
+
+    /// Has whisprd decode what it recorded. The audio stays there, so there
+    /// is no draft and no redo in another language. `None` if it was too
+    /// quiet.
+    fn decode_in_daemon(&mut self, remote: &daemon::Remote, whisper_settings: &config::WhisperSettings, config: &WhisprConfig) -> Option<DecodedDictation> {
+        let captured = match self.captured.take() {
+            Some(captured) => captured,
+            None => {
+                let result = Err("whisprd didn't stop the recording".to_string());
+                return Some(DecodedDictation { result, recording: None, clipping: None, audio: None, draft: None });
+            }
+        };
+        let clipping = Self::clipping(captured.clip_percent, config);
+        if self.too_quiet(captured.level_db, config) {
+            if let Err(e) = remote.discard_capture() {
+                warn!("Failed to discard the recording in whisprd: {:#}", e);
+            }
+            return None;
+        }
+        let mut recording = captured.recording;
+        let app_handle = &self.app_handle;
+        let decode = |unfiltered| {
+            let decode_started = Instant::now();
+            let result = remote.decode_captured(whisper_settings, unfiltered, progress_emitter(app_handle), partial_text_emitter(app_handle, config));
+            if let Ok(decoded) = &result {
+                metrics::record_decode(metrics::Source::Dictation, decoded.audio_secs, decode_started.elapsed());
+            }
+            result.map(|decoded| decoded.decoding)
+        };
+        let result = match decode(false) {
+            // Silence removal can swallow quiet speech entirely, give it one more pass without
+            Ok(decoding) if decoding.segments.is_empty() && config.output.on_empty == OnEmpty::Retry => {
+                info!("Empty transcription, retrying without silence removal");
+                recording = None;
+                decode(true)
+            }
+            result => result,
+        };
+        Some(DecodedDictation { result, recording, clipping, audio: None, draft: None })
+    }
+}
+
+impl Dictation for AppDictation {
+    type Transcript = Transcript;
+
+    fn start_capture(&mut self) -> std::result::Result<(), DictationError> {
+        let state = self.app_handle.try_state::<AppState>()
+            .ok_or_else(|| DictationError::new(ErrorCode::AudioDevice, "App is not ready"))?;
+        language_switch::dismiss();
+        state.overlay.lock().unwrap().show();
+        self.captured = None;
+        self.remote_capture = daemon::remote().filter(|remote| remote.captures());
+        if let Some(remote) = &self.remote_capture {
+            let config = ConfigManager::<WhisprConfig>::new("settings")
+                .and_then(|manager| manager.load_config("settings"))
+                .unwrap_or_default();
+            return remote.start_capture(&config.audio)
+                .map_err(|e| DictationError::new(ErrorCode::AudioDevice, e));
+        }
+        state.audio.lock().unwrap().start_capture()
+            .map_err(|e| DictationError::new(ErrorCode::AudioDevice, e.to_string()))?;
+        state.probe_whisper_after_wake(&self.app_handle);
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) -> bool {
+        let Some(state) = self.app_handle.try_state::<AppState>() else {
+            return false;
+        };
+        self.released = Instant::now();
+        // Busy before the recording start is cleared, so quitting never sees
+        // neither; the next transition or the abort below settles it
+        state.set_transcribing(true);
+        match &self.remote_capture {
+            Some(remote) => match remote.stop_capture() {
+                Ok(captured) => self.captured = Some(captured),
+                Err(e) => error!("Failed to stop the recording in whisprd: {}", e),
+            },
+            None => state.audio.lock().unwrap().stop_capture(),
+        }
+        crate::tray::set_recording_indicator(&self.app_handle, false);
+        // There is no recording start if it was aborted (e.g. by sleep)
+        let started = state.recording_start.lock().unwrap().take();
+        if started.is_none() {
+            state.set_transcribing(false);
+            if let Some(remote) = self.remote_capture.take() {
+                let _ = remote.discard_capture();
+            }
+        }
+        started.is_some()
+    }
+
+    fn transcribe(&mut self, event: &TriggerEvent) -> std::result::Result<Option<Transcript>, DictationError> {
+        // Decoding needs `self` mutably
+        let app_handle = &self.app_handle.clone();
+        let state = app_handle.try_state::<AppState>()
+            .ok_or_else(|| DictationError::new(ErrorCode::ModelNotReady, "App is not ready"))?;
+        let Some(whisper) = state.decoder() else {
+            return Err(DictationError::new(ErrorCode::ModelNotReady, "Whisper model is still loading, recording discarded"));
+        };
+
+        // Settings can change from the tray at any time, pick them up per dictation
+        let mut current_config = ConfigManager::<WhisprConfig>::new("settings")
+            .and_then(|manager| manager.load_config("settings"))
+            .unwrap_or_else(|e| {
+                error!("Failed to load configuration, using defaults: {}", e);
+                WhisprConfig::default()
+            });
+        api::configure(&current_config);
+
+        // Holding Shift while releasing the key translates just this dictation
+        let mut whisper_settings = current_config.whisper.clone();
+        session_dictionary::extend(&mut whisper_settings);
+        if event.shift_held() && !whisper_settings.translate {
+            info!("Shift held on release, translating this dictation to English");
+            whisper_settings.translate = true;
+        }
+        let on_empty = current_config.output.on_empty;
+
+        // Holding Control quick-sends just this dictation to the chat channel instead
+        // An empty webhook_url, e.g. cleared in the settings, counts as unset
+        let quick_send = event.control_held()
+            && current_config.integrations.chat.webhook_url.as_deref().is_some_and(|url| !url.trim().is_empty());
+        if quick_send {
+            info!("Control held on release, sending this dictation to {:?}", current_config.integrations.chat.service);
+            current_config.output.sinks = vec![SinkKind::Chat];
+        }
+
+        // A draft can only be corrected by typing over it
+        let draft_whisper = whisper.local().and_then(|whisper| draft::processor(app_handle, &current_config, whisper));
+        let can_type_draft = !quick_send
+            && current_config.output.sinks.contains(&SinkKind::Keyboard)
+            && state.dictation_file.lock().unwrap().is_none();
+
+        let decoding = match self.remote_capture.take() {
+            Some(remote) => self.decode_in_daemon(&remote, &whisper_settings, &current_config),
+            None => self.decode_local(&state, &whisper, &whisper_settings, &current_config, draft_whisper.as_deref(), can_type_draft),
+        };
+        let Some(DecodedDictation { result, recording, clipping, audio: retained_audio, draft: typed_draft }) = decoding else {
+            return Ok(None);
+        };
+
+        let whisper::Decoding { mut segments, confidence, language, words } = match result {
+            Ok(result) => result,
+            Err(e) => {
+                erase_draft(typed_draft);
+                return Err(DictationError::new(ErrorCode::Transcription, e));
+            }
+        };
+        let repetitions_trimmed = quality::trim_segments(&mut segments);
+        if stop_phrase::take_heard() {
+            if let Some(phrase) = current_config.stop_phrase.as_deref().and_then(StopPhrase::new) {
+                phrase.strip_segments(&mut segments);
+            }
+        }
+        if segments.is_empty() {
+            info!("No transcription segments produced");
+            erase_draft(typed_draft);
+            notify_empty(app_handle, on_empty, &state.overlay.lock().unwrap());
+            self.keep_overlay = true;
+            return Ok(None);
+        }
+
+        let transcription = pipeline::join_segments(&segments, current_config.output.paragraph_pause_secs);
+        let transcription = cjk::format(&transcription, language.as_deref(), &current_config.output.cjk);
+        info!("Transcribed {} characters", transcription.chars().count());
+        api::emit_final_text(app_handle, transcription.trim_end());
+        // Nothing outlives an ephemeral dictation, not even for Paste Last Transcription
+        *state.last_transcription.lock().unwrap() = (!ephemeral::is_active()).then(|| transcription.trim_end().to_string());
+        // whispr never takes focus, so this is the app the text is about to go to
+        let target_app = focus::frontmost_app();
+        // Voice commands aren't dictations, and "tag that" refers to the one before
+        let is_command = current_config.command_mode
+            || commands::CommandRegistry::from_config(&current_config).matches(&transcription);
+        if !is_command {
+            let mut entry = history::HistoryEntry::new(&transcription, &segments, confidence, recording.as_deref());
+            if let Some(app) = &target_app {
+                entry = entry.with_app(&app.name, &app.bundle_id);
+            }
+            entry.low_confidence = repetitions_trimmed;
+            if let Err(e) = history::record(&current_config, entry) {
+                error!("Failed to save transcription to history: {}", e);
+            }
+            if recording.is_some() {
+                menu::refresh_storage_usage(app_handle);
+            }
+            session_dictionary::record_uses(&transcription, &current_config);
+        }
+        for (_, _, segment) in &mut segments {
+            ephemeral::scrub(segment);
+        }
+
+        Ok(Some(Transcript {
+            text: transcription,
+            confidence,
+            words,
+            config: current_config,
+            quick_send,
+            is_command,
+            target_app,
+            language,
+            audio: retained_audio.filter(|_| !is_command),
+            clipping,
+            draft: typed_draft,
+        }))
+    }
+
+    fn inject(&mut self, mut transcript: Transcript) -> std::result::Result<(), DictationError> {
+        let app_handle = &self.app_handle;
+        let state = app_handle.try_state::<AppState>()
+            .ok_or_else(|| DictationError::new(ErrorCode::Output, "App is not ready"))?;
+        // "Dictate to file" bypasses injection until it is turned off again
+        let dictation_file = if transcript.quick_send { None } else { state.dictation_file.lock().unwrap().clone() };
+
+        // Don't type possibly garbled text straight into the user's document
+        let min_confidence = transcript.config.output.min_confidence;
+        if transcript.confidence < min_confidence {
+            warn!("Low transcription confidence ({:.2} < {:.2}), asking for confirmation", transcript.confidence, min_confidence);
+            erase_draft(transcript.draft.take());
+            confirm_low_confidence(app_handle, &mut transcript, dictation_file);
+            return Ok(());
+        }
+
+        if !transcript.is_command {
+            let direction = text_direction::resolve(transcript.config.ui.text_direction, transcript.language.as_deref(), &transcript.text);
+            transcript.text = text_direction::mark_for_injection(&transcript.text, direction).into_owned();
+        }
+
+        // Typing without the permission silently goes nowhere
+        let types = dictation_file.is_none() && transcript.config.output.sinks.contains(&SinkKind::Keyboard);
+        if types && !transcript.is_command && !focus::is_trusted() {
+            return Err(DictationError::new(ErrorCode::PermissionAx, "whispr needs the Accessibility permission to type"));
+        }
+        let confirmation = match transcript.draft.take() {
+            // Correct the typed draft, the other outputs get the text as usual
+            Some(draft) if !transcript.is_command => {
+                draft.replace_with(&transcript.text, &transcript.config.output.injection)
+                    .map_err(|e| DictationError::new(ErrorCode::Output, e.to_string()))?;
+                let mut config = transcript.config.clone();
+                config.output.sinks.retain(|sink| *sink != SinkKind::Keyboard);
+                if config.output.sinks.is_empty() {
+                    None
+                } else {
+                    output::deliver(&transcript.text, &config, None)
+                        .map_err(|e| DictationError::new(ErrorCode::Output, e.to_string()))?
+                }
+            }
+            draft => {
+                erase_draft(draft);
+                let confirmation = output::deliver(&transcript.text, &transcript.config, dictation_file.as_deref())
+                    .map_err(|e| DictationError::new(ErrorCode::Output, e.to_string()))?;
+                offer_paste_app(app_handle);
+                confirmation
+            }
+        };
+        if !transcript.is_command {
+            let latency_ms = self.released.elapsed().as_millis() as u64;
+            let stats = analytics::DictationStats::new(&transcript.text, transcript.target_app.as_ref(), latency_ms, transcript.language.clone());
+            analytics::record(&transcript.config, stats);
+        }
+
+        let overlay = state.overlay.lock().unwrap();
+        // A voice command ran or the text went elsewhere, tell the user
+        if let Some(confirmation) = confirmation {
+            show_toast_with(app_handle, &overlay, &confirmation);
+            self.keep_overlay = true;
+            return Ok(());
+        }
+
+        // Auto-detection may have picked the wrong language, offer a redo
+        if let (Some(audio), Some(detected)) = (transcript.audio.take(), transcript.language.as_deref()) {
+            if language_switch::check(app_handle, &overlay, &transcript.config, detected, audio, &transcript.text) {
+                self.keep_overlay = true;
+                return Ok(());
+            }
+        }
+
+        // Clipping measurably hurts accuracy and is easily fixed in System Settings
+        if transcript.clipping.is_some() {
+            show_toast_with(app_handle, &overlay, "Input was clipping — consider lowering mic gain");
+            self.keep_overlay = true;
+        }
+        Ok(())
+    }
+
+    fn state_changed(&mut self, dictation_state: &DictationState) {
+        let app_handle = &self.app_handle;
+        let Some(state) = app_handle.try_state::<AppState>() else {
+            return;
+        };
+        let busy = matches!(dictation_state, DictationState::Transcribing | DictationState::Injecting);
+        state.set_transcribing(busy);
+        match dictation_state {
+            DictationState::Recording { started } => {
+                self.keep_overlay = false;
+                *state.recording_start.lock().unwrap() = Some(*started);
+                crate::tray::set_recording_indicator(app_handle, true);
+                let _ = app_handle.emit("status-change", "Listening");
+            }
+            DictationState::Transcribing => {
+                let _ = app_handle.emit("status-change", "Transcribing");
+            }
+            DictationState::Injecting => {}
+            DictationState::Idle | DictationState::Error(_) => {
+                if ephemeral::is_active() {
+                    state.audio.lock().unwrap().scrub_buffers();
+                    logging::scrub_backlog();
+                }
+                if let DictationState::Error(error) = dictation_state {
+                    api::emit_error(app_handle, error.code, &error.message);
+                    self.keep_overlay = false;
+                }
+                let _ = app_handle.emit("status-change", "Ready");
+                if !self.keep_overlay {
+                    state.overlay.lock().unwrap().hide();
+                }
+            }
+        }
+    }
+}
+
+/// Replaces a damaged model with a fresh download and loads it.
+fn redownload_model<R: Runtime>(app_handle: AppHandle<R>, model: config::Model, model_path: PathBuf, config: WhisprConfig) {
+    std::thread::spawn(move || {
+        if let Err(e) = models::remove_model(&model_path) {
+            error!("Failed to remove damaged model {}: {}", model_path.display(), e);
+        }
+        let result = models::download_model(&model, &model_path, &config, |status| {
+            menu::set_model_status(&app_handle, &format!("{} · {}", model.display_name, status));
+        });
+        match result {
+            Ok(()) => {
+                if let Some(state) = app_handle.try_state::<AppState>() {
+                    let _ = state.load_model(&app_handle, model_path.clone(), config).join();
+                    // Retried once, a fresh download that still fails won't get better
+                    let loaded = state.decoder().is_some_and(|whisper| whisper.model_path() == model_path);
+                    if !loaded {
+                        let message = format!("{} failed to load even after downloading it again", model.display_name);
+                        error!("[{}] {}", ErrorCode::ModelLoad, message);
+                        api::emit_error(&app_handle, ErrorCode::ModelLoad, &message);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("[{}] Failed to download {} again: {}", ErrorCode::Download, model.display_name, e);
+                api::emit_error(&app_handle, ErrorCode::Download, &e.to_string());
+                menu::set_model_status(&app_handle, &format!("{} download failed", model.display_name));
+            }
+        }
+    });
+}
+
+/// After a model that should fit into memory failed to load, offers to
+/// delete it and download it again from its configured URL.
+fn offer_redownload<R: Runtime>(app_handle: &AppHandle<R>, model: config::Model, model_path: PathBuf, config: WhisprConfig) {
+    let message = format!("Model {} failed to load", model_path.display());
+    error!("[{}] {}", ErrorCode::ModelLoad, message);
+    api::emit_error(app_handle, ErrorCode::ModelLoad, &message);
+    let redownload_handle = app_handle.clone();
+    app_handle.dialog()
+        .message(format!(
+            "{} could not be loaded. The file {} may be incomplete or damaged, \
+            e.g. from an interrupted download. Delete it and download it again?",
+            model.display_name,
+            model_path.display()
+        ))
+        .kind(MessageDialogKind::Error)
+        .title("Model Failed to Load")
+        .buttons(MessageDialogButtons::OkCancelCustom("Download Again".to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            if confirmed {
+                redownload_model(redownload_handle, model, model_path, config);
+            }
+        });
+}
+
+/// After an app ignored typing and took the pasted dictation instead, asks
+/// whether to always paste into it.
+fn offer_paste_app<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Some(app) = output::take_paste_suggestion() else {
+        return;
+    };
+    app_handle.dialog()
+        .message(format!(
+            "{} ignored the typed dictation, so whispr pasted it instead. \
+            Always paste into {}?",
+            app.name, app.name
+        ))
+        .kind(MessageDialogKind::Info)
+        .title("Paste Instead of Typing?")
+        .buttons(MessageDialogButtons::OkCancelCustom("Always Paste".to_string(), "Keep Typing".to_string()))
+        .show(move |confirmed| {
+            if confirmed {
+                output::remember_paste_app(&app.bundle_id);
+            } else {
+                output::keep_typing_into(&app.bundle_id);
+            }
+        });
+}
+
+/// After the preferred model failed to load on a Mac with too little memory
+/// for it, offers to switch to a quantized model that fits.
+fn offer_lighter_model<R: Runtime>(app_handle: &AppHandle<R>, model: &config::Model, lighter: config::CatalogModel) {
+    let ram = models::system_ram_gb().unwrap_or_default();
+    let select_handle = app_handle.clone();
+    app_handle.dialog()
+        .message(format!(
+            "{} could not be loaded and needs more memory than this Mac's {} GB. \
+            Switch to {}, which is a little less accurate but fits?",
+            model.display_name, ram, lighter.display_name
+        ))
+        .kind(MessageDialogKind::Warning)
+        .title("Model Too Large")
+        .buttons(MessageDialogButtons::OkCancelCustom("Switch".to_string(), "Cancel".to_string()))
+        .show(move |confirmed| {
+            if confirmed {
+                menu::select_model(&select_handle, lighter);
+            }
+        });
+}
+
+/// First run without a model: fetch the small fallback model so dictation works
+/// right away, then download the preferred model and swap it in when ready.
+fn bootstrap_models<R: Runtime>(app_handle: AppHandle<R>, config: WhisprConfig, config_dir: PathBuf) {
+    std::thread::spawn(move || {
+        let fallback_path = models::fallback_model_path(&config, &config_dir);
+        let model_path = models::download_path(&config, &config_dir);
+        let fallback_name = config.fallback_model.display_name.clone();
+        let preferred_name = config.model.display_name.clone();
+
+        if !fallback_path.exists() {
+            let result = models::download_model(&config.fallback_model, &fallback_path, &config, |status| {
+                menu::set_model_status(&app_handle, &format!("{} · {}", fallback_name, status));
+            });
+            if let Err(e) = result {
+                error!("Failed to download fallback model: {}", e);
+                menu::set_model_status(&app_handle, &format!("{} download failed", fallback_name));
+            }
+        }
+
+        if fallback_path.exists() {
+            if let Some(state) = app_handle.try_state::<AppState>() {
+                let _ = state.load_model(&app_handle, fallback_path, config.clone()).join();
+            }
+        }
+
+        let result = models::download_model(&config.model, &model_path, &config, |status| {
+            menu::set_model_status(&app_handle, &format!("{} · {} {}", fallback_name, preferred_name, status));
+        });
+        match result {
+            Ok(_) => {
+                let active_path = models::active_model_path(&config, &config_dir);
+                if active_path == model_path {
+                    info!("Preferred model downloaded, switching from fallback");
+                    if let Some(state) = app_handle.try_state::<AppState>() {
+                        state.load_model(&app_handle, active_path, config);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to download preferred model: {}", e);
+                menu::set_model_status(&app_handle, &format!("{} · {} download failed", fallback_name, preferred_name));
+            }
+        }
+    });
+}
+
+/// Starts the dictation key monitors and checks that presses arrive.
+fn arm_dictation_key<R: Runtime>(app_handle: &AppHandle<R>, config: &WhisprConfig, controller: RecordingController) {
+    let mut hotkey_manager = HotkeyManager::new(config.clone());
+
+    if let Err(e) = hotkey_manager.attach(controller) {
+        error!("Failed to start hotkey manager: {}", e);
+    }
+
+    let self_test_handle = app_handle.clone();
+    let shortcut = config.keyboard_shortcut.replace('_', " ");
+    hotkey_manager.run_self_test(move |received| {
+        if received {
+            return;
+        }
+        let remappers = hotkey::running_key_remappers();
+        let remapper_hint = if remappers.is_empty() {
+            String::new()
+        } else {
+            format!(" {} is running - make sure it doesn't remap the {}.", remappers.join(" and "), shortcut)
+        };
+        self_test_handle.dialog()
+            .message(format!(
+                "whispr did not receive a test press of the {}.{} Also check that whispr is allowed under \
+                System Settings → Privacy & Security → Accessibility and Input Monitoring.",
+                shortcut, remapper_hint
+            ))
+            .kind(MessageDialogKind::Warning)
+            .title("Keyboard Shortcut Not Detected")
+            .show(|_| {});
+    });
+
+    hotkey_watch::start(app_handle.clone(), hotkey_manager.heartbeat());
+    TRIGGERS.with(|triggers| triggers.borrow_mut().push(Box::new(hotkey_manager)));
 }
 
 fn setup_app(app: &mut App<Wry>) -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -92,23 +1271,6 @@ fn setup_app(app: &mut App<Wry>) -> std::result::Result<(), Box<dyn std::error::
     let config_manager = ConfigManager::<WhisprConfig>::new("settings")
         .map_err(|e| WhisprError::ConfigError(e.to_string()))?;
     
-    // Check if model file exists
-    let model_path = config_manager.get_config_dir().join("model.bin");
-    if !model_path.exists() {
-        app.dialog()
-            .message("Model file not found at ~/.whispr/model.bin - see README.md")
-            .kind(MessageDialogKind::Error)
-            .title("Error")
-            .blocking_show();
-        
-        let _ = app.shell().command("open")
-            .args(["https://github.com/dbpprt/whispr?tab=readme-ov-file#usage"])
-            .spawn();
-
-        app.handle().exit(1);
-        return Ok(());
-    }
-    
     let mut whispr_config = if config_manager.config_exists("settings") {
         config_manager.load_config("settings")
             .map_err(|e| WhisprError::ConfigError(e.to_string()))?
@@ -116,14 +1278,46 @@ fn setup_app(app: &mut App<Wry>) -> std::result::Result<(), Box<dyn std::error::
         WhisprConfig::default()
     };
 
+    // Check if the models directory is reachable and the model file exists
+    let model_path = models::model_path(&whispr_config, config_manager.get_config_dir());
+    // With `model_path` the model can live elsewhere than `models_dir`
+    let models_dir = match model_path.parent() {
+        Some(dir) => dir.to_path_buf(),
+        None => models::models_dir(&whispr_config, config_manager.get_config_dir()),
+    };
+    let models_dir_available = models::is_dir_readable(&models_dir);
+    if !models_dir_available {
+        warn!("Models directory {} is not available, waiting for it", models_dir.display());
+        app.dialog()
+            .message(format!(
+                "The model volume is not mounted ({}). whispr will load the model as soon as it becomes available.",
+                models_dir.display()
+            ))
+            .kind(MessageDialogKind::Warning)
+            .title("Model Not Available")
+            .show(|_| {});
+    }
+    let model_missing = models_dir_available && !model_path.exists();
+
+    // First run on a Mac without the memory for the configured model: start
+    // out with a quantized one that fits
+    if model_missing {
+        if let Some(lighter) = models::lighter_model_for(&whispr_config.model) {
+            info!("{} needs more memory than available, using {}", whispr_config.model.display_name, lighter.display_name);
+            whispr_config.model = lighter.model();
+            config_manager.save_config(&whispr_config, "settings")
+                .map_err(|e| WhisprError::ConfigError(e.to_string()))?;
+        }
+    }
+
+    // Initialize application state, the one place audio devices are opened
+    let state = AppState::new()?;
+
     // Set default audio device if none is configured
-    if whispr_config.audio.device_name.is_none() {
-        let temp_audio = AudioManager::new()
-            .map_err(|e| WhisprError::AudioError(e.to_string()))?;
-        if let Some(first_device) = temp_audio.list_input_devices()
-            .map_err(|e| WhisprError::AudioError(e.to_string()))?
-            .first() {
-            whispr_config.audio.device_name = Some(first_device.clone());
+    if whispr_config.audio.selected_device().is_none() {
+        if let Some(first_device) = state.input_devices().first() {
+            whispr_config.audio.device_name = Some(first_device.name.clone());
+            whispr_config.audio.device_id = Some(first_device.id.clone());
             config_manager.save_config(&whispr_config, "settings")
                 .map_err(|e| WhisprError::ConfigError(e.to_string()))?;
         }
@@ -135,141 +1329,161 @@ fn setup_app(app: &mut App<Wry>) -> std::result::Result<(), Box<dyn std::error::
         Err(e) => warn!("Failed to initialize Enigo: {}", e),
     }
 
-    // Initialize application state
-    let state = AppState::new(whispr_config.clone())?;
+    api::configure(&whispr_config);
+    api::init(app_handle);
+
+    let armed_handle = app_handle.clone();
+    state.audio.lock().unwrap().set_armed_handler(move |armed| {
+        crate::tray::set_pre_roll_indicator(&armed_handle, armed);
+    });
     state.configure_audio(&whispr_config)?;
+    let device_lost_handle = app_handle.clone();
+    state.audio.lock().unwrap().set_device_lost_handler(move || {
+        // Called on the audio callback thread, which must not block
+        let app_handle = device_lost_handle.clone();
+        std::thread::spawn(move || {
+            let config = ConfigManager::<WhisprConfig>::new("settings")
+                .and_then(|manager| manager.load_config("settings"))
+                .unwrap_or_default();
+            if let Some(state) = app_handle.try_state::<AppState>() {
+                state.handle_device_lost(&app_handle, &config);
+            }
+        });
+    });
+    if let Err(e) = state.configure_rolling_buffer(&whispr_config) {
+        error!("Failed to start rolling buffer: {}", e);
+    }
+    device_watch::start(app_handle.clone());
+    if model_missing {
+        info!("No model found at {}, bootstrapping with the fallback model", model_path.display());
+        bootstrap_models(app_handle.clone(), whispr_config.clone(), config_manager.get_config_dir().to_path_buf());
+    } else if models_dir_available {
+        let active_path = models::active_model_path(&whispr_config, config_manager.get_config_dir());
+        state.load_startup_model(app_handle, active_path, whispr_config.clone());
+    } else {
+        menu::set_model_status(app_handle, "Model volume not mounted");
+        let handle = app_handle.clone();
+        let config = whispr_config.clone();
+        let config_dir = config_manager.get_config_dir().to_path_buf();
+        models::wait_for_dir(models_dir, move || {
+            if let Some(state) = handle.try_state::<AppState>() {
+                let active_path = models::active_model_path(&config, &config_dir);
+                state.load_startup_model(&handle, active_path, config);
+            }
+        });
+    }
     
-    // Create window
-    state.overlay.lock().unwrap().create_window(app_handle);
+    // Create windows
+    let display = accessibility::DisplayPreferences::resolve(&whispr_config.ui);
+    state.overlay.lock().unwrap().create_window(app_handle, display);
+    let mut record_button = state.record_button.lock().unwrap();
+    record_button.create_window(app_handle);
+    if whispr_config.record_button {
+        record_button.show();
+    }
+    drop(record_button);
     
     // Store state
     app.manage(state);
 
     // Setup tray and menu
-    let (tray_menu, menu_state) = create_tray_menu(app_handle);
+    let (tray_menu, menu_state) = create_tray_menu(app_handle)?;
     app.manage(menu_state);
 
     let handle_clone = app.handle().clone();
     let tray = tauri::tray::TrayIconBuilder::new()
         .icon(app_handle.default_window_icon().unwrap().clone())
+        .tooltip("whispr")
         .menu_on_left_click(false)
         .menu(&tray_menu)
+        // The disk usage item would otherwise show the numbers from launch
+        .on_tray_icon_event(|tray, event| {
+            if let tauri::tray::TrayIconEvent::Click { button_state: tauri::tray::MouseButtonState::Down, .. } = event {
+                menu::refresh_storage_usage(tray.app_handle());
+            }
+        })
         .on_menu_event(move |app, event| {
-            let menu_state = handle_clone.state::<MenuState<_>>();
+            let Some(menu_state) = handle_clone.try_state::<MenuState<_>>() else {
+                error!("Menu event {:?} before the menu state exists", event.id().0);
+                return;
+            };
             crate::menu::handle_menu_event(app.clone(), &event.id().0, &menu_state);
         })
         .build(app.handle())
         .map_err(|e| Box::new(WhisprError::SystemError(e.to_string())) as Box<dyn std::error::Error>)?;
     
     app.manage(tray);
+    // The pre-roll was armed before there was an icon to show it on
+    tray::set_recording_indicator(app_handle, false);
+    tray::update_summary(app_handle, &whispr_config);
 
-    // Setup hotkey manager
-    let app_handle_clone = app.handle().clone();
-    let mut hotkey_manager = HotkeyManager::new(move |is_speaking| {
-        if let Some(state) = app_handle_clone.try_state::<AppState>() {
-            let overlay = state.overlay.lock().unwrap();
-            
-            if is_speaking {
-                // Try to acquire the semaphore permit
-                if let Ok(_permit) = state.recording_semaphore.try_acquire() {
-                    overlay.show();
-                    let mut audio = state.audio.lock().unwrap();
-                    if let Err(e) = audio.start_capture() {
-                        error!("Failed to start audio capture: {}", e);
-                        return;
-                    }
-                    *state.recording_start.lock().unwrap() = Some(Instant::now());
-                    let _ = app_handle_clone.emit("status-change", "Listening");
-                } else {
-                    warn!("Recording already in progress");
-                }
-            } else {
-                let mut audio = state.audio.lock().unwrap();
-                audio.stop_capture();
-                
-                // Check recording duration
-                if let Some(start_time) = state.recording_start.lock().unwrap().take() {
-                    let duration = start_time.elapsed();
-                    if duration < MIN_RECORDING_DURATION {
-                        debug!("Recording too short ({:.2}s), discarding", duration.as_secs_f32());
-                        let _ = app_handle_clone.emit("status-change", "Ready");
-                        overlay.hide();
-                        return;
-                    }
-                }
-                
-                let _ = app_handle_clone.emit("status-change", "Transcribing");
-                
-                if let Some(captured_audio) = audio.get_captured_audio(16000, 1) {
-                    debug!("Got captured audio: {} samples", captured_audio.len());
-                    
-                    match state.whisper.process_audio(captured_audio) {
-                        Ok(segments) => {
-                            if segments.is_empty() {
-                                info!("No transcription segments produced");
-                                let _ = app_handle_clone.emit("status-change", "Ready");
-                                overlay.hide();
-                                return;
-                            }
-                            
-                            let mut transcription: String = segments.iter()
-                                .map(|(_, _, segment)| segment.clone())
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            // Add trailing space if last character is punctuation, allowing for "chaining" of recordings
-                            if let Some(last_char) = transcription.chars().last() {
-                                if last_char.is_ascii_punctuation() {
-                                    transcription.push(' ');
-                                }
-                            }
-                            info!("Transcription: {}", transcription);
-
-                            // Create a new Enigo instance for text input
-                            let mut enigo = match Enigo::new(&Settings::default()) {
-                                Ok(enigo) => enigo,
-                                Err(e) => {
-                                    error!("Failed to create Enigo instance: {}", e);
-                                    let _ = app_handle_clone.emit("status-change", "Ready");
-                                    overlay.hide();
-                                    return;
-                                }
-                            };
-                            
-                            if let Err(e) = enigo.text(&transcription) {
-                                error!("Failed to send text: {}", e);
-                                let _ = app_handle_clone.emit("status-change", "Ready");
-                                overlay.hide();
-                                return;
-                            }
-                            
-                            let _ = app_handle_clone.emit("status-change", "Ready");
-                        }
-                        Err(e) => {
-                            error!("Failed to process audio: {}", e);
-                            let _ = app_handle_clone.emit("status-change", "Ready");
-                            overlay.hide();
-                            return;
-                        }
-                    }
-                } else {
-                    info!("No audio captured");
-                    let _ = app_handle_clone.emit("status-change", "Ready");
-                    overlay.hide();
-                    return;
-                }
-                
-                overlay.hide();
-                
-                // Release the semaphore permit
-                state.recording_semaphore.add_permits(1);
-            }
+    // Every trigger (dictation key, record button, API) feeds the same controller
+    let dictation = Arc::new(DictationController::new(AppDictation::new(app.handle().clone()), MIN_RECORDING_DURATION));
+    let controller = RecordingController::new(move |event: TriggerEvent| {
+        let now = Instant::now();
+        if event.pressed {
+            dictation.handle(event, now);
+            return;
         }
-    }, whispr_config.clone());
+        // Transcribing and typing take a while, the dictation key's monitor runs on the main thread
+        let dictation = dictation.clone();
+        std::thread::spawn(move || dictation.handle(event, now));
+    });
+    app.manage(controller.clone());
+    let arm_delay = Duration::from_millis(whispr_config.startup.arm_delay_ms);
+    if arm_delay.is_zero() {
+        arm_dictation_key(app_handle, &whispr_config, controller);
+    } else {
+        info!("Arming the dictation key in {:?}", arm_delay);
+        let arm_handle = app.handle().clone();
+        let config = whispr_config.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(arm_delay);
+            let main_handle = arm_handle.clone();
+            // NSEvent monitors are added on the main thread
+            if let Err(e) = main_handle.run_on_main_thread(move || arm_dictation_key(&arm_handle, &config, controller)) {
+                error!("Failed to arm the dictation key: {}", e);
+            }
+        });
+    }
 
-    if let Err(e) = hotkey_manager.start() {
-        error!("Failed to start hotkey manager: {}", e);
+    // Monitors and audio devices don't reliably survive sleep, rebuild them on wake
+    let sleep_handle = app.handle().clone();
+    let wake_handle = app.handle().clone();
+    power::observe_sleep_wake(
+        move || {
+            if let Some(state) = sleep_handle.try_state::<AppState>() {
+                state.prepare_for_sleep(&sleep_handle);
+            }
+        },
+        move || {
+            restart_triggers();
+            // The other Mac may have changed settings while this one slept
+            std::thread::spawn(sync::pull);
+            let config = ConfigManager::<WhisprConfig>::new("settings")
+                .and_then(|manager| manager.load_config("settings"))
+                .unwrap_or_else(|e| {
+                    error!("Failed to load configuration, using defaults: {}", e);
+                    WhisprConfig::default()
+                });
+            if let Some(state) = wake_handle.try_state::<AppState>() {
+                state.recover_after_wake(&wake_handle, config);
+            }
+        },
+    );
+
+    if let Err(e) = shortcuts::register(app.handle(), &whispr_config) {
+        error!("Failed to register shortcuts: {}", e);
     }
 
+    if let Err(e) = server::start(app.handle().clone(), &whispr_config.api) {
+        error!("Failed to start API server: {}", e);
+    }
+
+    sync::start();
+
+    safemode::mark_launch_successful_when_stable();
+
     Ok(())
 }
 
@@ -280,8 +1494,10 @@ fn main() {
     }
     
     info!("Starting Whispr application");
+
+    let safe_mode = safemode::begin_launch();
     
-    tauri::Builder::default()
+    let builder = tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
             info!("{}, {argv:?}, {cwd}", app.package_info().name);
         }))
@@ -289,7 +1505,50 @@ fn main() {
         .plugin(tauri_plugin_autostart::init(tauri_plugin_autostart::MacosLauncher::LaunchAgent, None))
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_process::init())  // Register the process plugin
-        .setup(setup_app)
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            record_button,
+            accessibility::display_preferences,
+            console::console_backlog,
+            api::api_version,
+            api::get_status,
+            api::start_recording,
+            api::stop_recording,
+            api::toggle_recording,
+            history::history_entries,
+            history::play_history_segment,
+            playback::playback_play,
+            playback::playback_pause,
+            playback::playback_resume,
+            playback::playback_seek,
+            playback::playback_stop,
+            playback::playback_status,
+            retranscribe::retranscribe_entries,
+            history::set_history_tags,
+            history::set_history_favorite,
+            history::export_history,
+            summarize::summarize_history_entry,
+            history::history_search,
+            analytics::analytics_months,
+            analytics::analytics_report,
+            analytics::export_analytics_report,
+            language_switch::redo_in_language,
+            review::review_pending,
+            review::review_insert,
+            review::review_discard,
+        ]);
+
+    let builder = if safe_mode {
+        builder.setup(safemode::setup_safe_mode)
+    } else {
+        builder.setup(setup_app)
+    };
+
+    builder
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                shutdown(app_handle);
+            }
+        });
 }
@@ -1,39 +1,77 @@
 use tauri::{
     AppHandle, Manager, Runtime,
-    menu::{Menu, MenuItem, Submenu, CheckMenuItem, PredefinedMenuItem},
+    menu::{Menu, MenuItem, CheckMenuItem},
 };
-use log::{error, info, debug};
+use log::{error, info, debug, warn};
 use std::collections::HashMap;
-use crate::audio::AudioManager;
-use crate::config::{ConfigManager, WhisprConfig};
+use crate::config::{AudioSource, CatalogModel, ConfigManager, Quality, SinkKind, WhisprConfig};
+use crate::menu_spec::{MenuSpec, RenderedMenu, LANGUAGES, SHORTCUTS};
+use crate::models;
+use crate::storage;
+use tauri::Emitter;
 use tauri_plugin_shell::ShellExt;
 use tauri_plugin_autostart::ManagerExt;
-use tauri_plugin_dialog::{DialogExt, MessageDialogButtons}; // Added import for tauri_plugin_dialog
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind}; // Added import for tauri_plugin_dialog
 
 #[derive(Default)]
 pub struct MenuState<R: Runtime> {
     pub audio_device_map: HashMap<String, CheckMenuItem<R>>,
+    pub system_audio_item: Option<CheckMenuItem<R>>,
     pub remove_silence_item: Option<CheckMenuItem<R>>,
+    pub denoise_item: Option<CheckMenuItem<R>>,
     pub save_recordings_item: Option<CheckMenuItem<R>>,
+    pub save_raw_audio_item: Option<CheckMenuItem<R>>,
     pub language_items: HashMap<String, CheckMenuItem<R>>,
     pub translate_item: Option<CheckMenuItem<R>>,
     pub start_at_login_item: Option<CheckMenuItem<R>>,
     pub whisper_logging_item: Option<CheckMenuItem<R>>,
     pub logging_item: Option<CheckMenuItem<R>>,
     pub keyboard_shortcut_items: HashMap<String, CheckMenuItem<R>>,
+    pub quality_items: HashMap<String, CheckMenuItem<R>>,
+    pub model_items: HashMap<String, CheckMenuItem<R>>,
+    pub download_model_item: Option<MenuItem<R>>,
+    pub dictate_to_file_item: Option<CheckMenuItem<R>>,
+    pub output_items: HashMap<String, CheckMenuItem<R>>,
+    pub storage_item: Option<MenuItem<R>>,
+    pub summary_item: Option<MenuItem<R>>,
+    pub model_status_item: Option<MenuItem<R>>,
+    pub rolling_buffer_item: Option<CheckMenuItem<R>>,
+    pub recall_item: Option<MenuItem<R>>,
+    pub record_button_item: Option<CheckMenuItem<R>>,
+    pub command_mode_item: Option<CheckMenuItem<R>>,
+    pub ephemeral_session_item: Option<CheckMenuItem<R>>,
+}
+
+/// Emits `model-status` and mirrors it in the disabled status item at the top
+/// of the tray menu.
+pub fn set_model_status<R: Runtime>(app: &AppHandle<R>, status: &str) {
+    let _ = app.emit("model-status", status);
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        if let Some(item) = &menu_state.model_status_item {
+            if let Err(e) = item.set_text(format!("Model: {}", status)) {
+                error!("Failed to update model status item: {}", e);
+            }
+        }
+    }
 }
 
 pub fn handle_menu_event<R: Runtime>(app: AppHandle<R>, id: &str, menu_state: &MenuState<R>) {
     match id {
         "quit" => {
             info!("Quit menu item selected");
-            app.exit(0);
+            handle_quit(&app);
         }
+        "system_audio" => handle_system_audio_selection(&app),
         "remove_silence" => {
             if let Some(remove_silence_item) = &menu_state.remove_silence_item {
                 handle_remove_silence_selection(&app, remove_silence_item);
             }
         }
+        "denoise" => {
+            if let Some(denoise_item) = &menu_state.denoise_item {
+                handle_denoise_selection(&app, denoise_item);
+            }
+        }
         id if id.starts_with("audio_device_") => {
             if let Some(device_id) = id.strip_prefix("audio_device_") {
                 handle_audio_device_selection(&app, device_id, &menu_state.audio_device_map);
@@ -52,19 +90,36 @@ pub fn handle_menu_event<R: Runtime>(app: AppHandle<R>, id: &str, menu_state: &M
                 .spawn();
         }
         id if id.starts_with("language_") => {
-            if let Some(item) = menu_state.language_items.get(id) {
-                let language = match id.strip_prefix("language_").unwrap() {
-                    "Automatic" => "auto",
-                    "English" => "en",
-                    "German" => "de",
-                    "French" => "fr",
-                    "Spanish" => "es",
-                    _ => {
-                        error!("Unknown language selected: {}", id);
-                        return;
-                    }
+            if menu_state.language_items.contains_key(id) {
+                let label = id.strip_prefix("language_");
+                let Some((_, language)) = LANGUAGES.iter().find(|(name, _)| Some(*name) == label) else {
+                    error!("Unknown language selected: {}", id);
+                    return;
                 };
-                handle_language_selection(&app, item.clone(), language);
+                handle_language_selection(&app, language);
+            }
+        }
+        id if id.starts_with("quality_") => {
+            match id.strip_prefix("quality_").and_then(Quality::from_id) {
+                Some(quality) => handle_quality_selection(&app, quality),
+                None => error!("Unknown quality selected: {}", id),
+            }
+        }
+        id if id.starts_with("select_model_") => {
+            match id.strip_prefix("select_model_").and_then(CatalogModel::from_id) {
+                Some(model) => select_model(&app, model),
+                None => error!("Unknown model selected: {}", id),
+            }
+        }
+        id if id.starts_with("output_") => {
+            match id.strip_prefix("output_").and_then(SinkKind::from_id) {
+                Some(sink) => handle_output_selection(&app, sink),
+                None => error!("Unknown output selected: {}", id),
+            }
+        }
+        "dictate_to_file" => {
+            if let Some(dictate_to_file_item) = &menu_state.dictate_to_file_item {
+                handle_dictate_to_file_selection(&app, dictate_to_file_item);
             }
         }
         "translate" => {
@@ -77,6 +132,11 @@ pub fn handle_menu_event<R: Runtime>(app: AppHandle<R>, id: &str, menu_state: &M
                 handle_start_at_login_selection(&app, start_at_login_item);
             }
         }
+        "save_raw_audio" => {
+            if let Some(save_raw_audio_item) = &menu_state.save_raw_audio_item {
+                handle_save_raw_audio_selection(save_raw_audio_item);
+            }
+        }
         "whisper_logging" => {
             if let Some(whisper_logging_item) = &menu_state.whisper_logging_item {
                 handle_whisper_logging_selection(&app, whisper_logging_item);
@@ -84,13 +144,10 @@ pub fn handle_menu_event<R: Runtime>(app: AppHandle<R>, id: &str, menu_state: &M
         }
         id if id.starts_with("keyboard_shortcut_") => {
             if let Some(item) = menu_state.keyboard_shortcut_items.get(id) {
-                let shortcut = match id.strip_prefix("keyboard_shortcut_").unwrap() {
-                    "right_option_key" => "right_option_key",
-                    "right_command_key" => "right_command_key",
-                    _ => {
-                        error!("Unknown keyboard shortcut selected: {}", id);
-                        return;
-                    }
+                let value = id.strip_prefix("keyboard_shortcut_");
+                let Some((_, shortcut)) = SHORTCUTS.iter().find(|(_, shortcut)| Some(*shortcut) == value) else {
+                    error!("Unknown keyboard shortcut selected: {}", id);
+                    return;
                 };
                 handle_keyboard_shortcut_selection(&app, item.clone(), shortcut);
             }
@@ -101,19 +158,73 @@ pub fn handle_menu_event<R: Runtime>(app: AppHandle<R>, id: &str, menu_state: &M
             }
         }
         "restart" => {
+            crate::shutdown(&app);
             app.restart();
         }
+        "models_dir" => {
+            handle_models_dir_selection(&app);
+        }
+        "download_model" => {
+            handle_download_model(&app);
+        }
+        "capture_stats" => {
+            handle_capture_stats(&app);
+        }
+        "console" => {
+            crate::console::open_console(&app);
+        }
+        "spectrogram" => {
+            crate::spectrogram::open_spectrogram(&app);
+        }
+        "benchmark" => {
+            crate::benchmark::run(&app);
+        }
+        "rolling_buffer" => {
+            if let Some(rolling_buffer_item) = &menu_state.rolling_buffer_item {
+                handle_rolling_buffer_selection(&app, rolling_buffer_item);
+            }
+        }
+        "recall" => {
+            handle_recall(&app);
+        }
+        "record_button" => {
+            if let Some(record_button_item) = &menu_state.record_button_item {
+                handle_record_button_selection(&app, record_button_item);
+            }
+        }
+        "history" => {
+            crate::history::open_history(&app);
+        }
+        "analytics_report" => {
+            crate::analytics::open_report(&app);
+        }
+        "paste_last" => {
+            crate::shortcuts::paste_last_transcription(&app);
+        }
+        "command_mode" => {
+            if let Some(command_mode_item) = &menu_state.command_mode_item {
+                handle_command_mode_selection(command_mode_item);
+            }
+        }
+        "ephemeral_session" => {
+            if let Some(ephemeral_session_item) = &menu_state.ephemeral_session_item {
+                handle_ephemeral_session_selection(&app, ephemeral_session_item);
+            }
+        }
         _ => {
             error!("Unhandled menu item: {:?}", id);
         }
     }
-}
 
-pub fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> (Menu<R>, MenuState<R>) {
-    let separator = PredefinedMenuItem::separator(app).unwrap();
-    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<String>).unwrap();
+    // Most items change the configuration, keep the summary in sync
+    match ConfigManager::<WhisprConfig>::new("settings").and_then(|manager| manager.load_config("settings")) {
+        Ok(config) => crate::tray::update_summary(&app, &config),
+        Err(e) => error!("Failed to load configuration: {}", e),
+    }
+}
 
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+pub fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<(Menu<R>, MenuState<R>)> {
+    let config_manager = ConfigManager::<WhisprConfig>::new("settings")?;
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -123,205 +234,146 @@ pub fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> (Menu<R>, MenuState<R
         }
     }
 
-    let mut audio_device_items = Vec::new();
-    let mut audio_device_map = HashMap::new();
-    let audio_manager = AudioManager::new().unwrap();
-    
-    if let Ok(devices) = audio_manager.list_input_devices() {
-        for device in devices {
-            let is_active = whispr_config.audio.device_name.as_ref().map_or(false, |d| d == &device);
-            let item_id = format!("audio_device_{}", device);
-            let item = CheckMenuItem::with_id(app, &item_id, &device, true, is_active, None::<String>).unwrap();
-            audio_device_items.push(item.clone());
-            audio_device_map.insert(device.to_string(), item);
-        }
-    } else {
-        error!("Failed to get list of input devices");
-    }
+    let devices = app.try_state::<crate::AppState>()
+        .map(|state| state.input_devices())
+        .unwrap_or_default();
 
-    let audio_device_refs: Vec<&dyn tauri::menu::IsMenuItem<R>> = audio_device_items.iter()
-        .map(|item| item as &dyn tauri::menu::IsMenuItem<R>)
-        .collect();
+    let spec = MenuSpec::from_config(&whispr_config, &devices, storage_usage_label(config_manager.get_config_dir()), crate::language_names::ui_locale(), models::recommended_model());
+    let RenderedMenu { menu, mut checks, mut items } = spec.render(app)?;
 
-    let audio_submenu = Submenu::with_items(
-        app,
-        "Audio Device",
-        true,
-        &audio_device_refs
-    ).unwrap();
-    
-    let initial_remove_silence_state = whispr_config.audio.remove_silence;
-    let remove_silence_item = CheckMenuItem::with_id(
-        app, 
-        "remove_silence", 
-        "Remove Silence", 
-        true, 
-        initial_remove_silence_state, 
-        None::<String>
-    ).unwrap();
-    
-    let developer_options_separator = PredefinedMenuItem::separator(app).unwrap();
-
-    let save_recordings_item = CheckMenuItem::with_id(
-        app,
-        "save_recordings",
-        "Save Recordings",
-        true,
-        whispr_config.developer.save_recordings,
-        None::<String>
-    ).unwrap();
-    
-    let whisper_logging_item = CheckMenuItem::with_id(
-        app,
-        "whisper_logging",
-        "Whisper Logging",
-        true,
-        whispr_config.developer.whisper_logging,
-        None::<String>
-    ).unwrap();
-
-    let restart = MenuItem::with_id(app, "restart", "Restart", true, None::<String>).unwrap();
-
-    let logging_item = CheckMenuItem::with_id(
-        app,
-        "logging",
-        "Logging",
-        true,
-        whispr_config.developer.logging,
-        None::<String>
-    ).unwrap();
-
-    let developer_options_submenu = Submenu::with_items(
-        app,
-        "Developer Options",
-        true,
-        &[
-            &save_recordings_item as &dyn tauri::menu::IsMenuItem<R>,
-            &whisper_logging_item as &dyn tauri::menu::IsMenuItem<R>,
-            &logging_item as &dyn tauri::menu::IsMenuItem<R>,
-            &restart as &dyn tauri::menu::IsMenuItem<R>
-        ]
-    ).unwrap();
-
-    let language_items = vec![
-        ("Automatic", whispr_config.whisper.language.as_ref().map_or(true, |l| l == "auto")),
-        ("English", whispr_config.whisper.language.as_ref().map_or(false, |l| l == "en")),
-        ("German", whispr_config.whisper.language.as_ref().map_or(false, |l| l == "de")),
-        ("French", whispr_config.whisper.language.as_ref().map_or(false, |l| l == "fr")),
-        ("Spanish", whispr_config.whisper.language.as_ref().map_or(false, |l| l == "es")),
-    ];
-
-    let mut language_check_items = HashMap::new();
-    let mut language_menu_items: Vec<&'static dyn tauri::menu::IsMenuItem<R>> = Vec::new();
-
-    for (language, is_active) in language_items {
-        let item_id = format!("language_{}", language);
-        let item = CheckMenuItem::with_id(app, &item_id, language, true, is_active, None::<String>).unwrap();
-        language_check_items.insert(item_id.clone(), item.clone());
-        language_menu_items.push(Box::leak(Box::new(item)) as &'static dyn tauri::menu::IsMenuItem<R>);
-    }
-
-    let language_submenu = Submenu::with_items(
-        app,
-        "Language",
-        true,
-        &language_menu_items
-    ).unwrap();
-
-    let translate_item = CheckMenuItem::with_id(
-        app,
-        "translate",
-        "Translate to English",
-        true,
-        whispr_config.whisper.translate,
-        None::<String>
-    ).unwrap();
-
-    let start_at_login_item = CheckMenuItem::with_id(
-        app,
-        "start_at_login",
-        "Start at Login",
-        true,
-        whispr_config.start_at_login,
-        None::<String>
-    ).unwrap();
-
-    let keyboard_shortcut_items = vec![
-        ("Right Option Key", whispr_config.keyboard_shortcut == "right_option_key"),
-        ("Right Command Key", whispr_config.keyboard_shortcut == "right_command_key"),
-    ];
-
-    let mut keyboard_shortcut_check_items = HashMap::new();
-    let mut keyboard_shortcut_menu_items: Vec<&'static dyn tauri::menu::IsMenuItem<R>> = Vec::new();
-
-    for (shortcut, is_active) in keyboard_shortcut_items {
-        let item_id = format!("keyboard_shortcut_{}", shortcut.to_lowercase().replace(' ', "_"));
-        let item = CheckMenuItem::with_id(app, &item_id, shortcut, true, is_active, None::<String>).unwrap();
-        keyboard_shortcut_check_items.insert(item_id.clone(), item.clone());
-        keyboard_shortcut_menu_items.push(Box::leak(Box::new(item)) as &'static dyn tauri::menu::IsMenuItem<R>);
-    }
-
-    let keyboard_shortcut_submenu = Submenu::with_items(
-        app,
-        "Keyboard Shortcut",
-        true,
-        &keyboard_shortcut_menu_items
-    ).unwrap();
-
-    let about = MenuItem::with_id(app, "about", "About", true, None::<String>).unwrap();
-
-    let main_items: Vec<&dyn tauri::menu::IsMenuItem<R>> = vec![
-        &quit,
-        &separator,
-        &start_at_login_item,
-        &keyboard_shortcut_submenu,
-        &separator,
-        &audio_submenu,
-        &language_submenu,
-        &translate_item,
-        &remove_silence_item,
-        &developer_options_separator,
-        &developer_options_submenu,
-        &about,
-    ];
-
-    let menu = Menu::with_items(app, &main_items).unwrap();
     let menu_state = MenuState {
-        audio_device_map,
-        remove_silence_item: Some(remove_silence_item),
-        save_recordings_item: Some(save_recordings_item),
-        language_items: language_check_items,
-        translate_item: Some(translate_item),
-        start_at_login_item: Some(start_at_login_item),
-        whisper_logging_item: Some(whisper_logging_item),
-        logging_item: Some(logging_item),
-        keyboard_shortcut_items: keyboard_shortcut_check_items,
+        audio_device_map: checks_with_prefix(&checks, "audio_device_")
+            .into_iter()
+            .filter_map(|(id, item)| Some((id.strip_prefix("audio_device_")?.to_string(), item)))
+            .collect(),
+        system_audio_item: checks.remove("system_audio"),
+        remove_silence_item: checks.remove("remove_silence"),
+        denoise_item: checks.remove("denoise"),
+        save_recordings_item: checks.remove("save_recordings"),
+        save_raw_audio_item: checks.remove("save_raw_audio"),
+        language_items: checks_with_prefix(&checks, "language_"),
+        translate_item: checks.remove("translate"),
+        start_at_login_item: checks.remove("start_at_login"),
+        whisper_logging_item: checks.remove("whisper_logging"),
+        logging_item: checks.remove("logging"),
+        keyboard_shortcut_items: checks_with_prefix(&checks, "keyboard_shortcut_"),
+        quality_items: checks_with_prefix(&checks, "quality_"),
+        model_items: checks_with_prefix(&checks, "select_model_"),
+        download_model_item: items.remove("download_model"),
+        dictate_to_file_item: checks.remove("dictate_to_file"),
+        output_items: checks_with_prefix(&checks, "output_"),
+        storage_item: items.remove("storage_usage"),
+        summary_item: items.remove("summary"),
+        model_status_item: items.remove("model_status"),
+        rolling_buffer_item: checks.remove("rolling_buffer"),
+        recall_item: items.remove("recall"),
+        record_button_item: checks.remove("record_button"),
+        command_mode_item: checks.remove("command_mode"),
+        ephemeral_session_item: checks.remove("ephemeral_session"),
     };
     
-    (menu, menu_state)
+    Ok((menu, menu_state))
+}
+
+fn checks_with_prefix<R: Runtime>(checks: &HashMap<String, CheckMenuItem<R>>, prefix: &str) -> HashMap<String, CheckMenuItem<R>> {
+    checks.iter()
+        .filter(|(id, _)| id.starts_with(prefix))
+        .map(|(id, item)| (id.clone(), item.clone()))
+        .collect()
+}
+
+/// The settings manager, or `None` after logging why there is none, for
+/// handlers that can only give up.
+fn config_manager() -> Option<ConfigManager<WhisprConfig>> {
+    ConfigManager::<WhisprConfig>::new("settings")
+        .map_err(|e| error!("Failed to create config manager: {}", e))
+        .ok()
+}
+
+/// Checks or unchecks `item`, a menu that failed to update isn't worth
+/// failing the handler for.
+fn set_checked<R: Runtime>(item: &CheckMenuItem<R>, checked: bool) {
+    if let Err(e) = item.set_checked(checked) {
+        error!("Failed to update menu item: {}", e);
+    }
+}
+
+/// Quits right away unless a dictation is in progress, then asks whether to
+/// finish it first or drop it.
+fn handle_quit<R: Runtime>(app: &AppHandle<R>) {
+    let active_work = app.try_state::<crate::AppState>().and_then(|state| state.active_work());
+    let Some(active_work) = active_work else {
+        app.exit(0);
+        return;
+    };
+
+    let app_handle = app.clone();
+    app.dialog()
+        .message(format!("A {} is in progress. Finish it before quitting or discard it?", active_work))
+        .kind(MessageDialogKind::Warning)
+        .title("Quit whispr?")
+        .buttons(MessageDialogButtons::OkCancelCustom("Finish and Quit".to_string(), "Discard and Quit".to_string()))
+        .show(move |finish| {
+            if !finish {
+                info!("Discarding the active {} and quitting", active_work);
+                app_handle.exit(0);
+                return;
+            }
+            // Waiting here would block the main thread the release and the
+            // transcription need
+            std::thread::spawn(move || {
+                if let Some(state) = app_handle.try_state::<crate::AppState>() {
+                    state.finish_active_work(&app_handle);
+                }
+                app_handle.exit(0);
+            });
+        });
+}
+
+/// Checks the device dictations are captured from in the Audio Device
+/// submenu, e.g. after falling back to the default device.
+pub fn check_input_device<R: Runtime>(app: &AppHandle<R>, id: &str) {
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        for (device_id, item) in &menu_state.audio_device_map {
+            if let Err(e) = item.set_checked(device_id == id) {
+                error!("Failed to update audio device item: {}", e);
+            }
+        }
+    }
 }
 
 fn handle_audio_device_selection<R: Runtime>(app: &AppHandle<R>, id: &str, audio_device_map: &HashMap<String, CheckMenuItem<R>>) {
     if let Some(app_state) = app.try_state::<crate::AppState>() {
-        let mut audio_manager = app_state.audio.lock().unwrap();
-        if let Err(e) = audio_manager.set_input_device(id) {
+        let result = app_state.audio.lock().unwrap().set_input_device(id);
+        if let Err(e) = result {
             error!("Failed to set input device: {}", e);
-            if let Ok(current_device) = audio_manager.get_current_device_name() {
+            if let Some(current_device) = app_state.input_device_id() {
                 for (device_id, item) in audio_device_map {
-                    item.set_checked(device_id == &current_device).unwrap();
+                    set_checked(item, device_id == &current_device);
                 }
             }
         } else {
+            crate::device_watch::clear_fallback();
             for (device_id, item) in audio_device_map {
-                item.set_checked(device_id == id).unwrap();
+                set_checked(item, device_id == id);
+            }
+            if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+                if let Some(item) = &menu_state.system_audio_item {
+                    set_checked(item, false);
+                }
             }
 
-            let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+            let Some(config_manager) = config_manager() else {
+                return;
+            };
             let mut whispr_config = WhisprConfig::default();
             if let Ok(config) = config_manager.load_config("settings") {
                 whispr_config = config;
             }
-            whispr_config.audio.device_name = Some(id.to_string());
+            let name = app_state.input_devices().into_iter().find(|device| device.id == id).map(|device| device.name);
+            whispr_config.audio.device_name = name.or_else(|| Some(id.to_string()));
+            whispr_config.audio.device_id = Some(id.to_string());
+            whispr_config.audio.source = AudioSource::Microphone;
             if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
                 error!("Failed to save configuration: {}", e);
             }
@@ -329,6 +381,68 @@ fn handle_audio_device_selection<R: Runtime>(app: &AppHandle<R>, id: &str, audio
     }
 }
 
+/// Switches between capturing the microphone and what the Mac plays through
+/// a loopback device.
+fn handle_system_audio_selection<R: Runtime>(app: &AppHandle<R>) {
+    let Some(app_state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = match config_manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return;
+        }
+    };
+
+    let enable = whispr_config.audio.source != AudioSource::SystemAudio;
+    let mut audio = app_state.audio.lock().unwrap();
+    let result = if enable {
+        audio.use_loopback_device(whispr_config.audio.loopback_device.as_deref())
+    } else {
+        match whispr_config.audio.selected_device() {
+            Some(id) => match audio.set_input_device(id) {
+                Ok(()) => audio.get_current_device_name(),
+                Err(e) => {
+                    warn!("{}, using the default input device until it is back", e);
+                    crate::device_watch::mark_fallback();
+                    audio.use_default_input_device()
+                }
+            },
+            None => audio.use_default_input_device(),
+        }
+    };
+    let current = audio.get_current_device_id().ok();
+    drop(audio);
+
+    let capturing_system_audio = enable == result.is_ok();
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        if let Some(item) = &menu_state.system_audio_item {
+            set_checked(item, capturing_system_audio);
+        }
+    }
+    if let Some(current) = current {
+        check_input_device(app, &current);
+    }
+    match result {
+        Ok(name) => {
+            info!("Capturing from {}", name);
+            whispr_config.audio.source = if enable { AudioSource::SystemAudio } else { AudioSource::Microphone };
+            if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+                error!("Failed to save configuration: {}", e);
+            }
+            crate::show_toast(app, &format!("Capturing from {}", name));
+        }
+        Err(e) => {
+            error!("Failed to switch the audio source: {}", e);
+            crate::show_toast(app, &e.to_string());
+        }
+    }
+}
+
 fn handle_remove_silence_selection<R: Runtime>(app: &AppHandle<R>, remove_silence_item: &CheckMenuItem<R>) {
     if let Some(app_state) = app.try_state::<crate::AppState>() {
         let mut audio_manager = app_state.audio.lock().unwrap();
@@ -337,10 +451,12 @@ fn handle_remove_silence_selection<R: Runtime>(app: &AppHandle<R>, remove_silenc
         
         debug!("Remove Silence before toggle: {}", current_state);
         audio_manager.set_remove_silence(new_state);
-        remove_silence_item.set_checked(new_state).unwrap();
+        set_checked(remove_silence_item, new_state);
         debug!("Remove Silence after toggle: {}", new_state);
 
-        let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+        let Some(config_manager) = config_manager() else {
+            return;
+        };
         let mut whispr_config = WhisprConfig::default();
         if let Ok(config) = config_manager.load_config("settings") {
             whispr_config = config;
@@ -352,8 +468,69 @@ fn handle_remove_silence_selection<R: Runtime>(app: &AppHandle<R>, remove_silenc
     }
 }
 
-fn handle_save_recordings_selection<R: Runtime>(_app: &AppHandle<R>, save_recordings_item: &CheckMenuItem<R>) {
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+fn handle_denoise_selection<R: Runtime>(app: &AppHandle<R>, denoise_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = match config_manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return;
+        }
+    };
+
+    let new_state = !whispr_config.audio.dsp.denoise;
+    set_checked(denoise_item, new_state);
+    whispr_config.audio.dsp.denoise = new_state;
+    debug!("Reduce Noise: {}", new_state);
+    if let Some(app_state) = app.try_state::<crate::AppState>() {
+        app_state.audio.lock().unwrap().set_dsp(whispr_config.audio.dsp.clone());
+    }
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+}
+
+/// Recomputes the disk usage item, e.g. when the menu is opened or a
+/// recording or model was written. Sizing the recordings folder can take a
+/// moment, so it happens in the background.
+pub fn refresh_storage_usage<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let Some(config_manager) = config_manager() else {
+            return;
+        };
+        let label = storage_usage_label(config_manager.get_config_dir());
+        if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+            if let Some(storage_item) = &menu_state.storage_item {
+                if let Err(e) = storage_item.set_text(label) {
+                    error!("Failed to update disk usage item: {}", e);
+                }
+            }
+        }
+    });
+}
+
+fn storage_usage_label(config_dir: &std::path::Path) -> String {
+    let recordings = storage::dir_size(&config_dir.join("recordings"));
+    match storage::available_space(config_dir) {
+        Ok(available) => format!(
+            "Disk: {} free, recordings {}",
+            storage::format_bytes(available),
+            storage::format_bytes(recordings)
+        ),
+        Err(e) => {
+            error!("Failed to read disk usage: {}", e);
+            format!("Recordings: {}", storage::format_bytes(recordings))
+        }
+    }
+}
+
+fn handle_save_recordings_selection<R: Runtime>(app: &AppHandle<R>, save_recordings_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -366,8 +543,23 @@ fn handle_save_recordings_selection<R: Runtime>(_app: &AppHandle<R>, save_record
     let current_state = whispr_config.developer.save_recordings;
     let new_state = !current_state;
 
+    refresh_storage_usage(app);
+
+    if new_state {
+        if let Err(e) = storage::check_free_space(config_manager.get_config_dir(), 0, &whispr_config) {
+            error!("Refusing to enable Save Recordings: {}", e);
+            set_checked(save_recordings_item, current_state);
+            app.dialog()
+                .message(e)
+                .kind(MessageDialogKind::Warning)
+                .title("Low Disk Space")
+                .show(|_| {});
+            return;
+        }
+    }
+
     debug!("Save Recordings before toggle: {}", current_state);
-    save_recordings_item.set_checked(new_state).unwrap();
+    set_checked(save_recordings_item, new_state);
     debug!("Save Recordings after toggle: {}", new_state);
 
     whispr_config.developer.save_recordings = new_state;
@@ -376,8 +568,32 @@ fn handle_save_recordings_selection<R: Runtime>(_app: &AppHandle<R>, save_record
     }
 }
 
+/// Toggles keeping the unprocessed audio next to saved recordings, which
+/// only takes effect while Save Recordings is on.
+fn handle_save_raw_audio_selection<R: Runtime>(save_raw_audio_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = match config_manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return;
+        }
+    };
+
+    let new_state = !whispr_config.developer.save_raw_audio;
+    set_checked(save_raw_audio_item, new_state);
+    whispr_config.developer.save_raw_audio = new_state;
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+}
+
 fn handle_whisper_logging_selection<R: Runtime>(_app: &AppHandle<R>, whisper_logging_item: &CheckMenuItem<R>) { // New function for Whisper logging
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -391,18 +607,21 @@ fn handle_whisper_logging_selection<R: Runtime>(_app: &AppHandle<R>, whisper_log
     let new_state = !current_state;
 
     debug!("Whisper Logging before toggle: {}", current_state);
-    whisper_logging_item.set_checked(new_state).unwrap();
+    set_checked(whisper_logging_item, new_state);
     debug!("Whisper Logging after toggle: {}", new_state);
 
     whispr_config.developer.whisper_logging = new_state;
+    crate::whisper::set_whisper_logging(new_state);
     if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
         error!("Failed to save configuration: {}", e);
     }
 }
 
-fn handle_language_selection<R: Runtime>(app: &AppHandle<R>, _item: CheckMenuItem<R>, language: &str) {
+pub fn handle_language_selection<R: Runtime>(app: &AppHandle<R>, language: &str) {
     debug!("handle_language_selection called with language: {}", language);
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     debug!("ConfigManager created");
     let mut whispr_config = WhisprConfig::default();
     debug!("WhisprConfig initialized");
@@ -427,15 +646,339 @@ fn handle_language_selection<R: Runtime>(app: &AppHandle<R>, _item: CheckMenuIte
         return;
     }
 
-    let menu_state = app.state::<MenuState<R>>();
+    let Some(menu_state) = app.try_state::<MenuState<R>>() else {
+        return;
+    };
     for (item_id, menu_item) in &menu_state.language_items {
-        menu_item.set_checked(item_id.strip_prefix("language_").unwrap() == language).unwrap();
+        let label = item_id.strip_prefix("language_");
+        let is_active = LANGUAGES.iter().any(|(name, code)| Some(*name) == label && *code == language);
+        set_checked(menu_item, is_active);
     }
     debug!("Menu items updated");
 }
 
+pub fn handle_quality_selection<R: Runtime>(app: &AppHandle<R>, quality: Quality) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = WhisprConfig::default();
+
+    if config_manager.config_exists("settings") {
+        match config_manager.load_config("settings") {
+            Ok(config) => whispr_config = config,
+            Err(e) => {
+                error!("Failed to load configuration: {}", e);
+                return;
+            }
+        }
+    }
+
+    whispr_config.whisper.quality = quality;
+    debug!("Quality updated to: {:?}", quality);
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+        return;
+    }
+
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        for (item_id, menu_item) in &menu_state.quality_items {
+            set_checked(menu_item, item_id.strip_prefix("quality_") == Some(quality.id()));
+        }
+    }
+
+    // Swap the model without a restart if the preset uses a different one
+    let desired_path = models::active_model_path(&whispr_config, config_manager.get_config_dir());
+    if let Some(app_state) = app.try_state::<crate::AppState>() {
+        let loaded_path = app_state.decoder().map(|whisper| whisper.model_path().to_path_buf());
+        if loaded_path.as_deref() != Some(desired_path.as_path()) && desired_path.exists() {
+            info!("Switching to {} for {} quality", desired_path.display(), quality.display_name());
+            app_state.load_model(app, desired_path, whispr_config);
+        }
+    }
+}
+
+fn sink_configuration_hint(sink: SinkKind, config: &WhisprConfig) -> Option<&'static str> {
+    match sink {
+        SinkKind::Obsidian if config.integrations.obsidian.vault_path.is_none() =>
+            Some("Set integrations.obsidian.vault_path in ~/.whispr/settings.json to use the Obsidian output."),
+        SinkKind::File if config.output.file_path.is_none() =>
+            Some("Set output.file_path in ~/.whispr/settings.json to use the journal file output."),
+        SinkKind::Webhook if config.output.webhook.url.is_none() =>
+            Some("Set output.webhook.url in ~/.whispr/settings.json to use the webhook output."),
+        SinkKind::Obs if config.integrations.obs.caption_file.is_none() =>
+            Some("Set integrations.obs.caption_file in ~/.whispr/settings.json and point an OBS text source at it to use the OBS output."),
+        SinkKind::Chat if config.integrations.chat.webhook_url.is_none() =>
+            Some("Set integrations.chat.webhook_url in ~/.whispr/settings.json to a Slack or Discord incoming webhook to use the chat output."),
+        _ => None,
+    }
+}
+
+/// Outputs are a multi-selection: toggling adds the sink to the end of the
+/// pipeline or removes it, but at least one output always stays active.
+fn handle_output_selection<R: Runtime>(app: &AppHandle<R>, sink: SinkKind) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = WhisprConfig::default();
+
+    if config_manager.config_exists("settings") {
+        match config_manager.load_config("settings") {
+            Ok(config) => whispr_config = config,
+            Err(e) => {
+                error!("Failed to load configuration: {}", e);
+                return;
+            }
+        }
+    }
+
+    let sinks = &mut whispr_config.output.sinks;
+    if let Some(position) = sinks.iter().position(|s| *s == sink) {
+        if sinks.len() == 1 {
+            debug!("Refusing to remove the last output");
+        } else {
+            sinks.remove(position);
+        }
+    } else {
+        if let Some(hint) = sink_configuration_hint(sink, &whispr_config) {
+            app.dialog()
+                .message(hint)
+                .kind(MessageDialogKind::Warning)
+                .title("Output Not Configured")
+                .show(|_| {});
+        }
+        whispr_config.output.sinks.push(sink);
+    }
+
+    debug!("Outputs updated to: {:?}", whispr_config.output.sinks);
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+
+    let Some(menu_state) = app.try_state::<MenuState<R>>() else {
+        return;
+    };
+    for (item_id, menu_item) in &menu_state.output_items {
+        let is_active = item_id.strip_prefix("output_").and_then(SinkKind::from_id)
+            .is_some_and(|kind| whispr_config.output.sinks.contains(&kind));
+        set_checked(menu_item, is_active);
+    }
+}
+
+fn handle_dictate_to_file_selection<R: Runtime>(app: &AppHandle<R>, dictate_to_file_item: &CheckMenuItem<R>) {
+    let Some(app_state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+
+    // Turning the mode off just goes back to typing into the focused app
+    if let Some(path) = app_state.dictation_file.lock().unwrap().take() {
+        info!("Stopped dictating to {}", path.display());
+        set_checked(dictate_to_file_item, false);
+        return;
+    }
+
+    // Keep the item unchecked until a file was actually chosen
+    set_checked(dictate_to_file_item, false);
+    let app_handle = app.clone();
+    let dictate_to_file_item = dictate_to_file_item.clone();
+    app.dialog()
+        .file()
+        .set_title("Dictate to File")
+        .set_file_name(format!("dictation-{}.txt", chrono::Local::now().format("%Y-%m-%d")))
+        .add_filter("Text", &["txt", "md"])
+        .save_file(move |file| {
+            let Some(file) = file else {
+                debug!("Dictate to file cancelled");
+                return;
+            };
+            let path = match file.into_path() {
+                Ok(path) => path,
+                Err(e) => {
+                    error!("Invalid dictation file: {}", e);
+                    return;
+                }
+            };
+            if let Some(app_state) = app_handle.try_state::<crate::AppState>() {
+                info!("Dictating to {}", path.display());
+                *app_state.dictation_file.lock().unwrap() = Some(path);
+                set_checked(&dictate_to_file_item, true);
+            }
+        });
+}
+
+fn handle_rolling_buffer_selection<R: Runtime>(app: &AppHandle<R>, rolling_buffer_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = WhisprConfig::default();
+
+    if config_manager.config_exists("settings") {
+        match config_manager.load_config("settings") {
+            Ok(config) => whispr_config = config,
+            Err(e) => error!("Failed to load configuration: {}", e),
+        }
+    }
+
+    whispr_config.rolling_buffer.enabled = !whispr_config.rolling_buffer.enabled;
+    if let Some(app_state) = app.try_state::<crate::AppState>() {
+        if let Err(e) = app_state.configure_rolling_buffer(&whispr_config) {
+            error!("Failed to start rolling buffer: {}", e);
+            whispr_config.rolling_buffer.enabled = false;
+        }
+    }
+
+    let enabled = whispr_config.rolling_buffer.enabled;
+    set_checked(rolling_buffer_item, enabled);
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        if let Some(recall_item) = &menu_state.recall_item {
+            let _ = recall_item.set_enabled(enabled);
+        }
+    }
+    debug!("Rolling buffer enabled: {}", enabled);
+    // Its shortcut only takes the keys while there is something to recall
+    if let Err(e) = crate::shortcuts::register(app, &whispr_config) {
+        error!("Failed to register shortcuts: {}", e);
+    }
+
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+}
+
+fn handle_record_button_selection<R: Runtime>(app: &AppHandle<R>, record_button_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = WhisprConfig::default();
+
+    if config_manager.config_exists("settings") {
+        match config_manager.load_config("settings") {
+            Ok(config) => whispr_config = config,
+            Err(e) => error!("Failed to load configuration: {}", e),
+        }
+    }
+
+    whispr_config.record_button = !whispr_config.record_button;
+    if let Some(app_state) = app.try_state::<crate::AppState>() {
+        let record_button = app_state.record_button.lock().unwrap();
+        if whispr_config.record_button {
+            record_button.show();
+        } else {
+            record_button.hide();
+        }
+    }
+    set_checked(record_button_item, whispr_config.record_button);
+    debug!("Record button shown: {}", whispr_config.record_button);
+
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+}
+
+fn handle_command_mode_selection<R: Runtime>(command_mode_item: &CheckMenuItem<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = WhisprConfig::default();
+
+    if config_manager.config_exists("settings") {
+        match config_manager.load_config("settings") {
+            Ok(config) => whispr_config = config,
+            Err(e) => error!("Failed to load configuration: {}", e),
+        }
+    }
+
+    whispr_config.command_mode = !whispr_config.command_mode;
+    set_checked(command_mode_item, whispr_config.command_mode);
+    debug!("Command mode: {}", whispr_config.command_mode);
+
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+    }
+}
+
+fn handle_ephemeral_session_selection<R: Runtime>(app: &AppHandle<R>, ephemeral_session_item: &CheckMenuItem<R>) {
+    let active = !crate::ephemeral::is_active();
+    crate::ephemeral::set_active(active);
+    if active {
+        // Earlier lines stay in the log file, nothing new goes there
+        log::logger().flush();
+    }
+    set_checked(ephemeral_session_item, active);
+    crate::tray::set_ephemeral_indicator(app, active);
+}
+
+fn handle_capture_stats<R: Runtime>(app: &AppHandle<R>) {
+    let Some(app_state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+    let stats = app_state.audio.lock().unwrap().capture_stats();
+    app.dialog()
+        .message(format!(
+            "Callbacks: {}\nSamples: {} ({} kept after silence removal)\nClipping: {:.2}%\nStream errors: {}\nGaps: {} (longest {} ms)",
+            stats.callbacks, stats.total_samples, stats.kept_samples, stats.clip_percent(), stats.stream_errors, stats.gaps, stats.longest_gap_ms
+        ))
+        .kind(MessageDialogKind::Info)
+        .title("Last Capture Stats")
+        .show(|_| {});
+}
+
+/// Transcribes whatever is currently in the rolling buffer and shows it,
+/// from the menu or `rolling_buffer.shortcut`.
+pub fn handle_recall<R: Runtime>(app: &AppHandle<R>) {
+    let Some(app_state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+    let audio = match app_state.rolling_buffer.lock().unwrap().as_ref().map(|buffer| buffer.snapshot()) {
+        Some(Ok(audio)) => audio,
+        Some(Err(e)) => {
+            error!("Failed to read rolling buffer: {}", e);
+            return;
+        }
+        None => {
+            debug!("Rolling buffer is not running");
+            crate::show_toast(app, "Rolling buffer is off");
+            return;
+        }
+    };
+    let Some(whisper) = app_state.decoder() else {
+        app.dialog()
+            .message("The model is still loading, try again in a moment.")
+            .kind(MessageDialogKind::Info)
+            .title("What Did They Just Say?")
+            .show(|_| {});
+        return;
+    };
+
+    let whisper_settings = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .map(|config| config.whisper)
+        .unwrap_or_default();
+
+    let app_handle = app.clone();
+    std::thread::spawn(move || {
+        let message = match whisper.process_audio(&audio, &whisper_settings) {
+            Ok(segments) if !segments.is_empty() => segments.iter()
+                .map(|(_, _, segment)| segment.trim())
+                .collect::<Vec<&str>>()
+                .join(" "),
+            Ok(_) => "Nothing was said in the last few seconds.".to_string(),
+            Err(e) => {
+                error!("Failed to transcribe rolling buffer: {}", e);
+                format!("Transcription failed: {}", e)
+            }
+        };
+        app_handle.dialog()
+            .message(message)
+            .kind(MessageDialogKind::Info)
+            .title("What Did They Just Say?")
+            .show(|_| {});
+    });
+}
+
 fn handle_translate_selection<R: Runtime>(_app: &AppHandle<R>, translate_item: &CheckMenuItem<R>) {
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -449,7 +992,7 @@ fn handle_translate_selection<R: Runtime>(_app: &AppHandle<R>, translate_item: &
     let new_state = !current_state;
 
     debug!("Translate before toggle: {}", current_state);
-    translate_item.set_checked(new_state).unwrap();
+    set_checked(translate_item, new_state);
     debug!("Translate after toggle: {}", new_state);
 
     whispr_config.whisper.translate = new_state;
@@ -461,7 +1004,9 @@ fn handle_translate_selection<R: Runtime>(_app: &AppHandle<R>, translate_item: &
 fn handle_start_at_login_selection<R: Runtime>(app: &AppHandle<R>, start_at_login_item: &CheckMenuItem<R>) {
     debug!("Start at login selection handler called");
     
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -504,7 +1049,9 @@ fn handle_start_at_login_selection<R: Runtime>(app: &AppHandle<R>, start_at_logi
 }
 
 fn handle_logging_selection<R: Runtime>(app: &AppHandle<R>, logging_item: &CheckMenuItem<R>) {
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -533,16 +1080,19 @@ fn handle_logging_selection<R: Runtime>(app: &AppHandle<R>, logging_item: &Check
                     return;
                 }
 
-                logging_item.set_checked(new_state).unwrap();
+                set_checked(&logging_item, new_state);
+                crate::shutdown(&app_handle);
                 app_handle.restart();
             } else {
-                logging_item.set_checked(current_state).unwrap();
+                set_checked(&logging_item, current_state);
             }
         });
 }
 
 fn handle_keyboard_shortcut_selection<R: Runtime>(app: &AppHandle<R>, _item: CheckMenuItem<R>, shortcut: &str) {
-    let config_manager = ConfigManager::<WhisprConfig>::new("settings").expect("Failed to create config manager");
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
     let mut whispr_config = WhisprConfig::default();
     
     if config_manager.config_exists("settings") {
@@ -570,19 +1120,152 @@ fn handle_keyboard_shortcut_selection<R: Runtime>(app: &AppHandle<R>, _item: Che
                     return;
                 }
 
-                let menu_state = app_handle.state::<MenuState<R>>();
-                for (item_id, menu_item) in &menu_state.keyboard_shortcut_items {
-                    menu_item.set_checked(item_id.strip_prefix("keyboard_shortcut_").unwrap() == target_shortcut).unwrap();
+                if let Some(menu_state) = app_handle.try_state::<MenuState<R>>() {
+                    for (item_id, menu_item) in &menu_state.keyboard_shortcut_items {
+                        set_checked(menu_item, item_id.strip_prefix("keyboard_shortcut_") == Some(target_shortcut));
+                    }
                 }
 
                 // Restart the app
+                crate::shutdown(&app_handle);
                 app_handle.restart();
             } else {
                 // Revert the menu item state to the previous shortcut
-                let menu_state = app_handle.state::<MenuState<R>>();
+                let Some(menu_state) = app_handle.try_state::<MenuState<R>>() else {
+                    return;
+                };
                 for (item_id, menu_item) in &menu_state.keyboard_shortcut_items {
-                    menu_item.set_checked(item_id.strip_prefix("keyboard_shortcut_").unwrap() == current_shortcut).unwrap();
+                    set_checked(menu_item, item_id.strip_prefix("keyboard_shortcut_") == Some(current_shortcut));
                 }
             }
         });
 }
+
+fn handle_models_dir_selection<R: Runtime>(app: &AppHandle<R>) {
+    let app_handle = app.clone();
+    app.dialog()
+        .file()
+        .set_title("Choose Models Folder")
+        .pick_folder(move |folder| {
+            let Some(folder) = folder else {
+                debug!("Models folder selection cancelled");
+                return;
+            };
+            let models_dir = match folder.into_path() {
+                Ok(path) => path,
+                Err(e) => {
+                    error!("Invalid models folder: {}", e);
+                    return;
+                }
+            };
+
+            let Some(config_manager) = config_manager() else {
+                return;
+            };
+            let mut whispr_config = WhisprConfig::default();
+            if let Ok(config) = config_manager.load_config("settings") {
+                whispr_config = config;
+            }
+            whispr_config.models_dir = Some(models_dir.to_string_lossy().to_string());
+            if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+                error!("Failed to save configuration: {}", e);
+                return;
+            }
+            info!("Models folder set to {}", models_dir.display());
+
+            let model_path = models::model_path(&whispr_config, config_manager.get_config_dir());
+            if !model_path.exists() {
+                app_handle.dialog()
+                    .message(format!("No model found at {}", model_path.display()))
+                    .kind(MessageDialogKind::Warning)
+                    .title("Model Not Found")
+                    .show(|_| {});
+                return;
+            }
+
+            if let Some(app_state) = app_handle.try_state::<crate::AppState>() {
+                app_state.load_model(&app_handle, model_path, whispr_config);
+            }
+        });
+}
+
+/// Makes `model` the preferred model, then downloads it over the current
+/// one and swaps it in. Quantized models trade a little accuracy for fitting
+/// into less memory.
+pub fn select_model<R: Runtime>(app: &AppHandle<R>, model: CatalogModel) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let mut whispr_config = match config_manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return;
+        }
+    };
+
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        for (item_id, menu_item) in &menu_state.model_items {
+            set_checked(menu_item, item_id.strip_prefix("select_model_") == Some(model.id));
+        }
+        if let Some(item) = &menu_state.download_model_item {
+            let _ = item.set_text(format!("Download {}", model.display_name));
+        }
+    }
+    if whispr_config.model.filename == model.filename {
+        return;
+    }
+
+    whispr_config.model = model.model();
+    info!("Preferred model changed to {}", model.display_name);
+    if let Err(e) = config_manager.save_config(&whispr_config, "settings") {
+        error!("Failed to save configuration: {}", e);
+        return;
+    }
+    let model_path = models::download_path(&whispr_config, config_manager.get_config_dir());
+    download_and_load(app, whispr_config, model_path);
+}
+
+fn handle_download_model<R: Runtime>(app: &AppHandle<R>) {
+    let Some(config_manager) = config_manager() else {
+        return;
+    };
+    let whispr_config = match config_manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return;
+        }
+    };
+    let model_path = models::download_path(&whispr_config, config_manager.get_config_dir());
+    download_and_load(app, whispr_config, model_path);
+}
+
+fn download_and_load<R: Runtime>(app: &AppHandle<R>, whispr_config: WhisprConfig, model_path: std::path::PathBuf) {
+    let app_handle = app.clone();
+
+    std::thread::spawn(move || {
+        let result = models::download_model(&whispr_config.model, &model_path, &whispr_config, |status| {
+            set_model_status(&app_handle, &status.to_string());
+        });
+
+        match result {
+            Ok(_) => {
+                info!("Downloaded {} to {}", whispr_config.model.display_name, model_path.display());
+                refresh_storage_usage(&app_handle);
+                if let Some(app_state) = app_handle.try_state::<crate::AppState>() {
+                    app_state.load_model(&app_handle, model_path, whispr_config);
+                }
+            }
+            Err(e) => {
+                error!("Model download failed: {}", e);
+                set_model_status(&app_handle, "Download failed");
+                app_handle.dialog()
+                    .message(format!("Failed to download the model: {}", e))
+                    .kind(MessageDialogKind::Error)
+                    .title("Download Failed")
+                    .show(|_| {});
+            }
+        }
+    });
+}
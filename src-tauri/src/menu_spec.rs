@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use tauri::{
+    AppHandle, Runtime,
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, MenuItemKind, PredefinedMenuItem, Submenu},
+};
+use crate::config::{AudioSource, CatalogModel, MenuSection, Quality, SinkKind, WhisprConfig, MODEL_CATALOG};
+use crate::input_device::DeviceEntry;
+use crate::language_names;
+
+/// Language submenu entries as (English name, whisper language code). The
+/// English name is part of the item id, e.g. `language_German`, the menu
+/// shows the name from [`crate::language_names`].
+pub const LANGUAGES: [(&str, &str); 5] = [
+    ("Automatic", "auto"),
+    ("English", "en"),
+    ("German", "de"),
+    ("French", "fr"),
+    ("Spanish", "es"),
+];
+
+/// Keyboard shortcut submenu entries as (label, `keyboard_shortcut` value).
+pub const SHORTCUTS: [(&str, &str); 3] = [
+    ("Right Option Key", "right_option_key"),
+    ("Right Command Key", "right_command_key"),
+    ("Globe Key", "globe_key"),
+];
+
+/// One line summary of the active configuration for the tray tooltip and the
+/// menu header, e.g. "Balanced · Whisper Large v3 Turbo · German · Copy to Clipboard".
+/// The language is named in the UI `locale`.
+pub fn config_summary(config: &WhisprConfig, locale: &str) -> String {
+    let quality = config.whisper.quality;
+    let model = if quality.uses_fallback_model() { &config.fallback_model } else { &config.model };
+    let code = config.whisper.language.as_deref().unwrap_or("auto");
+    let language = language_names::display_name(code, locale, false);
+    let outputs = if config.command_mode {
+        "Command Mode".to_string()
+    } else {
+        config.output.sinks.iter()
+            .map(|sink| sink.display_name())
+            .collect::<Vec<_>>()
+            .join(" + ")
+    };
+    format!("{} · {} · {} · {}", quality.display_name(), model.display_name, language, outputs)
+}
+
+/// A single entry of the tray menu, independent of Tauri so the mapping from
+/// the configuration can be tested.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuEntry {
+    Item { id: String, label: String, enabled: bool },
+    Check { id: String, label: String, checked: bool },
+    Submenu { label: String, entries: Vec<MenuEntry> },
+    Separator,
+}
+
+fn item(id: &str, label: impl Into<String>) -> MenuEntry {
+    MenuEntry::Item { id: id.to_string(), label: label.into(), enabled: true }
+}
+
+fn disabled_item(id: &str, label: impl Into<String>) -> MenuEntry {
+    MenuEntry::Item { id: id.to_string(), label: label.into(), enabled: false }
+}
+
+fn check(id: impl Into<String>, label: impl Into<String>, checked: bool) -> MenuEntry {
+    MenuEntry::Check { id: id.into(), label: label.into(), checked }
+}
+
+fn submenu(label: &str, entries: Vec<MenuEntry>) -> MenuEntry {
+    MenuEntry::Submenu { label: label.to_string(), entries }
+}
+
+/// Declarative description of the tray menu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuSpec {
+    pub entries: Vec<MenuEntry>,
+}
+
+/// Tauri menu built from a [`MenuSpec`], with its items by id so handlers can
+/// update them later.
+pub struct RenderedMenu<R: Runtime> {
+    pub menu: Menu<R>,
+    pub checks: HashMap<String, CheckMenuItem<R>>,
+    pub items: HashMap<String, MenuItem<R>>,
+}
+
+impl MenuSpec {
+    /// The tray menu for `config`. `devices` are the available input devices,
+    /// `storage_label` the current disk usage line, `locale` the UI locale
+    /// language names are shown in and `recommended` the model suited to
+    /// this Mac's memory.
+    pub fn from_config(config: &WhisprConfig, devices: &[DeviceEntry], storage_label: String, locale: &str, recommended: Option<CatalogModel>) -> Self {
+        let language = config.whisper.language.as_deref().unwrap_or("auto");
+
+        let keyboard_shortcuts = SHORTCUTS.iter()
+            .map(|(label, shortcut)| check(format!("keyboard_shortcut_{}", shortcut), *label, config.keyboard_shortcut == *shortcut))
+            .collect();
+        let microphone = config.audio.source == AudioSource::Microphone;
+        let mut audio_devices: Vec<MenuEntry> = devices.iter()
+            .map(|device| check(format!("audio_device_{}", device.id), device.label(), microphone && config.audio.selected_device() == Some(device.id.as_str())))
+            .collect();
+        audio_devices.push(MenuEntry::Separator);
+        audio_devices.push(check("system_audio", "System Audio", !microphone));
+        let qualities = Quality::ALL.iter()
+            .map(|quality| check(format!("quality_{}", quality.id()), quality.display_name(), config.whisper.quality == *quality))
+            .collect();
+        let languages = LANGUAGES.iter()
+            .map(|(name, code)| {
+                let label = language_names::display_name(code, locale, config.ui.native_language_names);
+                check(format!("language_{}", name), label, language == *code)
+            })
+            .collect();
+        let model_choices = MODEL_CATALOG.iter()
+            .map(|model| {
+                let label = if recommended == Some(*model) {
+                    format!("{} · Recommended", model.display_name)
+                } else {
+                    model.display_name.to_string()
+                };
+                check(format!("select_model_{}", model.id), label, config.model.filename == model.filename)
+            })
+            .collect();
+        let mut outputs: Vec<MenuEntry> = SinkKind::ALL.iter()
+            .map(|sink| check(format!("output_{}", sink.id()), sink.display_name(), config.output.sinks.contains(sink)))
+            .collect();
+        // Dictate to File is per session and never restored
+        outputs.push(check("dictate_to_file", "Dictate to File…", false));
+
+        let shown = |section: MenuSection| config.ui.menu_sections.contains(&section);
+        let mut entries = vec![
+            disabled_item("summary", config_summary(config, locale)),
+            disabled_item("model_status", format!("Model: {}", config.model.display_name)),
+            item("quit", "Quit"),
+            MenuEntry::Separator,
+            check("start_at_login", "Start at Login", config.start_at_login),
+        ];
+        if shown(MenuSection::KeyboardShortcut) {
+            entries.push(submenu("Keyboard Shortcut", keyboard_shortcuts));
+            entries.push(check("record_button", "Show Record Button", config.record_button));
+        }
+        entries.push(MenuEntry::Separator);
+        if shown(MenuSection::AudioDevice) {
+            entries.push(submenu("Audio Device", audio_devices));
+        }
+        if shown(MenuSection::Quality) {
+            entries.push(submenu("Quality", qualities));
+        }
+        if shown(MenuSection::Language) {
+            entries.push(submenu("Language", languages));
+            entries.push(check("translate", "Translate to English", config.whisper.translate));
+        }
+        entries.push(check("remove_silence", "Remove Silence", config.audio.remove_silence));
+        entries.push(check("denoise", "Reduce Noise", config.audio.dsp.denoise));
+        if shown(MenuSection::Output) {
+            entries.push(submenu("Output", outputs));
+            entries.push(check("command_mode", "Command Mode", config.command_mode));
+            entries.push(item("paste_last", "Paste Last Transcription"));
+            // Per session like Dictate to File
+            entries.push(check("ephemeral_session", "Ephemeral Session", crate::ephemeral::is_active()));
+            entries.push(item("history", "History…"));
+            if config.analytics.enabled {
+                entries.push(item("analytics_report", "Monthly Report…"));
+            }
+        }
+        if shown(MenuSection::Models) {
+            entries.push(submenu("Model", model_choices));
+            entries.push(item("models_dir", "Models Folder…"));
+            entries.push(item("download_model", format!("Download {}", config.model.display_name)));
+        }
+        if shown(MenuSection::RollingBuffer) {
+            entries.push(MenuEntry::Separator);
+            entries.push(check("rolling_buffer", format!("Keep Last {}s in Memory", config.rolling_buffer.seconds), config.rolling_buffer.enabled));
+            entries.push(MenuEntry::Item {
+                id: "recall".to_string(),
+                label: "What Did They Just Say?".to_string(),
+                enabled: config.rolling_buffer.enabled,
+            });
+        }
+        if shown(MenuSection::DeveloperOptions) {
+            entries.push(MenuEntry::Separator);
+            entries.push(submenu("Developer Options", vec![
+                check("save_recordings", "Save Recordings", config.developer.save_recordings),
+                check("save_raw_audio", "Also Save Raw Audio", config.developer.save_raw_audio),
+                check("whisper_logging", "Whisper Logging", config.developer.whisper_logging),
+                check("logging", "Logging", config.developer.logging),
+                disabled_item("storage_usage", storage_label),
+                item("capture_stats", "Last Capture Stats…"),
+                item("console", "Log Console…"),
+                item("spectrogram", "Spectrogram…"),
+                item("benchmark", "Benchmark Model"),
+                item("restart", "Restart"),
+            ]));
+        }
+        entries.push(item("about", "About"));
+
+        Self { entries }
+    }
+
+    /// Checked state of every check item by id, including those in submenus.
+    pub fn checked_states(&self) -> HashMap<String, bool> {
+        fn collect(entries: &[MenuEntry], states: &mut HashMap<String, bool>) {
+            for entry in entries {
+                match entry {
+                    MenuEntry::Check { id, checked, .. } => {
+                        states.insert(id.clone(), *checked);
+                    }
+                    MenuEntry::Submenu { entries, .. } => collect(entries, states),
+                    MenuEntry::Item { .. } | MenuEntry::Separator => {}
+                }
+            }
+        }
+
+        let mut states = HashMap::new();
+        collect(&self.entries, &mut states);
+        states
+    }
+
+    pub fn render<R: Runtime>(&self, app: &AppHandle<R>) -> tauri::Result<RenderedMenu<R>> {
+        let mut checks = HashMap::new();
+        let mut items = HashMap::new();
+        let kinds = render_entries(app, &self.entries, &mut checks, &mut items)?;
+        let refs: Vec<&dyn IsMenuItem<R>> = kinds.iter().map(|kind| kind as &dyn IsMenuItem<R>).collect();
+        let menu = Menu::with_items(app, &refs)?;
+        Ok(RenderedMenu { menu, checks, items })
+    }
+}
+
+fn render_entries<R: Runtime>(
+    app: &AppHandle<R>,
+    entries: &[MenuEntry],
+    checks: &mut HashMap<String, CheckMenuItem<R>>,
+    items: &mut HashMap<String, MenuItem<R>>,
+) -> tauri::Result<Vec<MenuItemKind<R>>> {
+    let mut kinds = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let kind = match entry {
+            MenuEntry::Item { id, label, enabled } => {
+                let menu_item = MenuItem::with_id(app, id, label, *enabled, None::<String>)?;
+                items.insert(id.clone(), menu_item.clone());
+                MenuItemKind::MenuItem(menu_item)
+            }
+            MenuEntry::Check { id, label, checked } => {
+                let check_item = CheckMenuItem::with_id(app, id, label, true, *checked, None::<String>)?;
+                checks.insert(id.clone(), check_item.clone());
+                MenuItemKind::Check(check_item)
+            }
+            MenuEntry::Submenu { label, entries } => {
+                let children = render_entries(app, entries, checks, items)?;
+                let refs: Vec<&dyn IsMenuItem<R>> = children.iter().map(|kind| kind as &dyn IsMenuItem<R>).collect();
+                MenuItemKind::Submenu(Submenu::with_items(app, label, true, &refs)?)
+            }
+            MenuEntry::Separator => MenuItemKind::Predefined(PredefinedMenuItem::separator(app)?),
+        };
+        kinds.push(kind);
+    }
+    Ok(kinds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn spec(config: &WhisprConfig) -> MenuSpec {
+        let devices = crate::input_device::identify(["MacBook Pro Microphone".to_string(), "USB Mic".to_string()]);
+        MenuSpec::from_config(config, &devices, "Disk: 1 GB free".to_string(), "en", None)
+    }
+
+    fn find<'a>(entries: &'a [MenuEntry], wanted: &str) -> Option<&'a MenuEntry> {
+        entries.iter().find_map(|entry| match entry {
+            MenuEntry::Item { id, .. } | MenuEntry::Check { id, .. } if id == wanted => Some(entry),
+            MenuEntry::Submenu { entries, .. } => find(entries, wanted),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn default_config_checks() {
+        let states = spec(&WhisprConfig::default()).checked_states();
+        assert!(states["language_Automatic"]);
+        assert!(!states["language_German"]);
+        assert!(states["keyboard_shortcut_right_command_key"]);
+        assert!(!states["keyboard_shortcut_right_option_key"]);
+        assert!(states["quality_balanced"]);
+        assert!(states["remove_silence"]);
+        assert!(!states["denoise"]);
+        assert!(!states["system_audio"]);
+        assert!(!states["dictate_to_file"]);
+        assert!(!states["record_button"]);
+        assert!(!states["command_mode"]);
+        assert!(!states["ephemeral_session"]);
+    }
+
+    #[test]
+    fn checks_follow_config() {
+        let mut config = WhisprConfig::default();
+        config.whisper.language = Some("de".to_string());
+        config.whisper.quality = Quality::Accurate;
+        config.keyboard_shortcut = "globe_key".to_string();
+        config.audio.device_name = Some("USB Mic".to_string());
+        config.audio.remove_silence = false;
+        config.audio.dsp.denoise = true;
+        config.developer.save_recordings = true;
+        config.output.sinks = vec![SinkKind::Clipboard, SinkKind::File];
+
+        let states = spec(&config).checked_states();
+        assert!(states["language_German"]);
+        assert!(!states["language_Automatic"]);
+        assert!(states["quality_accurate"]);
+        assert!(!states["quality_balanced"]);
+        assert!(states["keyboard_shortcut_globe_key"]);
+        assert!(states["audio_device_USB Mic"]);
+        assert!(!states["audio_device_MacBook Pro Microphone"]);
+        assert!(!states["remove_silence"]);
+        assert!(states["denoise"]);
+        assert!(states["save_recordings"]);
+        for sink in SinkKind::ALL {
+            assert_eq!(states[&format!("output_{}", sink.id())], config.output.sinks.contains(&sink));
+        }
+    }
+
+    #[test]
+    fn system_audio_replaces_the_microphone() {
+        let mut config = WhisprConfig::default();
+        config.audio.device_name = Some("USB Mic".to_string());
+        config.audio.source = AudioSource::SystemAudio;
+        let states = spec(&config).checked_states();
+        assert!(states["system_audio"]);
+        assert!(!states["audio_device_USB Mic"]);
+    }
+
+    #[test]
+    fn duplicate_device_names_stay_apart() {
+        let devices = crate::input_device::identify(["USB Audio Device".to_string(), "USB Audio Device".to_string()]);
+        assert_eq!(devices[0].id, "USB Audio Device");
+        assert_eq!(devices[1].id, "USB Audio Device#2");
+
+        let mut config = WhisprConfig::default();
+        config.audio.device_name = Some("USB Audio Device".to_string());
+        config.audio.device_id = Some("USB Audio Device#2".to_string());
+        let spec = MenuSpec::from_config(&config, &devices, "Disk: 1 GB free".to_string(), "en", None);
+        let states = spec.checked_states();
+        assert!(!states["audio_device_USB Audio Device"]);
+        assert!(states["audio_device_USB Audio Device#2"]);
+        match find(&spec.entries, "audio_device_USB Audio Device#2") {
+            Some(MenuEntry::Check { label, .. }) => assert_eq!(label, "USB Audio Device (2)"),
+            other => panic!("unexpected entry {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recall_follows_rolling_buffer() {
+        let mut config = WhisprConfig::default();
+        config.rolling_buffer.enabled = false;
+        assert!(matches!(find(&spec(&config).entries, "recall"), Some(MenuEntry::Item { enabled: false, .. })));
+
+        config.rolling_buffer.enabled = true;
+        assert!(matches!(find(&spec(&config).entries, "recall"), Some(MenuEntry::Item { enabled: true, .. })));
+        assert!(spec(&config).checked_states()["rolling_buffer"]);
+    }
+
+    #[test]
+    fn hidden_sections_are_left_out() {
+        let mut config = WhisprConfig::default();
+        config.ui.menu_sections = vec![MenuSection::Quality];
+        let spec = spec(&config);
+        let states = spec.checked_states();
+
+        assert!(states.contains_key("quality_balanced"));
+        assert!(!states.contains_key("language_Automatic"));
+        assert!(!states.contains_key("translate"));
+        assert!(!states.contains_key("save_recordings"));
+        assert!(find(&spec.entries, "recall").is_none());
+        assert!(find(&spec.entries, "quit").is_some());
+        assert!(find(&spec.entries, "about").is_some());
+    }
+
+    #[test]
+    fn summary_names_quality_model_language_and_outputs() {
+        let mut config = WhisprConfig::default();
+        config.whisper.language = Some("de".to_string());
+        config.output.sinks = vec![SinkKind::Clipboard];
+        assert_eq!(config_summary(&config, "en"), "Balanced · Whisper Large v3 Turbo · German · Copy to Clipboard");
+
+        config.whisper.quality = Quality::Fast;
+        config.whisper.language = Some("sw".to_string());
+        config.output.sinks = vec![SinkKind::Keyboard, SinkKind::File];
+        assert_eq!(config_summary(&config, "en"), "Fast · Whisper Tiny (quantized) · sw · Type into Focused App + Append to Journal File");
+
+        config.whisper.language = Some("de".to_string());
+        assert!(config_summary(&config, "fr").contains(" · Allemand · "));
+    }
+
+    #[test]
+    fn language_labels_are_localized() {
+        let devices = crate::input_device::identify(["USB Mic".to_string()]);
+        let mut config = WhisprConfig::default();
+        let label = |spec: &MenuSpec, id: &str| match find(&spec.entries, id) {
+            Some(MenuEntry::Check { label, .. }) => label.clone(),
+            other => panic!("unexpected entry {:?}", other),
+        };
+
+        let spec = MenuSpec::from_config(&config, &devices, "Disk: 1 GB free".to_string(), "de", None);
+        assert_eq!(label(&spec, "language_German"), "Deutsch");
+        assert_eq!(label(&spec, "language_Automatic"), "Automatisch");
+
+        config.ui.native_language_names = true;
+        let spec = MenuSpec::from_config(&config, &devices, "Disk: 1 GB free".to_string(), "en", None);
+        assert_eq!(label(&spec, "language_German"), "Deutsch (German)");
+        assert_eq!(label(&spec, "language_English"), "English");
+    }
+
+    #[test]
+    fn model_choices_mark_the_recommendation() {
+        let mut config = WhisprConfig::default();
+        config.model = CatalogModel::from_id("large-v3-turbo-q5_0").unwrap().model();
+        let devices = crate::input_device::identify(["USB Mic".to_string()]);
+        let spec = MenuSpec::from_config(&config, &devices, "Disk: 1 GB free".to_string(), "en", Some(CatalogModel::recommended(8)));
+        let states = spec.checked_states();
+        assert!(states["select_model_large-v3-turbo-q5_0"]);
+        assert!(!states["select_model_large-v3-turbo"]);
+        match find(&spec.entries, "select_model_large-v3-turbo-q8_0") {
+            Some(MenuEntry::Check { label, .. }) => assert_eq!(label, "Whisper Large v3 Turbo (q8_0) · Recommended"),
+            other => panic!("unexpected entry {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        fn collect_ids(entries: &[MenuEntry], ids: &mut Vec<String>) {
+            for entry in entries {
+                match entry {
+                    MenuEntry::Item { id, .. } | MenuEntry::Check { id, .. } => ids.push(id.clone()),
+                    MenuEntry::Submenu { entries, .. } => collect_ids(entries, ids),
+                    MenuEntry::Separator => {}
+                }
+            }
+        }
+
+        let mut ids = Vec::new();
+        collect_ids(&spec(&WhisprConfig::default()).entries, &mut ids);
+        let unique: HashSet<&String> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+    }
+}
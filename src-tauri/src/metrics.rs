@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use whispr_core::error::ErrorCode;
+
+const DECODE_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+const AUDIO_BUCKETS: &[f64] = &[1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// What a transcription was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Dictation,
+    Api,
+}
+
+impl Source {
+    fn label(self) -> &'static str {
+        match self {
+            Source::Dictation => "dictation",
+            Source::Api => "api",
+        }
+    }
+}
+
+struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket, the last one above every bound
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64]) -> Self {
+        Self { bounds, counts: Vec::new(), sum: 0.0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        self.counts.resize(self.bounds.len() + 1, 0);
+        let bucket = self.bounds.iter().position(|bound| value <= *bound).unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (index, bound) in self.bounds.iter().enumerate() {
+            cumulative += self.counts.get(index).copied().unwrap_or(0);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count: u64 = self.counts.iter().sum();
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// Counters since whispr started, for the API's `/metrics`.
+struct Metrics {
+    transcriptions: BTreeMap<Source, u64>,
+    errors: BTreeMap<&'static str, u64>,
+    decode_seconds: Histogram,
+    audio_seconds: Histogram,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            transcriptions: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            decode_seconds: Histogram::new(DECODE_BUCKETS),
+            audio_seconds: Histogram::new(AUDIO_BUCKETS),
+        }
+    }
+
+    fn record_decode(&mut self, source: Source, audio_secs: f32, decode: Duration) {
+        *self.transcriptions.entry(source).or_default() += 1;
+        self.decode_seconds.observe(decode.as_secs_f64());
+        self.audio_seconds.observe(audio_secs as f64);
+    }
+
+    /// The Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP whispr_transcriptions_total Whisper runs that produced a result.");
+        let _ = writeln!(out, "# TYPE whispr_transcriptions_total counter");
+        for source in [Source::Dictation, Source::Api] {
+            let count = self.transcriptions.get(&source).copied().unwrap_or(0);
+            let _ = writeln!(out, "whispr_transcriptions_total{{source=\"{}\"}} {}", source.label(), count);
+        }
+        self.decode_seconds.render(&mut out, "whispr_decode_seconds", "Time whisper took per transcription.");
+        self.audio_seconds.render(&mut out, "whispr_audio_seconds", "Length of the transcribed audio.");
+        let _ = writeln!(out, "# HELP whispr_errors_total Failed dictations and API transcriptions by error code.");
+        let _ = writeln!(out, "# TYPE whispr_errors_total counter");
+        for (code, count) in &self.errors {
+            let _ = writeln!(out, "whispr_errors_total{{code=\"{}\"}} {}", code, count);
+        }
+        out
+    }
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+/// Counts a successful whisper run over `audio_secs` of audio that took
+/// `decode`.
+pub fn record_decode(source: Source, audio_secs: f32, decode: Duration) {
+    METRICS.lock().unwrap().record_decode(source, audio_secs, decode);
+}
+
+pub fn record_error(code: ErrorCode) {
+    *METRICS.lock().unwrap().errors.entry(code.as_str()).or_default() += 1;
+}
+
+pub fn render() -> String {
+    METRICS.lock().unwrap().render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_cumulative_buckets() {
+        let mut metrics = Metrics::new();
+        metrics.record_decode(Source::Dictation, 4.0, Duration::from_millis(800));
+        metrics.record_decode(Source::Dictation, 45.0, Duration::from_secs(3));
+        metrics.record_decode(Source::Api, 900.0, Duration::from_secs(120));
+        *metrics.errors.entry(ErrorCode::Transcription.as_str()).or_default() += 1;
+        let text = metrics.render();
+
+        assert!(text.contains("whispr_transcriptions_total{source=\"dictation\"} 2\n"));
+        assert!(text.contains("whispr_transcriptions_total{source=\"api\"} 1\n"));
+        assert!(text.contains("whispr_decode_seconds_bucket{le=\"0.5\"} 0\n"));
+        assert!(text.contains("whispr_decode_seconds_bucket{le=\"1\"} 1\n"));
+        assert!(text.contains("whispr_decode_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("whispr_decode_seconds_bucket{le=\"60\"} 2\n"));
+        assert!(text.contains("whispr_decode_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("whispr_decode_seconds_count 3\n"));
+        assert!(text.contains("whispr_audio_seconds_sum 949\n"));
+        assert!(text.contains("whispr_errors_total{code=\"E_TRANSCRIPTION\"} 1\n"));
+    }
+
+    #[test]
+    fn empty_histograms_have_every_bucket() {
+        let text = Metrics::new().render();
+        assert!(text.contains("whispr_audio_seconds_bucket{le=\"600\"} 0\n"));
+        assert!(text.contains("whispr_audio_seconds_count 0\n"));
+    }
+}
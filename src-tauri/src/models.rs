@@ -0,0 +1,285 @@
+use anyhow::{Context, Result};
+use log::{info, debug, warn};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use crate::config::{expand_path, CatalogModel, Model, WhisprConfig};
+use crate::download;
+use crate::whisper::{coreml_encoder_path, ModelStatus};
+
+/// Name the preferred model was stored under before it was kept under its
+/// own filename, whatever model it is.
+const LEGACY_MODEL_FILENAME: &str = "model.bin";
+const MOUNT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Directory holding the models, falls back to the config directory when no
+/// `models_dir` is configured.
+pub fn models_dir(config: &WhisprConfig, config_dir: &Path) -> PathBuf {
+    match &config.models_dir {
+        Some(dir) if !dir.trim().is_empty() => expand_path(dir),
+        _ => config_dir.to_path_buf(),
+    }
+}
+
+/// Where the preferred model is downloaded to: `model_path` if configured,
+/// else `model.filename` in the models directory.
+pub fn download_path(config: &WhisprConfig, config_dir: &Path) -> PathBuf {
+    match &config.model_path {
+        Some(path) if !path.trim().is_empty() => expand_path(path),
+        _ => models_dir(config, config_dir).join(&config.model.filename),
+    }
+}
+
+/// The preferred model, see `download_path`. A `model.bin` left by an older
+/// version is used until the model is downloaded under its own name.
+pub fn model_path(config: &WhisprConfig, config_dir: &Path) -> PathBuf {
+    let path = download_path(config, config_dir);
+    if path.exists() || config.model_path.as_deref().is_some_and(|path| !path.trim().is_empty()) {
+        return path;
+    }
+    let legacy = models_dir(config, config_dir).join(LEGACY_MODEL_FILENAME);
+    if legacy.exists() {
+        debug!("Using {} until {} is downloaded", legacy.display(), config.model.filename);
+        return legacy;
+    }
+    path
+}
+
+/// Location of the small model used until the preferred model is available.
+pub fn fallback_model_path(config: &WhisprConfig, config_dir: &Path) -> PathBuf {
+    models_dir(config, config_dir).join(&config.fallback_model.filename)
+}
+
+/// Model to load for the configured quality preset. Fast uses the fallback
+/// model if it has been downloaded, everything else the preferred model.
+pub fn active_model_path(config: &WhisprConfig, config_dir: &Path) -> PathBuf {
+    if config.whisper.quality.uses_fallback_model() {
+        let fallback_path = fallback_model_path(config, config_dir);
+        if fallback_path.exists() {
+            return fallback_path;
+        }
+        debug!("Fallback model not downloaded, using the preferred model for {:?}", config.whisper.quality);
+    }
+    model_path(config, config_dir)
+}
+
+/// Downloads `url` to `dest`, reporting progress as whole percent steps.
+fn download_with_progress<F>(url: &str, dest: &Path, config: &WhisprConfig, on_percent: F) -> Result<()>
+where
+    F: Fn(u8),
+{
+    let mut last_percent = None;
+    download::download_file(url, dest, config, |downloaded, total| {
+        if total == 0 {
+            return;
+        }
+        let percent = (downloaded * 100 / total) as u8;
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            on_percent(percent);
+        }
+    })
+}
+
+/// Downloads `model` to `dest`, followed by its Core ML encoder if enabled.
+/// An encoder of the model previously at `dest` is removed either way.
+pub fn download_model<F>(model: &Model, dest: &Path, config: &WhisprConfig, on_status: F) -> Result<()>
+where
+    F: Fn(ModelStatus),
+{
+    info!("Downloading {} to {}", model.display_name, dest.display());
+    remove_coreml_encoder(dest);
+    let pinned = model.published_sha256();
+    let expected = pinned.clone().or_else(|| download::published_sha256(&model.url, &config.network));
+    download_with_progress(&model.url, dest, config, |percent| on_status(ModelStatus::Downloading(percent)))?;
+    let hash = sha256_file(dest).with_context(|| format!("Failed to verify {}", dest.display()))?;
+    if let Some(expected) = expected {
+        if !hash.eq_ignore_ascii_case(expected.trim()) {
+            remove_model(dest)?;
+            anyhow::bail!("Downloaded {} does not match its SHA-256 checksum", model.display_name);
+        }
+    }
+    // Later launches can tell whether a model without a checksum in the config was damaged since
+    if pinned.is_none() {
+        if let Err(e) = fs::write(hash_path(dest), &hash) {
+            warn!("Failed to record hash of {}: {}", dest.display(), e);
+        }
+    }
+    if wants_coreml_encoder(model, config) {
+        // Transcription works without it, just slower
+        if let Err(e) = download_coreml_encoder(model, dest, config, &on_status) {
+            warn!("Failed to download Core ML encoder for {}: {}", model.display_name, e);
+        }
+    }
+    Ok(())
+}
+
+/// URL of the zipped Core ML encoder published next to `model` on Hugging
+/// Face, `None` if the model URL doesn't end in its file name.
+fn coreml_encoder_url(model: &Model) -> Option<String> {
+    let base = model.url.strip_suffix(model.filename.as_str())?;
+    let encoder = coreml_encoder_path(Path::new(&model.filename));
+    Some(format!("{}{}.zip", base, encoder.file_name()?.to_string_lossy()))
+}
+
+/// Whether `model` should get a Core ML encoder: on Apple Silicon with
+/// `whisper.coreml` on, for models published with one.
+pub fn wants_coreml_encoder(model: &Model, config: &WhisprConfig) -> bool {
+    cfg!(target_arch = "aarch64") && config.whisper.coreml && coreml_encoder_url(model).is_some()
+}
+
+fn remove_coreml_encoder(model_path: &Path) {
+    let encoder_path = coreml_encoder_path(model_path);
+    if encoder_path.exists() {
+        if let Err(e) = fs::remove_dir_all(&encoder_path) {
+            warn!("Failed to remove Core ML encoder {}: {}", encoder_path.display(), e);
+        }
+    }
+}
+
+/// Downloads the Core ML encoder of `model` and unpacks it next to
+/// `model_path`, where whisper.cpp looks for it.
+pub fn download_coreml_encoder<F>(model: &Model, model_path: &Path, config: &WhisprConfig, on_status: F) -> Result<()>
+where
+    F: Fn(ModelStatus),
+{
+    let url = coreml_encoder_url(model)
+        .ok_or_else(|| anyhow::anyhow!("No Core ML encoder published for {}", model.display_name))?;
+    let encoder_path = coreml_encoder_path(model_path);
+    let archive_path = encoder_path.with_extension("mlmodelc.zip");
+    info!("Downloading Core ML encoder {} to {}", url, encoder_path.display());
+    download_with_progress(&url, &archive_path, config, |percent| on_status(ModelStatus::DownloadingEncoder(percent)))?;
+
+    // The archive holds the encoder under the published model's name
+    let unpack_dir = encoder_path.with_extension("unpacking");
+    let _ = fs::remove_dir_all(&unpack_dir);
+    let status = Command::new("/usr/bin/ditto").args(["-x", "-k"]).arg(&archive_path).arg(&unpack_dir).status()?;
+    if !status.success() {
+        anyhow::bail!("Failed to unpack {}", archive_path.display());
+    }
+    let unpacked = fs::read_dir(&unpack_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|extension| extension == "mlmodelc"))
+        .ok_or_else(|| anyhow::anyhow!("{} contains no Core ML model", archive_path.display()))?;
+    remove_coreml_encoder(model_path);
+    fs::rename(&unpacked, &encoder_path)?;
+    let _ = fs::remove_dir_all(&unpack_dir);
+    let _ = fs::remove_file(&archive_path);
+    Ok(())
+}
+
+/// The configured model stored at `model_path`, the fallback model or the
+/// preferred one.
+pub fn model_for_path<'a>(config: &'a WhisprConfig, model_path: &Path) -> &'a Model {
+    let is_fallback = model_path.file_name()
+        .is_some_and(|name| name == config.fallback_model.filename.as_str());
+    if is_fallback { &config.fallback_model } else { &config.model }
+}
+
+/// Installed memory in gigabytes, `None` if sysctl can't tell.
+pub fn system_ram_gb() -> Option<u64> {
+    let mut bytes: u64 = 0;
+    let mut size = std::mem::size_of::<u64>();
+    let result = unsafe {
+        libc::sysctlbyname(
+            b"hw.memsize\0".as_ptr() as *const libc::c_char,
+            &mut bytes as *mut u64 as *mut libc::c_void,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (result == 0).then_some(bytes / (1024 * 1024 * 1024))
+}
+
+/// The best catalog model for this Mac's memory.
+pub fn recommended_model() -> Option<CatalogModel> {
+    system_ram_gb().map(CatalogModel::recommended)
+}
+
+/// The recommended model if `model` needs more memory than this Mac has,
+/// e.g. large-v3-turbo on an 8 GB MacBook Air. `None` for models outside the
+/// catalog, whose needs are unknown.
+pub fn lighter_model_for(model: &Model) -> Option<CatalogModel> {
+    let ram_gb = system_ram_gb()?;
+    let configured = CatalogModel::for_model(model)?;
+    (configured.min_ram_gb > ram_gb).then(|| CatalogModel::recommended(ram_gb))
+}
+
+/// Deletes a model file and its recorded hash, e.g. to download it again.
+pub fn remove_model(model_path: &Path) -> Result<()> {
+    if model_path.exists() {
+        fs::remove_file(model_path)?;
+    }
+    let _ = fs::remove_file(hash_path(model_path));
+    Ok(())
+}
+
+fn hash_path(model_path: &Path) -> PathBuf {
+    let mut name = model_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    model_path.with_file_name(name)
+}
+
+/// Hex SHA-256 of a file, read in chunks since models are gigabytes.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn record_hash(model_path: &Path) -> Result<String> {
+    let hash = sha256_file(model_path)?;
+    fs::write(hash_path(model_path), &hash)?;
+    Ok(hash)
+}
+
+/// Whether the model matches `expected`, its published checksum, or else
+/// still has the hash recorded when it was downloaded. A model with neither
+/// (copied in by hand or downloaded by an older whispr) is trusted and its
+/// hash recorded now.
+pub fn verify_model(model_path: &Path, expected: Option<&str>) -> Result<bool> {
+    let Some(expected) = expected.map(str::to_string).or_else(|| fs::read_to_string(hash_path(model_path)).ok()) else {
+        info!("No recorded hash for {}, recording it", model_path.display());
+        record_hash(model_path)?;
+        return Ok(true);
+    };
+    let actual = sha256_file(model_path)?;
+    debug!("SHA-256 of {}: {}", model_path.display(), actual);
+    Ok(actual.eq_ignore_ascii_case(expected.trim()))
+}
+
+/// A models directory on an external drive disappears while the volume is not
+/// mounted, so "exists" isn't enough - it also has to be listable.
+pub fn is_dir_readable(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok()
+}
+
+/// Polls until `dir` becomes readable (e.g. the external volume gets mounted)
+/// and then invokes `on_available` once from the polling thread.
+pub fn wait_for_dir<F>(dir: PathBuf, on_available: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    std::thread::spawn(move || {
+        info!("Waiting for models directory {} to become available", dir.display());
+        while !is_dir_readable(&dir) {
+            debug!("Models directory {} still unavailable", dir.display());
+            std::thread::sleep(MOUNT_POLL_INTERVAL);
+        }
+        info!("Models directory {} is available", dir.display());
+        on_available();
+    });
+}
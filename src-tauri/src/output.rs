@@ -0,0 +1,541 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use log::{error, info, debug, warn};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::clipboard;
+use crate::commands::{self, CommandRegistry};
+use crate::config::{
+    AppleSettings, ChatSettings, ConfigManager, EmailSettings, InjectionSettings, NetworkSettings, ObsSettings,
+    ObsidianSettings, SinkKind, TerminalStrategy, WebhookSettings, WhisprConfig,
+};
+use crate::download;
+use crate::focus::{self, FrontmostApp};
+use crate::integrations::{apple, chat, email, obs, obsidian};
+
+const KNOWN_TERMINALS: &[&str] = &[
+    "com.apple.Terminal",
+    "com.googlecode.iterm2",
+    "net.kovidgoyal.kitty",
+    "io.alacritty",
+    "org.alacritty",
+    "com.github.wez.wezterm",
+    "dev.warp.Warp-Stable",
+    "co.zeit.hyper",
+    "com.mitchellh.ghostty",
+    "org.gnu.Emacs",
+    "org.vim.MacVim",
+];
+// Give the target app time to read the pasteboard before restoring it
+const CLIPBOARD_RESTORE_DELAY: Duration = Duration::from_millis(300);
+// How long the target app gets to process typed events before its field is
+// taken to have ignored them, and how often the field is read meanwhile
+const VERIFY_TIMEOUT: Duration = Duration::from_millis(1500);
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// App that ignored typing and was pasted into instead, until the user is asked
+// whether to always paste into it
+static PASTE_SUGGESTION: Mutex<Option<FrontmostApp>> = Mutex::new(None);
+// Apps the user chose to keep typing into this session
+static TYPING_KEPT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn create_enigo() -> Result<Enigo> {
+    Enigo::new(&Settings::default())
+        .map_err(|e| anyhow::anyhow!("Failed to create Enigo instance: {}", e))
+}
+
+fn is_terminal(bundle_id: &str, settings: &InjectionSettings) -> bool {
+    if settings.typing_apps.iter().any(|app| app == bundle_id) {
+        return false;
+    }
+    KNOWN_TERMINALS.contains(&bundle_id) || settings.terminal_apps.iter().any(|app| app == bundle_id)
+}
+
+/// Newlines and control characters would execute or trigger commands in a
+/// shell or modal editor, so flatten them to spaces.
+fn sanitize_for_terminal(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Pastes through the clipboard so terminals receive it as a single
+/// (bracketed) paste, then restores the previous clipboard content.
+pub fn paste_text(text: &str) -> Result<()> {
+    // All of it, not just the text, so a copied image or file isn't lost
+    let previous = clipboard::save();
+    clipboard::set_text(text)?;
+
+    let mut enigo = create_enigo()?;
+    enigo.key(Key::Meta, Direction::Press)
+        .and_then(|_| enigo.key(Key::Unicode('v'), Direction::Click))
+        .and_then(|_| enigo.key(Key::Meta, Direction::Release))
+        .map_err(|e| anyhow::anyhow!("Failed to send paste shortcut: {}", e))?;
+
+    std::thread::sleep(CLIPBOARD_RESTORE_DELAY);
+    if let Err(e) = clipboard::restore(&previous) {
+        warn!("Failed to restore clipboard: {}", e);
+    }
+    Ok(())
+}
+
+fn type_slowly(text: &str, delay: Duration) -> Result<()> {
+    let mut enigo = create_enigo()?;
+    let mut buffer = [0u8; 4];
+    for c in text.chars() {
+        enigo.text(c.encode_utf8(&mut buffer))
+            .map_err(|e| anyhow::anyhow!("Failed to send text: {}", e))?;
+        std::thread::sleep(delay);
+    }
+    Ok(())
+}
+
+/// Types the transcription into the focused application, switching to a
+/// terminal-safe strategy when the frontmost app is a terminal or editor.
+pub fn type_text(text: &str, settings: &InjectionSettings) -> Result<()> {
+    let app = focus::frontmost_app();
+    if let Some(app) = &app {
+        if is_terminal(&app.bundle_id, settings) {
+            let text = sanitize_for_terminal(text);
+            debug!("{} ({}) is a terminal, using {:?}", app.name, app.bundle_id, settings.terminal_strategy);
+            return match settings.terminal_strategy {
+                TerminalStrategy::Paste => paste_text(&text),
+                TerminalStrategy::SlowTyping => type_slowly(&text, Duration::from_millis(settings.typing_delay_ms)),
+            };
+        }
+        if settings.paste_apps.iter().any(|paste_app| *paste_app == app.bundle_id) {
+            debug!("{} ({}) ignores typing, pasting", app.name, app.bundle_id);
+            return paste_text(text);
+        }
+    }
+
+    // Only fields that expose their value can be checked
+    let before = settings.verify_typing.then(focused_field).flatten();
+    let mut enigo = create_enigo()?;
+    enigo.text(text)
+        .map_err(|e| anyhow::anyhow!("Failed to send text: {}", e))?;
+
+    let (Some(app), Some(before)) = (app, before) else {
+        return Ok(());
+    };
+    if verify(&before) != Verification::Ignored {
+        return Ok(());
+    }
+    warn!("{} ({}) ignored the typed text, pasting instead", app.name, app.bundle_id);
+    paste_text(text)?;
+    if verify(&before) == Verification::Took {
+        suggest_paste_app(app);
+    }
+    Ok(())
+}
+
+/// The focused field and its value before typing.
+struct Field {
+    target: focus::TypingTarget,
+    value: String,
+}
+
+fn focused_field() -> Option<Field> {
+    let target = focus::typing_target()?;
+    let value = focus::focused_value()?;
+    Some(Field { target, value })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Verification {
+    /// The field's value changed
+    Took,
+    /// Still the same field with the same value after `VERIFY_TIMEOUT`
+    Ignored,
+    /// The focus moved on, so the field can't tell
+    Unknown,
+}
+
+/// Waits for the field typed into to change, giving slow apps up to
+/// `VERIFY_TIMEOUT`.
+fn verify(before: &Field) -> Verification {
+    let deadline = Instant::now() + VERIFY_TIMEOUT;
+    loop {
+        std::thread::sleep(VERIFY_POLL_INTERVAL);
+        if !before.target.is_current() {
+            return Verification::Unknown;
+        }
+        if focus::focused_value().as_ref() != Some(&before.value) {
+            return Verification::Took;
+        }
+        if Instant::now() >= deadline {
+            return Verification::Ignored;
+        }
+    }
+}
+
+/// Offers to always paste into `app`, unless the user already chose to keep
+/// typing into it this session. See `take_paste_suggestion`.
+fn suggest_paste_app(app: FrontmostApp) {
+    if TYPING_KEPT.lock().unwrap().contains(&app.bundle_id) {
+        return;
+    }
+    *PASTE_SUGGESTION.lock().unwrap() = Some(app);
+}
+
+/// The app pasting worked in after typing was ignored, for asking the user
+/// whether to add it to `injection.paste_apps`.
+pub fn take_paste_suggestion() -> Option<FrontmostApp> {
+    PASTE_SUGGESTION.lock().unwrap().take()
+}
+
+/// The user wants to keep typing into `bundle_id`, don't ask again until
+/// restarted.
+pub fn keep_typing_into(bundle_id: &str) {
+    TYPING_KEPT.lock().unwrap().push(bundle_id.to_string());
+}
+
+/// Types `text` only where it can be taken back with backspaces later: not
+/// into terminals or apps that are pasted into, and, if the field can be
+/// checked, only if it took the text. Returns whether it was typed.
+pub fn type_erasable(text: &str, settings: &InjectionSettings) -> Result<bool> {
+    if let Some(app) = focus::frontmost_app() {
+        if is_terminal(&app.bundle_id, settings) || settings.paste_apps.iter().any(|paste_app| *paste_app == app.bundle_id) {
+            debug!("{} ({}) isn't typed into, not typing a draft", app.name, app.bundle_id);
+            return Ok(false);
+        }
+    }
+    let before = settings.verify_typing.then(focused_field).flatten();
+    let mut enigo = create_enigo()?;
+    enigo.text(text)
+        .map_err(|e| anyhow::anyhow!("Failed to send text: {}", e))?;
+    let Some(before) = before else {
+        return Ok(true);
+    };
+    // Pasting instead couldn't be erased reliably, the final text is delivered
+    // as usual. Once the focus moved there is nothing left to erase it from.
+    Ok(verify(&before) == Verification::Took)
+}
+
+/// Adds `bundle_id` to `injection.paste_apps` so later dictations are pasted
+/// right away.
+pub fn remember_paste_app(bundle_id: &str) {
+    let manager = match ConfigManager::<WhisprConfig>::new("settings") {
+        Ok(manager) => manager,
+        Err(e) => {
+            error!("Failed to remember pasting into {}: {}", bundle_id, e);
+            return;
+        }
+    };
+    let mut config = match manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to remember pasting into {}: {}", bundle_id, e);
+            return;
+        }
+    };
+    let paste_apps = &mut config.output.injection.paste_apps;
+    if paste_apps.iter().any(|app| app == bundle_id) {
+        return;
+    }
+    info!("Pasting into {} from now on", bundle_id);
+    paste_apps.push(bundle_id.to_string());
+    if let Err(e) = manager.save_config(&config, "settings") {
+        error!("Failed to save paste apps: {}", e);
+    }
+}
+
+/// Deletes the last `count` graphemes typed, to replace a dictation. A
+/// backspace removes a whole grapheme, e.g. an emoji with a skin tone.
+pub fn erase_text(count: usize) -> Result<()> {
+    let mut enigo = create_enigo()?;
+    for _ in 0..count {
+        enigo.key(Key::Backspace, Direction::Click)
+            .map_err(|e| anyhow::anyhow!("Failed to send backspace: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Appends the transcription to `path` as a timestamped line, creating the
+/// file if needed.
+pub fn append_to_file(path: &Path, text: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), text.trim_end())?;
+    info!("Appended transcription to {}", path.display());
+    Ok(())
+}
+
+/// A destination for finished transcriptions. Sinks are combined into an
+/// `OutputPipeline` so one dictation can go to several places.
+pub trait OutputSink: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn deliver(&self, text: &str) -> Result<()>;
+}
+
+struct KeyboardSink {
+    settings: InjectionSettings,
+}
+
+impl OutputSink for KeyboardSink {
+    fn name(&self) -> &'static str {
+        "keyboard"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        type_text(text, &self.settings)
+    }
+}
+
+struct ClipboardSink;
+
+impl OutputSink for ClipboardSink {
+    fn name(&self) -> &'static str {
+        "clipboard"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        clipboard::set_text(text)
+    }
+}
+
+struct FileSink {
+    path: PathBuf,
+}
+
+impl OutputSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        append_to_file(&self.path, text)
+    }
+}
+
+struct WebhookSink {
+    settings: WebhookSettings,
+    network: NetworkSettings,
+}
+
+impl OutputSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        let url = self.settings.url.as_deref()
+            .filter(|url| !url.is_empty())
+            .context("No webhook configured (output.webhook.url)")?;
+        let mut request = download::shared_client(&self.network)?
+            .post(url)
+            .json(&serde_json::json!({
+                "text": text.trim(),
+                "timestamp": Local::now().to_rfc3339(),
+            }));
+        for (name, value) in &self.settings.headers {
+            request = request.header(name, value);
+        }
+        request.send()?.error_for_status()?;
+        debug!("Posted transcription to webhook");
+        Ok(())
+    }
+}
+
+struct ObsidianSink {
+    settings: ObsidianSettings,
+}
+
+impl OutputSink for ObsidianSink {
+    fn name(&self) -> &'static str {
+        "obsidian"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        obsidian::append_to_daily_note(&self.settings, text)
+    }
+}
+
+struct AppleNotesSink {
+    settings: AppleSettings,
+}
+
+impl OutputSink for AppleNotesSink {
+    fn name(&self) -> &'static str {
+        "apple_notes"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        apple::create_note(&self.settings, text)
+    }
+}
+
+struct AppleRemindersSink {
+    settings: AppleSettings,
+}
+
+impl OutputSink for AppleRemindersSink {
+    fn name(&self) -> &'static str {
+        "apple_reminders"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        apple::create_reminder(&self.settings, text)
+    }
+}
+
+struct ObsSink {
+    settings: ObsSettings,
+}
+
+impl OutputSink for ObsSink {
+    fn name(&self) -> &'static str {
+        "obs"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        obs::show_caption(&self.settings, text)
+    }
+}
+
+struct ChatSink {
+    settings: ChatSettings,
+    network: NetworkSettings,
+}
+
+impl OutputSink for ChatSink {
+    fn name(&self) -> &'static str {
+        "chat"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        chat::send(&self.settings, &self.network, text)
+    }
+}
+
+struct EmailSink {
+    settings: EmailSettings,
+}
+
+impl OutputSink for EmailSink {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn deliver(&self, text: &str) -> Result<()> {
+        email::compose(&self.settings, text)
+    }
+}
+
+fn create_sink(kind: SinkKind, config: &WhisprConfig) -> Option<Box<dyn OutputSink>> {
+    let sink: Box<dyn OutputSink> = match kind {
+        SinkKind::Keyboard => Box::new(KeyboardSink { settings: config.output.injection.clone() }),
+        SinkKind::Clipboard => Box::new(ClipboardSink),
+        SinkKind::File => match config.output.file_path.as_deref().filter(|path| !path.is_empty()) {
+            Some(path) => Box::new(FileSink { path: PathBuf::from(path) }),
+            None => {
+                warn!("File output selected but output.file_path is not set, skipping");
+                return None;
+            }
+        },
+        SinkKind::Webhook => Box::new(WebhookSink {
+            settings: config.output.webhook.clone(),
+            network: config.network.clone(),
+        }),
+        SinkKind::Obsidian => Box::new(ObsidianSink { settings: config.integrations.obsidian.clone() }),
+        SinkKind::AppleNotes => Box::new(AppleNotesSink { settings: config.integrations.apple.clone() }),
+        SinkKind::AppleReminders => Box::new(AppleRemindersSink { settings: config.integrations.apple.clone() }),
+        SinkKind::Obs => Box::new(ObsSink { settings: config.integrations.obs.clone() }),
+        SinkKind::Chat => Box::new(ChatSink {
+            settings: config.integrations.chat.clone(),
+            network: config.network.clone(),
+        }),
+        SinkKind::Email => Box::new(EmailSink { settings: config.integrations.email.clone() }),
+    };
+    Some(sink)
+}
+
+/// Ordered set of sinks a dictation is delivered to.
+pub struct OutputPipeline {
+    sinks: Vec<Box<dyn OutputSink>>,
+}
+
+impl OutputPipeline {
+    pub fn new(sinks: Vec<Box<dyn OutputSink>>) -> Self {
+        Self { sinks }
+    }
+
+    /// Builds the pipeline from `output.sinks`. An active "Dictate to File"
+    /// session replaces all configured sinks.
+    pub fn from_config(config: &WhisprConfig, dictation_file: Option<&Path>) -> Self {
+        if let Some(path) = dictation_file {
+            return Self::new(vec![Box::new(FileSink { path: path.to_path_buf() })]);
+        }
+        Self::new(config.output.sinks.iter()
+            .filter_map(|kind| create_sink(*kind, config))
+            .collect())
+    }
+
+    /// Delivers to every sink in order. A failing sink doesn't stop the
+    /// others, the first error is returned once all sinks ran.
+    pub fn deliver(&self, text: &str) -> Result<()> {
+        if self.sinks.is_empty() {
+            anyhow::bail!("No output configured");
+        }
+
+        let mut first_error = None;
+        for sink in &self.sinks {
+            match sink.deliver(text) {
+                Ok(_) => debug!("Delivered transcription to {}", sink.name()),
+                Err(e) => {
+                    error!("Output {} failed: {}", sink.name(), e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Without a focused text field typed text would silently go nowhere, copy
+/// it instead. Returns the notice to show if the outputs were changed.
+fn guard_focus(config: &WhisprConfig) -> Option<(WhisprConfig, &'static str)> {
+    if !config.output.injection.focus_guard || !config.output.sinks.contains(&SinkKind::Keyboard) {
+        return None;
+    }
+    if focus::text_focus() != focus::TextFocus::NotEditable {
+        return None;
+    }
+    info!("No text field focused, copying to the clipboard instead of typing");
+    let mut config = config.clone();
+    config.output.sinks.retain(|sink| *sink != SinkKind::Keyboard);
+    if !config.output.sinks.contains(&SinkKind::Clipboard) {
+        config.output.sinks.insert(0, SinkKind::Clipboard);
+    }
+    Some((config, "Copied to clipboard (no text field focused)"))
+}
+
+/// Sends the transcription to its destinations, unless it is a voice command.
+/// Returns a message for the user if a command ran instead or the text
+/// ended up somewhere else than configured.
+pub fn deliver(text: &str, config: &WhisprConfig, dictation_file: Option<&Path>) -> Result<Option<String>> {
+    if config.command_mode {
+        return commands::run_command(text, config).map(Some);
+    }
+    if dictation_file.is_none() {
+        if let Some(result) = CommandRegistry::from_config(config).execute(text) {
+            return result.map(Some);
+        }
+    }
+
+    if dictation_file.is_none() {
+        if let Some((config, notice)) = guard_focus(config) {
+            OutputPipeline::from_config(&config, None).deliver(text)?;
+            return Ok(Some(notice.to_string()));
+        }
+    }
+
+    OutputPipeline::from_config(config, dictation_file).deliver(text)?;
+    Ok(None)
+}
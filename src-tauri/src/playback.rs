@@ -0,0 +1,214 @@
+use anyhow::{Context, Result};
+use log::{info, debug, error};
+use once_cell::sync::Lazy;
+use rodio::{Decoder, OutputStream, Sink, Source};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+/// What the history window shows for the recording being played.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlaybackStatus {
+    /// History entry the recording belongs to
+    pub id: Option<i64>,
+    pub playing: bool,
+    pub paused: bool,
+    pub position_secs: f32,
+    pub duration_secs: f32,
+}
+
+enum PlaybackCommand {
+    Play {
+        id: i64,
+        path: PathBuf,
+        start: Duration,
+        /// Stop after this long instead of at the end of the recording
+        length: Option<Duration>,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    Pause,
+    Resume,
+    Seek {
+        position: Duration,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    Stop,
+    Status {
+        reply: mpsc::Sender<PlaybackStatus>,
+    },
+}
+
+/// The output stream currently playing and where its source starts in the
+/// recording.
+struct Current {
+    // Dropping the stream stops the sound, keep it alive next to the sink
+    _stream: OutputStream,
+    sink: Sink,
+    id: i64,
+    offset: Duration,
+    duration: Duration,
+    seekable: bool,
+}
+
+fn open(path: &PathBuf, start: Duration, length: Option<Duration>) -> Result<(OutputStream, Sink, Duration, bool)> {
+    let file = File::open(path).with_context(|| format!("Failed to open recording {}", path.display()))?;
+    let decoder = Decoder::new(BufReader::new(file)).context("Failed to decode recording")?;
+    let total = decoder.total_duration().unwrap_or_default();
+    let (stream, handle) = OutputStream::try_default().context("No audio output device")?;
+    let sink = Sink::try_new(&handle)?;
+
+    match length {
+        Some(length) => {
+            sink.append(decoder.skip_duration(start).take_duration(length));
+            Ok((stream, sink, length.min(total.saturating_sub(start)), false))
+        }
+        None => {
+            sink.append(decoder);
+            if !start.is_zero() {
+                sink.try_seek(start).map_err(|e| anyhow::anyhow!("Failed to seek: {}", e))?;
+            }
+            Ok((stream, sink, total, true))
+        }
+    }
+}
+
+fn run(receiver: mpsc::Receiver<PlaybackCommand>) {
+    let mut current: Option<Current> = None;
+    for command in receiver {
+        match command {
+            PlaybackCommand::Play { id, path, start, length, reply } => {
+                current = None;
+                let result = open(&path, start, length).map(|(stream, sink, duration, seekable)| {
+                    let offset = if seekable { Duration::ZERO } else { start };
+                    current = Some(Current { _stream: stream, sink, id, offset, duration, seekable });
+                });
+                let _ = reply.send(result);
+            }
+            PlaybackCommand::Pause => {
+                if let Some(current) = &current {
+                    current.sink.pause();
+                }
+            }
+            PlaybackCommand::Resume => {
+                if let Some(current) = &current {
+                    current.sink.play();
+                }
+            }
+            PlaybackCommand::Seek { position, reply } => {
+                let result = match &current {
+                    Some(current) if current.seekable => current.sink
+                        .try_seek(position.min(current.duration))
+                        .map_err(|e| anyhow::anyhow!("Failed to seek: {}", e)),
+                    Some(_) => Err(anyhow::anyhow!("Segments can't be seeked, play the whole recording")),
+                    None => Err(anyhow::anyhow!("Nothing is playing")),
+                };
+                let _ = reply.send(result);
+            }
+            PlaybackCommand::Stop => current = None,
+            PlaybackCommand::Status { reply } => {
+                let status = match &current {
+                    Some(current) => PlaybackStatus {
+                        id: Some(current.id),
+                        playing: !current.sink.empty(),
+                        paused: current.sink.is_paused(),
+                        position_secs: (current.offset + current.sink.get_pos()).as_secs_f32(),
+                        duration_secs: current.duration.as_secs_f32(),
+                    },
+                    None => PlaybackStatus::default(),
+                };
+                let _ = reply.send(status);
+            }
+        }
+    }
+    debug!("Playback thread exiting");
+}
+
+// Output streams can't be moved between threads, one thread owns them all
+static PLAYBACK: Lazy<Mutex<mpsc::Sender<PlaybackCommand>>> = Lazy::new(|| {
+    let (commands, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("playback".to_string())
+        .spawn(move || run(receiver))
+        .expect("Failed to spawn playback thread");
+    Mutex::new(commands)
+});
+
+fn send(command: PlaybackCommand) -> Result<()> {
+    PLAYBACK.lock().unwrap().send(command).map_err(|_| anyhow::anyhow!("Playback thread is not running"))
+}
+
+fn request<T>(command: impl FnOnce(mpsc::Sender<T>) -> PlaybackCommand) -> Result<T> {
+    let (reply, response) = mpsc::channel();
+    send(command(reply))?;
+    response.recv().map_err(|_| anyhow::anyhow!("Playback thread is not running"))
+}
+
+/// Plays the recording of history entry `id`, replacing anything playing.
+/// With `length` only that part from `start` is played.
+pub fn play(id: i64, path: PathBuf, start: Duration, length: Option<Duration>) -> Result<()> {
+    info!("Playing {} from {:.2}s", path.display(), start.as_secs_f32());
+    request(|reply| PlaybackCommand::Play { id, path, start, length, reply })?
+}
+
+pub fn pause() -> Result<()> {
+    send(PlaybackCommand::Pause)
+}
+
+pub fn resume() -> Result<()> {
+    send(PlaybackCommand::Resume)
+}
+
+pub fn seek(position: Duration) -> Result<()> {
+    request(|reply| PlaybackCommand::Seek { position, reply })?
+}
+
+pub fn stop() -> Result<()> {
+    send(PlaybackCommand::Stop)
+}
+
+pub fn status() -> PlaybackStatus {
+    request(|reply| PlaybackCommand::Status { reply }).unwrap_or_default()
+}
+
+fn log_error(result: Result<()>) -> Result<(), String> {
+    result.map_err(|e| {
+        error!("Playback failed: {}", e);
+        e.to_string()
+    })
+}
+
+/// Plays the whole saved recording of a history entry from `start_secs`.
+#[tauri::command]
+pub fn playback_play(id: i64, start_secs: Option<f32>) -> Result<(), String> {
+    log_error(crate::history::recording_path(id).and_then(|path| {
+        play(id, path, Duration::from_secs_f32(start_secs.unwrap_or(0.0).max(0.0)), None)
+    }))
+}
+
+#[tauri::command]
+pub fn playback_pause() -> Result<(), String> {
+    log_error(pause())
+}
+
+#[tauri::command]
+pub fn playback_resume() -> Result<(), String> {
+    log_error(resume())
+}
+
+#[tauri::command]
+pub fn playback_seek(position_secs: f32) -> Result<(), String> {
+    log_error(seek(Duration::from_secs_f32(position_secs.max(0.0))))
+}
+
+#[tauri::command]
+pub fn playback_stop() -> Result<(), String> {
+    log_error(stop())
+}
+
+#[tauri::command]
+pub fn playback_status() -> PlaybackStatus {
+    status()
+}
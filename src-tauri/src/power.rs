@@ -0,0 +1,44 @@
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use log::{info, debug};
+use objc::{class, msg_send, sel, sel_impl};
+
+const WILL_SLEEP_NOTIFICATION: &str = "NSWorkspaceWillSleepNotification";
+const DID_WAKE_NOTIFICATION: &str = "NSWorkspaceDidWakeNotification";
+
+fn observe<F>(name: &str, handler: F)
+where
+    F: Fn() + 'static,
+{
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let center: id = msg_send![workspace, notificationCenter];
+        let queue: id = msg_send![class!(NSOperationQueue), mainQueue];
+        let name_string = NSString::alloc(nil).init_str(name);
+        let block = block::ConcreteBlock::new(move |_notification: id| handler()).copy();
+        // The observer token is retained by the notification center for the
+        // lifetime of the app, there is nothing to unregister
+        let _: id = msg_send![center, addObserverForName:name_string
+            object:nil
+            queue:queue
+            usingBlock:&*block];
+    }
+    debug!("Observing {}", name);
+}
+
+/// Registers for macOS sleep and wake notifications. Both handlers run on the
+/// main thread. Must be called from the main thread.
+pub fn observe_sleep_wake<S, W>(on_sleep: S, on_wake: W)
+where
+    S: Fn() + 'static,
+    W: Fn() + 'static,
+{
+    observe(WILL_SLEEP_NOTIFICATION, move || {
+        info!("System is going to sleep");
+        on_sleep();
+    });
+    observe(DID_WAKE_NOTIFICATION, move || {
+        info!("System woke up");
+        on_wake();
+    });
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use log::{info, error};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::history::{self, HistoryVersion};
+use crate::pipeline::{self, WHISPER_SAMPLE_RATE};
+use crate::whisper::Decoding;
+
+/// Entries waiting to be transcribed again, and whether a worker drains them.
+struct Queue {
+    pending: VecDeque<i64>,
+    running: bool,
+    done: usize,
+    total: usize,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue { pending: VecDeque::new(), running: false, done: 0, total: 0 });
+
+#[derive(Debug, Clone, Serialize)]
+struct Progress {
+    /// Entry being transcribed, `None` once the queue is empty
+    id: Option<i64>,
+    done: usize,
+    total: usize,
+    /// Progress of the current entry
+    percent: u8,
+}
+
+fn retranscribe<R: Runtime>(app: &AppHandle<R>, id: i64, config: &WhisprConfig, on_percent: impl FnMut(u8) + Send + 'static) -> Result<()> {
+    let state = app.try_state::<crate::AppState>().context("App is not ready")?;
+    let whisper = state.decoder().context("Whisper model is still loading")?;
+    let (format, samples) = pipeline::read_wav(&history::recording_path(id)?)?;
+    let converter = state.audio.lock().unwrap().recording_converter();
+    let audio = converter.convert(format, samples, WHISPER_SAMPLE_RATE, 1)?
+        .context("Recording is empty")?;
+
+    let mut on_percent = on_percent;
+    let Decoding { mut segments, confidence, .. } = whisper
+        .process_audio_with_confidence(&audio, &config.whisper, Some(Box::new(move |progress| on_percent(progress.percent))), None)
+        .map_err(|e| anyhow::anyhow!(e))?;
+    crate::quality::trim_segments(&mut segments);
+
+    let model = if config.whisper.quality.uses_fallback_model() { &config.fallback_model } else { &config.model };
+    let version = HistoryVersion::new(&model.display_name, &segments, confidence);
+    history::update(id, |entry| entry.versions.push(version))
+}
+
+fn run<R: Runtime>(app: AppHandle<R>) {
+    let mut failed = 0;
+    loop {
+        let (id, done, total) = {
+            let mut queue = QUEUE.lock().unwrap();
+            match queue.pending.pop_front() {
+                Some(id) => (id, queue.done, queue.total),
+                None => {
+                    queue.running = false;
+                    let total = queue.total;
+                    queue.done = 0;
+                    queue.total = 0;
+                    drop(queue);
+                    let _ = app.emit("retranscribe-progress", Progress { id: None, done: total, total, percent: 100 });
+                    let message = match failed {
+                        0 => format!("Re-transcribed {} recording(s)", total),
+                        _ => format!("Re-transcribed {} of {} recording(s)", total - failed, total),
+                    };
+                    crate::show_toast(&app, &message);
+                    return;
+                }
+            }
+        };
+
+        // Settings can change between entries, the latest ones apply
+        let config = ConfigManager::<WhisprConfig>::new("settings")
+            .and_then(|manager| manager.load_config("settings"))
+            .unwrap_or_default();
+        let progress_handle = app.clone();
+        let on_percent = move |percent| {
+            let _ = progress_handle.emit("retranscribe-progress", Progress { id: Some(id), done, total, percent });
+        };
+        info!("Re-transcribing history entry {} ({}/{})", id, done + 1, total);
+        if let Err(e) = retranscribe(&app, id, &config, on_percent) {
+            error!("Failed to re-transcribe history entry {}: {}", id, e);
+            failed += 1;
+        }
+        QUEUE.lock().unwrap().done += 1;
+    }
+}
+
+/// Queues entries to be transcribed again with the current settings, one
+/// after another in the background. Results are stored as new versions.
+pub fn enqueue<R: Runtime>(app: &AppHandle<R>, ids: Vec<i64>) {
+    let mut queue = QUEUE.lock().unwrap();
+    for id in ids {
+        if !queue.pending.contains(&id) {
+            queue.pending.push_back(id);
+            queue.total += 1;
+        }
+    }
+    if queue.running || queue.pending.is_empty() {
+        return;
+    }
+    queue.running = true;
+    let app = app.clone();
+    std::thread::spawn(move || run(app));
+}
+
+#[tauri::command]
+pub fn retranscribe_entries(app: AppHandle, ids: Vec<i64>) {
+    enqueue(&app, ids);
+}
@@ -0,0 +1,158 @@
+use log::{error, info};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+use whispr_core::whisper::WordConfidence;
+use crate::config::WhisprConfig;
+use crate::text_direction::{self, Direction};
+use crate::{ephemeral, focus, output};
+
+const REVIEW_LABEL: &str = "whispr:review";
+
+/// A low confidence transcription waiting in the review window.
+pub struct PendingReview {
+    pub text: String,
+    pub words: Vec<WordConfidence>,
+    pub confidence: f32,
+    /// Marks only the words below it, `None` colors every word
+    pub threshold: Option<f32>,
+    pub direction: Direction,
+    pub config: WhisprConfig,
+    pub dictation_file: Option<PathBuf>,
+}
+
+/// A word of the delivered text, `None` if whisper's tokens don't cover it
+/// (e.g. after a repetition was trimmed).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewWord {
+    pub word: String,
+    pub probability: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewView {
+    pub confidence: f32,
+    pub threshold: Option<f32>,
+    pub words: Vec<ReviewWord>,
+    pub dir: Direction,
+}
+
+static PENDING: Mutex<Option<PendingReview>> = Mutex::new(None);
+
+/// Pairs the words of `text` with the decoder's word confidences in order,
+/// skipping decoded words that didn't make it into the text.
+pub fn align(text: &str, words: &[WordConfidence]) -> Vec<ReviewWord> {
+    let mut next = 0;
+    text.split_whitespace()
+        .map(|word| {
+            let found = words[next..].iter().position(|decoded| decoded.word == word);
+            let probability = found.map(|offset| {
+                next += offset + 1;
+                words[next - 1].probability
+            });
+            ReviewWord { word: word.to_string(), probability }
+        })
+        .collect()
+}
+
+/// Shows `review` in the review window, replacing one still waiting there.
+pub fn open<R: Runtime>(app: &AppHandle<R>, review: PendingReview) {
+    *PENDING.lock().unwrap() = Some(review);
+    if let Some(window) = app.get_webview_window(REVIEW_LABEL) {
+        let _ = app.emit_to(REVIEW_LABEL, "review-changed", ());
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let window = WebviewWindowBuilder::new(app, REVIEW_LABEL, WebviewUrl::App("index.html".into()))
+        .title("Low Confidence Transcription")
+        .inner_size(520.0, 280.0)
+        .min_inner_size(360.0, 200.0)
+        .always_on_top(true)
+        .build();
+    if let Err(e) = window {
+        error!("Failed to open review window: {}", e);
+    }
+}
+
+fn close<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(REVIEW_LABEL) {
+        let _ = window.close();
+    }
+    // Hand the keyboard back to the app the text goes to
+    focus::yield_focus();
+}
+
+#[tauri::command]
+pub fn review_pending() -> Option<ReviewView> {
+    PENDING.lock().unwrap().as_ref().map(|review| ReviewView {
+        confidence: review.confidence,
+        threshold: review.threshold,
+        words: align(&review.text, &review.words),
+        dir: review.direction,
+    })
+}
+
+#[tauri::command]
+pub fn review_insert(app: AppHandle) {
+    let Some(review) = PENDING.lock().unwrap().take() else {
+        return;
+    };
+    close(&app);
+    std::thread::spawn(move || {
+        // Give focus a moment to return to the previous app
+        std::thread::sleep(crate::FOCUS_RETURN_DELAY);
+        let text = text_direction::mark_for_injection(&review.text, review.direction);
+        match output::deliver(&text, &review.config, review.dictation_file.as_deref()) {
+            Ok(Some(confirmation)) => crate::show_toast(&app, &confirmation),
+            Ok(None) => {}
+            Err(e) => error!("Failed to output transcription: {}", e),
+        }
+        discard(review);
+    });
+}
+
+#[tauri::command]
+pub fn review_discard(app: AppHandle) {
+    if let Some(review) = PENDING.lock().unwrap().take() {
+        info!("Low confidence transcription discarded");
+        discard(review);
+    }
+    close(&app);
+}
+
+fn discard(mut review: PendingReview) {
+    ephemeral::scrub(&mut review.text);
+    for word in &mut review.words {
+        ephemeral::scrub(&mut word.word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str, probability: f32) -> WordConfidence {
+        WordConfidence { word: word.to_string(), probability }
+    }
+
+    #[test]
+    fn words_pair_up_in_order() {
+        let words = [word("Meet", 0.9), word("at", 0.95), word("noon.", 0.3)];
+        let aligned = align("Meet at noon. ", &words);
+        assert_eq!(aligned.iter().map(|w| w.probability).collect::<Vec<_>>(), vec![Some(0.9), Some(0.95), Some(0.3)]);
+    }
+
+    #[test]
+    fn trimmed_and_unknown_words_are_skipped() {
+        // "go go" was trimmed from the text, "now" never decoded
+        let words = [word("go", 0.8), word("go", 0.2), word("go", 0.1), word("home", 0.7)];
+        let aligned = align("go home now", &words);
+        assert_eq!(aligned, vec![
+            ReviewWord { word: "go".to_string(), probability: Some(0.8) },
+            ReviewWord { word: "home".to_string(), probability: Some(0.7) },
+            ReviewWord { word: "now".to_string(), probability: None },
+        ]);
+    }
+}
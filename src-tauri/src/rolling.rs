@@ -0,0 +1,166 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::Device;
+use log::{error, info, debug, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+use crate::audio::AudioManager;
+use crate::resample::Resampler;
+
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One device's part of the buffer, mono at the device's rate.
+struct Track {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+}
+
+impl Track {
+    /// Captures `device` on a thread of its own until `stop` is set, keeping
+    /// its last `seconds`.
+    fn start(device: Device, seconds: u32, stop: Arc<AtomicBool>) -> Result<Self> {
+        let samples = Arc::new(Mutex::new(VecDeque::new()));
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        // cpal streams aren't Send, so the stream lives on its own thread
+        let thread_samples = samples.clone();
+        std::thread::spawn(move || {
+            let stream = (|| -> Result<(cpal::Stream, u32)> {
+                let config = device.default_input_config()?;
+                let channels = config.channels() as usize;
+                let sample_rate = config.sample_rate().0;
+                let capacity = sample_rate as usize * seconds as usize;
+
+                let stream = device.build_input_stream(
+                    &config.into(),
+                    move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                        let mut buffer = thread_samples.lock().unwrap();
+                        for frame in data.chunks_exact(channels) {
+                            buffer.push_back(frame.iter().sum::<f32>() / channels as f32);
+                        }
+                        let overflow = buffer.len().saturating_sub(capacity);
+                        buffer.drain(..overflow);
+                    },
+                    move |err| error!("Rolling buffer stream error: {}", err),
+                    None,
+                )?;
+                stream.play()?;
+                Ok((stream, sample_rate))
+            })();
+
+            match stream {
+                Ok((stream, sample_rate)) => {
+                    let _ = ready_tx.send(Ok(sample_rate));
+                    while !stop.load(Ordering::SeqCst) {
+                        std::thread::sleep(STOP_POLL_INTERVAL);
+                    }
+                    drop(stream);
+                    debug!("Rolling buffer stream stopped");
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                }
+            }
+        });
+
+        let sample_rate = ready_rx.recv()
+            .map_err(|_| anyhow::anyhow!("Rolling buffer thread exited"))??;
+        Ok(Self { samples, sample_rate })
+    }
+
+    fn wipe(&self) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.iter_mut().for_each(|sample| *sample = 0.0);
+        samples.clear();
+    }
+}
+
+/// Sums tracks of 16kHz audio that end at the same moment.
+fn mix(tracks: Vec<Vec<f32>>) -> Vec<f32> {
+    let len = tracks.iter().map(Vec::len).max().unwrap_or(0);
+    let mut mixed = vec![0.0; len];
+    for track in tracks {
+        let offset = len - track.len();
+        for (out, sample) in mixed[offset..].iter_mut().zip(track) {
+            *out = (*out + sample).clamp(-1.0, 1.0);
+        }
+    }
+    mixed
+}
+
+/// Continuously captures the microphone, and with `system_audio` what the
+/// Mac plays through a loopback device, into fixed size in-memory ring
+/// buffers so the last few seconds can be transcribed on demand. Nothing is
+/// ever written to disk and the buffers are wiped when stopped.
+pub struct RollingBuffer {
+    tracks: Vec<Track>,
+    stop: Arc<AtomicBool>,
+    resampler: Box<dyn Resampler>,
+}
+
+impl RollingBuffer {
+    /// `system_audio` is the loopback device to use, `Some(None)` for the
+    /// first known one. Without one the microphone is kept alone. Devices
+    /// are looked up through `audio`, which owns the one audio host.
+    pub fn start(audio: &AudioManager, device_id: Option<&str>, system_audio: Option<Option<&str>>, seconds: u32, resampler: Box<dyn Resampler>) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let microphone = Track::start(audio.find_input_device(device_id)?, seconds, stop.clone())?;
+        info!("Rolling buffer started ({}s at {}Hz)", seconds, microphone.sample_rate);
+        let mut tracks = vec![microphone];
+
+        if let Some(preferred) = system_audio {
+            let loopback = audio.find_loopback_device(preferred)
+                .and_then(|(_, device)| Track::start(device, seconds, stop.clone()));
+            match loopback {
+                Ok(track) => {
+                    info!("Rolling buffer includes system audio at {}Hz", track.sample_rate);
+                    tracks.push(track);
+                }
+                Err(e) => warn!("Rolling buffer keeps the microphone only: {}", e),
+            }
+        }
+
+        Ok(Self { tracks, stop, resampler })
+    }
+
+    /// Copy of the buffered audio as 16kHz mono, ready for whisper, with
+    /// system audio mixed in.
+    pub fn snapshot(&self) -> Result<Vec<f32>> {
+        let mut tracks = Vec::with_capacity(self.tracks.len());
+        for track in &self.tracks {
+            let samples: Vec<f32> = track.samples.lock().unwrap().iter().copied().collect();
+            tracks.push(if track.sample_rate == WHISPER_SAMPLE_RATE {
+                samples
+            } else {
+                self.resampler.resample(&samples, track.sample_rate, WHISPER_SAMPLE_RATE, 1)?
+            });
+        }
+        Ok(mix(tracks))
+    }
+}
+
+impl Drop for RollingBuffer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.tracks.iter().for_each(Track::wipe);
+        info!("Rolling buffer stopped and cleared");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_tracks_aligned_at_the_end() {
+        let mixed = mix(vec![vec![0.1, 0.2, 0.3], vec![0.5]]);
+        assert_eq!(mixed.len(), 3);
+        assert!((mixed[0] - 0.1).abs() < 1e-6);
+        assert!((mixed[2] - 0.8).abs() < 1e-6);
+        assert!(mix(vec![vec![0.9], vec![0.9]])[0] <= 1.0);
+    }
+}
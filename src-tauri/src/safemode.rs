@@ -0,0 +1,179 @@
+use tauri::{
+    App, AppHandle, Manager, Wry,
+    menu::{Menu, MenuItem, PredefinedMenuItem},
+};
+use log::{error, warn, info};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_shell::ShellExt;
+use crate::config::{ConfigManager, WhisprConfig};
+
+const STARTUP_MARKER: &str = "startup.marker";
+const MAX_FAILED_LAUNCHES: u32 = 3;
+// A launch counts as successful once the app survived this long after setup
+const STABLE_AFTER: Duration = Duration::from_secs(10);
+
+fn config_dir() -> Option<PathBuf> {
+    ConfigManager::<WhisprConfig>::new("settings")
+        .ok()
+        .map(|manager| manager.get_config_dir().to_path_buf())
+}
+
+fn marker_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(STARTUP_MARKER))
+}
+
+fn read_failed_launches() -> u32 {
+    marker_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn write_failed_launches(count: u32) {
+    if let Some(path) = marker_path() {
+        if let Err(e) = fs::write(&path, count.to_string()) {
+            error!("Failed to write startup marker {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Records the start of a launch and returns whether the previous launches
+/// crashed often enough that we should boot into safe mode.
+pub fn begin_launch() -> bool {
+    let failed_launches = read_failed_launches();
+    if failed_launches >= MAX_FAILED_LAUNCHES {
+        warn!("Last {} launches did not complete, entering safe mode", failed_launches);
+        return true;
+    }
+    write_failed_launches(failed_launches + 1);
+    false
+}
+
+pub fn mark_launch_successful() {
+    if let Some(path) = marker_path() {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                error!("Failed to remove startup marker: {}", e);
+            }
+        }
+    }
+}
+
+pub fn mark_launch_successful_when_stable() {
+    std::thread::spawn(|| {
+        std::thread::sleep(STABLE_AFTER);
+        info!("Application stable, clearing startup marker");
+        mark_launch_successful();
+    });
+}
+
+fn reset_config(app: &AppHandle) {
+    let Some(dir) = config_dir() else { return };
+    let settings_path = dir.join("settings.json");
+    match fs::remove_file(&settings_path) {
+        Ok(_) => info!("Removed configuration at {}", settings_path.display()),
+        Err(e) => error!("Failed to remove configuration: {}", e),
+    }
+    app.dialog()
+        .message("Configuration was reset to defaults")
+        .title("Safe Mode")
+        .show(|_| {});
+}
+
+fn delete_model(app: &AppHandle) {
+    let Ok(manager) = ConfigManager::<WhisprConfig>::new("settings") else { return };
+    // A configuration that fails to load falls back to the default model
+    let config = manager.load_config("settings").unwrap_or_default();
+    let model_path = crate::models::model_path(&config, manager.get_config_dir());
+    let app_handle = app.clone();
+    app.dialog()
+        .message(format!("Delete the model at {}?", model_path.display()))
+        .title("Safe Mode")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancel)
+        .show(move |answer| {
+            if !answer {
+                return;
+            }
+            match fs::remove_file(&model_path) {
+                Ok(_) => info!("Removed model at {}", model_path.display()),
+                Err(e) => {
+                    error!("Failed to remove model: {}", e);
+                    app_handle.dialog()
+                        .message(format!("Failed to delete model: {}", e))
+                        .kind(MessageDialogKind::Error)
+                        .title("Safe Mode")
+                        .show(|_| {});
+                }
+            }
+        });
+}
+
+fn open_logs(app: &AppHandle) {
+    let Some(dir) = config_dir() else { return };
+    let _ = app.shell().command("open")
+        .args([dir.join("logs").to_string_lossy().to_string()])
+        .spawn();
+}
+
+fn handle_safe_mode_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        "safe_mode_reset_config" => reset_config(app),
+        "safe_mode_delete_model" => delete_model(app),
+        "safe_mode_open_logs" => open_logs(app),
+        "safe_mode_restart" => {
+            info!("Leaving safe mode");
+            mark_launch_successful();
+            app.restart();
+        }
+        "quit" => app.exit(0),
+        _ => error!("Unhandled safe mode menu item: {:?}", id),
+    }
+}
+
+/// Minimal setup used instead of `setup_app` after repeated crashes: default
+/// configuration, no model, no hotkeys, only recovery actions in the tray.
+pub fn setup_safe_mode(app: &mut App<Wry>) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let app_handle = app.handle();
+
+    let header = MenuItem::with_id(app_handle, "safe_mode_header", "Safe Mode", false, None::<String>)?;
+    let reset_config = MenuItem::with_id(app_handle, "safe_mode_reset_config", "Reset Configuration", true, None::<String>)?;
+    let delete_model = MenuItem::with_id(app_handle, "safe_mode_delete_model", "Delete Model", true, None::<String>)?;
+    let open_logs = MenuItem::with_id(app_handle, "safe_mode_open_logs", "Open Logs", true, None::<String>)?;
+    let restart = MenuItem::with_id(app_handle, "safe_mode_restart", "Restart Normally", true, None::<String>)?;
+    let separator = PredefinedMenuItem::separator(app_handle)?;
+    let quit = MenuItem::with_id(app_handle, "quit", "Quit", true, None::<String>)?;
+
+    let menu = Menu::with_items(app_handle, &[
+        &header as &dyn tauri::menu::IsMenuItem<Wry>,
+        &separator,
+        &reset_config,
+        &delete_model,
+        &open_logs,
+        &restart,
+        &separator,
+        &quit,
+    ])?;
+
+    let tray = tauri::tray::TrayIconBuilder::new()
+        .icon(app_handle.default_window_icon().unwrap().clone())
+        .tooltip("whispr (safe mode)")
+        .menu_on_left_click(false)
+        .menu(&menu)
+        .on_menu_event(|app, event| handle_safe_mode_menu_event(app, &event.id().0))
+        .build(app_handle)?;
+    app.manage(tray);
+
+    app.dialog()
+        .message("whispr did not start correctly the last few times and is running in safe mode. \
+            Use the menubar icon to reset the configuration, delete the model or open the logs, \
+            then choose \"Restart Normally\".")
+        .kind(MessageDialogKind::Warning)
+        .title("Safe Mode")
+        .show(|_| {});
+
+    Ok(())
+}
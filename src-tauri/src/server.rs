@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use log::{info, debug, warn};
+use serde_json::{json, Value};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::TryRecvError;
+use whispr_core::error::ErrorCode;
+use whispr_core::http::{self, ConnectionSlot, Request};
+use crate::{api, jobs, metrics};
+use crate::config::{ApiSettings, ConfigManager, Quality, WhisprConfig};
+use crate::menu_spec::LANGUAGES;
+
+const MAX_BODY_BYTES: usize = 64 * 1024;
+// About an hour of 16 kHz 16 bit mono, or ten minutes of 48 kHz stereo
+const MAX_AUDIO_BYTES: usize = 128 * 1024 * 1024;
+const TRANSCRIPTIONS_PATH: &str = "/v1/transcriptions";
+// Per listener, event streams count separately
+const MAX_CONNECTIONS: usize = 16;
+const MAX_LAN_CONNECTIONS: usize = 4;
+// Across listeners, they stay open for as long as the client likes
+const MAX_EVENT_STREAMS: usize = 8;
+// A blank line this often on an idle event stream, to notice clients that are gone
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static EVENT_STREAMS: Lazy<Arc<AtomicUsize>> = Lazy::new(|| Arc::new(AtomicUsize::new(0)));
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, content_type: "application/json", body: body.to_string() }
+    }
+
+    fn error(status: u16, code: ErrorCode, message: &str) -> Self {
+        Self { status, content_type: "application/json", body: json!({ "error": message, "code": code }).to_string() }
+    }
+
+    /// Prometheus' text exposition format
+    fn metrics(body: String) -> Self {
+        Self { status: 200, content_type: "text/plain; version=0.0.4", body }
+    }
+}
+
+fn load_config() -> WhisprConfig {
+    ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default()
+}
+
+fn next_quality(app: &AppHandle) -> Response {
+    let config = load_config();
+    let index = Quality::ALL.iter().position(|quality| *quality == config.whisper.quality).unwrap_or(0);
+    let quality = Quality::ALL[(index + 1) % Quality::ALL.len()];
+    crate::menu::handle_quality_selection(app, quality);
+    crate::tray::update_summary(app, &load_config());
+    Response::ok(json!({ "quality": quality.id() }))
+}
+
+fn next_language(app: &AppHandle) -> Response {
+    let config = load_config();
+    let current = config.whisper.language.as_deref().unwrap_or("auto");
+    let index = LANGUAGES.iter().position(|(_, code)| *code == current).unwrap_or(0);
+    let (label, code) = LANGUAGES[(index + 1) % LANGUAGES.len()];
+    crate::menu::handle_language_selection(app, code);
+    crate::tray::update_summary(app, &load_config());
+    Response::ok(json!({ "language": code, "label": label }))
+}
+
+fn last_transcription(app: &AppHandle) -> Response {
+    let text = app.try_state::<crate::AppState>()
+        .and_then(|state| state.last_transcription.lock().unwrap().clone());
+    if let Some(text) = &text {
+        crate::show_toast(app, text);
+    }
+    Response::ok(json!({ "text": text }))
+}
+
+fn transcribe(app: &AppHandle, request: &Request) -> Response {
+    match jobs::transcribe(app, &request.body, &load_config().api) {
+        Ok(result) => Response::ok(json!(result)),
+        Err(e) => {
+            warn!("API transcription failed: {}", e);
+            metrics::record_error(e.code());
+            Response::error(e.status(), e.code(), &e.to_string())
+        }
+    }
+}
+
+fn route(app: &AppHandle, request: &Request) -> Response {
+    debug!("API request: {} {}", request.method, request.path);
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/v1/status") => Response::ok(json!({
+            "version": api::API_VERSION,
+            "status": api::current_status(app),
+            "last_error": api::last_error(),
+        })),
+        ("POST", "/v1/recording/toggle") => {
+            api::toggle(app);
+            Response::ok(json!({ "status": api::current_status(app) }))
+        }
+        ("POST", "/v1/quality/next") => next_quality(app),
+        ("POST", "/v1/language/next") => next_language(app),
+        ("GET", "/v1/transcription/last") => last_transcription(app),
+        ("POST", TRANSCRIPTIONS_PATH) => transcribe(app, request),
+        ("GET", "/metrics") => Response::metrics(metrics::render()),
+        _ => Response::error(404, ErrorCode::NotFound, "Unknown endpoint"),
+    }
+}
+
+/// Writes the current status and then every change as one JSON object per
+/// line, with a blank line when nothing changed for `HEARTBEAT_INTERVAL`,
+/// until the client disconnects.
+fn stream_status(app: &AppHandle, stream: &mut impl Write) -> Result<()> {
+    let mut changes = api::subscribe_status();
+    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n")?;
+    let mut status = api::current_status(app);
+    loop {
+        writeln!(stream, "{}", json!({ "version": api::API_VERSION, "status": status }))?;
+        stream.flush()?;
+        let mut last_write = Instant::now();
+        status = loop {
+            match changes.try_recv() {
+                Ok(status) => break status,
+                // Only the latest status matters
+                Err(TryRecvError::Lagged(_)) => break api::current_status(app),
+                Err(TryRecvError::Closed) => return Ok(()),
+                Err(TryRecvError::Empty) => {}
+            }
+            // Writing fails once the client is gone, which ends the stream
+            if last_write.elapsed() >= HEARTBEAT_INTERVAL {
+                writeln!(stream)?;
+                stream.flush()?;
+                last_write = Instant::now();
+            }
+            std::thread::sleep(STATUS_POLL_INTERVAL);
+        };
+    }
+}
+
+fn max_body(path: &str) -> usize {
+    if path == TRANSCRIPTIONS_PATH { MAX_AUDIO_BYTES } else { MAX_BODY_BYTES }
+}
+
+fn handle_connection<S: Read + Write>(app: &AppHandle, stream: &mut S, token: &str, slot: ConnectionSlot) {
+    let response = match http::read_request(stream, token, max_body) {
+        Ok(None) => Response::error(401, ErrorCode::Unauthorized, "Missing or invalid token"),
+        Ok(Some(request)) if request.method == "GET" && request.path == "/v1/events" => {
+            // Event streams don't take connections from requests
+            match ConnectionSlot::take(&EVENT_STREAMS, MAX_EVENT_STREAMS) {
+                Some(_stream_slot) => {
+                    drop(slot);
+                    if let Err(e) = stream_status(app, stream) {
+                        debug!("Event stream ended: {}", e);
+                    }
+                    return;
+                }
+                None => Response::error(503, ErrorCode::Busy, "Too many event streams"),
+            }
+        }
+        Ok(Some(request)) => route(app, &request),
+        Err(e) => {
+            debug!("Invalid API request: {}", e);
+            Response::error(400, ErrorCode::BadRequest, "Invalid request")
+        }
+    };
+    if let Err(e) = http::write_response(stream, response.status, response.content_type, &response.body) {
+        debug!("Failed to write API response: {}", e);
+    }
+}
+
+/// The certificate chain and key of `api.tls_cert` and `api.tls_key`.
+fn tls_config(settings: &ApiSettings) -> Result<Arc<ServerConfig>> {
+    let (Some(cert_path), Some(key_path)) = (&settings.tls_cert, &settings.tls_key) else {
+        anyhow::bail!("Serving the API to the network needs api.tls_cert and api.tls_key");
+    };
+    let mut cert_file = BufReader::new(File::open(cert_path).with_context(|| format!("Failed to open {}", cert_path))?);
+    let certs = rustls_pemfile::certs(&mut cert_file).collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read certificates from {}", cert_path))?;
+    anyhow::ensure!(!certs.is_empty(), "No certificate in {}", cert_path);
+    let mut key_file = BufReader::new(File::open(key_path).with_context(|| format!("Failed to open {}", key_path))?);
+    let key = rustls_pemfile::private_key(&mut key_file)
+        .with_context(|| format!("Failed to read the private key from {}", key_path))?
+        .with_context(|| format!("No private key in {}", key_path))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("The certificate doesn't match the key")?;
+    Ok(Arc::new(config))
+}
+
+fn serve_connection(app: &AppHandle, mut stream: TcpStream, slot: ConnectionSlot, token: &str, tls: Option<Arc<ServerConfig>>) {
+    let Some(tls) = tls else {
+        handle_connection(app, &mut stream, token, slot);
+        return;
+    };
+    let connection = match ServerConnection::new(tls) {
+        Ok(connection) => connection,
+        Err(e) => {
+            warn!("Failed to set up TLS: {}", e);
+            return;
+        }
+    };
+    let mut stream = StreamOwned::new(connection, stream);
+    handle_connection(app, &mut stream, token, slot);
+    stream.conn.send_close_notify();
+    let _ = stream.flush();
+}
+
+fn accept(app: AppHandle, listener: TcpListener, token: String, tls: Option<Arc<ServerConfig>>, max_connections: usize) {
+    std::thread::spawn(move || {
+        http::accept(listener, max_connections, move |stream, slot| serve_connection(&app, stream, slot, &token, tls.clone()));
+    });
+}
+
+/// Serves the HTTP API on 127.0.0.1 if `api.server_enabled` is set, and with
+/// `api.lan_enabled` to the network on `api.lan_port` as well. The network
+/// only gets HTTPS with the user's certificate, the token would travel in
+/// the clear otherwise. Every request needs the Keychain token as
+/// `Authorization: Bearer`.
+pub fn start(app: AppHandle, settings: &ApiSettings) -> Result<()> {
+    if !settings.server_enabled {
+        return Ok(());
+    }
+    let token = crate::keychain::api_token()?;
+    let listener = TcpListener::bind(("127.0.0.1", settings.port))
+        .with_context(|| format!("Failed to listen on port {}", settings.port))?;
+    info!("API server listening on 127.0.0.1:{}", settings.port);
+    accept(app.clone(), listener, token.clone(), None, MAX_CONNECTIONS);
+
+    if settings.lan_enabled {
+        let tls = tls_config(settings)?;
+        let listener = TcpListener::bind(("0.0.0.0", settings.lan_port))
+            .with_context(|| format!("Failed to listen on port {}", settings.lan_port))?;
+        warn!("API server reachable from the network over HTTPS on port {}", settings.lan_port);
+        accept(app, listener, token, Some(tls), MAX_LAN_CONNECTIONS);
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use log::{error, info};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use crate::config::{ConfigManager, WhisperSettings, WhisprConfig};
+use crate::ephemeral;
+
+/// Words spelled out with "spell that" since launch, prompted to whisper on
+/// top of `whisper.dictionary` until whispr quits.
+#[derive(Debug, Default)]
+pub struct SessionDictionary {
+    /// Each word with the number of dictations that used it since
+    words: Vec<(String, u32)>,
+}
+
+impl SessionDictionary {
+    /// Adds `word` unless it is already known. Returns whether it was new.
+    pub fn add(&mut self, word: &str) -> bool {
+        if self.words.iter().any(|(known, _)| known.eq_ignore_ascii_case(word)) {
+            return false;
+        }
+        self.words.push((word.to_string(), 0));
+        true
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|(word, _)| word.as_str())
+    }
+
+    /// Counts the words `text` uses. Words used in `promote_after` dictations
+    /// are removed and returned to be kept for good, 0 never promotes.
+    pub fn record_uses(&mut self, text: &str, promote_after: u32) -> Vec<String> {
+        let used: Vec<String> = text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        for (word, uses) in &mut self.words {
+            if used.contains(&word.to_lowercase()) {
+                *uses += 1;
+            }
+        }
+        if promote_after == 0 {
+            return Vec::new();
+        }
+        let (promoted, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.words).into_iter().partition(|(_, uses)| *uses >= promote_after);
+        self.words = kept;
+        promoted.into_iter().map(|(word, _)| word).collect()
+    }
+}
+
+static SESSION: Lazy<Mutex<SessionDictionary>> = Lazy::new(|| Mutex::new(SessionDictionary::default()));
+
+/// The word spelled out in "K-U-B-E-R-N-E-T-E-S", "k u b e" or an already
+/// joined "KUBERNETES". Letters spelled in capitals are taken as a name and
+/// capitalized, "Kubernetes".
+pub fn parse_spelling(spelled: &str) -> Option<String> {
+    let parts: Vec<&str> = spelled.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let word = match parts.as_slice() {
+        [word] if word.chars().count() > 1 => word.to_string(),
+        parts if parts.len() > 1 && parts.iter().all(|part| part.chars().count() == 1) => parts.concat(),
+        _ => return None,
+    };
+    if word.chars().any(char::is_lowercase) {
+        return Some(word);
+    }
+    let mut chars = word.chars();
+    let first = chars.next()?;
+    Some(first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect())
+}
+
+/// Adds a spelled word for the rest of the session.
+pub fn add(word: &str) -> bool {
+    SESSION.lock().unwrap().add(word)
+}
+
+/// Appends the session's words to the dictionary whisper is prompted with.
+pub fn extend(settings: &mut WhisperSettings) {
+    let session = SESSION.lock().unwrap();
+    let mut dictionary = settings.dictionary.take().unwrap_or_default();
+    for word in session.words() {
+        if !dictionary.iter().any(|known| known.eq_ignore_ascii_case(word)) {
+            dictionary.push(word.to_string());
+        }
+    }
+    settings.dictionary = (!dictionary.is_empty()).then_some(dictionary);
+}
+
+/// Counts the session words a delivered dictation used and moves the ones
+/// used often enough into `whisper.dictionary`. Nothing is kept from an
+/// ephemeral session.
+pub fn record_uses(text: &str, config: &WhisprConfig) {
+    if ephemeral::is_active() {
+        return;
+    }
+    let promoted = SESSION.lock().unwrap().record_uses(text, config.whisper.promote_spelled_after);
+    if promoted.is_empty() {
+        return;
+    }
+    let manager = match ConfigManager::<WhisprConfig>::new("settings") {
+        Ok(manager) => manager,
+        Err(e) => {
+            error!("Failed to promote {:?} to the dictionary: {}", promoted, e);
+            return;
+        }
+    };
+    let mut config = match manager.load_config("settings") {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to promote {:?} to the dictionary: {}", promoted, e);
+            return;
+        }
+    };
+    let dictionary = config.whisper.dictionary.get_or_insert_with(Vec::new);
+    for word in &promoted {
+        if !dictionary.iter().any(|known| known.eq_ignore_ascii_case(word)) {
+            info!("Adding {} to the dictionary after repeated use", word);
+            dictionary.push(word.clone());
+        }
+    }
+    if let Err(e) = manager.save_config(&config, "settings") {
+        error!("Failed to save dictionary: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_join_into_words() {
+        assert_eq!(parse_spelling("K-U-B-E-R-N-E-T-E-S").unwrap(), "Kubernetes");
+        assert_eq!(parse_spelling(": K, U, B, E.").unwrap(), "Kube");
+        assert_eq!(parse_spelling("k u b e c t l").unwrap(), "kubectl");
+        assert_eq!(parse_spelling("KUBERNETES").unwrap(), "Kubernetes");
+        assert_eq!(parse_spelling("gRPC").unwrap(), "gRPC");
+    }
+
+    #[test]
+    fn sentences_are_no_spellings() {
+        assert!(parse_spelling("it was a good day").is_none());
+        assert!(parse_spelling("K").is_none());
+        assert!(parse_spelling("").is_none());
+    }
+
+    #[test]
+    fn words_are_promoted_after_repeated_use() {
+        let mut session = SessionDictionary::default();
+        assert!(session.add("Kubernetes"));
+        assert!(!session.add("kubernetes"));
+        session.add("Helm");
+
+        assert!(session.record_uses("Deploy it to Kubernetes.", 2).is_empty());
+        assert!(session.record_uses("Nothing here", 2).is_empty());
+        assert_eq!(session.record_uses("Kubernetes and helm", 2), vec!["Kubernetes".to_string()]);
+        assert_eq!(session.words().collect::<Vec<_>>(), vec!["Helm"]);
+        // 0 keeps words in the session only
+        assert!(session.record_uses("Helm Helm", 0).is_empty());
+    }
+}
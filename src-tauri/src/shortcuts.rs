@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use log::{info, debug, error};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tauri::{AppHandle, Manager, Runtime};
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::output;
+
+thread_local! {
+    // Registered on the main thread and unregistered when dropped
+    static SHORTCUT_MANAGER: RefCell<Option<GlobalHotKeyManager>> = const { RefCell::new(None) };
+}
+
+/// Types the most recent transcription again, for when focus was in the
+/// wrong place the first time and the text went nowhere.
+pub fn paste_last_transcription<R: Runtime>(app: &AppHandle<R>) {
+    let Some(text) = app.try_state::<crate::AppState>()
+        .and_then(|state| state.last_transcription.lock().unwrap().clone())
+    else {
+        crate::show_toast(app, "Nothing to paste yet");
+        return;
+    };
+    let config = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default();
+
+    std::thread::spawn(move || {
+        // Give focus a moment to return from the menu to the previous app
+        std::thread::sleep(crate::FOCUS_RETURN_DELAY);
+        info!("Pasting last transcription again");
+        if let Err(e) = output::type_text(&text, &config.output.injection) {
+            error!("Failed to paste last transcription: {}", e);
+        }
+    });
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    PasteLast,
+    Recall,
+}
+
+/// Registers `paste_last_shortcut`, e.g. "ctrl+cmd+KeyV", and while the
+/// rolling buffer is on `rolling_buffer.shortcut`, replacing what was
+/// registered before. Has to run on the main thread.
+pub fn register<R: Runtime>(app: &AppHandle<R>, config: &WhisprConfig) -> Result<()> {
+    let recall_shortcut = config.rolling_buffer.shortcut.as_deref().filter(|_| config.rolling_buffer.enabled);
+    let shortcuts = [
+        ("paste_last_shortcut", config.paste_last_shortcut.as_deref(), Action::PasteLast),
+        ("rolling_buffer.shortcut", recall_shortcut, Action::Recall),
+    ];
+    // Dropping the previous manager unregisters its shortcuts
+    SHORTCUT_MANAGER.with(|cell| cell.borrow_mut().take());
+    let manager = GlobalHotKeyManager::new().context("Failed to create global shortcut manager")?;
+    let mut actions = HashMap::new();
+    for (setting, shortcut, action) in shortcuts {
+        let Some(shortcut) = shortcut.filter(|shortcut| !shortcut.is_empty()) else {
+            continue;
+        };
+        let registered = HotKey::from_str(shortcut)
+            .map_err(|e| anyhow::anyhow!("Invalid {} \"{}\": {}", setting, shortcut, e))
+            .and_then(|hotkey| {
+                manager.register(hotkey).with_context(|| format!("Failed to register shortcut {}", shortcut))?;
+                Ok(hotkey)
+            });
+        match registered {
+            Ok(hotkey) => {
+                actions.insert(hotkey.id(), action);
+                debug!("Registered {} {}", setting, shortcut);
+            }
+            // The other shortcuts still work
+            Err(e) => error!("{}", e),
+        }
+    }
+    if actions.is_empty() {
+        return Ok(());
+    }
+
+    let app = app.clone();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        // Wait for the release so the shortcut's modifiers don't alter the typed text
+        if event.state != HotKeyState::Released {
+            return;
+        }
+        match actions.get(&event.id) {
+            Some(Action::PasteLast) => paste_last_transcription(&app),
+            Some(Action::Recall) => crate::menu::handle_recall(&app),
+            None => {}
+        }
+    }));
+    SHORTCUT_MANAGER.with(|cell| *cell.borrow_mut() = Some(manager));
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use log::warn;
+use objc::{class, msg_send, sel, sel_impl};
+
+/// Plays one of the sounds from /System/Library/Sounds, e.g. "Basso".
+pub fn play_system_sound(name: &str) {
+    unsafe {
+        let name_string = NSString::alloc(nil).init_str(name);
+        let sound: id = msg_send![class!(NSSound), soundNamed: name_string];
+        if sound == nil {
+            warn!("System sound {} not found", name);
+            return;
+        }
+        let _: bool = msg_send![sound, play];
+    }
+}
@@ -0,0 +1,102 @@
+use log::{debug, error};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindowBuilder};
+use whispr_core::input_device::InputFormat;
+use whispr_core::spectrogram::MelSpectrogram;
+
+const SPECTROGRAM_LABEL: &str = "whispr:spectrogram";
+const MEL_BANDS: usize = 80;
+// How often the window checks whether it was closed while no audio arrives
+const CLOSED_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Frames computed from one buffer of device audio.
+#[derive(Debug, Clone, Serialize)]
+struct SpectrogramFrames {
+    frames: Vec<Vec<f32>>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+// Bumped per opened window so a worker that is shutting down leaves the
+// tap of its successor alone
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Opens the spectrogram window, or brings it to the front. While it is
+/// open, a log-mel spectrogram of the device audio is streamed to it as
+/// `spectrogram-frames` events, one 10 ms column per frame.
+pub fn open_spectrogram<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window(SPECTROGRAM_LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let Some(state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+
+    let window = WebviewWindowBuilder::new(app, SPECTROGRAM_LABEL, WebviewUrl::App("index.html".into()))
+        .title("whispr Spectrogram")
+        .inner_size(800.0, 320.0)
+        .min_inner_size(400.0, 200.0)
+        .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(e) => {
+            error!("Failed to open spectrogram window: {}", e);
+            return;
+        }
+    };
+
+    // The audio callback only hands the buffers over, the FFTs run here
+    let (sender, receiver) = mpsc::channel::<(Vec<f32>, InputFormat)>();
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    state.audio.lock().unwrap().set_tap(Some(Box::new(move |data, format| {
+        let _ = sender.send((data.to_vec(), format));
+    })));
+
+    let app_handle = app.clone();
+    std::thread::spawn(move || {
+        let mut spectrogram: Option<(InputFormat, MelSpectrogram)> = None;
+        loop {
+            match receiver.recv_timeout(CLOSED_POLL_INTERVAL) {
+                Ok((data, format)) => {
+                    // A different device starts a new spectrogram
+                    if spectrogram.as_ref().map(|(current, _)| *current) != Some(format) {
+                        spectrogram = Some((format, MelSpectrogram::new(format.sample_rate, MEL_BANDS)));
+                    }
+                    let (_, mel) = spectrogram.as_mut().unwrap();
+                    let frames = mel.push(&downmix(&data, format.channels));
+                    if !frames.is_empty() {
+                        let payload = SpectrogramFrames { frames, sample_rate: format.sample_rate, channels: format.channels };
+                        if window.emit_to(SPECTROGRAM_LABEL, "spectrogram-frames", payload).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // Replaced by another window's tap
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if app_handle.get_webview_window(SPECTROGRAM_LABEL).is_none() {
+                break;
+            }
+        }
+        if GENERATION.load(Ordering::SeqCst) == generation {
+            if let Some(state) = app_handle.try_state::<crate::AppState>() {
+                state.audio.lock().unwrap().set_tap(None);
+            }
+        }
+        debug!("Spectrogram closed, stopped streaming frames");
+    });
+}
+
+/// Averages interleaved channels into one.
+fn downmix(data: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    data.chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
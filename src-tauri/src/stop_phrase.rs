@@ -0,0 +1,72 @@
+use log::{debug, info, error};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+use whispr_core::config::{ConfigManager, WhisprConfig};
+use whispr_core::stop_phrase::StopPhrase;
+use crate::api::{self, Status};
+use crate::trigger::TriggerSource;
+
+// How often the end of the recording is transcribed while it runs
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// Long enough for the phrase at a slow pace
+const WINDOW_SECS: f32 = 3.0;
+
+static HEARD: AtomicBool = AtomicBool::new(false);
+// Only the watcher of the latest recording keeps going
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Transcribes the last seconds of the running recording every second and
+/// ends it once `stop_phrase` is heard. Does nothing without a stop phrase.
+pub fn watch<R: Runtime>(app: &AppHandle<R>) {
+    let config = ConfigManager::<WhisprConfig>::new("settings")
+        .and_then(|manager| manager.load_config("settings"))
+        .unwrap_or_default();
+    let Some(phrase) = config.stop_phrase.as_deref().and_then(StopPhrase::new) else {
+        return;
+    };
+    HEARD.store(false, Ordering::SeqCst);
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let app = app.clone();
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(CHECK_INTERVAL);
+            if GENERATION.load(Ordering::SeqCst) != generation || api::current_status(&app) != Status::Recording {
+                return;
+            }
+            let Some(state) = app.try_state::<crate::AppState>() else {
+                return;
+            };
+            let Some(whisper) = state.decoder() else {
+                continue;
+            };
+            let audio = match state.audio.lock().unwrap().recent_audio(WINDOW_SECS, 16000, 1) {
+                Ok(Some(audio)) => audio,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Failed to read the running recording: {}", e);
+                    return;
+                }
+            };
+            let text = match whisper.process_audio_with_confidence(&audio, &config.whisper, None, None) {
+                Ok(decoding) => whispr_core::pipeline::join_segments(&decoding.segments, 0.0),
+                Err(e) => {
+                    debug!("Stop phrase check failed: {}", e);
+                    continue;
+                }
+            };
+            if phrase.find(&text).is_some() && GENERATION.load(Ordering::SeqCst) == generation {
+                info!("Heard the stop phrase, ending the dictation");
+                HEARD.store(true, Ordering::SeqCst);
+                crate::trigger::send(&app, TriggerSource::StopPhrase, false);
+                return;
+            }
+        }
+    });
+}
+
+/// Whether the dictation that just ended was stopped by the phrase, which
+/// then has to be removed from its text. Resets for the next dictation.
+pub fn take_heard() -> bool {
+    HEARD.swap(false, Ordering::SeqCst)
+}
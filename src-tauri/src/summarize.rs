@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use crate::config::{ConfigManager, LlmSettings, WhisprConfig};
+use crate::history;
+
+// Entries being summarized, clicking Summarize again meanwhile does nothing
+static RUNNING: Mutex<Vec<i64>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize)]
+struct SummaryStatus {
+    id: i64,
+    running: bool,
+    error: Option<String>,
+}
+
+/// The model's answer in an OpenAI compatible chat completions response.
+fn completion_text(response: &Value) -> Option<&str> {
+    response["choices"][0]["message"]["content"].as_str()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// Runs `text` through the local LLM with `llm.summary_prompt`.
+pub fn summarize(settings: &LlmSettings, text: &str) -> Result<String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(settings.timeout_secs))
+        .build()?;
+    let response: Value = client.post(&settings.endpoint)
+        .json(&json!({
+            "model": settings.model,
+            "messages": [
+                { "role": "system", "content": settings.summary_prompt },
+                { "role": "user", "content": text },
+            ],
+            "stream": false,
+        }))
+        .send()
+        .with_context(|| format!("No LLM reachable at {}", settings.endpoint))?
+        .error_for_status()?
+        .json()?;
+    completion_text(&response)
+        .map(str::to_string)
+        .context("The LLM returned no summary")
+}
+
+fn summarize_entry(id: i64) -> Result<()> {
+    let config = ConfigManager::<WhisprConfig>::new("settings")?.load_config("settings")?;
+    let entries = history::load()?;
+    let entry = entries.iter().find(|entry| entry.id == id).context("Unknown history entry")?;
+    // The latest re-transcription is the best text there is
+    let text = entry.versions.last().map_or(&entry.text, |version| &version.text);
+    info!("Summarizing history entry {} with {}", id, config.llm.model);
+    let summary = summarize(&config.llm, text)?;
+    history::update(id, |entry| entry.summary = Some(summary))
+}
+
+/// Summarizes a history entry in the background and stores the summary
+/// with it. `summary-status` events report the progress to the history
+/// window.
+#[tauri::command]
+pub fn summarize_history_entry(app: AppHandle, id: i64) {
+    {
+        let mut running = RUNNING.lock().unwrap();
+        if running.contains(&id) {
+            return;
+        }
+        running.push(id);
+    }
+    let _ = app.emit("summary-status", SummaryStatus { id, running: true, error: None });
+    std::thread::spawn(move || {
+        let result = summarize_entry(id);
+        RUNNING.lock().unwrap().retain(|running| *running != id);
+        let error = result.err().map(|e| {
+            error!("Failed to summarize history entry {}: {}", id, e);
+            e.to_string()
+        });
+        let _ = app.emit("summary-status", SummaryStatus { id, running: false, error });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_first_choice() {
+        let response = json!({ "choices": [{ "message": { "role": "assistant", "content": "\n- Ship it\n" } }] });
+        assert_eq!(completion_text(&response), Some("- Ship it"));
+    }
+
+    #[test]
+    fn empty_answers_are_no_summary() {
+        assert_eq!(completion_text(&json!({ "choices": [{ "message": { "content": "  " } }] })), None);
+        assert_eq!(completion_text(&json!({ "error": "model not found" })), None);
+    }
+}
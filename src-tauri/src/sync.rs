@@ -0,0 +1,357 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use log::{info, debug, error};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::config::{ConfigManager, WhisprConfig};
+use crate::history::{self, HistoryEntry};
+
+const SYNC_DIR: &str = "whispr";
+const MACHINE_ID_FILE: &str = "machine_id";
+const STATE_FILE: &str = "sync_state.json";
+const PULL_INTERVAL: Duration = Duration::from_secs(60);
+
+// Settings that only make sense on the Mac they were made on (devices, local
+// paths, what this Mac exposes on the network), and webhook URLs, which carry
+// their credentials and shouldn't land in a shared folder
+const LOCAL_KEYS: &[&str] = &[
+    "sync",
+    "audio.device_name",
+    "audio.device_id",
+    "audio.recordings_dir",
+    "models_dir",
+    "model_path",
+    "start_at_login",
+    "developer",
+    "daemon",
+    "api.lan_enabled",
+    "api.lan_port",
+    "api.tls_cert",
+    "api.tls_key",
+    "output.file_path",
+    "output.injection.paste_apps",
+    "integrations.obsidian.vault_path",
+    "integrations.obs.caption_file",
+    "integrations.chat.webhook_url",
+    "output.webhook.url",
+];
+
+/// One settings change. Every Mac only appends to its own journal, so cloud
+/// drives never see two writers on the same file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    /// Milliseconds since the epoch, 0 for values seeded when sync was enabled
+    timestamp: i64,
+    machine: String,
+    key: String,
+    value: Value,
+}
+
+fn machine_id(config_dir: &Path) -> Result<String> {
+    let path = config_dir.join(MACHINE_ID_FILE);
+    if let Ok(id) = fs::read_to_string(&path) {
+        if !id.trim().is_empty() {
+            return Ok(id.trim().to_string());
+        }
+    }
+    let mut bytes = [0u8; 8];
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .context("Failed to read random bytes")?;
+    let id: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    fs::write(&path, &id)?;
+    Ok(id)
+}
+
+fn sync_dir(folder: &str) -> PathBuf {
+    PathBuf::from(folder).join(SYNC_DIR)
+}
+
+fn is_local(key: &str) -> bool {
+    LOCAL_KEYS.iter().any(|local| key == *local || key.starts_with(&format!("{}.", local)))
+}
+
+/// Leaf values by dotted path, arrays count as a single value.
+fn flatten(value: &Value, prefix: &str, leaves: &mut HashMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(value, &path, leaves);
+            }
+        }
+        leaf => {
+            leaves.insert(prefix.to_string(), leaf.clone());
+        }
+    }
+}
+
+fn set_path(root: &mut Value, key: &str, value: Value) {
+    let mut current = root;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let map = current.as_object_mut().unwrap();
+        if parts.peek().is_none() {
+            map.insert(part.to_string(), value);
+            return;
+        }
+        current = map.entry(part.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+fn append_journal(dir: &Path, machine: &str, changes: Vec<(String, Value)>, timestamp: i64) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(dir.join(format!("journal-{}.jsonl", machine)))?;
+    for (key, value) in changes {
+        debug!("Journaling setting {}", key);
+        let entry = JournalEntry { timestamp, machine: machine.to_string(), key, value };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    }
+    Ok(())
+}
+
+fn read_journals(dir: &Path) -> Result<Vec<JournalEntry>> {
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        let is_journal = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("journal-") && name.ends_with(".jsonl"));
+        if !is_journal {
+            continue;
+        }
+        for line in BufReader::new(File::open(&path)?).lines() {
+            // The cloud drive may still be downloading the end of a journal
+            match serde_json::from_str::<JournalEntry>(&line?) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => debug!("Skipping unreadable journal line in {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Last writer wins per key, ties broken by machine id so every Mac picks
+/// the same value.
+fn latest_values(entries: Vec<JournalEntry>) -> HashMap<String, JournalEntry> {
+    let mut latest: HashMap<String, JournalEntry> = HashMap::new();
+    for entry in entries {
+        if is_local(&entry.key) {
+            continue;
+        }
+        let newer = match latest.get(&entry.key) {
+            Some(current) => (entry.timestamp, &entry.machine) > (current.timestamp, &current.machine),
+            None => true,
+        };
+        if newer {
+            latest.insert(entry.key.clone(), entry);
+        }
+    }
+    latest
+}
+
+/// Settings as of the last sync, to tell what changed here since.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    folder: Option<String>,
+    values: HashMap<String, Value>,
+}
+
+fn load_state(config_dir: &Path) -> SyncState {
+    fs::read_to_string(config_dir.join(STATE_FILE))
+        .ok()
+        .and_then(|state| serde_json::from_str(&state).ok())
+        .unwrap_or_default()
+}
+
+fn synced_leaves(config: &WhisprConfig) -> Result<HashMap<String, Value>> {
+    let mut leaves = HashMap::new();
+    flatten(&serde_json::to_value(config)?, "", &mut leaves);
+    leaves.retain(|key, _| !is_local(key));
+    Ok(leaves)
+}
+
+/// Journals what changed here since the last sync, then applies the latest
+/// value of every key across all journals. Settings are usually edited by
+/// hand, so changes are found by comparing against the last synced state
+/// rather than hooking saves.
+fn sync_settings(manager: &ConfigManager<WhisprConfig>, config: WhisprConfig, folder: &str) -> Result<()> {
+    let config_dir = manager.get_config_dir();
+    let machine = machine_id(config_dir)?;
+    let dir = sync_dir(folder);
+    fs::create_dir_all(&dir)?;
+
+    let current = synced_leaves(&config)?;
+    let state = load_state(config_dir);
+    if state.folder.as_deref() == Some(folder) {
+        let changes: Vec<(String, Value)> = current.iter()
+            .filter(|(key, value)| state.values.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        append_journal(&dir, &machine, changes, Utc::now().timestamp_millis())?;
+    } else {
+        // First sync with this folder, anything the other Macs synced wins
+        info!("Seeding sync folder {} with this Mac's settings", folder);
+        append_journal(&dir, &machine, current.clone().into_iter().collect(), 0)?;
+    }
+
+    let mut settings = serde_json::to_value(&config)?;
+    let mut changed = 0;
+    for (key, entry) in latest_values(read_journals(&dir)?) {
+        if current.get(&key) != Some(&entry.value) {
+            set_path(&mut settings, &key, entry.value);
+            changed += 1;
+        }
+    }
+    let config = if changed > 0 {
+        // Values this version of whispr doesn't understand leave settings untouched
+        let merged: WhisprConfig = serde_json::from_value(settings).context("Synced settings are invalid")?;
+        manager.save_config(&merged, "settings")?;
+        info!("Applied {} synced setting(s)", changed);
+        merged
+    } else {
+        config
+    };
+
+    let state = SyncState { folder: Some(folder.to_string()), values: synced_leaves(&config)? };
+    fs::write(config_dir.join(STATE_FILE), serde_json::to_string(&state)?)?;
+    Ok(())
+}
+
+/// Copies a new history entry to this Mac's history file in the sync folder.
+pub fn publish_history_entry(config: &WhisprConfig, entry: &HistoryEntry) {
+    let Some(folder) = config.sync.folder.as_deref().filter(|folder| !folder.is_empty() && config.sync.history) else {
+        return;
+    };
+    let publish = || -> Result<()> {
+        let manager = ConfigManager::<WhisprConfig>::new("settings")?;
+        let machine = machine_id(manager.get_config_dir())?;
+        let dir = sync_dir(folder);
+        fs::create_dir_all(&dir)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(dir.join(format!("history-{}.jsonl", machine)))?;
+        // Recordings stay on the Mac they were made on
+        let entry = HistoryEntry { recording: None, ..entry.clone() };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    };
+    if let Err(e) = publish() {
+        error!("Failed to publish history entry to sync folder: {}", e);
+    }
+}
+
+fn pull_history(config: &WhisprConfig, config_dir: &Path, folder: &str) -> Result<()> {
+    let machine = machine_id(config_dir)?;
+    let own_file = format!("history-{}.jsonl", machine);
+    let local = history::load()?;
+    let known: HashSet<i64> = local.iter().map(|entry| entry.id).collect();
+    // Entries older than a full history would only be pruned again
+    let oldest = match local.first() {
+        Some(entry) if local.len() >= config.history.max_entries => entry.id,
+        _ => i64::MIN,
+    };
+
+    let mut new_entries = Vec::new();
+    for file in fs::read_dir(sync_dir(folder))? {
+        let path = file?.path();
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if !name.starts_with("history-") || !name.ends_with(".jsonl") || name == own_file {
+            continue;
+        }
+        for line in BufReader::new(File::open(&path)?).lines() {
+            if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line?) {
+                if entry.id > oldest && !known.contains(&entry.id) {
+                    new_entries.push(entry);
+                }
+            }
+        }
+    }
+    if !new_entries.is_empty() {
+        info!("Merging {} history entries from other Macs", new_entries.len());
+        history::merge(new_entries, config.history.max_entries)?;
+    }
+    Ok(())
+}
+
+/// Shares this Mac's changes and applies what the other Macs synced. Does
+/// nothing unless `sync.folder` is set.
+pub fn pull() {
+    let pull = || -> Result<()> {
+        let manager = ConfigManager::<WhisprConfig>::new("settings")?;
+        let config = manager.load_config("settings")?;
+        let Some(folder) = config.sync.folder.clone().filter(|folder| !folder.is_empty()) else {
+            return Ok(());
+        };
+        sync_settings(&manager, config, &folder)?;
+        // Synced settings may have just turned history off
+        let config = manager.load_config("settings")?;
+        if config.sync.history && config.history.enabled {
+            pull_history(&config, manager.get_config_dir(), &folder)?;
+        }
+        Ok(())
+    };
+    if let Err(e) = pull() {
+        error!("Failed to sync: {}", e);
+    }
+}
+
+/// Pulls now and then every minute in the background.
+pub fn start() {
+    std::thread::spawn(|| loop {
+        pull();
+        std::thread::sleep(PULL_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(timestamp: i64, machine: &str, key: &str, value: Value) -> JournalEntry {
+        JournalEntry { timestamp, machine: machine.to_string(), key: key.to_string(), value }
+    }
+
+    #[test]
+    fn last_writer_wins_per_key() {
+        let latest = latest_values(vec![
+            entry(10, "a", "whisper.language", json!("de")),
+            entry(20, "b", "whisper.language", json!("en")),
+            entry(30, "a", "whisper.translate", json!(true)),
+            entry(5, "b", "whisper.translate", json!(false)),
+        ]);
+        assert_eq!(latest["whisper.language"].value, json!("en"));
+        assert_eq!(latest["whisper.translate"].value, json!(true));
+    }
+
+    #[test]
+    fn local_keys_are_not_synced() {
+        let latest = latest_values(vec![
+            entry(10, "a", "audio.device_name", json!("USB Mic")),
+            entry(10, "a", "sync.folder", json!("/elsewhere")),
+            entry(10, "a", "integrations.chat.webhook_url", json!("https://hooks.slack.com/services/T/B/x")),
+            entry(10, "a", "output.webhook.url", json!("https://example.com/hook")),
+            entry(10, "a", "api.lan_enabled", json!(true)),
+            entry(10, "a", "daemon.port", json!(43212)),
+            entry(10, "a", "integrations.obsidian.vault_path", json!("/Users/a/Vault")),
+        ]);
+        assert!(latest.is_empty());
+    }
+
+    #[test]
+    fn set_path_creates_objects() {
+        let mut settings = json!({ "whisper": { "language": "de" } });
+        set_path(&mut settings, "whisper.language", json!("en"));
+        set_path(&mut settings, "ui.menu_sections", json!(["quality"]));
+        assert_eq!(settings, json!({ "whisper": { "language": "en" }, "ui": { "menu_sections": ["quality"] } }));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Serialize;
+use std::borrow::Cow;
+use crate::config::TextDirection;
+
+/// Whisper languages written right to left.
+const RTL_LANGUAGES: &[&str] = &["ar", "fa", "he", "ps", "sd", "ur", "yi"];
+/// Unicode RIGHT-TO-LEFT MARK, an invisible strong right-to-left character
+const RLM: char = '\u{200F}';
+
+/// The direction of a text, serialized as the HTML `dir` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// Text for the overlay with the direction to lay it out in.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectedText<'a> {
+    pub text: &'a str,
+    pub dir: Direction,
+}
+
+impl<'a> DirectedText<'a> {
+    /// Messages mix whispr's own words with dictated ones, so they go by
+    /// their first strong character.
+    pub fn new(text: &'a str) -> Self {
+        Self { text, dir: of_text(text).unwrap_or(Direction::Ltr) }
+    }
+}
+
+pub fn is_rtl_language(code: &str) -> bool {
+    RTL_LANGUAGES.contains(&code)
+}
+
+fn is_rtl_char(c: char) -> bool {
+    matches!(c,
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and Arabic extensions
+        '\u{0590}'..='\u{08FF}'
+        // Hebrew and Arabic presentation forms
+        | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}'
+        | RLM | '\u{202B}' | '\u{202E}' | '\u{2067}'
+    )
+}
+
+fn is_ltr_mark(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{202A}' | '\u{202D}' | '\u{2066}')
+}
+
+/// The direction of the first strongly directional character, as Unicode's
+/// bidi algorithm picks a paragraph's direction. `None` for text of only
+/// digits, punctuation and the like.
+pub fn of_text(text: &str) -> Option<Direction> {
+    text.chars().find_map(|c| {
+        if is_rtl_char(c) {
+            Some(Direction::Rtl)
+        } else if c.is_alphabetic() || is_ltr_mark(c) {
+            Some(Direction::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+/// The direction to show a transcription in with `ui.text_direction`.
+/// `Auto` goes by the detected language and, if it isn't known, the text.
+pub fn resolve(setting: TextDirection, language: Option<&str>, text: &str) -> Direction {
+    match setting {
+        TextDirection::Ltr => Direction::Ltr,
+        TextDirection::Rtl => Direction::Rtl,
+        TextDirection::Auto => match language {
+            Some(language) if is_rtl_language(language) => Direction::Rtl,
+            Some(_) => Direction::Ltr,
+            None => of_text(text).unwrap_or(Direction::Ltr),
+        },
+    }
+}
+
+/// Right-to-left text starting with e.g. a Latin product name or a number
+/// would be laid out left to right by apps that pick the direction from the
+/// first character. A leading right-to-left mark keeps it right to left, marks
+/// already in the text are passed on as they are.
+pub fn mark_for_injection(text: &str, direction: Direction) -> Cow<'_, str> {
+    if direction == Direction::Rtl && of_text(text) != Some(Direction::Rtl) {
+        Cow::Owned(format!("{}{}", RLM, text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_strong_character_decides() {
+        assert_eq!(of_text("שלום world"), Some(Direction::Rtl));
+        assert_eq!(of_text("123, مرحبا"), Some(Direction::Rtl));
+        assert_eq!(of_text("Hello مرحبا"), Some(Direction::Ltr));
+        assert_eq!(of_text("\u{200F}iPhone"), Some(Direction::Rtl));
+        assert_eq!(of_text("42!"), None);
+    }
+
+    #[test]
+    fn auto_follows_the_language_before_the_text() {
+        assert_eq!(resolve(TextDirection::Auto, Some("ar"), "iPhone"), Direction::Rtl);
+        assert_eq!(resolve(TextDirection::Auto, Some("en"), "שלום"), Direction::Ltr);
+        assert_eq!(resolve(TextDirection::Auto, None, "שלום"), Direction::Rtl);
+        assert_eq!(resolve(TextDirection::Auto, None, "42"), Direction::Ltr);
+        assert_eq!(resolve(TextDirection::Rtl, Some("en"), "Hello"), Direction::Rtl);
+        assert_eq!(resolve(TextDirection::Ltr, Some("he"), "שלום"), Direction::Ltr);
+    }
+
+    #[test]
+    fn right_to_left_text_is_marked_only_when_needed() {
+        assert_eq!(mark_for_injection("iPhone جديد", Direction::Rtl), "\u{200F}iPhone جديد");
+        assert_eq!(mark_for_injection("مرحبا iPhone", Direction::Rtl), "مرحبا iPhone");
+        assert_eq!(mark_for_injection("\u{200F}2024", Direction::Rtl), "\u{200F}2024");
+        assert_eq!(mark_for_injection("Hello", Direction::Ltr), "Hello");
+    }
+}
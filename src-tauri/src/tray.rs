@@ -0,0 +1,127 @@
+use tauri::{AppHandle, Manager, Runtime};
+use tauri::image::Image;
+use tauri::tray::TrayIcon;
+use log::{error, debug};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::config::WhisprConfig;
+use crate::menu::MenuState;
+use crate::menu_spec::config_summary;
+
+const IDLE_TOOLTIP: &str = "whispr";
+const EPHEMERAL_TOOLTIP: &str = "whispr (ephemeral session)";
+const RECORDING_TOOLTIP: &str = "whispr - microphone in use";
+const PRE_ROLL_TOOLTIP: &str = "whispr - microphone in use for the pre-roll";
+// Shown next to the icon in the menubar for the whole session
+const EPHEMERAL_TITLE: &str = "Ephemeral";
+
+// Tooltip while idle, restored after each recording
+static IDLE_SUMMARY: Mutex<Option<String>> = Mutex::new(None);
+// Either one keeps the microphone open
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static PRE_ROLL: AtomicBool = AtomicBool::new(false);
+
+// Tints the default icon red so the menubar shows that the microphone is live,
+// independently of whether the overlay window is visible.
+fn recording_icon(icon: &Image<'_>) -> Image<'static> {
+    let mut rgba = icon.rgba().to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        if pixel[3] > 0 {
+            pixel[0] = 255;
+            pixel[1] = pixel[1] / 4;
+            pixel[2] = pixel[2] / 4;
+        }
+    }
+    Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+pub fn set_recording_indicator<R: Runtime>(app: &AppHandle<R>, recording: bool) {
+    RECORDING.store(recording, Ordering::SeqCst);
+    update_microphone_indicator(app);
+}
+
+/// Keeps showing the microphone as in use between recordings while it stays
+/// open for the pre-roll.
+pub fn set_pre_roll_indicator<R: Runtime>(app: &AppHandle<R>, armed: bool) {
+    PRE_ROLL.store(armed, Ordering::SeqCst);
+    update_microphone_indicator(app);
+}
+
+fn microphone_in_use() -> bool {
+    RECORDING.load(Ordering::SeqCst) || PRE_ROLL.load(Ordering::SeqCst)
+}
+
+fn update_microphone_indicator<R: Runtime>(app: &AppHandle<R>) {
+    let Some(tray) = app.try_state::<TrayIcon<R>>() else {
+        debug!("Tray icon not available, skipping recording indicator");
+        return;
+    };
+    let Some(default_icon) = app.default_window_icon() else {
+        error!("No default window icon available");
+        return;
+    };
+
+    let (icon, tooltip) = if RECORDING.load(Ordering::SeqCst) {
+        (recording_icon(default_icon), RECORDING_TOOLTIP.to_string())
+    } else if PRE_ROLL.load(Ordering::SeqCst) {
+        (recording_icon(default_icon), PRE_ROLL_TOOLTIP.to_string())
+    } else {
+        (default_icon.clone().to_owned(), idle_tooltip())
+    };
+
+    if let Err(e) = tray.set_icon(Some(icon)) {
+        error!("Failed to update tray icon: {}", e);
+    }
+    if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+        error!("Failed to update tray tooltip: {}", e);
+    }
+}
+
+fn idle_tooltip() -> String {
+    let tooltip = if crate::ephemeral::is_active() { EPHEMERAL_TOOLTIP } else { IDLE_TOOLTIP };
+    match IDLE_SUMMARY.lock().unwrap().as_deref() {
+        Some(summary) => format!("{} - {}", tooltip, summary),
+        None => tooltip.to_string(),
+    }
+}
+
+/// Labels the menubar icon while the session is ephemeral.
+pub fn set_ephemeral_indicator<R: Runtime>(app: &AppHandle<R>, active: bool) {
+    let Some(tray) = app.try_state::<TrayIcon<R>>() else {
+        debug!("Tray icon not available, skipping ephemeral indicator");
+        return;
+    };
+    if let Err(e) = tray.set_title(active.then_some(EPHEMERAL_TITLE)) {
+        error!("Failed to update tray title: {}", e);
+    }
+    if microphone_in_use() {
+        return;
+    }
+    if let Err(e) = tray.set_tooltip(Some(idle_tooltip())) {
+        error!("Failed to update tray tooltip: {}", e);
+    }
+}
+
+/// Summarizes the active configuration in the tray tooltip and the menu
+/// header. Call whenever the configuration changed.
+pub fn update_summary<R: Runtime>(app: &AppHandle<R>, config: &WhisprConfig) {
+    let summary = config_summary(config, crate::language_names::ui_locale());
+    if let Some(menu_state) = app.try_state::<MenuState<R>>() {
+        if let Some(item) = &menu_state.summary_item {
+            if let Err(e) = item.set_text(&summary) {
+                error!("Failed to update summary item: {}", e);
+            }
+        }
+    }
+    *IDLE_SUMMARY.lock().unwrap() = Some(summary);
+
+    // The microphone tooltip stays until the microphone is closed
+    if microphone_in_use() {
+        return;
+    }
+    if let Some(tray) = app.try_state::<TrayIcon<R>>() {
+        if let Err(e) = tray.set_tooltip(Some(idle_tooltip())) {
+            error!("Failed to update tray tooltip: {}", e);
+        }
+    }
+}
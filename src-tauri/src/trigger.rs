@@ -0,0 +1,116 @@
+use anyhow::Result;
+use log::{info, debug, error};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, Runtime};
+
+type NSUInteger = libc::c_ulong;
+
+const NSEVENT_MODIFIER_FLAG_SHIFT: NSUInteger = 1 << 17;
+const NSEVENT_MODIFIER_FLAG_CONTROL: NSUInteger = 1 << 18;
+
+/// What started or ended a dictation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerSource {
+    Hotkey,
+    RecordButton,
+    /// The companion app API, HTTP or events
+    Api,
+    /// whispr itself, e.g. finishing a recording before quitting
+    System,
+    /// The stop phrase heard in a hands-free dictation
+    StopPhrase,
+}
+
+/// A press or release from any trigger, with the modifier flags held at the
+/// time so chords like Shift+release work for every trigger that has them.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerEvent {
+    pub source: TriggerSource,
+    pub pressed: bool,
+    pub modifier_flags: NSUInteger,
+}
+
+impl TriggerEvent {
+    pub fn new(source: TriggerSource, pressed: bool) -> Self {
+        Self { source, pressed, modifier_flags: 0 }
+    }
+
+    pub fn shift_held(&self) -> bool {
+        self.modifier_flags & NSEVENT_MODIFIER_FLAG_SHIFT != 0
+    }
+
+    pub fn control_held(&self) -> bool {
+        self.modifier_flags & NSEVENT_MODIFIER_FLAG_CONTROL != 0
+    }
+}
+
+/// The single entry point all triggers feed, managed as app state.
+#[derive(Clone)]
+pub struct RecordingController {
+    handler: Arc<dyn Fn(TriggerEvent) + Send + Sync>,
+}
+
+impl RecordingController {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(TriggerEvent) + Send + Sync + 'static,
+    {
+        Self { handler: Arc::new(handler) }
+    }
+
+    pub fn send(&self, event: TriggerEvent) {
+        debug!("{:?} {}", event.source, if event.pressed { "pressed" } else { "released" });
+        (self.handler)(event)
+    }
+}
+
+/// Sends a press or release to the app's recording controller, for triggers
+/// that are called into (the record button, the API) rather than attached.
+pub fn send<R: Runtime>(app: &AppHandle<R>, source: TriggerSource, pressed: bool) {
+    match app.try_state::<RecordingController>() {
+        Some(controller) => controller.send(TriggerEvent::new(source, pressed)),
+        None => debug!("Dictation is not available yet, ignoring {:?}", source),
+    }
+}
+
+/// An activation method that watches for input on its own, like the
+/// dictation key monitors. New ones (e.g. a wake word) implement this, are
+/// attached to the controller and pushed to `Triggers` in setup, no further
+/// wiring needed.
+pub trait Trigger {
+    fn name(&self) -> &'static str;
+    /// Starts delivering presses and releases to `controller`.
+    fn attach(&mut self, controller: RecordingController) -> Result<()>;
+    fn detach(&mut self);
+    /// Recovers after sleep, when event sources often stop delivering.
+    fn restart(&mut self) -> Result<()>;
+}
+
+/// The attached triggers, owned by the main thread.
+#[derive(Default)]
+pub struct Triggers {
+    triggers: Vec<Box<dyn Trigger>>,
+}
+
+impl Triggers {
+    /// Keeps an attached trigger for restarts after sleep and shutdown.
+    pub fn push(&mut self, trigger: Box<dyn Trigger>) {
+        info!("Attached {} trigger", trigger.name());
+        self.triggers.push(trigger);
+    }
+
+    pub fn restart(&mut self) {
+        for trigger in &mut self.triggers {
+            if let Err(e) = trigger.restart() {
+                error!("Failed to restart {} trigger: {}", trigger.name(), e);
+            }
+        }
+    }
+
+    pub fn detach(&mut self) {
+        for trigger in &mut self.triggers {
+            trigger.detach();
+        }
+        self.triggers.clear();
+    }
+}
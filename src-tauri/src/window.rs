@@ -2,8 +2,11 @@ use tauri::{WebviewUrl, WebviewWindow, WebviewWindowBuilder};
 use tauri::utils::WindowEffect;
 use log::{error, info};
 use tauri::utils::config::WindowEffectsConfig;
+use crate::accessibility::DisplayPreferences;
 
 const WINDOW_TITLE: &str = "whispr:overlay";
+const RECORD_BUTTON_TITLE: &str = "whispr:record_button";
+const RECORD_BUTTON_SIZE: f64 = 56.0;
 
 #[derive(Default)]
 pub struct OverlayWindow {
@@ -15,8 +18,8 @@ impl OverlayWindow {
         Self { window: None }
     }
 
-    pub fn create_window(&mut self, app_handle: &tauri::AppHandle) {
-        let window = WebviewWindowBuilder::new(
+    pub fn create_window(&mut self, app_handle: &tauri::AppHandle, display: DisplayPreferences) {
+        let builder = WebviewWindowBuilder::new(
             app_handle,
             WINDOW_TITLE,
             WebviewUrl::App("index.html".into())
@@ -25,18 +28,24 @@ impl OverlayWindow {
         .inner_size(350.0, 85.0)
         .decorations(false)
         .transparent(true)
-        .always_on_top(true)
-        .effects(WindowEffectsConfig {
-            effects: vec![
-                // For macOS
-                WindowEffect::HudWindow,
-                // For Windows
-                WindowEffect::Acrylic,
-            ],
-            state: None,
-            radius: Some(16.0),
-            color: None,
-        })
+        .always_on_top(true);
+        // With Reduce Transparency the page paints a solid background instead
+        let builder = if display.reduce_transparency {
+            builder
+        } else {
+            builder.effects(WindowEffectsConfig {
+                effects: vec![
+                    // For macOS
+                    WindowEffect::HudWindow,
+                    // For Windows
+                    WindowEffect::Acrylic,
+                ],
+                state: None,
+                radius: Some(16.0),
+                color: None,
+            })
+        };
+        let window = builder
         .skip_taskbar(true)
         .focused(false)
         .visible(false)
@@ -93,6 +102,16 @@ impl OverlayWindow {
         }
     }
 
+    /// The overlay lets clicks through to the app below, except while it
+    /// shows something to tap.
+    pub fn set_interactive(&self, interactive: bool) {
+        if let Some(window) = &self.window {
+            if let Err(e) = window.set_ignore_cursor_events(!interactive) {
+                error!("Failed to change overlay cursor events: {}", e);
+            }
+        }
+    }
+
     pub fn hide(&self) {
         if let Some(window) = &self.window {
             if let Err(e) = window.hide().and_then(|_| window.hide_menu()) {
@@ -103,3 +122,80 @@ impl OverlayWindow {
         }
     }
 }
+
+/// Floating push-to-talk button for users who can't use the modifier key
+/// shortcut. Stays on top at the right edge of the screen while enabled.
+#[derive(Default)]
+pub struct RecordButtonWindow {
+    window: Option<WebviewWindow>,
+}
+
+impl RecordButtonWindow {
+    pub fn new() -> Self {
+        Self { window: None }
+    }
+
+    pub fn create_window(&mut self, app_handle: &tauri::AppHandle) {
+        let window = WebviewWindowBuilder::new(
+            app_handle,
+            RECORD_BUTTON_TITLE,
+            WebviewUrl::App("index.html".into())
+        )
+        .title("whispr")
+        .inner_size(RECORD_BUTTON_SIZE, RECORD_BUTTON_SIZE)
+        .decorations(false)
+        .transparent(true)
+        .always_on_top(true)
+        .visible_on_all_workspaces(true)
+        .accept_first_mouse(true)
+        .shadow(false)
+        .skip_taskbar(true)
+        .focused(false)
+        .visible(false)
+        .resizable(false)
+        .build();
+
+        match window {
+            Ok(window) => self.window = Some(window),
+            Err(e) => error!("Failed to create record button window: {}", e),
+        }
+    }
+
+    fn move_right_center(&self, margin: i32) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(window) = &self.window {
+            let screen = window.current_monitor()?.ok_or("No monitor found")?;
+            let screen_position = screen.position();
+            let screen_size = screen.size();
+            let window_size = window.outer_size()?;
+
+            let x = screen_position.x + (screen_size.width as i32 - window_size.width as i32 - margin);
+            let y = screen_position.y + (screen_size.height as i32 - window_size.height as i32) / 2;
+
+            window.set_position(tauri::PhysicalPosition::new(x, y))?;
+        }
+        Ok(())
+    }
+
+    pub fn show(&self) {
+        if let Some(window) = &self.window {
+            if let Err(e) = self.move_right_center(16) {
+                error!("Failed to move record button: {}", e);
+            }
+            if let Err(e) = window.show() {
+                error!("Failed to show record button: {}", e);
+            } else {
+                info!("Record button shown");
+            }
+        }
+    }
+
+    pub fn hide(&self) {
+        if let Some(window) = &self.window {
+            if let Err(e) = window.hide() {
+                error!("Failed to hide record button: {}", e);
+            } else {
+                info!("Record button hidden");
+            }
+        }
+    }
+}
@@ -1,14 +1,54 @@
 import { useState, useEffect } from 'react';
 import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
 import icon from './icon.png';
 
+interface LanguageChip {
+  detected: string;
+  usual: string;
+}
+
+interface DisplayPreferences {
+  reduce_motion: boolean;
+  reduce_transparency: boolean;
+}
+
+// Text with the direction it is written in
+interface DirectedText {
+  text: string;
+  dir: 'ltr' | 'rtl';
+}
+
+interface TranscriptionProgress {
+  percent: number;
+  remaining_secs: number | null;
+}
+
 function App() {
   const [isActive, setIsActive] = useState(false);
+  const [toast, setToast] = useState<DirectedText | null>(null);
+  const [draft, setDraft] = useState<DirectedText | null>(null);
+  const [languageChip, setLanguageChip] = useState<LanguageChip | null>(null);
+  const [progress, setProgress] = useState<TranscriptionProgress | null>(null);
+  const [display, setDisplay] = useState<DisplayPreferences>({
+    reduce_motion: false,
+    reduce_transparency: false,
+  });
   const [heights, setHeights] = useState(() => 
     Array.from({ length: 35 }, () => 0.2)
   );
 
   useEffect(() => {
+    invoke<DisplayPreferences>('display_preferences').then(setDisplay);
+  }, []);
+
+  useEffect(() => {
+    // Reduce Motion: a still waveform that only changes level with the status
+    if (display.reduce_motion) {
+      setHeights(prevHeights => prevHeights.map(() => (isActive ? 0.5 : 0.2)));
+      return;
+    }
+
     let animationFrame: number;
 
     let time = 0;
@@ -41,35 +81,93 @@ function App() {
         cancelAnimationFrame(animationFrame);
       }
     };
-  }, [isActive]);
+  }, [isActive, display.reduce_motion]);
 
   useEffect(() => {
     const unlistenStart = listen('status-change', (event) => {
       const newStatus = event.payload as string;
       setIsActive(newStatus !== '');
+      if (newStatus === 'Listening') {
+        setToast(null);
+      }
+      if (newStatus !== '' && newStatus !== 'Ready') {
+        setLanguageChip(null);
+      }
+      if (newStatus !== 'Transcribing') {
+        setProgress(null);
+        setDraft(null);
+      }
+    });
+    const unlistenProgress = listen('transcription-progress', (event) => {
+      setProgress(event.payload as TranscriptionProgress);
+    });
+    const unlistenDraft = listen('draft-text', (event) => {
+      setDraft(event.payload as DirectedText);
+    });
+    const unlistenToast = listen('toast', (event) => {
+      setToast(event.payload as DirectedText);
+    });
+    const unlistenLanguageChip = listen('language-chip', (event) => {
+      setToast(null);
+      setLanguageChip(event.payload as LanguageChip);
     });
 
     return () => {
       unlistenStart.then((unlistenFn) => unlistenFn());
+      unlistenToast.then((unlistenFn) => unlistenFn());
+      unlistenLanguageChip.then((unlistenFn) => unlistenFn());
+      unlistenProgress.then((unlistenFn) => unlistenFn());
+      unlistenDraft.then((unlistenFn) => unlistenFn());
     };
   }, []);
 
   return (
-    <div className={`App ${isActive ? 'active' : ''}`}>
+    <div
+      className={[
+        'App',
+        isActive ? 'active' : '',
+        display.reduce_motion ? 'reduce-motion' : '',
+        display.reduce_transparency ? 'reduce-transparency' : '',
+      ].join(' ')}
+    >
       <img src={icon} className="tauri-icon" alt="Tauri logo" />
       <div className="waveform-container">
-        <div className="waveform">
-          {heights.map((height, i) => (
-            <div
-              key={i}
-              className={`bar ${isActive ? 'active' : ''}`}
-              style={{
-                height: `${height * 100}%`,
-                transform: `scaleY(${height})`
-              }}
-            />
-          ))}
-        </div>
+        {languageChip ? (
+          <button
+            className="language-chip"
+            onClick={() => {
+              setLanguageChip(null);
+              invoke('redo_in_language');
+            }}
+          >
+            Detected {languageChip.detected} — tap to redo in {languageChip.usual}
+          </button>
+        ) : toast ? (
+          <div className="toast" dir={toast.dir}>{toast.text}</div>
+        ) : draft ? (
+          <div className="toast draft" dir={draft.dir}>{draft.text}</div>
+        ) : (
+          <div className="waveform">
+            {heights.map((height, i) => (
+              <div
+                key={i}
+                className={`bar ${isActive ? 'active' : ''}`}
+                style={{
+                  height: `${height * 100}%`,
+                  transform: `scaleY(${height})`
+                }}
+              />
+            ))}
+          </div>
+        )}
+        {progress && (
+          <div className="progress">
+            <div className="progress-bar" style={{ width: `${progress.percent}%` }} />
+            {progress.remaining_secs !== null && (
+              <span className="progress-eta">~{Math.ceil(progress.remaining_secs)}s</span>
+            )}
+          </div>
+        )}
       </div>
       <div className="microphone">
         <svg viewBox="0 0 24 24" width="24" height="24">
@@ -0,0 +1,95 @@
+import { useState, useEffect, useRef } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+
+interface LogLine {
+  timestamp: string;
+  level: string;
+  location: string;
+  message: string;
+}
+
+const LEVELS = ['ERROR', 'WARN', 'INFO', 'DEBUG', 'TRACE'];
+// Keep the DOM small, the full log is in ~/.whispr/logs
+const MAX_LINES = 5000;
+
+function Console() {
+  const [lines, setLines] = useState<LogLine[]>([]);
+  const [level, setLevel] = useState('DEBUG');
+  const [search, setSearch] = useState('');
+  const [follow, setFollow] = useState(true);
+  const bottom = useRef<HTMLDivElement>(null);
+
+  useEffect(() => {
+    invoke<LogLine[]>('console_backlog').then((backlog) => {
+      setLines((streamed) => [...backlog, ...streamed].slice(-MAX_LINES));
+    });
+
+    const unlistenLine = listen('log-line', (event) => {
+      setLines((previous) => [...previous, event.payload as LogLine].slice(-MAX_LINES));
+    });
+    const unlistenLagged = listen('log-lagged', (event) => {
+      const skipped: LogLine = {
+        timestamp: '',
+        level: 'WARN',
+        location: 'console',
+        message: `${event.payload as number} lines skipped`,
+      };
+      setLines((previous) => [...previous, skipped].slice(-MAX_LINES));
+    });
+
+    return () => {
+      unlistenLine.then((unlistenFn) => unlistenFn());
+      unlistenLagged.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (follow) {
+      bottom.current?.scrollIntoView();
+    }
+  }, [lines, follow]);
+
+  const maxLevel = LEVELS.indexOf(level);
+  const query = search.toLowerCase();
+  const visible = lines.filter((line) =>
+    LEVELS.indexOf(line.level) <= maxLevel &&
+    (query === '' || line.message.toLowerCase().includes(query) || line.location.toLowerCase().includes(query))
+  );
+
+  return (
+    <div className="console">
+      <div className="console-toolbar">
+        <select value={level} onChange={(event) => setLevel(event.target.value)}>
+          {LEVELS.map((name) => (
+            <option key={name} value={name}>{name}</option>
+          ))}
+        </select>
+        <input
+          type="search"
+          placeholder="Search"
+          value={search}
+          onChange={(event) => setSearch(event.target.value)}
+        />
+        <label>
+          <input type="checkbox" checked={follow} onChange={(event) => setFollow(event.target.checked)} />
+          Follow
+        </label>
+        <button onClick={() => setLines([])}>Clear</button>
+      </div>
+      <div className="console-lines">
+        {visible.map((line, i) => (
+          <div key={i} className={`console-line level-${line.level.toLowerCase()}`}>
+            <span className="console-timestamp">{line.timestamp}</span>
+            <span className="console-level">{line.level}</span>
+            <span className="console-location">{line.location}</span>
+            <span className="console-message">{line.message}</span>
+          </div>
+        ))}
+        <div ref={bottom} />
+      </div>
+    </div>
+  );
+}
+
+export default Console;
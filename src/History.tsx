@@ -0,0 +1,382 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+interface HistorySegment {
+  start_secs: number;
+  end_secs: number;
+  text: string;
+}
+
+interface HistoryVersion {
+  timestamp: string;
+  model: string;
+  text: string;
+  segments: HistorySegment[];
+  confidence: number;
+}
+
+interface HistoryEntry {
+  id: number;
+  timestamp: string;
+  text: string;
+  segments: HistorySegment[];
+  confidence: number;
+  recording: string | null;
+  versions: HistoryVersion[];
+  tags: string[];
+  favorite: boolean;
+  app: string | null;
+  bundle_id: string | null;
+  low_confidence: boolean;
+  summary: string | null;
+}
+
+interface SummaryStatus {
+  id: number;
+  running: boolean;
+  error: string | null;
+}
+
+interface RetranscribeProgress {
+  id: number | null;
+  done: number;
+  total: number;
+  percent: number;
+}
+
+interface PlaybackStatus {
+  id: number | null;
+  playing: boolean;
+  paused: boolean;
+  position_secs: number;
+  duration_secs: number;
+}
+
+interface SearchHit {
+  id: number;
+  snippet: string;
+}
+
+const STATUS_INTERVAL_MS = 250;
+const SEARCH_DELAY_MS = 150;
+// Shorter dictations are quicker to read than a summary
+const SUMMARY_MIN_WORDS = 100;
+// history_search wraps matches in these
+const MATCH_START = '\u0002';
+const MATCH_END = '\u0003';
+
+function Snippet({ text }: { text: string }) {
+  const parts = text.split(MATCH_START);
+  return (
+    <span>
+      {parts[0]}
+      {parts.slice(1).map((part, i) => {
+        const [match, rest] = part.split(MATCH_END);
+        return (
+          <span key={i}>
+            <mark>{match}</mark>
+            {rest}
+          </span>
+        );
+      })}
+    </span>
+  );
+}
+
+function formatOffset(secs: number) {
+  const minutes = Math.floor(secs / 60);
+  const seconds = (secs % 60).toFixed(1).padStart(4, '0');
+  return `${minutes}:${seconds}`;
+}
+
+function History() {
+  const [entries, setEntries] = useState<HistoryEntry[]>([]);
+  const [error, setError] = useState<string | null>(null);
+  const [playback, setPlayback] = useState<PlaybackStatus | null>(null);
+  const [selected, setSelected] = useState<Set<number>>(new Set());
+  const [progress, setProgress] = useState<RetranscribeProgress | null>(null);
+  const [favoritesOnly, setFavoritesOnly] = useState(false);
+  const [tagFilter, setTagFilter] = useState('');
+  const [appFilter, setAppFilter] = useState('');
+  const [query, setQuery] = useState('');
+  const [hits, setHits] = useState<SearchHit[] | null>(null);
+  const [summarizing, setSummarizing] = useState<Set<number>>(new Set());
+
+  const refresh = () => {
+    invoke<HistoryEntry[]>('history_entries')
+      .then((loaded) => setEntries(loaded.reverse()))
+      .catch((e) => setError(String(e)));
+  };
+
+  useEffect(refresh, []);
+
+  useEffect(() => {
+    const interval = setInterval(() => {
+      invoke<PlaybackStatus>('playback_status').then(setPlayback);
+    }, STATUS_INTERVAL_MS);
+    return () => clearInterval(interval);
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<RetranscribeProgress>('retranscribe-progress', (event) => {
+      const update = event.payload;
+      setProgress(update.id === null ? null : update);
+      if (update.id === null || update.percent === 100) {
+        refresh();
+      }
+    });
+    return () => {
+      unlisten.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<SummaryStatus>('summary-status', (event) => {
+      const status = event.payload;
+      setSummarizing((previous) => {
+        const next = new Set(previous);
+        if (status.running) {
+          next.add(status.id);
+        } else {
+          next.delete(status.id);
+        }
+        return next;
+      });
+      if (status.error !== null) {
+        setError(status.error);
+      }
+      if (!status.running) {
+        refresh();
+      }
+    });
+    return () => {
+      unlisten.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (query.trim() === '') {
+      setHits(null);
+      return;
+    }
+    const timeout = setTimeout(() => {
+      invoke<SearchHit[]>('history_search', { query }).then(setHits).catch((e) => setError(String(e)));
+    }, SEARCH_DELAY_MS);
+    return () => clearTimeout(timeout);
+  }, [query]);
+
+  const toggleSelected = (id: number) => {
+    setSelected((previous) => {
+      const next = new Set(previous);
+      if (!next.delete(id)) {
+        next.add(id);
+      }
+      return next;
+    });
+  };
+
+  const retranscribe = (ids: number[]) => {
+    report(invoke('retranscribe_entries', { ids }));
+    setSelected(new Set());
+  };
+
+  const report = (promise: Promise<unknown>) => promise.catch((e) => setError(String(e)));
+
+  const togglePlayback = (entry: HistoryEntry) => {
+    if (playback?.id === entry.id && playback.playing) {
+      report(invoke(playback.paused ? 'playback_resume' : 'playback_pause'));
+    } else {
+      report(invoke('playback_play', { id: entry.id }));
+    }
+  };
+
+  const play = (entry: HistoryEntry, segment: number) => {
+    report(invoke('play_history_segment', { id: entry.id, segment }));
+  };
+
+  const setTags = (entry: HistoryEntry, tags: string[]) => {
+    report(invoke('set_history_tags', { id: entry.id, tags }).then(refresh));
+  };
+
+  const toggleFavorite = (entry: HistoryEntry) => {
+    report(invoke('set_history_favorite', { id: entry.id, favorite: !entry.favorite }).then(refresh));
+  };
+
+  const summarize = (entry: HistoryEntry) => {
+    setError(null);
+    report(invoke('summarize_history_entry', { id: entry.id }));
+  };
+
+  const exportEntries = () => {
+    report(invoke('export_history', { tag: tagFilter === '' ? null : tagFilter }));
+  };
+
+  const allTags = [...new Set(entries.flatMap((entry) => entry.tags))].sort();
+  const appCounts = new Map<string, number>();
+  entries.forEach((entry) => {
+    if (entry.app) {
+      appCounts.set(entry.app, (appCounts.get(entry.app) ?? 0) + 1);
+    }
+  });
+  // Most dictated-into first
+  const allApps = [...appCounts.keys()].sort((a, b) => appCounts.get(b)! - appCounts.get(a)! || a.localeCompare(b));
+  const byId = new Map(entries.map((entry) => [entry.id, entry]));
+  const snippets = new Map((hits ?? []).map((hit) => [hit.id, hit.snippet]));
+  // Search results come ranked, best match first
+  const candidates = hits === null
+    ? entries
+    : hits.flatMap((hit) => byId.get(hit.id) ?? []);
+  const visible = candidates.filter((entry) =>
+    (!favoritesOnly || entry.favorite) &&
+    (tagFilter === '' || entry.tags.includes(tagFilter)) &&
+    (appFilter === '' || entry.app === appFilter)
+  );
+
+  return (
+    <div className="history">
+      <div className="history-toolbar">
+        <input
+          type="search"
+          placeholder='Search, "exact phrase" or prefix*'
+          value={query}
+          onChange={(event) => setQuery(event.target.value)}
+        />
+        <button onClick={refresh}>Refresh</button>
+        <button onClick={() => report(invoke('playback_stop'))}>Stop</button>
+        <button disabled={selected.size === 0} onClick={() => retranscribe([...selected])}>
+          Re-transcribe Selected
+        </button>
+        <label>
+          <input type="checkbox" checked={favoritesOnly} onChange={(event) => setFavoritesOnly(event.target.checked)} />
+          Favorites
+        </label>
+        <select value={tagFilter} onChange={(event) => setTagFilter(event.target.value)}>
+          <option value="">All tags</option>
+          {allTags.map((tag) => (
+            <option key={tag} value={tag}>#{tag}</option>
+          ))}
+        </select>
+        <select value={appFilter} onChange={(event) => setAppFilter(event.target.value)}>
+          <option value="">All apps</option>
+          {allApps.map((app) => (
+            <option key={app} value={app}>{app} ({appCounts.get(app)})</option>
+          ))}
+        </select>
+        <button onClick={exportEntries}>Export</button>
+        {progress && (
+          <span className="history-progress">
+            Re-transcribing {progress.done + 1} of {progress.total} · {progress.percent}%
+          </span>
+        )}
+        {error && <span className="history-error">{error}</span>}
+      </div>
+      <div className="history-entries">
+        {visible.length === 0 && (
+          <div className="history-empty">{hits === null ? 'No transcriptions yet' : 'No matches'}</div>
+        )}
+        {visible.map((entry) => (
+          <div key={entry.id} className="history-entry">
+            <div className="history-meta">
+              {entry.recording && (
+                <input type="checkbox" checked={selected.has(entry.id)} onChange={() => toggleSelected(entry.id)} />
+              )}
+              <button
+                className={entry.favorite ? 'history-star favorite' : 'history-star'}
+                onClick={() => toggleFavorite(entry)}
+                title="Favorite"
+              >
+                {entry.favorite ? '★' : '☆'}
+              </button>
+              {new Date(entry.timestamp).toLocaleString()} · {Math.round(entry.confidence * 100)}%
+              {entry.app && <span title={entry.bundle_id ?? undefined}> · {entry.app}</span>}
+              {entry.low_confidence && (
+                <span className="history-error" title="Repeated words were trimmed, check the recording"> · low confidence</span>
+              )}
+              {entry.recording && (
+                <button className="history-link" onClick={() => retranscribe([entry.id])}>
+                  Re-transcribe with current settings
+                </button>
+              )}
+              {entry.text.split(/\s+/).length >= SUMMARY_MIN_WORDS && (
+                <button className="history-link" disabled={summarizing.has(entry.id)} onClick={() => summarize(entry)}>
+                  {summarizing.has(entry.id) ? 'Summarizing…' : entry.summary ? 'Summarize again' : 'Summarize'}
+                </button>
+              )}
+            </div>
+            {entry.summary && (
+              <div className="history-summary">
+                <div className="history-meta">Summary</div>
+                <div>{entry.summary}</div>
+              </div>
+            )}
+            {snippets.has(entry.id) && (
+              <div className="history-snippet">
+                <Snippet text={snippets.get(entry.id)!} />
+              </div>
+            )}
+            <div className="history-tags">
+              {entry.tags.map((tag) => (
+                <span key={tag} className="history-tag">
+                  #{tag}
+                  <button onClick={() => setTags(entry, entry.tags.filter((other) => other !== tag))}>×</button>
+                </span>
+              ))}
+              <input
+                placeholder="Add tag"
+                onKeyDown={(event) => {
+                  const input = event.currentTarget;
+                  if (event.key === 'Enter' && input.value.trim() !== '') {
+                    setTags(entry, [...entry.tags, input.value]);
+                    input.value = '';
+                  }
+                }}
+              />
+            </div>
+            {entry.recording && (
+              <div className="history-player">
+                <button onClick={() => togglePlayback(entry)}>
+                  {playback?.id === entry.id && playback.playing && !playback.paused ? 'Pause' : 'Play'}
+                </button>
+                {playback?.id === entry.id && playback.playing && (
+                  <>
+                    <input
+                      type="range"
+                      min={0}
+                      max={playback.duration_secs}
+                      step={0.1}
+                      value={playback.position_secs}
+                      onChange={(event) => report(invoke('playback_seek', { positionSecs: Number(event.target.value) }))}
+                    />
+                    <span className="history-offset">
+                      {formatOffset(playback.position_secs)} / {formatOffset(playback.duration_secs)}
+                    </span>
+                  </>
+                )}
+              </div>
+            )}
+            {entry.segments.map((segment, i) => (
+              <div key={i} className="history-segment">
+                {entry.recording && (
+                  <button className="history-play" onClick={() => play(entry, i)} title="Play this segment">▶</button>
+                )}
+                <span className="history-offset">{formatOffset(segment.start_secs)}</span>
+                <span>{segment.text}</span>
+              </div>
+            ))}
+            {entry.versions.map((version, i) => (
+              <div key={i} className="history-version">
+                <div className="history-meta">
+                  {version.model} · {new Date(version.timestamp).toLocaleString()} · {Math.round(version.confidence * 100)}%
+                </div>
+                <div>{version.text}</div>
+              </div>
+            ))}
+          </div>
+        ))}
+      </div>
+    </div>
+  );
+}
+
+export default History;
@@ -0,0 +1,54 @@
+import { useState, useEffect, useRef, type PointerEvent } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+
+function RecordButton() {
+  const [status, setStatus] = useState('Ready');
+
+  useEffect(() => {
+    const unlistenStatus = listen('status-change', (event) => {
+      setStatus(event.payload as string);
+    });
+
+    return () => {
+      unlistenStatus.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  // Hold to talk, just like the dictation key
+  const pressed = useRef(false);
+  const press = (event: PointerEvent<HTMLButtonElement>) => {
+    event.currentTarget.setPointerCapture(event.pointerId);
+    pressed.current = true;
+    invoke('record_button', { pressed: true });
+  };
+  // A cancelled pointer or lost capture ends the recording like lifting the
+  // finger, releasing the capture on pointer up fires the latter again
+  const release = (event: PointerEvent<HTMLButtonElement>) => {
+    if (!pressed.current) {
+      return;
+    }
+    pressed.current = false;
+    if (event.currentTarget.hasPointerCapture(event.pointerId)) {
+      event.currentTarget.releasePointerCapture(event.pointerId);
+    }
+    invoke('record_button', { pressed: false });
+  };
+
+  return (
+    <button
+      className={`record-button ${status === 'Listening' ? 'recording' : ''} ${status === 'Transcribing' ? 'busy' : ''}`}
+      onPointerDown={press}
+      onPointerUp={release}
+      onPointerCancel={release}
+      onLostPointerCapture={release}
+      title="Hold to dictate"
+    >
+      <svg viewBox="0 0 24 24" width="24" height="24">
+        <path d="M12 14c1.66 0 3-1.34 3-3V5c0-1.66-1.34-3-3-3S9 3.34 9 5v6c0 1.66 1.34 3 3 3zm5.91-3c-.49 0-.9.36-.98.85C16.52 14.2 14.47 16 12 16s-4.52-1.8-4.93-4.15c-.08-.49-.49-.85-.98-.85-.61 0-1.09.54-1 1.14.49 3 2.89 5.35 5.91 5.78V20c0 .55.45 1 1 1s1-.45 1-1v-2.08c3.02-.43 5.42-2.78 5.91-5.78.1-.6-.39-1.14-1-1.14z" />
+      </svg>
+    </button>
+  );
+}
+
+export default RecordButton;
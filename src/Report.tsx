@@ -0,0 +1,78 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+interface AppUsage {
+  app: string;
+  dictations: number;
+  words: number;
+}
+
+interface MonthlyReport {
+  month: string;
+  dictations: number;
+  words: number;
+  average_latency_ms: number;
+  top_apps: AppUsage[];
+  top_language: string | null;
+}
+
+function Report() {
+  const [months, setMonths] = useState<string[]>([]);
+  const [month, setMonth] = useState<string | null>(null);
+  const [report, setReport] = useState<MonthlyReport | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<string[]>('analytics_months').then(setMonths).catch((e) => setError(String(e)));
+  }, []);
+
+  useEffect(() => {
+    invoke<MonthlyReport>('analytics_report', { month }).then(setReport).catch((e) => setError(String(e)));
+  }, [month]);
+
+  const exportReport = () => {
+    invoke('export_analytics_report', { month }).catch((e) => setError(String(e)));
+  };
+
+  return (
+    <div className="history">
+      <div className="history-toolbar">
+        <select value={month ?? ''} onChange={(event) => setMonth(event.target.value === '' ? null : event.target.value)}>
+          <option value="">This month</option>
+          {months.map((other) => (
+            <option key={other} value={other}>{other}</option>
+          ))}
+        </select>
+        <button onClick={exportReport}>Export HTML</button>
+        {error && <span className="history-error">{error}</span>}
+      </div>
+      {report && (
+        <div className="history-entries">
+          {report.dictations === 0 && <div className="history-empty">No dictations in {report.month}</div>}
+          {report.dictations > 0 && (
+            <>
+              <div className="report-totals">
+                <div><strong>{report.words}</strong> words</div>
+                <div><strong>{report.dictations}</strong> dictations</div>
+                <div><strong>{report.average_latency_ms}</strong> ms average latency</div>
+                <div><strong>{report.top_language ?? '–'}</strong> most used language</div>
+              </div>
+              <table className="report-apps">
+                <thead>
+                  <tr><th>App</th><th>Dictations</th><th>Words</th></tr>
+                </thead>
+                <tbody>
+                  {report.top_apps.map((usage) => (
+                    <tr key={usage.app}><td>{usage.app}</td><td>{usage.dictations}</td><td>{usage.words}</td></tr>
+                  ))}
+                </tbody>
+              </table>
+            </>
+          )}
+        </div>
+      )}
+    </div>
+  );
+}
+
+export default Report;
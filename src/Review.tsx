@@ -0,0 +1,75 @@
+import { useState, useEffect } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+
+interface ReviewWord {
+  word: string;
+  probability: number | null;
+}
+
+interface ReviewView {
+  confidence: number;
+  // Only words below it are marked, null colors every word
+  threshold: number | null;
+  words: ReviewWord[];
+  dir: 'ltr' | 'rtl';
+}
+
+// Token probabilities at or above these count as sure and unsure
+const HIGH_CONFIDENCE = 0.8;
+const MEDIUM_CONFIDENCE = 0.5;
+
+function confidenceClass(probability: number | null, threshold: number | null): string {
+  if (probability === null) {
+    return '';
+  }
+  if (threshold !== null) {
+    return probability < threshold ? 'review-low' : '';
+  }
+  if (probability >= HIGH_CONFIDENCE) {
+    return 'review-high';
+  }
+  return probability >= MEDIUM_CONFIDENCE ? 'review-medium' : 'review-low';
+}
+
+function Review() {
+  const [review, setReview] = useState<ReviewView | null>(null);
+
+  useEffect(() => {
+    const load = () => invoke<ReviewView | null>('review_pending').then(setReview);
+    load();
+    const unlisten = listen('review-changed', load);
+    return () => {
+      unlisten.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  if (!review) {
+    return <div className="history"><div className="history-empty">Nothing to review</div></div>;
+  }
+
+  return (
+    <div className="history">
+      <div className="history-toolbar">
+        <span>whispr isn't sure it understood you ({Math.round(review.confidence * 100)}% confidence)</span>
+      </div>
+      <div className="history-entries review-text" dir={review.dir}>
+        {review.words.map((word, i) => (
+          <span
+            key={i}
+            className={confidenceClass(word.probability, review.threshold)}
+            title={word.probability === null ? undefined : `${Math.round(word.probability * 100)}%`}
+          >
+            {word.word}{' '}
+          </span>
+        ))}
+      </div>
+      <div className="history-toolbar review-actions">
+        <button onClick={() => invoke('review_discard')}>Discard</button>
+        <button onClick={() => invoke('review_insert')}>Insert</button>
+      </div>
+    </div>
+  );
+}
+
+export default Review;
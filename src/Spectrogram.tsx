@@ -0,0 +1,73 @@
+import { useState, useEffect, useRef } from 'react';
+import { listen } from '@tauri-apps/api/event';
+
+interface SpectrogramFrames {
+  frames: number[][];
+  sample_rate: number;
+  channels: number;
+}
+
+// Levels run from 0 (-100 dBFS) to 1 (full scale)
+function color(level: number): string {
+  const hue = 260 - level * 210;
+  const lightness = 4 + level * 56;
+  return `hsl(${hue}, 90%, ${lightness}%)`;
+}
+
+function Spectrogram() {
+  const canvas = useRef<HTMLCanvasElement>(null);
+  const [format, setFormat] = useState<string | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen<SpectrogramFrames>('spectrogram-frames', (event) => {
+      const { frames, sample_rate, channels } = event.payload;
+      setFormat(`${sample_rate / 1000} kHz · ${channels === 1 ? 'mono' : `${channels} channels`}`);
+
+      const context = canvas.current?.getContext('2d');
+      if (!context || frames.length === 0) {
+        return;
+      }
+      const { width, height } = context.canvas;
+      // One pixel column per 10 ms frame, scrolling to the left
+      const shift = Math.min(frames.length, width);
+      context.drawImage(context.canvas, -shift, 0);
+      const bandHeight = height / frames[0].length;
+      frames.slice(-shift).forEach((frame, column) => {
+        const x = width - shift + column;
+        frame.forEach((level, band) => {
+          context.fillStyle = color(level);
+          // Low frequencies at the bottom
+          context.fillRect(x, height - (band + 1) * bandHeight, 1, Math.ceil(bandHeight));
+        });
+      });
+    });
+
+    return () => {
+      unlisten.then((unlistenFn) => unlistenFn());
+    };
+  }, []);
+
+  useEffect(() => {
+    const resize = () => {
+      if (canvas.current) {
+        canvas.current.width = canvas.current.clientWidth;
+        canvas.current.height = canvas.current.clientHeight;
+      }
+    };
+    resize();
+    window.addEventListener('resize', resize);
+    return () => window.removeEventListener('resize', resize);
+  }, []);
+
+  return (
+    <div className="spectrogram">
+      <div className="spectrogram-toolbar">
+        {format ?? 'Start a dictation to see the incoming audio'}
+        <span className="spectrogram-scale">0–8 kHz, mel scale · before silence removal</span>
+      </div>
+      <canvas ref={canvas} className="spectrogram-canvas" />
+    </div>
+  );
+}
+
+export default Spectrogram;
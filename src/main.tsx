@@ -1,10 +1,26 @@
 import React from 'react'
 import ReactDOM from 'react-dom/client'
+import { getCurrentWindow } from '@tauri-apps/api/window'
 import App from './App'
+import RecordButton from './RecordButton'
+import Console from './Console'
+import History from './History'
+import Report from './Report'
+import Review from './Review'
+import Spectrogram from './Spectrogram'
 import './App.css'
 
+const views: Record<string, React.ReactElement> = {
+  'whispr:record_button': <RecordButton />,
+  'whispr:console': <Console />,
+  'whispr:history': <History />,
+  'whispr:report': <Report />,
+  'whispr:review': <Review />,
+  'whispr:spectrogram': <Spectrogram />,
+}
+
 ReactDOM.createRoot(document.getElementById('root')!).render(
   <React.StrictMode>
-    <App />
+    {views[getCurrentWindow().label] ?? <App />}
   </React.StrictMode>,
 )